@@ -6,6 +6,7 @@ use crate::memory::PAGE_SHIFT;
 use crate::memory::address::{PA, VA};
 use crate::memory::permissions::PtePermissions;
 use crate::memory::region::PhysMemoryRegion;
+use crate::memory::swap::SwapEntry;
 
 /// Trait for common behavior across different types of page table entries.
 pub trait PageTableEntry: Sized + Copy + Clone {
@@ -314,6 +315,43 @@ impl L3Descriptor {
     pub fn mark_as_swapped(self) -> Self {
         Self(Self::SWAPPED_BIT | (self.0 & !Self::STATE_MASK))
     }
+
+    // A swap entry is held in a descriptor with bits[1:0] = 01, which is
+    // reserved, and so faults, at L3.
+    const SWAP_ENTRY_BITS: u64 = 0b01;
+    const SWAP_ENTRY_SHIFT: u64 = 12;
+    const AF_BIT: u64 = 1 << 10;
+
+    /// Creates a non-present descriptor for a page which has been swapped
+    /// out to `entry`.
+    pub fn new_swap_entry(entry: SwapEntry) -> Self {
+        Self((entry.as_raw() << Self::SWAP_ENTRY_SHIFT) | Self::SWAP_ENTRY_BITS)
+    }
+
+    /// Returns the swap entry held by this descriptor, if it's for a page
+    /// which has been swapped out.
+    pub fn swap_entry(self) -> Option<SwapEntry> {
+        if self.0 & Self::STATE_MASK == Self::SWAP_ENTRY_BITS {
+            Some(SwapEntry::from_raw(self.0 >> Self::SWAP_ENTRY_SHIFT))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the access flag is set. Accessing a page whose flag
+    /// is clear raises an access flag fault.
+    pub fn is_accessed(self) -> bool {
+        self.0 & Self::AF_BIT != 0
+    }
+
+    /// Sets or clears the access flag.
+    pub fn set_accessed(self, accessed: bool) -> Self {
+        if accessed {
+            Self(self.0 | Self::AF_BIT)
+        } else {
+            Self(self.0 & !Self::AF_BIT)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -571,4 +609,35 @@ mod tests {
         assert!(d.mapped_address().is_none());
         assert!(d.permissions().is_none());
     }
+
+    #[test]
+    fn test_l3_swap_entry() {
+        let entry = SwapEntry::new(1, 0x42);
+        let d = L3Descriptor::new_swap_entry(entry);
+
+        // The walkers visit it, but the MMU doesn't.
+        assert!(d.is_valid());
+        assert_eq!(d.swap_entry(), Some(entry));
+        assert!(d.mapped_address().is_none());
+        assert!(d.permissions().is_none());
+
+        let pa = PA::from_value(PAGE_SIZE * 8);
+        let page = L3Descriptor::new_map_pa(pa, MemoryType::Normal, PtePermissions::rw(true));
+        assert!(page.swap_entry().is_none());
+        assert!(page.mark_as_swapped().swap_entry().is_none());
+    }
+
+    #[test]
+    fn test_l3_access_flag() {
+        let pa = PA::from_value(PAGE_SIZE * 8);
+        let d = L3Descriptor::new_map_pa(pa, MemoryType::Normal, PtePermissions::rw(true));
+
+        assert!(d.is_accessed());
+
+        let old = d.set_accessed(false);
+        assert!(!old.is_accessed());
+        assert_eq!(old.mapped_address(), Some(pa));
+        assert_eq!(old.permissions(), Some(PtePermissions::rw(true)));
+        assert_eq!(old.set_accessed(true), d);
+    }
 }
@@ -357,6 +357,55 @@ where
     Ok(())
 }
 
+/// Installs `desc` as the L3 descriptor for the page at `va`, allocating any
+/// intermediate tables that are needed.
+///
+/// Unlike [`map_range`], the descriptor is written as given, so that a page's
+/// existing attributes, or a non-present descriptor such as a swap entry, can
+/// be moved to a new address without mapping anything in the meantime.
+///
+/// # Errors
+///
+/// - `MapError::VirtNotAligned`: `va` is not page-aligned.
+/// - `MapError::AlreadyMapped`: `va` is covered by a block mapping, or its L3
+///   descriptor is already in use.
+/// - `MapError::OutOfMemory`: The `allocator` in `ctx` fails to provide a new
+///   page table when one is needed.
+pub fn install_desc<PA, PM>(
+    l0_table: TPA<PgTableArray<L0Table>>,
+    va: VA,
+    desc: L3Descriptor,
+    ctx: &mut MappingContext<PA, PM>,
+) -> Result<()>
+where
+    PA: PageAllocator,
+    PM: PageTableMapper,
+{
+    if !va.is_page_aligned() {
+        Err(MapError::VirtNotAligned)?
+    }
+
+    let l1 = map_at_level(l0_table, va, ctx)?;
+    let l2 = map_at_level(l1, va, ctx)?;
+    let l3 = map_at_level(l2, va, ctx)?;
+
+    unsafe {
+        ctx.mapper.with_page_table(l3, |tbl| {
+            let table = L3Table::from_ptr(tbl);
+
+            // A non-present descriptor, such as a swap entry, still occupies
+            // the slot.
+            if table.get_desc(va).as_raw() != 0 {
+                Err(MapError::AlreadyMapped)?
+            }
+
+            table.set_desc(va, desc, ctx.invalidator);
+
+            Ok(())
+        })?
+    }
+}
+
 fn try_map_pa<L, PA, PM>(
     table: TPA<PgTableArray<L>>,
     va: VA,
@@ -456,7 +505,10 @@ pub mod tests {
     use crate::{
         arch::arm64::memory::pg_walk::{WalkContext, walk_and_modify_region},
         error::KernelError,
-        memory::address::{IdentityTranslator, PA, VA},
+        memory::{
+            address::{IdentityTranslator, PA, VA},
+            swap::SwapEntry,
+        },
     };
 
     /// A mock TLB invalidator that does nothing for unit testing.
@@ -545,6 +597,11 @@ pub mod tests {
             }
         }
 
+        /// Returns the number of page tables allocated, including the L0 table.
+        pub fn tables_allocated(&self) -> usize {
+            self.allocator.pages_allocated
+        }
+
         pub fn create_walk_ctx(&mut self) -> WalkContext<'_, PassthroughMapper> {
             WalkContext {
                 mapper: &mut self.mapper,
@@ -1085,4 +1142,58 @@ pub mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_install_desc() -> Result<()> {
+        let mut harness = TestHarness::new(4);
+        let va = VA::from_value(0x1_0000_0000);
+        let entry = SwapEntry::new(0, 7);
+
+        // The tables are created without mapping anything at `va` first.
+        install_desc(
+            harness.l0_table,
+            va,
+            L3Descriptor::new_swap_entry(entry),
+            &mut harness.create_map_ctx(),
+        )?;
+        assert_eq!(harness.tables_allocated(), 4);
+
+        let mut found = None;
+        walk_and_modify_region(
+            harness.l0_table,
+            VirtMemoryRegion::new(va, PAGE_SIZE),
+            &mut harness.create_walk_ctx(),
+            |_, desc| {
+                found = Some(desc);
+                desc
+            },
+        )?;
+        let found = found.unwrap();
+        assert_eq!(found.swap_entry(), Some(entry));
+        assert!(found.mapped_address().is_none());
+
+        // The slot is in use, even though nothing is mapped there.
+        let page = L3Descriptor::new_map_pa(
+            PA::from_value(0x9000),
+            MemoryType::Normal,
+            PtePermissions::rw(true),
+        );
+        let result = install_desc(harness.l0_table, va, page, &mut harness.create_map_ctx());
+        assert!(matches!(
+            result,
+            Err(KernelError::MappingError(MapError::AlreadyMapped))
+        ));
+
+        // A neighbouring page reuses the tables.
+        install_desc(
+            harness.l0_table,
+            va.add_pages(1),
+            page,
+            &mut harness.create_map_ctx(),
+        )?;
+        assert_eq!(harness.tables_allocated(), 4);
+        harness.verify_perms(va.add_pages(1), PtePermissions::rw(true));
+
+        Ok(())
+    }
 }
@@ -7,7 +7,7 @@ use crate::{
     error::{MapError, Result},
     memory::{
         PAGE_SIZE,
-        address::{TPA, VA},
+        address::{PA, TPA, VA},
         region::VirtMemoryRegion,
     },
 };
@@ -149,6 +149,126 @@ where
     L0Table::walk(l0_table, region, ctx, &mut modifier)
 }
 
+trait RecursiveTeardown: PgTable + Sized {
+    fn teardown<F, PM>(
+        table_pa: TPA<PgTableArray<Self>>,
+        region: VirtMemoryRegion,
+        mapper: &mut PM,
+        free_table: &mut F,
+    ) -> Result<()>
+    where
+        PM: PageTableMapper,
+        F: FnMut(PA);
+}
+
+impl<T> RecursiveTeardown for T
+where
+    T: TableMapperTable,
+    T::NextLevel: RecursiveTeardown,
+{
+    fn teardown<F, PM>(
+        table_pa: TPA<PgTableArray<Self>>,
+        region: VirtMemoryRegion,
+        mapper: &mut PM,
+        free_table: &mut F,
+    ) -> Result<()>
+    where
+        PM: PageTableMapper,
+        F: FnMut(PA),
+    {
+        let table_coverage = 1 << T::SHIFT;
+
+        let start_idx = Self::pg_index(region.start_address());
+        let end_idx = Self::pg_index(region.end_address_inclusive());
+
+        let table_base_va = region.start_address().align(1 << (T::SHIFT + 9));
+
+        for idx in start_idx..=end_idx {
+            let entry_va = table_base_va.add_bytes(idx * table_coverage);
+
+            let desc = unsafe {
+                mapper
+                    .with_page_table(table_pa, |pgtable| T::from_ptr(pgtable).get_desc(entry_va))?
+            };
+
+            let Some(next_table) = desc.next_table_address() else {
+                continue;
+            };
+
+            let entry_region = VirtMemoryRegion::new(entry_va, table_coverage);
+            let sub_region = entry_region
+                .intersection(region)
+                .expect("Sub region should overlap with parent region");
+
+            T::NextLevel::teardown(next_table.cast(), sub_region, mapper, free_table)?;
+
+            // A table is only freed if nothing outside of the region could be
+            // mapped by it.
+            if sub_region == entry_region {
+                unsafe {
+                    mapper.with_page_table(table_pa, |pgtable| {
+                        T::from_ptr(pgtable).set_desc(
+                            entry_va,
+                            T::Descriptor::invalid(),
+                            &NullTlbInvalidator {},
+                        )
+                    })?
+                };
+
+                free_table(next_table);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RecursiveTeardown for L3Table {
+    fn teardown<F, PM>(
+        _table_pa: TPA<PgTableArray<Self>>,
+        _region: VirtMemoryRegion,
+        _mapper: &mut PM,
+        _free_table: &mut F,
+    ) -> Result<()>
+    where
+        PM: PageTableMapper,
+        F: FnMut(PA),
+    {
+        // The pages mapped by an L3 table are the caller's concern.
+        Ok(())
+    }
+}
+
+/// Frees the page tables below the root (L0) table which only map addresses
+/// within `region`, passing the physical address of each to `free_table`.
+///
+/// The pages mapped by the tables aren't touched, so should already have been
+/// unmapped. Nor is the TLB invalidated: the tables must not be in use by any
+/// CPU, e.g. because the address space that they belong to is being destroyed.
+///
+/// # Errors
+/// - `MapError::VirtNotAligned`: The provided `region` is not page-aligned.
+pub fn free_page_tables<F, PM>(
+    l0_table: TPA<PgTableArray<L0Table>>,
+    region: VirtMemoryRegion,
+    mapper: &mut PM,
+    mut free_table: F,
+) -> Result<()>
+where
+    PM: PageTableMapper,
+    F: FnMut(PA),
+{
+    if !region.is_page_aligned() {
+        Err(MapError::VirtNotAligned)?;
+    }
+
+    if region.size() == 0 {
+        return Ok(());
+    }
+
+    L0Table::teardown(l0_table, region, mapper, &mut free_table)
+}
+
 /// Obtain the PTE that mapps the VA into the current address space.
 pub fn get_pte<PM: PageTableMapper>(
     l0_table: TPA<PgTableArray<L0Table>>,
@@ -445,4 +565,78 @@ mod tests {
             Err(KernelError::MappingError(MapError::VirtNotAligned))
         ));
     }
+
+    #[test]
+    fn free_page_tables_all() {
+        let mut harness = TestHarness::new(20);
+
+        // Two mappings that don't share any table below L0.
+        harness
+            .map_4k_pages(0x8_0000, 0x1_0000_0000, 2, PtePermissions::rw(false))
+            .unwrap();
+        harness
+            .map_4k_pages(0x9_0000, 0x80_0000_0000, 1, PtePermissions::rw(false))
+            .unwrap();
+
+        let mut freed = Vec::new();
+
+        free_page_tables(
+            harness.l0_table,
+            VirtMemoryRegion::new(VA::from_value(0), 1 << 48),
+            &mut harness.mapper,
+            |table| freed.push(table),
+        )
+        .unwrap();
+
+        // Everything but the L0 table is freed, and each table only once.
+        assert_eq!(freed.len(), harness.tables_allocated() - 1);
+        freed.sort();
+        freed.dedup();
+        assert_eq!(freed.len(), harness.tables_allocated() - 1);
+        assert!(!freed.contains(&harness.l0_table.to_untyped()));
+
+        // The L0 table no longer refers to them.
+        assert!(
+            get_pte(
+                harness.l0_table,
+                VA::from_value(0x1_0000_0000),
+                &mut harness.mapper
+            )
+            .unwrap()
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn free_page_tables_partial() {
+        let mut harness = TestHarness::new(20);
+        let va1 = 0x1_0000_0000;
+        let va2 = va1 + (1 << 21); // The next L3 table.
+
+        harness
+            .map_4k_pages(0x8_0000, va1, 1, PtePermissions::rw(false))
+            .unwrap();
+        harness
+            .map_4k_pages(0x9_0000, va2, 1, PtePermissions::rw(false))
+            .unwrap();
+
+        let mut freed = Vec::new();
+
+        free_page_tables(
+            harness.l0_table,
+            VirtMemoryRegion::new(VA::from_value(va1), 1 << 21),
+            &mut harness.mapper,
+            |table| freed.push(table),
+        )
+        .unwrap();
+
+        // Only the first L3 table is freed; the L1 and L2 tables also map the
+        // second page.
+        assert_eq!(freed.len(), 1);
+        assert!(
+            get_pte(harness.l0_table, VA::from_value(va2), &mut harness.mapper)
+                .unwrap()
+                .is_some()
+        );
+    }
 }
@@ -1,6 +1,8 @@
 use crate::memory::address::VA;
 pub trait TLBInvalidator {
-    fn invalidate_page(&self, va: VA);
+    /// Invalidates any cached translation for `va`. Invalidators which flush
+    /// the whole TLB when dropped need not override this.
+    fn invalidate_page(&self, _va: VA) {}
 }
 
 pub struct NullTlbInvalidator {}
@@ -5,6 +5,7 @@ use crate::memory::PAGE_SHIFT;
 use crate::memory::address::{PA, VA};
 use crate::memory::permissions::PtePermissions;
 use crate::memory::region::PhysMemoryRegion;
+use crate::memory::swap::SwapEntry;
 
 /// Trait for common behavior across different types of page table entries.
 pub trait PageTableEntry: Sized + Copy + Clone {
@@ -74,7 +75,7 @@ register_bitfields![u64,
         ACCESSED  OFFSET(6) NUMBITS(1) [],
         DIRTY     OFFSET(7) NUMBITS(1) [],
         // Software defined bits in RSW (bits 8-9)
-        COW       OFFSET(8) NUMBITS(1) [], 
+        COW       OFFSET(8) NUMBITS(1) [],
         // PPN is bits 10-53
         PPN       OFFSET(10) NUMBITS(44) [],
         // Reserved/Pbmt bits 54-63
@@ -105,7 +106,7 @@ macro_rules! define_descriptor {
         pub struct $name(u64);
 
         impl PageTableEntry for $name {
-            fn is_valid(self) -> bool { 
+            fn is_valid(self) -> bool {
                 let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
                 reg.is_set(CommonFields::VALID)
             }
@@ -118,14 +119,14 @@ macro_rules! define_descriptor {
             impl TableMapper for $name {
                 fn next_table_address(self) -> Option<PA> {
                      // Macro syntax requirement: use the captured variable
-                     const _IS_TABLE: bool = $can_table; 
+                     const _IS_TABLE: bool = $can_table;
 
                      let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
                      // A valid non-leaf PTE has V=1, and R=W=X=0
                      if reg.is_set(CommonFields::VALID)
                         && !reg.is_set(CommonFields::READ)
                         && !reg.is_set(CommonFields::WRITE)
-                        && !reg.is_set(CommonFields::EXECUTE) 
+                        && !reg.is_set(CommonFields::EXECUTE)
                     {
                         let ppn = reg.read(CommonFields::PPN);
                         Some(TableAddr::from_raw_ppn(ppn).0)
@@ -137,10 +138,10 @@ macro_rules! define_descriptor {
                 fn new_next_table(pa: PA) -> Self {
                     let reg = InMemoryRegister::<u64, CommonFields::Register>::new(0);
                     let ppn = (pa.value() >> PAGE_SHIFT) as u64;
-                    
-                    reg.modify(CommonFields::VALID::SET 
+
+                    reg.modify(CommonFields::VALID::SET
                         + CommonFields::PPN.val(ppn));
-                    
+
                     Self(reg.get())
                 }
             }
@@ -151,7 +152,7 @@ macro_rules! define_descriptor {
                 /// Returns the interpreted permissions
                 pub fn permissions(self) -> Option<PtePermissions> {
                     let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
-                    
+
                     if !reg.is_set(CommonFields::VALID) {
                         return None;
                     }
@@ -159,7 +160,7 @@ macro_rules! define_descriptor {
                     let r = reg.is_set(CommonFields::READ);
                     let w = reg.is_set(CommonFields::WRITE);
                     let x = reg.is_set(CommonFields::EXECUTE);
-                    
+
                     if !r && !x {
                         return None;
                     }
@@ -168,31 +169,31 @@ macro_rules! define_descriptor {
                     let cow = reg.is_set(CommonFields::COW);
 
                     Some(PtePermissions::from_raw_bits(
-                        true, 
-                        w,    
-                        x,    
-                        user, 
-                        cow,  
+                        true,
+                        w,
+                        x,
+                        user,
+                        cow,
                     ))
                 }
 
                 pub fn set_permissions(self, perms: PtePermissions) -> Self {
                     let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
 
-                    if perms.is_user() { reg.modify(CommonFields::USER::SET); } 
+                    if perms.is_user() { reg.modify(CommonFields::USER::SET); }
                     else { reg.modify(CommonFields::USER::CLEAR); }
 
                     reg.modify(CommonFields::READ::SET);
 
-                    if perms.is_write() { reg.modify(CommonFields::WRITE::SET); } 
+                    if perms.is_write() { reg.modify(CommonFields::WRITE::SET); }
                     else { reg.modify(CommonFields::WRITE::CLEAR); }
 
-                    if perms.is_execute() { reg.modify(CommonFields::EXECUTE::SET); } 
+                    if perms.is_execute() { reg.modify(CommonFields::EXECUTE::SET); }
                     else { reg.modify(CommonFields::EXECUTE::CLEAR); }
 
-                    if perms.is_cow() { reg.modify(CommonFields::COW::SET); } 
+                    if perms.is_cow() { reg.modify(CommonFields::COW::SET); }
                     else { reg.modify(CommonFields::COW::CLEAR); }
-                    
+
                     Self(reg.get())
                 }
             }
@@ -214,12 +215,12 @@ macro_rules! define_descriptor {
                     }
 
                     let reg = InMemoryRegister::<u64, CommonFields::Register>::new(0);
-                    
+
                     let ppn = (page_address.value() >> PAGE_SHIFT) as u64;
                     reg.modify(CommonFields::PPN.val(ppn));
-                    
-                    reg.modify(CommonFields::VALID::SET 
-                        + CommonFields::ACCESSED::SET 
+
+                    reg.modify(CommonFields::VALID::SET
+                        + CommonFields::ACCESSED::SET
                         + CommonFields::DIRTY::SET);
 
                     match memory_type {
@@ -236,11 +237,11 @@ macro_rules! define_descriptor {
 
                 fn mapped_address(self) -> Option<PA> {
                     let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
-                    
+
                     if !reg.is_set(CommonFields::VALID) { return None; }
 
                     if !reg.is_set(CommonFields::READ) && !reg.is_set(CommonFields::EXECUTE) {
-                        return None; 
+                        return None;
                     }
 
                     let ppn = reg.read(CommonFields::PPN);
@@ -254,27 +255,27 @@ macro_rules! define_descriptor {
 define_descriptor!(
     /// A Level 0 descriptor. (Root in Sv48)
     L0Descriptor,
-    table: true, 
+    table: true,
     map: {
-        shift: 39,     
+        shift: 39,
     },
 );
 
 define_descriptor!(
-    /// A Level 1 descriptor. 
+    /// A Level 1 descriptor.
     L1Descriptor,
     table: true,
     map: {
-        shift: 30,     
+        shift: 30,
     },
 );
 
 define_descriptor!(
-    /// A Level 2 descriptor. 
+    /// A Level 2 descriptor.
     L2Descriptor,
     table: true,
     map: {
-        shift: 21,    
+        shift: 21,
     },
 );
 
@@ -282,7 +283,7 @@ define_descriptor!(
     /// A Level 3 descriptor. The standard 4K Page.
     L3Descriptor,
     map: {
-        shift: 12,    
+        shift: 12,
     },
 );
 
@@ -293,7 +294,7 @@ pub enum L3DescriptorState {
 }
 
 impl L3Descriptor {
-    const SWAPPED_MASK: u64 = 1 << 63; 
+    const SWAPPED_MASK: u64 = 1 << 63;
 
     pub fn state(self) -> L3DescriptorState {
         if self.is_valid() {
@@ -310,4 +311,78 @@ impl L3Descriptor {
         reg.modify(CommonFields::VALID::CLEAR);
         Self::from_raw(reg.get() | Self::SWAPPED_MASK)
     }
-}
\ No newline at end of file
+
+    // A swap entry is held in the PPN field of a descriptor with V=0, tagged
+    // with the second RSW bit.
+    const SWAP_ENTRY_BIT: u64 = 1 << 9;
+
+    /// Creates a non-present descriptor for a page which has been swapped
+    /// out to `entry`.
+    pub fn new_swap_entry(entry: SwapEntry) -> Self {
+        let reg = InMemoryRegister::<u64, CommonFields::Register>::new(Self::SWAP_ENTRY_BIT);
+        reg.modify(CommonFields::PPN.val(entry.as_raw()));
+        Self(reg.get())
+    }
+
+    /// Returns the swap entry held by this descriptor, if it's for a page
+    /// which has been swapped out.
+    pub fn swap_entry(self) -> Option<SwapEntry> {
+        let tagged = self.0 & (Self::SWAPPED_MASK | Self::SWAP_ENTRY_BIT) == Self::SWAP_ENTRY_BIT;
+
+        if self.is_valid() || !tagged {
+            return None;
+        }
+
+        let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
+        Some(SwapEntry::from_raw(reg.read(CommonFields::PPN)))
+    }
+
+    /// Returns `true` if the accessed bit is set.
+    pub fn is_accessed(self) -> bool {
+        let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
+        reg.is_set(CommonFields::ACCESSED)
+    }
+
+    /// Sets or clears the accessed bit.
+    pub fn set_accessed(self, accessed: bool) -> Self {
+        let reg = InMemoryRegister::<u64, CommonFields::Register>::new(self.0);
+        if accessed {
+            reg.modify(CommonFields::ACCESSED::SET);
+        } else {
+            reg.modify(CommonFields::ACCESSED::CLEAR);
+        }
+        Self(reg.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::PAGE_SIZE;
+
+    #[test]
+    fn test_l3_swap_entry() {
+        let entry = SwapEntry::new(1, 0x42);
+        let d = L3Descriptor::new_swap_entry(entry);
+
+        // The entry is tagged with RSW bit 9, and V is clear so that the MMU
+        // never walks it.
+        assert!(!d.is_valid());
+        assert_ne!(d.as_raw() & (1 << 9), 0);
+        assert_eq!(d.swap_entry(), Some(entry));
+        assert!(d.mapped_address().is_none());
+        assert!(d.permissions().is_none());
+
+        // Every bit of the entry fits in the PPN field.
+        let max = SwapEntry::from_raw(u64::MAX);
+        let d = L3Descriptor::new_swap_entry(max);
+        assert!(!d.is_valid());
+        assert_eq!(d.swap_entry(), Some(max));
+
+        let pa = PA::from_value(PAGE_SIZE * 8);
+        let page = L3Descriptor::new_map_pa(pa, MemoryType::Normal, PtePermissions::rw(true));
+        assert!(page.swap_entry().is_none());
+        assert!(page.mark_as_swapped().swap_entry().is_none());
+        assert!(L3Descriptor::invalid().swap_entry().is_none());
+    }
+}
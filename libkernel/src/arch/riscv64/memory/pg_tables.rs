@@ -139,6 +139,13 @@ impl TableMapperTable for L2Table {
 impl_pgtable!(L3Table, 12, L3Descriptor);
 
 pub trait PageTableMapper {
+    /// Map a physical address to a usable reference of the page table, run the
+    /// closure, and unmap.
+    ///
+    /// # Safety
+    /// This function is `unsafe` because the caller must ensure:
+    /// - The given physical address `pa` is valid and correctly aligned for type `T`.
+    /// - The contents at that physical address represent a valid page table of type `T`.
     unsafe fn with_page_table<T: PgTable, R>(
         &mut self,
         pa: TPA<PgTableArray<T>>,
@@ -222,6 +229,45 @@ where
     Ok(())
 }
 
+/// Installs `desc` as the L3 descriptor for the page at `va`, allocating any
+/// intermediate tables that are needed. Unlike [`map_range`], the descriptor
+/// is written as given, so non-present descriptors such as swap entries can be
+/// installed too.
+pub fn install_desc<PA, PM>(
+    l0_table: TPA<PgTableArray<L0Table>>,
+    va: VA,
+    desc: L3Descriptor,
+    ctx: &mut MappingContext<PA, PM>,
+) -> Result<()>
+where
+    PA: PageAllocator,
+    PM: PageTableMapper,
+{
+    if !va.is_page_aligned() {
+        Err(MapError::VirtNotAligned)?
+    }
+
+    let l1 = map_at_level(l0_table, va, ctx)?;
+    let l2 = map_at_level(l1, va, ctx)?;
+    let l3 = map_at_level(l2, va, ctx)?;
+
+    unsafe {
+        ctx.mapper.with_page_table(l3, |tbl| {
+            let table = L3Table::from_ptr(tbl);
+
+            // A non-present descriptor, such as a swap entry, still occupies
+            // the slot.
+            if table.get_desc(va).as_raw() != 0 {
+                Err(MapError::AlreadyMapped)?
+            }
+
+            table.set_desc(va, desc, ctx.invalidator);
+
+            Ok(())
+        })?
+    }
+}
+
 fn try_map_pa<L, PA, PM>(
     table: TPA<PgTableArray<L>>,
     va: VA,
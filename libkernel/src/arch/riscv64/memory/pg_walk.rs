@@ -7,7 +7,7 @@ use crate::{
     error::{MapError, Result},
     memory::{
         PAGE_SIZE,
-        address::{TPA, VA},
+        address::{PA, TPA, VA},
         region::VirtMemoryRegion,
     },
 };
@@ -94,7 +94,10 @@ impl RecursiveWalker for L3Table {
                 let table = L3Table::from_ptr(pgtable);
                 for va in region.iter_pages() {
                     let desc = table.get_desc(va);
-                    if desc.is_valid() {
+                    // Visit non-present entries which still hold information,
+                    // such as swap entries, too. Modifiers must check for a
+                    // swap entry before treating a descriptor as mapped.
+                    if desc.as_raw() != 0 {
                         table.set_desc(va, modifier(va, desc), ctx.invalidator);
                     }
                 }
@@ -124,6 +127,126 @@ where
     L0Table::walk(l0_table, region, ctx, &mut modifier)
 }
 
+trait RecursiveTeardown: PgTable + Sized {
+    fn teardown<F, PM>(
+        table_pa: TPA<PgTableArray<Self>>,
+        region: VirtMemoryRegion,
+        mapper: &mut PM,
+        free_table: &mut F,
+    ) -> Result<()>
+    where
+        PM: PageTableMapper,
+        F: FnMut(PA);
+}
+
+impl<T> RecursiveTeardown for T
+where
+    T: TableMapperTable,
+    T::NextLevel: RecursiveTeardown,
+{
+    fn teardown<F, PM>(
+        table_pa: TPA<PgTableArray<Self>>,
+        region: VirtMemoryRegion,
+        mapper: &mut PM,
+        free_table: &mut F,
+    ) -> Result<()>
+    where
+        PM: PageTableMapper,
+        F: FnMut(PA),
+    {
+        let table_coverage = 1 << T::SHIFT;
+
+        let start_idx = Self::pg_index(region.start_address());
+        let end_idx = Self::pg_index(region.end_address_inclusive());
+
+        let table_base_va = region.start_address().align(1 << (T::SHIFT + 9));
+
+        for idx in start_idx..=end_idx {
+            let entry_va = table_base_va.add_bytes(idx * table_coverage);
+
+            let desc = unsafe {
+                mapper
+                    .with_page_table(table_pa, |pgtable| T::from_ptr(pgtable).get_desc(entry_va))?
+            };
+
+            let Some(next_table) = desc.next_table_address() else {
+                continue;
+            };
+
+            let entry_region = VirtMemoryRegion::new(entry_va, table_coverage);
+            let sub_region = entry_region
+                .intersection(region)
+                .expect("Sub region should overlap with parent region");
+
+            T::NextLevel::teardown(next_table.cast(), sub_region, mapper, free_table)?;
+
+            // A table is only freed if nothing outside of the region could be
+            // mapped by it.
+            if sub_region == entry_region {
+                unsafe {
+                    mapper.with_page_table(table_pa, |pgtable| {
+                        T::from_ptr(pgtable).set_desc(
+                            entry_va,
+                            T::Descriptor::invalid(),
+                            &NullTlbInvalidator {},
+                        )
+                    })?
+                };
+
+                free_table(next_table);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RecursiveTeardown for L3Table {
+    fn teardown<F, PM>(
+        _table_pa: TPA<PgTableArray<Self>>,
+        _region: VirtMemoryRegion,
+        _mapper: &mut PM,
+        _free_table: &mut F,
+    ) -> Result<()>
+    where
+        PM: PageTableMapper,
+        F: FnMut(PA),
+    {
+        // The pages mapped by an L3 table are the caller's concern.
+        Ok(())
+    }
+}
+
+/// Frees the page tables below the root (L0) table which only map addresses
+/// within `region`, passing the physical address of each to `free_table`.
+///
+/// The pages mapped by the tables aren't touched, so should already have been
+/// unmapped. Nor is the TLB invalidated: the tables must not be in use by any
+/// CPU, e.g. because the address space that they belong to is being destroyed.
+///
+/// # Errors
+/// - `MapError::VirtNotAligned`: The provided `region` is not page-aligned.
+pub fn free_page_tables<F, PM>(
+    l0_table: TPA<PgTableArray<L0Table>>,
+    region: VirtMemoryRegion,
+    mapper: &mut PM,
+    mut free_table: F,
+) -> Result<()>
+where
+    PM: PageTableMapper,
+    F: FnMut(PA),
+{
+    if !region.is_page_aligned() {
+        Err(MapError::VirtNotAligned)?;
+    }
+
+    if region.size() == 0 {
+        return Ok(());
+    }
+
+    L0Table::teardown(l0_table, region, mapper, &mut free_table)
+}
+
 pub fn get_pte<PM: PageTableMapper>(
     l0_table: TPA<PgTableArray<L0Table>>,
     va: VA,
@@ -147,4 +270,4 @@ pub fn get_pte<PM: PageTableMapper>(
     )?;
 
     Ok(descriptor)
-}
\ No newline at end of file
+}
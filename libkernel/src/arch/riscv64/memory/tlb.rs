@@ -1,4 +1,5 @@
 use crate::memory::address::VA;
+#[cfg(target_arch = "riscv64")]
 use core::arch::asm;
 
 pub trait TLBInvalidator {
@@ -15,18 +16,19 @@ impl TLBInvalidator for NullTlbInvalidator {
 pub struct AllTlbInvalidator;
 
 impl TLBInvalidator for AllTlbInvalidator {
-    fn invalidate_page(&self, va: VA) {
+    fn invalidate_page(&self, _va: VA) {
+        #[cfg(target_arch = "riscv64")]
         unsafe {
-            asm!("sfence.vma {}", in(reg) va.value());
+            asm!("sfence.vma {}", in(reg) _va.value());
         }
     }
 }
 
-
 impl Drop for AllTlbInvalidator {
     fn drop(&mut self) {
+        #[cfg(target_arch = "riscv64")]
         unsafe {
             asm!("sfence.vma x0, x0");
         }
     }
-}
\ No newline at end of file
+}
@@ -69,6 +69,8 @@ pub enum FsError {
     DriverNotFound,
     #[error("Too many open files")]
     TooManyFiles,
+    #[error("File too large")]
+    FileTooLarge,
     #[error("The device could not be found")]
     NoDevice,
 }
@@ -79,6 +81,10 @@ pub enum ExecError {
     InvalidElfFormat,
     #[error("Invalid Porgram Header Format")]
     InvalidPHdrFormat,
+    #[error("Exec format error")]
+    UnknownFormat,
+    #[error("Too many levels of interpreters")]
+    TooManyInterpreters,
 }
 
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
@@ -117,10 +123,18 @@ pub enum KernelError {
     BrokenPipe,
     #[error("Operation not permitted")]
     NotPermitted,
+    #[error("Permission denied")]
+    AccessDenied,
     #[error("Buffer is full")]
     BufferFull,
     #[error("No such process")]
     NoProcess,
+    #[error("No child processes")]
+    NoChild,
+    #[error("Resource temporarily unavailable")]
+    TryAgain,
+    #[error("Loop detected")]
+    Loop,
     #[error("Not implemented")]
     NotImplemented,
     #[error("Name too long")]
@@ -1,4 +1,4 @@
-use crate::error::FsError;
+use crate::error::{ExecError, FsError};
 
 use super::KernelError;
 
@@ -38,6 +38,7 @@ pub const EDOM: isize = -33;
 pub const ERANGE: isize = -34;
 pub const EWOULDBLOCK: isize = -EAGAIN;
 pub const ENOSYS: isize = -38;
+pub const ELOOP: isize = -40;
 
 pub fn kern_err_to_syscall(err: KernelError) -> isize {
     match err {
@@ -46,10 +47,24 @@ pub fn kern_err_to_syscall(err: KernelError) -> isize {
         KernelError::Fault => EFAULT,
         KernelError::BrokenPipe => EPIPE,
         KernelError::Fs(FsError::NotFound) => ENOENT,
+        KernelError::Fs(FsError::AlreadyExists) => EEXIST,
+        KernelError::Fs(FsError::TooManyFiles) => EMFILE,
+        KernelError::Fs(FsError::FileTooLarge) => EFBIG,
+        KernelError::Fs(FsError::NoDevice) => ENODEV,
         KernelError::NotATty => ENOTTY,
         KernelError::SeekPipe => ESPIPE,
         KernelError::NotSupported => ENOSYS,
         KernelError::NoMemory => ENOMEM,
+        KernelError::TooLarge => E2BIG,
+        KernelError::NotPermitted => EPERM,
+        KernelError::AccessDenied => EACCES,
+        KernelError::NoProcess => ESRCH,
+        KernelError::NoChild => ECHILD,
+        KernelError::TryAgain => EAGAIN,
+        KernelError::InUse => EBUSY,
+        KernelError::Loop => ELOOP,
+        KernelError::Exec(ExecError::TooManyInterpreters) => ELOOP,
+        KernelError::Exec(_) => ENOEXEC,
         _ => todo!(),
     }
 }
@@ -19,6 +19,18 @@ bitflags::bitflags! {
     }
 }
 
+bitflags! {
+    /// The capabilities which let a process bypass file permission checks.
+    #[derive(Debug, Clone, Copy)]
+    pub struct DacCaps: u32 {
+        /// `CAP_DAC_OVERRIDE`: bypasses read, write and execute checks.
+        const OVERRIDE = 1 << 0;
+        /// `CAP_DAC_READ_SEARCH`: bypasses read checks, and search checks on
+        /// directories.
+        const READ_SEARCH = 1 << 1;
+    }
+}
+
 bitflags! {
     #[derive(Clone, Copy, Debug)]
     pub struct FilePermissions: u16 {
@@ -86,23 +98,50 @@ impl FileAttr {
     /// # Arguments
     /// * `uid` - The user-ID that will be checked against this file's uid field.
     /// * `gid` - The group-ID that will be checked against this file's uid field.
+    /// * `caps` - The capabilities held which bypass permission checks.
     /// * `requested_mode` - A bitmask of `AccessMode` flags (`R_OK`, `W_OK`, `X_OK`) to check.
-    pub fn check_access(&self, uid: Uid, gid: Gid, requested_mode: AccessMode) -> Result<()> {
-        // root (UID 0) bypasses most permission checks. For execute, at
-        // least one execute bit must be set.
-        if uid.is_root() {
-            if requested_mode.contains(AccessMode::X_OK) {
-                // Root still needs at least one execute bit to be set for X_OK
-                if self.mode.intersects(
+    pub fn check_access(
+        &self,
+        uid: Uid,
+        gid: Gid,
+        caps: DacCaps,
+        requested_mode: AccessMode,
+    ) -> Result<()> {
+        if self.mode_permits(uid, gid, requested_mode) {
+            return Ok(());
+        }
+
+        let is_dir = matches!(self.file_type, FileType::Directory);
+
+        // CAP_DAC_OVERRIDE bypasses every check, except that for X_OK, a file
+        // other than a directory still needs at least one execute bit set.
+        if caps.contains(DacCaps::OVERRIDE)
+            && (!requested_mode.contains(AccessMode::X_OK)
+                || is_dir
+                || self.mode.intersects(
                     FilePermissions::S_IXUSR | FilePermissions::S_IXGRP | FilePermissions::S_IXOTH,
-                ) {
-                    return Ok(());
-                }
-            } else {
-                return Ok(());
-            }
+                ))
+        {
+            return Ok(());
+        }
+
+        // CAP_DAC_READ_SEARCH bypasses read checks, and search checks on
+        // directories.
+        let read_search = if is_dir {
+            AccessMode::R_OK | AccessMode::X_OK
+        } else {
+            AccessMode::R_OK
+        };
+
+        if caps.contains(DacCaps::READ_SEARCH) && read_search.contains(requested_mode) {
+            return Ok(());
         }
 
+        Err(KernelError::AccessDenied)
+    }
+
+    /// Returns true if the file's mode bits alone grant `requested_mode`.
+    fn mode_permits(&self, uid: Uid, gid: Gid, requested_mode: AccessMode) -> bool {
         // Determine which set of permission bits to use (owner, group, or other)
         let perms_to_check = if self.uid == uid {
             // User is the owner
@@ -115,23 +154,19 @@ impl FileAttr {
             FilePermissions::from_bits_truncate(self.mode.bits() << 6)
         };
 
-        if requested_mode.contains(AccessMode::R_OK)
-            && !perms_to_check.contains(FilePermissions::S_IRUSR)
-        {
-            return Err(KernelError::NotPermitted);
+        let mut required = FilePermissions::empty();
+
+        if requested_mode.contains(AccessMode::R_OK) {
+            required |= FilePermissions::S_IRUSR;
         }
-        if requested_mode.contains(AccessMode::W_OK)
-            && !perms_to_check.contains(FilePermissions::S_IWUSR)
-        {
-            return Err(KernelError::NotPermitted);
+        if requested_mode.contains(AccessMode::W_OK) {
+            required |= FilePermissions::S_IWUSR;
         }
-        if requested_mode.contains(AccessMode::X_OK)
-            && !perms_to_check.contains(FilePermissions::S_IXUSR)
-        {
-            return Err(KernelError::NotPermitted);
+        if requested_mode.contains(AccessMode::X_OK) {
+            required |= FilePermissions::S_IXUSR;
         }
 
-        Ok(())
+        perms_to_check.contains(required)
     }
 }
 
@@ -162,7 +197,7 @@ mod tests {
     fn root_can_read_without_perms() {
         let file = setup_file(FilePermissions::empty());
         assert!(
-            file.check_access(ROOT_UID, ROOT_GID, AccessMode::R_OK)
+            file.check_access(ROOT_UID, ROOT_GID, DacCaps::all(), AccessMode::R_OK)
                 .is_ok()
         );
     }
@@ -171,7 +206,7 @@ mod tests {
     fn root_can_write_without_perms() {
         let file = setup_file(FilePermissions::empty());
         assert!(
-            file.check_access(ROOT_UID, ROOT_GID, AccessMode::W_OK)
+            file.check_access(ROOT_UID, ROOT_GID, DacCaps::all(), AccessMode::W_OK)
                 .is_ok()
         );
     }
@@ -179,15 +214,15 @@ mod tests {
     #[test]
     fn root_cannot_execute_if_no_exec_bits_are_set() {
         let file = setup_file(FilePermissions::S_IRUSR | FilePermissions::S_IWUSR);
-        let result = file.check_access(ROOT_UID, ROOT_GID, AccessMode::X_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(ROOT_UID, ROOT_GID, DacCaps::all(), AccessMode::X_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
     fn root_can_execute_if_owner_exec_bit_is_set() {
         let file = setup_file(FilePermissions::S_IXUSR);
         assert!(
-            file.check_access(ROOT_UID, ROOT_GID, AccessMode::X_OK)
+            file.check_access(ROOT_UID, ROOT_GID, DacCaps::all(), AccessMode::X_OK)
                 .is_ok()
         );
     }
@@ -196,7 +231,7 @@ mod tests {
     fn root_can_execute_if_group_exec_bit_is_set() {
         let file = setup_file(FilePermissions::S_IXGRP);
         assert!(
-            file.check_access(ROOT_UID, ROOT_GID, AccessMode::X_OK)
+            file.check_access(ROOT_UID, ROOT_GID, DacCaps::all(), AccessMode::X_OK)
                 .is_ok()
         );
     }
@@ -205,7 +240,7 @@ mod tests {
     fn root_can_execute_if_other_exec_bit_is_set() {
         let file = setup_file(FilePermissions::S_IXOTH);
         assert!(
-            file.check_access(ROOT_UID, ROOT_GID, AccessMode::X_OK)
+            file.check_access(ROOT_UID, ROOT_GID, DacCaps::all(), AccessMode::X_OK)
                 .is_ok()
         );
     }
@@ -214,7 +249,7 @@ mod tests {
     fn owner_can_read_when_permitted() {
         let file = setup_file(FilePermissions::S_IRUSR);
         assert!(
-            file.check_access(OWNER_UID, OWNER_GID, AccessMode::R_OK)
+            file.check_access(OWNER_UID, OWNER_GID, DacCaps::empty(), AccessMode::R_OK)
                 .is_ok()
         );
     }
@@ -222,15 +257,15 @@ mod tests {
     #[test]
     fn owner_cannot_read_when_denied() {
         let file = setup_file(FilePermissions::S_IWUSR | FilePermissions::S_IXUSR);
-        let result = file.check_access(OWNER_UID, OWNER_GID, AccessMode::R_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(OWNER_UID, OWNER_GID, DacCaps::empty(), AccessMode::R_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
     fn owner_can_write_when_permitted() {
         let file = setup_file(FilePermissions::S_IWUSR);
         assert!(
-            file.check_access(OWNER_UID, OWNER_GID, AccessMode::W_OK)
+            file.check_access(OWNER_UID, OWNER_GID, DacCaps::empty(), AccessMode::W_OK)
                 .is_ok()
         );
     }
@@ -238,8 +273,8 @@ mod tests {
     #[test]
     fn owner_cannot_write_when_denied() {
         let file = setup_file(FilePermissions::S_IRUSR);
-        let result = file.check_access(OWNER_UID, OWNER_GID, AccessMode::W_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(OWNER_UID, OWNER_GID, DacCaps::empty(), AccessMode::W_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
@@ -248,45 +283,63 @@ mod tests {
             FilePermissions::S_IRUSR | FilePermissions::S_IWUSR | FilePermissions::S_IXUSR,
         );
         let mode = AccessMode::R_OK | AccessMode::W_OK | AccessMode::X_OK;
-        assert!(file.check_access(OWNER_UID, OWNER_GID, mode).is_ok());
+        assert!(
+            file.check_access(OWNER_UID, OWNER_GID, DacCaps::empty(), mode)
+                .is_ok()
+        );
     }
 
     #[test]
     fn owner_access_denied_if_one_of_many_perms_is_missing() {
         let file = setup_file(FilePermissions::S_IRUSR | FilePermissions::S_IXUSR);
         let mode = AccessMode::R_OK | AccessMode::W_OK | AccessMode::X_OK; // Requesting Write is denied
-        let result = file.check_access(OWNER_UID, OWNER_GID, mode);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(OWNER_UID, OWNER_GID, DacCaps::empty(), mode);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
     fn group_member_can_read_when_permitted() {
         let file = setup_file(FilePermissions::S_IRGRP);
         assert!(
-            file.check_access(GROUP_MEMBER_UID, FILE_GROUP_GID, AccessMode::R_OK)
-                .is_ok()
+            file.check_access(
+                GROUP_MEMBER_UID,
+                FILE_GROUP_GID,
+                DacCaps::empty(),
+                AccessMode::R_OK
+            )
+            .is_ok()
         );
     }
 
     #[test]
     fn group_member_cannot_write_when_owner_can() {
         let file = setup_file(FilePermissions::S_IWUSR | FilePermissions::S_IRGRP);
-        let result = file.check_access(GROUP_MEMBER_UID, FILE_GROUP_GID, AccessMode::W_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(
+            GROUP_MEMBER_UID,
+            FILE_GROUP_GID,
+            DacCaps::empty(),
+            AccessMode::W_OK,
+        );
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
     fn group_member_cannot_read_when_denied() {
         let file = setup_file(FilePermissions::S_IWGRP);
-        let result = file.check_access(GROUP_MEMBER_UID, FILE_GROUP_GID, AccessMode::R_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(
+            GROUP_MEMBER_UID,
+            FILE_GROUP_GID,
+            DacCaps::empty(),
+            AccessMode::R_OK,
+        );
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
     fn other_can_execute_when_permitted() {
         let file = setup_file(FilePermissions::S_IXOTH);
         assert!(
-            file.check_access(OTHER_UID, OTHER_GID, AccessMode::X_OK)
+            file.check_access(OTHER_UID, OTHER_GID, DacCaps::empty(), AccessMode::X_OK)
                 .is_ok()
         );
     }
@@ -294,15 +347,15 @@ mod tests {
     #[test]
     fn other_cannot_read_when_only_owner_and_group_can() {
         let file = setup_file(FilePermissions::S_IRUSR | FilePermissions::S_IRGRP);
-        let result = file.check_access(OTHER_UID, OTHER_GID, AccessMode::R_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(OTHER_UID, OTHER_GID, DacCaps::empty(), AccessMode::R_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
     fn other_cannot_write_when_denied() {
         let file = setup_file(FilePermissions::S_IROTH);
-        let result = file.check_access(OTHER_UID, OTHER_GID, AccessMode::W_OK);
-        assert!(matches!(result, Err(KernelError::NotPermitted)));
+        let result = file.check_access(OTHER_UID, OTHER_GID, DacCaps::empty(), AccessMode::W_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
     }
 
     #[test]
@@ -310,7 +363,7 @@ mod tests {
         // Checking for nothing should always succeed if the file exists.
         let file = setup_file(FilePermissions::empty());
         assert!(
-            file.check_access(OTHER_UID, OTHER_GID, AccessMode::empty())
+            file.check_access(OTHER_UID, OTHER_GID, DacCaps::empty(), AccessMode::empty())
                 .is_ok()
         );
     }
@@ -320,8 +373,65 @@ mod tests {
         let file = setup_file(FilePermissions::S_IROTH); // Only other can read
         // This user is not the owner and not in the file's group.
         assert!(
-            file.check_access(GROUP_MEMBER_UID, OTHER_GID, AccessMode::R_OK)
+            file.check_access(
+                GROUP_MEMBER_UID,
+                OTHER_GID,
+                DacCaps::empty(),
+                AccessMode::R_OK
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn root_without_caps_is_checked_like_other_users() {
+        let file = setup_file(FilePermissions::S_IROTH);
+        assert!(
+            file.check_access(ROOT_UID, ROOT_GID, DacCaps::empty(), AccessMode::R_OK)
+                .is_ok()
+        );
+        let result = file.check_access(ROOT_UID, ROOT_GID, DacCaps::empty(), AccessMode::W_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
+    }
+
+    #[test]
+    fn dac_override_applies_to_any_user() {
+        let file = setup_file(FilePermissions::empty());
+        assert!(
+            file.check_access(OTHER_UID, OTHER_GID, DacCaps::OVERRIDE, AccessMode::W_OK)
                 .is_ok()
         );
     }
+
+    #[test]
+    fn read_search_only_bypasses_reads() {
+        let file = setup_file(FilePermissions::empty());
+        assert!(
+            file.check_access(OTHER_UID, OTHER_GID, DacCaps::READ_SEARCH, AccessMode::R_OK)
+                .is_ok()
+        );
+        let result =
+            file.check_access(OTHER_UID, OTHER_GID, DacCaps::READ_SEARCH, AccessMode::W_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
+        let result =
+            file.check_access(OTHER_UID, OTHER_GID, DacCaps::READ_SEARCH, AccessMode::X_OK);
+        assert!(matches!(result, Err(KernelError::AccessDenied)));
+    }
+
+    #[test]
+    fn read_search_bypasses_directory_search() {
+        let dir = FileAttr {
+            file_type: FileType::Directory,
+            ..setup_file(FilePermissions::empty())
+        };
+        assert!(
+            dir.check_access(
+                OTHER_UID,
+                OTHER_GID,
+                DacCaps::READ_SEARCH,
+                AccessMode::R_OK | AccessMode::X_OK
+            )
+            .is_ok()
+        );
+    }
 }
@@ -144,6 +144,11 @@ mod test {
             1
         }
 
+        /// The number of blocks on the device.
+        fn num_blocks(&self) -> u64 {
+            self.data.len() as u64
+        }
+
         /// Flushes any caches to the underlying device.
         async fn sync(&self) -> Result<()> {
             unimplemented!()
@@ -22,6 +22,7 @@ pub mod pathbuf;
 use crate::{
     driver::CharDevDescriptor,
     error::{FsError, KernelError, Result},
+    proc::ids::{Gid, Uid},
 };
 use alloc::{boxed::Box, string::String, sync::Arc};
 use async_trait::async_trait;
@@ -152,10 +153,36 @@ pub trait BlockDevice: Send + Sync {
     /// The size of a single block in bytes.
     fn block_size(&self) -> usize;
 
+    /// The number of blocks on the device.
+    fn num_blocks(&self) -> u64;
+
     /// Flushes any caches to the underlying device.
     async fn sync(&self) -> Result<()>;
 }
 
+#[async_trait]
+impl<T: BlockDevice + ?Sized> BlockDevice for Arc<T> {
+    async fn read(&self, block_id: u64, buf: &mut [u8]) -> Result<()> {
+        (**self).read(block_id, buf).await
+    }
+
+    async fn write(&self, block_id: u64, buf: &[u8]) -> Result<()> {
+        (**self).write(block_id, buf).await
+    }
+
+    fn block_size(&self) -> usize {
+        (**self).block_size()
+    }
+
+    fn num_blocks(&self) -> u64 {
+        (**self).num_blocks()
+    }
+
+    async fn sync(&self) -> Result<()> {
+        (**self).sync().await
+    }
+}
+
 /// A stateless representation of a filesystem object.
 ///
 /// This trait represents an object on the disk (a file, a directory, etc.). All
@@ -188,6 +215,12 @@ pub trait Inode: Send + Sync {
         Err(KernelError::NotSupported)
     }
 
+    /// Changes the owner and/or group of the inode. A `None` leaves that ID
+    /// unchanged.
+    async fn chown(&self, _uid: Option<Uid>, _gid: Option<Gid>) -> Result<()> {
+        Err(KernelError::NotSupported)
+    }
+
     /// Looks up a name within a directory, returning the corresponding inode.
     async fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>> {
         Err(KernelError::NotSupported)
@@ -209,6 +209,43 @@ impl Path {
     pub fn file_name(&self) -> Option<&str> {
         self.components().last()
     }
+
+    /// Resolves the `.` and `..` components of the path lexically, without
+    /// consulting the filesystem. A `..` at the root of an absolute path stays
+    /// at the root, whereas leading `..` components of a relative path are
+    /// kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use libkernel::fs::path::Path;
+    /// use libkernel::fs::pathbuf::PathBuf;
+    ///
+    /// assert_eq!(Path::new("/usr/./lib/../bin").normalize(), PathBuf::from("/usr/bin"));
+    /// assert_eq!(Path::new("/..").normalize(), PathBuf::from("/"));
+    /// assert_eq!(Path::new("../a/../b").normalize(), PathBuf::from("../b"));
+    /// ```
+    pub fn normalize(&self) -> PathBuf {
+        let mut components: Vec<&str> = Vec::new();
+
+        for component in self.components() {
+            match component {
+                ".." if components.last().is_some_and(|c| *c != "..") => {
+                    components.pop();
+                }
+                ".." if self.is_absolute() => {}
+                _ => components.push(component),
+            }
+        }
+
+        let mut ret = PathBuf::from(if self.is_absolute() { "/" } else { "" });
+
+        for component in components {
+            ret.push(component);
+        }
+
+        ret
+    }
 }
 
 impl AsRef<Path> for str {
@@ -296,6 +333,15 @@ mod tests {
         assert_eq!(p2.components().collect::<Vec<_>>(), vec!["a", "b", "c"]);
     }
 
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Path::new("/a/b/../c/./d").normalize().as_str(), "/a/c/d");
+        assert_eq!(Path::new("/a/../../b").normalize().as_str(), "/b");
+        assert_eq!(Path::new("/a/..").normalize().as_str(), "/");
+        assert_eq!(Path::new("a/../../b").normalize().as_str(), "../b");
+        assert_eq!(Path::new("a/./b/").normalize().as_str(), "a/b");
+    }
+
     #[test]
     fn test_join() {
         assert_eq!(Path::new("/a/b").join(Path::new("c/d")), "/a/b/c/d".into());
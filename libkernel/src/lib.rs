@@ -1,12 +1,12 @@
 #![cfg_attr(not(test), no_std)]
 
-use alloc::vec::Vec;
 use error::Result;
 use memory::{
     address::VA,
     page::PageFrame,
     permissions::PtePermissions,
     region::{PhysMemoryRegion, VirtMemoryRegion},
+    swap::SwapEntry,
 };
 use sync::spinlock::SpinLockIrq;
 
@@ -91,7 +91,8 @@ pub trait UserAddressSpace: Send + Sync {
     ///
     /// This function creates a page table entry (PTE) that maps the given
     /// physical `page` to the specified virtual address `va` with the provided
-    /// `perms`. The implementation must handle the allocation and setup of any
+    /// `perms`. The mapping takes over the caller's reference to the frame.
+    /// The implementation must handle the allocation and setup of any
     /// intermediate page tables (e.g., L1 or L2 tables) if they do not already
     /// exist.
     ///
@@ -115,19 +116,19 @@ pub trait UserAddressSpace: Send + Sync {
     ///
     /// # Returns
     ///
-    /// The `PageFrame` that was previously mapped at `va`. This allows the
-    /// caller to manage the lifecycle of the physical memory (e.g., decrement a
-    /// reference count or free it). Returns an error if no page is mapped at
-    /// `va`.
+    /// The `PageFrame` that was previously mapped at `va`, along with the
+    /// mapping's reference to it. This allows the caller to manage the
+    /// lifecycle of the physical memory (e.g., decrement a reference count or
+    /// free it). Returns an error if no page is mapped at `va`.
     fn unmap(&mut self, va: VA) -> Result<PageFrame>;
 
     /// Atomically unmaps a page at `va` and maps a new page in its place.
     ///
     /// # Returns
     ///
-    /// The `PageFrame` of the *previously* mapped page, allowing the caller to
-    /// manage its lifecycle. Returns an error if no page was originally mapped
-    /// at `va`.
+    /// The `PageFrame` of the *previously* mapped page, along with the
+    /// mapping's reference to it, allowing the caller to manage its lifecycle.
+    /// Returns an error if no page was originally mapped at `va`.
     fn remap(&mut self, va: VA, new_page: PageFrame, perms: PtePermissions) -> Result<PageFrame>;
 
     /// Changes the protection flags for a range of virtual addresses.
@@ -146,11 +147,20 @@ pub trait UserAddressSpace: Send + Sync {
     /// walks the page tables for the given `va_range` and invalidates all PTEs
     /// within it.
     ///
-    /// # Returns
+    /// Each mapping holds a reference to the physical frame it maps, which
+    /// the implementation must drop as the PTE is invalidated, so that a frame
+    /// is freed once the last mapping of it has gone. Likewise, a PTE holding
+    /// a swap entry holds a reference to its swap slot, which must be dropped.
+    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<()>;
+
+    /// Moves the mappings within `va_range` so that they start at `new_start`
+    /// instead, keeping the same physical frames and permissions.
     ///
-    /// A `Vec<PageFrame>` containing all the physical frames that were
-    /// unmapped. This allows the caller to free all associated physical memory.
-    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<Vec<PageFrame>>;
+    /// This is the low-level implementation for `mremap`. The PTEs, along with
+    /// their references to the frames or swap slots, are moved rather than the
+    /// data being copied. Nothing may already be mapped in the destination
+    /// range, which mustn't overlap `va_range`.
+    fn move_range(&mut self, va_range: VirtMemoryRegion, new_start: VA) -> Result<()>;
 
     /// Translates a virtual address to its corresponding physical mapping
     /// information.
@@ -166,6 +176,51 @@ pub trait UserAddressSpace: Send + Sync {
     /// mapping exists for `va`.
     fn translate(&self, va: VA) -> Option<PageInfo>;
 
+    /// Clears the accessed flag of the page mapped at `va`, returning whether
+    /// it was set, i.e. whether the page has been accessed since the flag was
+    /// last cleared. This is how pages are aged for reclaim.
+    ///
+    /// Returns an error if no page is mapped at `va`.
+    fn test_and_clear_accessed(&mut self, va: VA) -> Result<bool>;
+
+    /// Sets the accessed flag of the page mapped at `va`, returning `true` if
+    /// it was clear. On architectures where accessing a page whose flag is
+    /// clear faults, rather than the hardware setting it, this resolves the
+    /// fault.
+    fn mark_accessed(&mut self, va: VA) -> bool;
+
+    /// Replaces the mapping at `va` with a non-present PTE holding the swap
+    /// `entry`, for a page which is being swapped out. The PTE takes over the
+    /// caller's reference to the swap slot.
+    ///
+    /// # Returns
+    ///
+    /// The `PageFrame` which was mapped at `va`, along with the mapping's
+    /// reference to it. Returns an error if no page was mapped at `va`.
+    fn swap_out(&mut self, va: VA, entry: SwapEntry) -> Result<PageFrame>;
+
+    /// Returns the swap entry held by the PTE for `va`, if the page there has
+    /// been swapped out.
+    fn swap_entry(&self, va: VA) -> Option<SwapEntry>;
+
+    /// Replaces the swap `entry` held by the PTE for `va` with a mapping of
+    /// `page`, once it has been read back in. The mapping takes over the
+    /// caller's reference to the frame, and the caller takes over the PTE's
+    /// reference to the swap slot.
+    ///
+    /// Returns an error if the PTE for `va` doesn't hold `entry`.
+    fn swap_in(
+        &mut self,
+        va: VA,
+        entry: SwapEntry,
+        page: PageFrame,
+        perms: PtePermissions,
+    ) -> Result<()>;
+
+    /// Returns the number of pages of `region` which are mapped, followed by
+    /// the number which have been swapped out.
+    fn count_pages(&self, region: VirtMemoryRegion) -> (usize, usize);
+
     /// Atomically protects a region in the source address space and clones the
     /// mappings into a destination address space.
     ///
@@ -187,6 +242,9 @@ pub trait UserAddressSpace: Send + Sync {
     /// 3. The PTE in the `self` (parent) address space has its permissions
     ///    updated to `perms`.
     ///
+    /// A PTE holding a swap entry is copied into `other` as it is, taking
+    /// another reference to the swap slot.
+    ///
     /// # Arguments
     ///
     /// * `&mut self`: The source (parent) address space. Its permissions for
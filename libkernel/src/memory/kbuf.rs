@@ -58,13 +58,12 @@ impl<T, S: Storage<Item = T>, C: CpuOps> KBufCore<T, S, C> {
         )
     }
 
-    pub async fn write_ready(&self) {
+    pub fn write_ready(&self) -> impl Future<Output = ()> + use<T, S, C> {
         wait_until(
             self.inner.clone(),
             |inner| &mut inner.write_waiters,
             |inner| if inner.buf.is_full() { None } else { Some(()) },
         )
-        .await
     }
 
     /// Pushes a value of type `T` into the buffer. If the buffer is full, this
@@ -1,5 +1,6 @@
 pub mod address;
 pub mod kbuf;
+pub mod oom;
 pub mod page;
 pub mod page_alloc;
 pub mod permissions;
@@ -7,6 +8,7 @@ pub mod pg_offset;
 pub mod proc_vm;
 pub mod region;
 pub mod smalloc;
+pub mod swap;
 
 pub const PAGE_SIZE: usize = 4096;
 pub const PAGE_SHIFT: usize = PAGE_SIZE.trailing_zeros() as usize;
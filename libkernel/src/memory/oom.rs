@@ -0,0 +1,85 @@
+//! Scoring of processes for the out-of-memory killer.
+
+/// The lowest `oom_score_adj`, which exempts a process from being killed when
+/// memory runs out.
+pub const OOM_SCORE_ADJ_MIN: i16 = -1000;
+
+/// The highest `oom_score_adj`, which makes a process the preferred victim
+/// when memory runs out.
+pub const OOM_SCORE_ADJ_MAX: i16 = 1000;
+
+/// Returns how good a victim a process would make when memory runs out, with
+/// higher scores being killed first. The score is the number of pages the
+/// process has in memory or swapped out, adjusted by `oom_score_adj`, which
+/// adds or removes that many thousandths of `total_pages`, the memory and swap
+/// of the whole system.
+///
+/// Returns `None` if the process mustn't be killed. Any other process scores
+/// at least 1, so that it may be chosen even if its adjustment cancels its
+/// usage out.
+pub fn badness(
+    resident: usize,
+    swapped: usize,
+    oom_score_adj: i16,
+    total_pages: usize,
+) -> Option<u64> {
+    if oom_score_adj == OOM_SCORE_ADJ_MIN {
+        return None;
+    }
+
+    let adj = oom_score_adj as i64 * (total_pages / 1000) as i64;
+    let points = (resident + swapped) as i64 + adj;
+
+    Some(points.max(1) as u64)
+}
+
+/// Returns the index of the process with the highest score, as returned by
+/// [`badness`], or `None` if there's no process which may be killed.
+pub fn choose_victim(scores: impl IntoIterator<Item = Option<u64>>) -> Option<usize> {
+    scores
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, points)| Some((i, points?)))
+        .max_by_key(|&(_, points)| points)
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn badness_counts_resident_and_swapped_pages() {
+        assert_eq!(badness(100, 20, 0, 10_000), Some(120));
+        assert_eq!(badness(0, 0, 0, 10_000), Some(1));
+    }
+
+    #[test]
+    fn badness_applies_adjustment() {
+        assert_eq!(badness(100, 0, 500, 10_000), Some(5100));
+        assert_eq!(badness(100, 0, -5, 10_000), Some(50));
+        assert_eq!(badness(100, 0, -999, 10_000), Some(1));
+        assert!(badness(100, 0, OOM_SCORE_ADJ_MAX, 10_000) > badness(5000, 0, 0, 10_000));
+    }
+
+    #[test]
+    fn badness_exempts_minimum_adjustment() {
+        assert_eq!(badness(1_000_000, 0, OOM_SCORE_ADJ_MIN, 10_000), None);
+    }
+
+    #[test]
+    fn choose_victim_picks_highest_score() {
+        assert_eq!(choose_victim([Some(10), None, Some(30), Some(20)]), Some(2));
+        assert_eq!(choose_victim([None, None]), None);
+        assert_eq!(choose_victim([]), None);
+    }
+
+    #[test]
+    fn negative_adjustment_changes_victim() {
+        let scores = |adj| [badness(5000, 0, adj, 10_000), badness(4000, 0, 0, 10_000)];
+
+        assert_eq!(choose_victim(scores(0)), Some(0));
+        assert_eq!(choose_victim(scores(-200)), Some(1));
+        assert_eq!(choose_victim(scores(OOM_SCORE_ADJ_MIN)), Some(1));
+    }
+}
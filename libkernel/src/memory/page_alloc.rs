@@ -140,6 +140,43 @@ impl FrameAllocatorInner {
 
         self.free_pages += 1 << initial_order;
     }
+
+    /// Returns the head of the allocated block containing `pfn`.
+    fn block_head(&self, mut pfn: PageFrame) -> PageFrame {
+        loop {
+            match self.get_frame(pfn).state {
+                FrameState::AllocatedHead(_) => return pfn,
+                FrameState::AllocatedTail(TailInfo { head }) => pfn = head,
+                _ => panic!("Inconsistent memory metadata detected"),
+            }
+        }
+    }
+
+    /// Takes another reference to the allocated block containing `pfn`.
+    fn get_block(&mut self, pfn: PageFrame) {
+        let head = self.block_head(pfn);
+
+        match self.get_frame_mut(head).state {
+            FrameState::AllocatedHead(ref mut alloc_info) => {
+                alloc_info.ref_count += 1;
+            }
+            _ => unreachable!("Logic error: head PFN is not an AllocatedHead"),
+        }
+    }
+
+    /// Drops a reference to the allocated block containing `pfn`, freeing it if
+    /// that was the last one.
+    fn put_block(&mut self, pfn: PageFrame) {
+        let head = self.block_head(pfn);
+
+        let FrameState::AllocatedHead(AllocatedInfo { order, .. }) = self.get_frame(head).state
+        else {
+            unreachable!("Logic error: head PFN is not an AllocatedHead");
+        };
+
+        self.free_frames(PhysMemoryRegion::new(head.pa(), (1 << order) << PAGE_SHIFT));
+    }
+
     #[inline]
     fn pfn_to_slice_index(&self, pfn: PageFrame) -> usize {
         assert!(pfn.value() >= self.base_page.value(), "PFN is below base");
@@ -204,17 +241,9 @@ impl<CPU: CpuOps> PageAllocation<'_, CPU> {
 
 impl<CPU: CpuOps> Clone for PageAllocation<'_, CPU> {
     fn clone(&self) -> Self {
-        let mut inner = self.inner.lock_save_irq();
-
-        match inner
-            .get_frame_mut(self.region.start_address().to_pfn())
-            .state
-        {
-            FrameState::AllocatedHead(ref mut alloc_info) => {
-                alloc_info.ref_count += 1;
-            }
-            _ => panic!("Inconsistent memory metadata detected"),
-        }
+        self.inner
+            .lock_save_irq()
+            .get_block(self.region.start_address().to_pfn());
 
         Self {
             region: self.region,
@@ -306,6 +335,40 @@ impl<CPU: CpuOps> FrameAllocator<CPU> {
         }
     }
 
+    /// Takes another reference to the allocated block containing `pfn`, e.g.
+    /// when a page is mapped into a second address space. The block isn't
+    /// freed until every reference has been dropped with
+    /// [`put_page`](Self::put_page).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pfn` isn't part of an allocated block.
+    pub fn get_page(&self, pfn: PageFrame) {
+        self.inner.lock_save_irq().get_block(pfn);
+    }
+
+    /// Drops a reference to the allocated block containing `pfn`, e.g. when a
+    /// mapping of the page is removed. The block is freed once the last
+    /// reference has been dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pfn` isn't part of an allocated block.
+    pub fn put_page(&self, pfn: PageFrame) {
+        self.inner.lock_save_irq().put_block(pfn);
+    }
+
+    /// Returns the number of free pages.
+    pub fn free_page_count(&self) -> usize {
+        self.inner.lock_save_irq().free_pages
+    }
+
+    /// Returns the number of pages spanned by the allocator, including those
+    /// which were reserved when it was created.
+    pub fn total_page_count(&self) -> usize {
+        self.inner.lock_save_irq().total_pages
+    }
+
     /// Returns `true` if the page is part of an allocated block, `false`
     /// otherwise.
     pub fn is_allocated(&self, pfn: PageFrame) -> bool {
@@ -423,7 +486,7 @@ impl<CPU: CpuOps> FrameAllocator<CPU> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::{
         memory::{
@@ -440,8 +503,8 @@ mod tests {
     const MIB: usize = 1024 * KIB;
     const PAGE_SIZE: usize = 4096;
 
-    struct TestFixture {
-        allocator: FrameAllocator<MockCpuOps>,
+    pub(crate) struct TestFixture {
+        pub(crate) allocator: FrameAllocator<MockCpuOps>,
         base_ptr: *mut u8,
         layout: Layout,
     }
@@ -452,7 +515,7 @@ mod tests {
         /// - `mem_regions`: A slice of `(start, size)` tuples defining available memory regions.
         ///   The `start` is relative to the beginning of the allocated memory block.
         /// - `res_regions`: A slice of `(start, size)` tuples for reserved regions (e.g., kernel).
-        fn new(mem_regions: &[(usize, usize)], res_regions: &[(usize, usize)]) -> Self {
+        pub(crate) fn new(mem_regions: &[(usize, usize)], res_regions: &[(usize, usize)]) -> Self {
             // Determine the total memory size required for the test environment.
             let total_size = mem_regions
                 .iter()
@@ -515,19 +578,19 @@ mod tests {
 
         /// Checks that the number of blocks in each free list matches the expected counts.
         fn assert_free_list_counts(&self, expected_counts: &[usize; MAX_ORDER + 1]) {
-            for order in 0..=MAX_ORDER {
+            for (order, &expected) in expected_counts.iter().enumerate() {
                 let count = self.allocator.inner.lock_save_irq().free_lists[order]
                     .iter()
                     .count();
                 assert_eq!(
-                    count, expected_counts[order],
+                    count, expected,
                     "Mismatch in free list count for order {}",
                     order
                 );
             }
         }
 
-        fn free_pages(&self) -> usize {
+        pub(crate) fn free_pages(&self) -> usize {
             self.allocator.inner.lock_save_irq().free_pages
         }
     }
@@ -657,9 +720,7 @@ mod tests {
         // Splitting a MAX_ORDER block to get an order 0 page should leave
         // one free block at each intermediate order.
         let mut expected_counts = [0; MAX_ORDER + 1];
-        for i in 0..MAX_ORDER {
-            expected_counts[i] = 1;
-        }
+        expected_counts[..MAX_ORDER].fill(1);
         fixture.assert_free_list_counts(&expected_counts);
     }
 
@@ -674,7 +735,7 @@ mod tests {
 
         // Check head page
         match fixture.frame_state(head_region.region.iter_pfns().next().unwrap()) {
-            FrameState::AllocatedHead(info) => assert_eq!(info.order, order as u8),
+            FrameState::AllocatedHead(info) => assert_eq!(info.order, order),
             _ => panic!("Head page has incorrect state"),
         }
 
@@ -781,4 +842,112 @@ mod tests {
         assert_eq!(fixture.free_pages(), initial_free);
         assert!(matches!(fixture.frame_state(pfn), FrameState::Free { .. }));
     }
+
+    /// Tests taking and dropping references to a page by its PFN.
+    #[test]
+    fn get_put_page() {
+        let fixture = TestFixture::new(&[(0, (1 << (MAX_ORDER + PAGE_SHIFT)) * 2)], &[]);
+        let initial_free = fixture.free_pages();
+
+        let pfn = fixture
+            .allocator
+            .alloc_frames(0)
+            .unwrap()
+            .leak()
+            .start_address()
+            .to_pfn();
+
+        fixture.allocator.get_page(pfn);
+        assert!(!fixture.allocator.is_allocated_exclusive(pfn));
+
+        fixture.allocator.put_page(pfn);
+        assert!(fixture.allocator.is_allocated_exclusive(pfn));
+        assert_eq!(fixture.allocator.free_page_count(), initial_free - 1);
+
+        fixture.allocator.put_page(pfn);
+        assert_eq!(fixture.allocator.free_page_count(), initial_free);
+        assert!(!fixture.allocator.is_allocated(pfn));
+    }
+
+    /// Tests that a reference dropped through a tail page frees the whole
+    /// block.
+    #[test]
+    fn put_page_tail() {
+        let fixture = TestFixture::new(&[(0, (1 << (MAX_ORDER + PAGE_SHIFT)) * 2)], &[]);
+        let initial_free = fixture.free_pages();
+
+        let region = fixture.allocator.alloc_frames(2).unwrap().leak();
+        let tail = region.iter_pfns().nth(3).unwrap();
+
+        fixture.allocator.get_page(tail);
+        fixture.allocator.put_page(tail);
+        assert_eq!(fixture.free_pages(), initial_free - 4);
+
+        fixture.allocator.put_page(tail);
+        assert_eq!(fixture.free_pages(), initial_free);
+        fixture.assert_free_list_counts(&{
+            let mut counts = [0; MAX_ORDER + 1];
+            counts[MAX_ORDER] = 1;
+            counts
+        });
+    }
+
+    /// Follows the references to pages through a fork, a CoW fault that copies
+    /// a page, and the exit of both processes, as the page tables take and
+    /// drop them.
+    #[test]
+    fn cow_lifecycle() {
+        let fixture = TestFixture::new(&[(0, (1 << (MAX_ORDER + PAGE_SHIFT)) * 2)], &[]);
+        let allocator = &fixture.allocator;
+        let initial_free = fixture.free_pages();
+
+        // The parent faults in two pages.
+        let parent: Vec<_> = (0..2)
+            .map(|_| {
+                allocator
+                    .alloc_frames(0)
+                    .unwrap()
+                    .leak()
+                    .start_address()
+                    .to_pfn()
+            })
+            .collect();
+
+        // Fork: the child maps the same pages.
+        let mut child = parent.clone();
+        for &pfn in &child {
+            allocator.get_page(pfn);
+        }
+
+        assert_eq!(fixture.free_pages(), initial_free - 2);
+
+        // The child writes to its first page, copying it and dropping its
+        // reference to the original.
+        let copy = allocator
+            .alloc_frames(0)
+            .unwrap()
+            .leak()
+            .start_address()
+            .to_pfn();
+        allocator.put_page(child[0]);
+        child[0] = copy;
+
+        assert!(allocator.is_allocated_exclusive(parent[0]));
+        assert!(!allocator.is_allocated_exclusive(parent[1]));
+        assert_eq!(fixture.free_pages(), initial_free - 3);
+
+        // The parent exits. Only the page that's still shared survives.
+        for pfn in parent {
+            allocator.put_page(pfn);
+        }
+
+        assert_eq!(fixture.free_pages(), initial_free - 2);
+
+        // Then the child.
+        for pfn in child {
+            allocator.put_page(pfn);
+        }
+
+        assert_eq!(fixture.free_pages(), initial_free);
+    }
 }
@@ -1,20 +1,56 @@
-use super::vmarea::{VMAPermissions, VMArea, VMAreaKind};
+use super::vmarea::{
+    STACK_GUARD_GAP, SharedPages, VMAFlags, VMAPermissions, VMAccessHint, VMArea, VMAreaKind,
+};
 use crate::{
     UserAddressSpace,
     error::{KernelError, Result},
     memory::{
-        PAGE_MASK, PAGE_SIZE, address::VA, page::PageFrame, permissions::PtePermissions,
-        region::VirtMemoryRegion,
+        PAGE_MASK, PAGE_SIZE, address::VA, permissions::PtePermissions, region::VirtMemoryRegion,
     },
 };
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+
+/// The default address below which mappings without a fixed address are placed.
+pub const MMAP_BASE: usize = 0x4000_0000_0000;
+
+/// Where `mremap` may place a resized mapping.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RemapRequest {
+    /// The mapping must stay where it is.
+    InPlace,
+    /// The mapping may be moved if it can't be grown in place.
+    MayMove,
+    /// The mapping must be moved to the given address, replacing any mappings
+    /// there.
+    Fixed(VA),
+}
 
-const MMAP_BASE: usize = 0x4000_0000_0000;
+/// Advice given by `madvise` about how a region of memory will be used.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MemoryAdvice {
+    /// The region will be accessed with the given pattern.
+    Access(VMAccessHint),
+    /// The region will be accessed soon. The map itself is unchanged; it's up
+    /// to the caller to fault the pages in.
+    WillNeed,
+    /// The region's pages aren't needed any more, and are dropped. They're
+    /// faulted in afresh on the next access.
+    DontNeed,
+    /// As `DontNeed`, but only valid for private anonymous memory.
+    Free,
+    /// The region isn't copied into the child on `fork`.
+    DontFork,
+    /// Undoes `DontFork`.
+    DoFork,
+}
 
 /// Manages mappings in a process's address space.
 pub struct MemoryMap<AS: UserAddressSpace> {
     vmas: BTreeMap<VA, VMArea>,
     address_space: AS,
+    mmap_base: VA,
+    /// Set by `mlockall(MCL_FUTURE)`: new mappings are locked in memory.
+    lock_future: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -30,6 +66,8 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         Ok(Self {
             vmas: BTreeMap::new(),
             address_space: AS::new()?,
+            mmap_base: VA::from_value(MMAP_BASE),
+            lock_future: false,
         })
     }
 
@@ -37,6 +75,8 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         Self {
             vmas: BTreeMap::new(),
             address_space,
+            mmap_base: VA::from_value(MMAP_BASE),
+            lock_future: false,
         }
     }
 
@@ -52,6 +92,8 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         Ok(Self {
             vmas: map,
             address_space: AS::new()?,
+            mmap_base: VA::from_value(MMAP_BASE),
+            lock_future: false,
         })
     }
 
@@ -80,12 +122,29 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
 
     /// Maps a region of memory.
     pub fn mmap(
+        &mut self,
+        requested_address: AddressRequest,
+        len: usize,
+        perms: VMAPermissions,
+        kind: VMAreaKind,
+    ) -> Result<VA> {
+        self.mmap_with_flags(requested_address, len, perms, kind, VMAFlags::default())
+    }
+
+    /// Maps a region of memory, as for [`mmap`](Self::mmap), with the given
+    /// VMA flags. A grows-down mapping must be anonymous.
+    pub fn mmap_with_flags(
         &mut self,
         requested_address: AddressRequest,
         mut len: usize,
         perms: VMAPermissions,
         kind: VMAreaKind,
+        mut flags: VMAFlags,
     ) -> Result<VA> {
+        if flags.grows_down && kind != VMAreaKind::Anon {
+            return Err(KernelError::InvalidValue);
+        }
+
         if len == 0 {
             return Err(KernelError::InvalidValue);
         }
@@ -133,9 +192,9 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
 
         // At this point, `start_addr` points to a valid, free region.
         // We can now create and insert the new VMA, handling merges.
-        let new_vma = VMArea::new(region, kind, perms);
+        flags.locked |= self.lock_future;
 
-        self.insert_and_merge(new_vma);
+        self.insert_and_merge(VMArea::new(region, kind, perms).with_flags(flags));
 
         Ok(region.start_address())
     }
@@ -152,7 +211,7 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
     /// # Returns
     /// * `Ok(())` on success.
     /// * `Err(MunmapError)` on failure.
-    pub fn munmap(&mut self, range: VirtMemoryRegion) -> Result<Vec<PageFrame>> {
+    pub fn munmap(&mut self, range: VirtMemoryRegion) -> Result<()> {
         if !range.is_page_aligned() {
             return Err(KernelError::InvalidValue);
         }
@@ -165,6 +224,18 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         self.unmap_region(range.align_to_page_boundary(), None)
     }
 
+    /// Changes the permissions of a region of memory, similar to the
+    /// `mprotect` syscall.
+    ///
+    /// The region may span several VMAs, which are split at its boundaries and
+    /// merged with their neighbours as their permissions change.
+    ///
+    /// # Returns
+    /// * `Ok(())` on success.
+    /// * `Err(KernelError::NoMemory)` if part of the region isn't mapped, in
+    ///   which case nothing is changed.
+    /// * `Err(KernelError::AccessDenied)` if write permission is requested for
+    ///   a special mapping, or one which may not be made writable.
     pub fn mprotect(
         &mut self,
         protect_region: VirtMemoryRegion,
@@ -178,52 +249,449 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
             return Err(KernelError::InvalidValue);
         }
 
-        let affected_vma_addr = self
-            .find_vma(protect_region.start_address())
-            .map(|x| x.region.start_address())
-            .ok_or(KernelError::NoMemory)?;
+        let protect_region = protect_region.align_to_page_boundary();
+
+        if !self.is_region_mapped(protect_region) {
+            return Err(KernelError::NoMemory);
+        }
+
+        if new_perms.write
+            && self.vmas.values().any(|vma| {
+                (vma.is_special() || !vma.flags.may_write) && vma.region.overlaps(protect_region)
+            })
+        {
+            return Err(KernelError::AccessDenied);
+        }
 
-        let affected_vma = self
+        // How the page tables are updated depends on the kind of each VMA, so
+        // do that first.
+        let affected: Vec<(VMArea, VirtMemoryRegion)> = self
             .vmas
-            .remove(&affected_vma_addr)
-            .expect("Should have the same key as the start address");
+            .values()
+            .filter_map(|vma| {
+                vma.region
+                    .intersection(protect_region)
+                    .map(|region| (vma.clone(), region))
+            })
+            .collect();
+
+        for (vma, region) in affected {
+            self.protect_pg_tables(&vma, region, new_perms)?;
+        }
 
-        // Easy case, the entire VMA is changing.
-        if affected_vma.region == protect_region {
-            let old_vma = affected_vma.clone();
-            let mut new_vma = old_vma.clone();
-            new_vma.permissions = new_perms;
+        self.update_region(protect_region, |vma| vma.permissions = new_perms);
 
-            self.insert_and_merge(new_vma.clone());
-            self.address_space
-                .protect_range(protect_region, new_perms.into())?;
+        Ok(())
+    }
+
+    /// Resizes and/or moves a mapping, similar to the `mremap` syscall.
+    ///
+    /// `old_region` must lie within a single VMA. Shrinking always happens in
+    /// place. Growing happens in place if the pages following the mapping are
+    /// free; otherwise the mapping is moved if `request` permits it. Moving a
+    /// mapping moves its page-table entries, so the data isn't copied.
+    ///
+    /// If `dont_unmap` is set, the mapping must be moved without being
+    /// resized, and `old_region` is left mapped, but empty.
+    ///
+    /// # Returns
+    /// * `Ok(VA)` with the new start address of the mapping on success.
+    /// * `Err(KernelError::Fault)` if `old_region` isn't within a single VMA.
+    /// * `Err(KernelError::NoMemory)` if the mapping couldn't be grown.
+    /// * `Err(KernelError::InvalidValue)` if the VMA is a special mapping.
+    pub fn mremap(
+        &mut self,
+        old_region: VirtMemoryRegion,
+        new_size: usize,
+        request: RemapRequest,
+        dont_unmap: bool,
+    ) -> Result<VA> {
+        if !old_region.is_page_aligned() || old_region.size() == 0 || new_size == 0 {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let old_region = old_region.align_to_page_boundary();
+        let new_size = new_size.next_multiple_of(PAGE_SIZE);
+        let old_start = old_region.start_address();
+
+        if dont_unmap && (request == RemapRequest::InPlace || new_size != old_region.size()) {
+            return Err(KernelError::InvalidValue);
+        }
+
+        if let RemapRequest::Fixed(address) = request
+            && (!address.is_page_aligned()
+                || VirtMemoryRegion::new(address, new_size).overlaps(old_region))
+        {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let vma = self
+            .find_vma(old_start)
+            .filter(|vma| vma.region.contains(old_region))
+            .cloned()
+            .ok_or(KernelError::Fault)?;
+
+        if vma.is_special() {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let fixed = matches!(request, RemapRequest::Fixed(_));
+
+        // Shrinking is done in place by unmapping the tail.
+        if new_size <= old_region.size() && !fixed && !dont_unmap {
+            if new_size < old_region.size() {
+                let tail = VirtMemoryRegion::new(
+                    old_start.add_bytes(new_size),
+                    old_region.size() - new_size,
+                );
+
+                self.unmap_region(tail, None)?;
+            }
 
+            return Ok(old_start);
+        }
+
+        // Try to grow in place, which requires the mapping to end at the end
+        // of its VMA, with nothing mapped after it.
+        if !fixed && !dont_unmap && old_region.end_address() == vma.region.end_address() {
+            let growth =
+                VirtMemoryRegion::new(old_region.end_address(), new_size - old_region.size());
+
+            if self.is_region_free(growth) {
+                let mut vma = self.vmas.remove(&vma.region.start_address()).unwrap();
+
+                // A file mapping which covers its whole VMA maps more of the
+                // file; otherwise the growth is zero-filled.
+                if let VMAreaKind::File(ref mut mapping) = vma.kind
+                    && mapping.len >= vma.region.size() as u64
+                {
+                    mapping.len += growth.size() as u64;
+                }
+
+                vma.region.expand_by(growth.size());
+                self.merge_neighbours(vma);
+
+                return Ok(old_start);
+            }
+        }
+
+        let dest = match request {
+            RemapRequest::InPlace => return Err(KernelError::NoMemory),
+            RemapRequest::MayMove => self
+                .find_free_region(new_size)
+                .ok_or(KernelError::NoMemory)?,
+            RemapRequest::Fixed(address) => {
+                let dest = VirtMemoryRegion::new(address, new_size);
+
+                self.unmap_region(dest, None)?;
+
+                dest
+            }
+        };
+
+        let moved_size = new_size.min(old_region.size());
+        let src = vma.shrink_to(old_region);
+        let mut new_vma = if new_size < old_region.size() {
+            src.shrink_to(VirtMemoryRegion::new(old_start, new_size))
+        } else {
+            src.clone()
+        };
+
+        new_vma.region = dest;
+
+        if let VMAreaKind::File(ref mut mapping) = new_vma.kind
+            && mapping.len >= moved_size as u64
+            && new_size > moved_size
+        {
+            mapping.len = new_size as u64;
+        }
+
+        self.address_space.move_range(
+            VirtMemoryRegion::new(old_start, moved_size),
+            dest.start_address(),
+        )?;
+
+        // The source pages are now unmapped, so with `dont_unmap` the old
+        // mapping is left in place, to be faulted in afresh. Otherwise, any
+        // pages that weren't moved are released along with it.
+        if !dont_unmap {
+            self.unmap_region(old_region, None)?;
+        }
+
+        self.merge_neighbours(new_vma);
+
+        Ok(dest.start_address())
+    }
+
+    /// Applies `advice` to a region of memory, similar to the `madvise`
+    /// syscall.
+    ///
+    /// # Returns
+    /// * `Ok(())` on success.
+    /// * `Err(KernelError::NoMemory)` if part of the region isn't mapped.
+    /// * `Err(KernelError::InvalidValue)` if the advice can't be applied to
+    ///   the mappings in the region, e.g. dropping the pages of locked memory,
+    ///   or of a special mapping.
+    pub fn madvise(&mut self, region: VirtMemoryRegion, advice: MemoryAdvice) -> Result<()> {
+        if !region.is_page_aligned() {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let region = region.align_to_page_boundary();
+
+        if region.size() == 0 {
             return Ok(());
         }
 
-        // Next case, a sub-region of a VMA is changing, requring a split.
-        if affected_vma.region.contains(protect_region) {
-            let (left, right) = affected_vma.region.punch_hole(protect_region);
-            let mut new_vma = affected_vma.clone().shrink_to(protect_region);
-            new_vma.permissions = new_perms;
+        if !self.is_region_mapped(region) {
+            return Err(KernelError::NoMemory);
+        }
+
+        match advice {
+            MemoryAdvice::Access(hint) => self.update_region(region, |vma| vma.flags.access = hint),
+            MemoryAdvice::WillNeed => {}
+            MemoryAdvice::DontNeed | MemoryAdvice::Free => {
+                let invalid = self.vmas.values().any(|vma| {
+                    vma.region.overlaps(region)
+                        && (vma.flags.locked
+                            || vma.is_special()
+                            || (advice == MemoryAdvice::Free && vma.kind != VMAreaKind::Anon))
+                });
+
+                if invalid {
+                    return Err(KernelError::InvalidValue);
+                }
+
+                // Dropping the page-table entries is all that's needed: the
+                // pages of a private mapping are freed, and the next fault
+                // zero-fills them, or reads them from the file. Shared pages
+                // live on in their shared object.
+                self.address_space.unmap_range(region)?;
+            }
+            MemoryAdvice::DontFork => self.update_region(region, |vma| vma.flags.dont_fork = true),
+            MemoryAdvice::DoFork => self.update_region(region, |vma| vma.flags.dont_fork = false),
+        }
+
+        Ok(())
+    }
+
+    /// Sets whether the pages of a region are locked in memory, similar to the
+    /// `mlock` and `munlock` syscalls. Faulting the pages in is left to the
+    /// caller.
+    ///
+    /// # Returns
+    /// * `Ok(())` on success.
+    /// * `Err(KernelError::NoMemory)` if part of the region isn't mapped.
+    pub fn mlock(&mut self, region: VirtMemoryRegion, locked: bool) -> Result<()> {
+        if !region.is_page_aligned() {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let region = region.align_to_page_boundary();
+
+        if !self.is_region_mapped(region) {
+            return Err(KernelError::NoMemory);
+        }
+
+        self.update_region(region, |vma| vma.flags.locked = locked);
+
+        Ok(())
+    }
+
+    /// Sets whether every current mapping is locked in memory, as for
+    /// `mlockall(MCL_CURRENT)` and `munlockall`.
+    pub fn mlock_all(&mut self, locked: bool) {
+        for mut vma in core::mem::take(&mut self.vmas).into_values() {
+            vma.flags.locked = locked;
+            self.merge_neighbours(vma);
+        }
+    }
+
+    /// Returns whether new mappings are locked in memory.
+    pub fn lock_future(&self) -> bool {
+        self.lock_future
+    }
+
+    /// Sets whether new mappings are locked in memory, as for
+    /// `mlockall(MCL_FUTURE)` and `munlockall`.
+    pub fn set_lock_future(&mut self, lock_future: bool) {
+        self.lock_future = lock_future;
+    }
+
+    /// Returns the size, in bytes, of the locked mappings within `region`.
+    pub fn locked_size_in(&self, region: VirtMemoryRegion) -> usize {
+        self.vmas
+            .values()
+            .filter(|vma| vma.flags.locked)
+            .filter_map(|vma| vma.region.intersection(region))
+            .map(|region| region.size())
+            .sum()
+    }
+
+    /// Returns the total size, in bytes, of the locked mappings in the address
+    /// space. This is the quantity limited by `RLIMIT_MEMLOCK`.
+    pub fn locked_size(&self) -> usize {
+        self.vmas
+            .values()
+            .filter(|vma| vma.flags.locked)
+            .map(|vma| vma.region.size())
+            .sum()
+    }
+
+    /// Applies `update` to the parts of the VMAs that lie within `region`,
+    /// splitting them at its boundaries, then merges the results with their
+    /// neighbours. The page tables are left untouched.
+    fn update_region(&mut self, region: VirtMemoryRegion, mut update: impl FnMut(&mut VMArea)) {
+        let affected: Vec<VA> = self
+            .vmas
+            .range(..region.end_address())
+            .rev()
+            .take_while(|(_, vma)| vma.region.end_address() > region.start_address())
+            .map(|(start, _)| *start)
+            .collect();
+
+        for start in affected {
+            let vma = self.vmas.remove(&start).unwrap();
+            let middle = vma.region.intersection(region).unwrap();
+            let (left, right) = vma.region.punch_hole(middle);
+            let mut updated = vma.shrink_to(middle);
+
+            update(&mut updated);
 
             if let Some(left) = left {
-                self.insert_and_merge(affected_vma.shrink_to(left));
+                self.merge_neighbours(vma.shrink_to(left));
             }
 
-            self.address_space
-                .protect_range(protect_region, new_perms.into())?;
-            self.insert_and_merge(new_vma);
+            self.merge_neighbours(updated);
 
             if let Some(right) = right {
-                self.insert_and_merge(affected_vma.shrink_to(right));
+                self.merge_neighbours(vma.shrink_to(right));
             }
+        }
+    }
 
-            return Ok(());
+    /// Returns the grows-down VMA which `addr` lies below, if `addr` isn't
+    /// mapped, i.e. the stack that a fault at `addr` would grow.
+    pub fn find_stack_above(&self, addr: VA) -> Option<&VMArea> {
+        if self.find_vma(addr).is_some() {
+            return None;
+        }
+
+        self.vmas
+            .range(addr..)
+            .next()
+            .map(|(_, vma)| vma)
+            .filter(|vma| vma.flags.grows_down)
+    }
+
+    /// Grows the grows-down VMA above `addr` downwards to cover it. Nothing is
+    /// done if `addr` already lies within a grows-down VMA.
+    ///
+    /// # Returns
+    /// * `Ok(())` on success.
+    /// * `Err(KernelError::Fault)` if there's no grows-down VMA above `addr`.
+    /// * `Err(KernelError::NoMemory)` if growing the stack would take it
+    ///   within the guard gap of the mapping below it.
+    pub fn grow_stack(&mut self, addr: VA) -> Result<()> {
+        if let Some(vma) = self.find_vma(addr) {
+            return if vma.flags.grows_down {
+                Ok(())
+            } else {
+                Err(KernelError::Fault)
+            };
+        }
+
+        let stack_start = self
+            .find_stack_above(addr)
+            .ok_or(KernelError::Fault)?
+            .region
+            .start_address();
+        let new_start = addr.page_aligned();
+
+        // Keep a gap between the stack and any accessible mapping below it,
+        // unless that's a stack itself.
+        if let Some((_, prev)) = self.vmas.range(..new_start).next_back()
+            && !prev.flags.grows_down
+            && (prev.permissions.read || prev.permissions.write || prev.permissions.execute)
+            && new_start.value() - prev.region.end_address().value() < STACK_GUARD_GAP
+        {
+            return Err(KernelError::NoMemory);
+        }
+
+        let mut stack = self.vmas.remove(&stack_start).unwrap();
+
+        stack.region =
+            VirtMemoryRegion::from_start_end_address(new_start, stack.region.end_address());
+        self.vmas.insert(new_start, stack);
+
+        Ok(())
+    }
+
+    /// Returns whether every page of `region` is mapped.
+    pub fn is_region_mapped(&self, region: VirtMemoryRegion) -> bool {
+        let mut next = region.start_address();
+
+        while next < region.end_address() {
+            match self.find_vma(next) {
+                Some(vma) => next = vma.region.end_address(),
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Unmaps the page at byte `offset` within the shared object `pages` from
+    /// every shared VMA which maps it. The page stays in the object, and is
+    /// faulted back in on the next access, such as to catch the next write to
+    /// a page which has been written back.
+    pub fn unmap_shared_page(&mut self, pages: &dyn SharedPages, offset: u64) -> Result<()> {
+        let regions: Vec<VirtMemoryRegion> = self
+            .vmas
+            .values()
+            .filter_map(|vma| {
+                let VMAreaKind::Shared(mapping) = &vma.kind else {
+                    return None;
+                };
+
+                let start = offset.checked_sub(mapping.offset)?;
+
+                (core::ptr::addr_eq(Arc::as_ptr(&mapping.pages), pages)
+                    && start < vma.region.size() as u64)
+                    .then(|| {
+                        VirtMemoryRegion::new(
+                            vma.region.start_address().add_bytes(start as usize),
+                            PAGE_SIZE,
+                        )
+                    })
+            })
+            .collect();
+
+        for region in regions {
+            self.address_space.unmap_range(region)?;
         }
 
-        // TODO: protecting over contiguous VMAreas.
-        Err(KernelError::NoMemory)
+        Ok(())
+    }
+
+    /// Applies `perms` to the page tables covering `region` of `vma`.
+    ///
+    /// The pages of a shared mapping are unmapped instead, since they may be
+    /// mapped with fewer permissions than their VMA's (e.g. to track writes to
+    /// them). They're faulted back in from the shared object as needed.
+    fn protect_pg_tables(
+        &mut self,
+        vma: &VMArea,
+        region: VirtMemoryRegion,
+        perms: VMAPermissions,
+    ) -> Result<()> {
+        if vma.is_shared() {
+            self.address_space.unmap_range(region)?;
+        } else {
+            self.address_space.protect_range(region, perms.into())?;
+        }
+
+        Ok(())
     }
 
     /// Checks if a given virtual memory region is completely free.
@@ -239,9 +707,10 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
             }
         }
 
-        // Check if the next VMA starts within our desired region.
-        if let Some((next_vma_start, _)) = self.vmas.range(region.start_address()..).next()
-            && *next_vma_start < region.end_address()
+        // Check if the next VMA, or the guard gap below it, starts within our
+        // desired region.
+        if let Some((_, next_vma)) = self.vmas.range(region.start_address()..).next()
+            && next_vma.start_gap() < region.end_address()
         {
             false
         } else {
@@ -249,14 +718,28 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         }
     }
 
+    /// Returns the address below which mappings without a fixed address are
+    /// placed.
+    pub fn mmap_base(&self) -> VA {
+        self.mmap_base
+    }
+
+    /// Sets the address below which mappings without a fixed address are
+    /// placed. This defaults to `MMAP_BASE`, and is moved by the ELF loader to
+    /// randomise the layout of the address space.
+    pub fn set_mmap_base(&mut self, base: VA) {
+        self.mmap_base = base.page_aligned();
+    }
+
     /// Finds a free region of at least `len` bytes. Searches downwards from
-    /// `MMAP_BASE`.
+    /// the mmap base.
     fn find_free_region(&self, len: usize) -> Option<VirtMemoryRegion> {
-        let mut last_vma_end = VA::from_value(MMAP_BASE);
+        let mut last_vma_end = self.mmap_base;
 
-        // Iterate through VMAs in reverse order to find a gap.
+        // Iterate through VMAs in reverse order to find a gap. The guard gap
+        // below a grows-down VMA isn't free.
         for (_, vma) in self.vmas.iter().rev() {
-            let vma_start = vma.region.start_address();
+            let vma_start = vma.start_gap();
             let vma_end = vma.region.end_address();
 
             if last_vma_end >= vma_end {
@@ -287,9 +770,15 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
 
     /// Inserts a new VMA, handling overlaps and merging it with neighbors if
     /// possible.
-    pub(super) fn insert_and_merge(&mut self, mut vma: VMArea) {
+    pub(super) fn insert_and_merge(&mut self, vma: VMArea) {
         let _ = self.unmap_region(vma.region, Some(vma.clone()));
 
+        self.merge_neighbours(vma);
+    }
+
+    /// Inserts a VMA into a free region of the map, merging it with its
+    /// neighbors if possible. The page tables are left untouched.
+    fn merge_neighbours(&mut self, mut vma: VMArea) {
         // Try to merge with next VMA.
         if let Some(next_vma) = self.vmas.get(&vma.region.end_address())
             && vma.can_merge_with(next_vma)
@@ -334,34 +823,39 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         fixup_region: VirtMemoryRegion,
         old_vma: VMArea,
         new_vma: Option<VMArea>,
-    ) -> Result<Vec<PageFrame>> {
+    ) -> Result<()> {
         let intersecting_region = fixup_region.intersection(old_vma.region);
 
         if let Some(intersection) = intersecting_region {
             match new_vma {
                 Some(new_vma) => {
-                    // We always unmap if file backing-stores are involoved.
-                    if old_vma.is_file_backed() || new_vma.is_file_backed() {
+                    // We always unmap if file backing-stores, shared pages
+                    // or special pages are involoved.
+                    if old_vma.is_file_backed()
+                        || new_vma.is_file_backed()
+                        || old_vma.is_shared()
+                        || new_vma.is_shared()
+                        || old_vma.is_special()
+                        || new_vma.is_special()
+                    {
                         self.address_space.unmap_range(intersection)
                     } else {
                         // the VMAs are anonymously mapped. Preserve data.
                         if new_vma.permissions != old_vma.permissions {
-                            self.address_space
-                                .protect_range(
-                                    intersection,
-                                    PtePermissions::from(new_vma.permissions),
-                                )
-                                .map(|_| Vec::new())
+                            self.address_space.protect_range(
+                                intersection,
+                                PtePermissions::from(new_vma.permissions),
+                            )
                         } else {
                             // If permissions match, fixup is a noop
-                            Ok(Vec::new())
+                            Ok(())
                         }
                     }
                 }
                 None => self.address_space.unmap_range(intersection),
             }
         } else {
-            Ok(Vec::new())
+            Ok(())
         }
     }
 
@@ -372,18 +866,14 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
     /// and the insert_and_merge code (replace_with = Some(<new vma>)). The
     /// `replace_with` parameter can be used to update the underlying page
     /// tables accordingly.
-    ///
-    /// # Returns
-    /// A list of all pages that were unmapped.
     fn unmap_region(
         &mut self,
         unmap_region: VirtMemoryRegion,
         replace_with: Option<VMArea>,
-    ) -> Result<Vec<PageFrame>> {
+    ) -> Result<()> {
         let mut affected_vmas = Vec::new();
         let unmap_start = unmap_region.start_address();
         let unmap_end = unmap_region.end_address();
-        let mut pages_unmapped = Vec::new();
 
         // Find all VMAs that intersect with the unmap region. Start with the
         // VMA that could contain the start address.
@@ -403,7 +893,7 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
         }
 
         if affected_vmas.is_empty() {
-            return Ok(Vec::new());
+            return Ok(());
         }
 
         for vma in affected_vmas {
@@ -412,11 +902,7 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
 
             self.vmas.remove(&vma_start).unwrap();
 
-            pages_unmapped.append(&mut self.fixup_pg_tables(
-                unmap_region,
-                vma.clone(),
-                replace_with.clone(),
-            )?);
+            self.fixup_pg_tables(unmap_region, vma.clone(), replace_with.clone())?;
 
             // VMA is completely contained within the unmap region. Handled by
             // just removing it.
@@ -454,27 +940,59 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
                 let new_region = VirtMemoryRegion::new(new_start, new_size);
                 let mut new_vma = vma.clone_with_new_region(new_region);
 
-                // Adjust file mapping offset if it's a file-backed VMA.
-                if let VMAreaKind::File(mapping) = &mut new_vma.kind {
-                    let offset_change = new_start.value() - vma_start.value();
-                    mapping.offset += offset_change as u64;
+                // Adjust the mapping offset if it's a file-backed or shared
+                // VMA.
+                let offset_change = (new_start.value() - vma_start.value()) as u64;
+
+                match &mut new_vma.kind {
+                    VMAreaKind::File(mapping) => mapping.offset += offset_change,
+                    VMAreaKind::Shared(mapping) => mapping.offset += offset_change,
+                    VMAreaKind::Anon | VMAreaKind::Special => {}
                 }
 
                 self.vmas.insert(new_vma.region.start_address(), new_vma);
             }
         }
 
-        Ok(pages_unmapped)
+        Ok(())
+    }
+
+    /// Removes every mapping from the address space, dropping the references
+    /// to the pages that were mapped.
+    pub fn unmap_all(&mut self) -> Result<()> {
+        for vma in core::mem::take(&mut self.vmas).into_values() {
+            self.address_space
+                .unmap_range(vma.region.align_to_page_boundary())?;
+        }
+
+        Ok(())
     }
 
     /// Attempts to clone this memory map, sharing any already-mapped writable
     /// pages as CoW pages. If the VMA isn't writable, the ref count is
     /// incremented.
+    ///
+    /// Shared VMAs aren't copied; the clone faults their pages in from the
+    /// same shared objects.
     pub fn clone_as_cow(&mut self) -> Result<Self> {
-        let mut new_as = AS::new()?;
-        let new_vmas = self.vmas.clone();
+        let mut new_map = Self::with_addr_spc(AS::new()?);
+
+        new_map.mmap_base = self.mmap_base;
+
+        // `MADV_DONTFORK` mappings aren't copied, and memory locks aren't
+        // inherited.
+        for vma in self.vmas.values().filter(|vma| !vma.flags.dont_fork) {
+            let mut vma = vma.clone();
 
-        for vma in new_vmas.values() {
+            vma.flags.locked = false;
+            new_map.merge_neighbours(vma);
+        }
+
+        for vma in self
+            .vmas
+            .values()
+            .filter(|vma| !vma.is_shared() && !vma.flags.dont_fork)
+        {
             let mut pte_perms = PtePermissions::from(vma.permissions);
 
             // Mark all writable pages as CoW.
@@ -484,15 +1002,12 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
 
             self.address_space.protect_and_clone_region(
                 vma.region.align_to_page_boundary(),
-                &mut new_as,
+                &mut new_map.address_space,
                 pte_perms,
             )?;
         }
 
-        Ok(Self {
-            vmas: new_vmas,
-            address_space: new_as,
-        })
+        Ok(new_map)
     }
 
     pub fn address_space_mut(&mut self) -> &mut AS {
@@ -502,6 +1017,61 @@ impl<AS: UserAddressSpace> MemoryMap<AS> {
     pub fn vma_count(&self) -> usize {
         self.vmas.len()
     }
+
+    /// Returns the total size, in bytes, of all mappings in the address space.
+    pub fn total_size(&self) -> usize {
+        self.vmas.values().map(|vma| vma.region.size()).sum()
+    }
+
+    /// Returns the total size, in bytes, of the private writable mappings in
+    /// the address space. This is the quantity limited by `RLIMIT_DATA`.
+    pub fn data_size(&self) -> usize {
+        self.vmas
+            .values()
+            .filter(|vma| vma.permissions.write && !vma.is_shared())
+            .map(|vma| vma.region.size())
+            .sum()
+    }
+
+    /// Returns the size, in bytes, of the mappings within `region`.
+    pub fn size_in(&self, region: VirtMemoryRegion) -> usize {
+        self.vmas
+            .values()
+            .filter_map(|vma| vma.region.intersection(region))
+            .map(|region| region.size())
+            .sum()
+    }
+
+    /// Returns the size, in bytes, of the private writable mappings within
+    /// `region`, as counted by [`data_size`](Self::data_size).
+    pub fn data_size_in(&self, region: VirtMemoryRegion) -> usize {
+        self.vmas
+            .values()
+            .filter(|vma| vma.permissions.write && !vma.is_shared())
+            .filter_map(|vma| vma.region.intersection(region))
+            .map(|region| region.size())
+            .sum()
+    }
+
+    /// Returns the number of pages of the address space which are resident,
+    /// followed by the number which have been swapped out.
+    pub fn page_counts(&self) -> (usize, usize) {
+        self.vmas
+            .values()
+            .map(|vma| {
+                self.address_space
+                    .count_pages(vma.region.align_to_page_boundary())
+            })
+            .fold((0, 0), |(resident, swapped), (r, s)| {
+                (resident + r, swapped + s)
+            })
+    }
+
+    /// Returns an iterator over all VMAs in the address space, in ascending
+    /// address order.
+    pub fn vmas(&self) -> impl Iterator<Item = &VMArea> {
+        self.vmas.values()
+    }
 }
 
 #[cfg(test)]
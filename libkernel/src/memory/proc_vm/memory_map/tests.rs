@@ -1,21 +1,28 @@
 use super::MemoryMap;
+use crate::test::MockCpuOps;
 use crate::{
     PageInfo, UserAddressSpace,
-    error::Result,
+    error::{KernelError, Result},
     fs::Inode,
     memory::{
         PAGE_SIZE,
         address::VA,
         page::PageFrame,
+        page_alloc::{FrameAllocator, tests::TestFixture},
         permissions::PtePermissions,
         proc_vm::{
-            memory_map::{AddressRequest, MMAP_BASE},
-            vmarea::{VMAPermissions, VMArea, VMAreaKind, VMFileMapping, tests::DummyTestInode},
+            memory_map::{AddressRequest, MMAP_BASE, MemoryAdvice, RemapRequest},
+            vmarea::{
+                STACK_GUARD_GAP, SharedPages, VMAFlags, VMAPermissions, VMAccessHint, VMArea,
+                VMAreaKind, VMFileMapping, tests::DummyTestInode,
+            },
         },
         region::VirtMemoryRegion,
+        swap::SwapEntry,
     },
 };
-use alloc::sync::Arc;
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::any::Any;
 use std::sync::Mutex;
 
 /// Represents a single operation performed on the mock page table.
@@ -28,6 +35,10 @@ pub enum MockPageTableOp {
         region: VirtMemoryRegion,
         perms: PtePermissions,
     },
+    MoveRange {
+        region: VirtMemoryRegion,
+        to: VA,
+    },
 }
 
 pub struct MockAddressSpace {
@@ -73,18 +84,59 @@ impl UserAddressSpace for MockAddressSpace {
         Ok(())
     }
 
-    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<Vec<PageFrame>> {
+    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<()> {
         self.ops_log
             .lock()
             .unwrap()
             .push(MockPageTableOp::UnmapRange { region: va_range });
-        Ok(Vec::new())
+        Ok(())
+    }
+
+    fn move_range(&mut self, va_range: VirtMemoryRegion, new_start: VA) -> Result<()> {
+        self.ops_log
+            .lock()
+            .unwrap()
+            .push(MockPageTableOp::MoveRange {
+                region: va_range,
+                to: new_start,
+            });
+        Ok(())
     }
 
     fn translate(&self, _va: VA) -> Option<PageInfo> {
         None
     }
 
+    fn test_and_clear_accessed(&mut self, _va: VA) -> Result<bool> {
+        unreachable!("Not called")
+    }
+
+    fn mark_accessed(&mut self, _va: VA) -> bool {
+        unreachable!("Not called")
+    }
+
+    fn swap_out(&mut self, _va: VA, _entry: SwapEntry) -> Result<PageFrame> {
+        unreachable!("Not called")
+    }
+
+    fn swap_entry(&self, _va: VA) -> Option<SwapEntry> {
+        None
+    }
+
+    fn swap_in(
+        &mut self,
+        _va: VA,
+        _entry: SwapEntry,
+        _page: PageFrame,
+        _perms: PtePermissions,
+    ) -> Result<()> {
+        unreachable!("Not called")
+    }
+
+    fn count_pages(&self, _region: VirtMemoryRegion) -> (usize, usize) {
+        unreachable!("Not called")
+    }
+
     fn protect_and_clone_region(
         &mut self,
         _region: VirtMemoryRegion,
@@ -131,6 +183,29 @@ fn create_file_vma(
     )
 }
 
+struct DummySharedPages;
+
+impl SharedPages for DummySharedPages {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Creates a shared VMA for testing.
+fn create_shared_vma(
+    start: usize,
+    size: usize,
+    perms: VMAPermissions,
+    offset: u64,
+    pages: Arc<dyn SharedPages>,
+) -> VMArea {
+    VMArea::new(
+        VirtMemoryRegion::new(VA::from_value(start), size),
+        VMAreaKind::new_shared(pages, offset),
+        perms,
+    )
+}
+
 // Creates an anonymous VMA for testing.
 fn create_anon_vma(start: usize, size: usize, perms: VMAPermissions) -> VMArea {
     VMArea::new(
@@ -203,6 +278,28 @@ fn test_mmap_any_empty() {
     assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
 }
 
+#[test]
+fn test_mmap_any_custom_base() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let base = MMAP_BASE - 0x1234 * PAGE_SIZE;
+    let size = 2 * PAGE_SIZE;
+
+    pvm.set_mmap_base(VA::from_value(base + 0x10));
+    assert_eq!(pvm.mmap_base().value(), base);
+
+    let addr = pvm
+        .mmap(
+            AddressRequest::Any,
+            size,
+            VMAPermissions::rw(),
+            VMAreaKind::Anon,
+        )
+        .unwrap();
+
+    assert_eq!(addr.value(), base - size);
+    assert_vma_exists(&pvm, base - size, size);
+}
+
 #[test]
 fn test_mmap_any_with_existing() {
     let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
@@ -603,7 +700,7 @@ fn test_munmap_full_vma() {
     assert!(pvm.vmas.is_empty());
     assert_eq!(
         *pvm.address_space.ops_log.lock().unwrap(),
-        &[MockPageTableOp::UnmapRange { region: region }]
+        &[MockPageTableOp::UnmapRange { region }]
     );
 }
 
@@ -625,7 +722,7 @@ fn test_munmap_truncate_start() {
     assert_vma_exists(&pvm, new_start, new_size);
     assert_eq!(
         *pvm.address_space.ops_log.lock().unwrap(),
-        &[MockPageTableOp::UnmapRange { region: region }]
+        &[MockPageTableOp::UnmapRange { region }]
     );
 }
 
@@ -646,7 +743,7 @@ fn test_munmap_truncate_end() {
     assert_vma_exists(&pvm, addr, new_size);
     assert_eq!(
         *pvm.address_space.ops_log.lock().unwrap(),
-        &[MockPageTableOp::UnmapRange { region: region }]
+        &[MockPageTableOp::UnmapRange { region }]
     );
 }
 
@@ -672,7 +769,7 @@ fn test_munmap_punch_hole() {
     assert_vma_exists(&pvm, right_start, right_size);
     assert_eq!(
         *pvm.address_space.ops_log.lock().unwrap(),
-        &[MockPageTableOp::UnmapRange { region: region }]
+        &[MockPageTableOp::UnmapRange { region }]
     );
 }
 
@@ -892,3 +989,1305 @@ fn test_mprotect_merge_restoration() {
     assert_vma_exists(&pvm, start, size);
     assert_vma_perms(&pvm, start, VMAPermissions::rw());
 }
+
+#[test]
+fn test_mprotect_spanning_vmas() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+    let inode = new_inode();
+
+    // A RELRO-like layout: file-backed data, followed by anonymous bss.
+    pvm.insert_and_merge(create_file_vma(
+        start,
+        2 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        inode.clone(),
+    ));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 2 * PAGE_SIZE,
+        2 * PAGE_SIZE,
+        VMAPermissions::rw(),
+    ));
+
+    let region = VirtMemoryRegion::new(VA::from_value(start), 3 * PAGE_SIZE);
+    pvm.mprotect(region, VMAPermissions::ro()).unwrap();
+
+    assert_eq!(pvm.vma_count(), 3);
+    assert_vma_exists(&pvm, start, 2 * PAGE_SIZE);
+    assert_vma_perms(&pvm, start, VMAPermissions::ro());
+    assert_vma_exists(&pvm, start + 2 * PAGE_SIZE, PAGE_SIZE);
+    assert_vma_perms(&pvm, start + 2 * PAGE_SIZE, VMAPermissions::ro());
+    assert_vma_exists(&pvm, start + 3 * PAGE_SIZE, PAGE_SIZE);
+    assert_vma_perms(&pvm, start + 3 * PAGE_SIZE, VMAPermissions::rw());
+
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[
+            MockPageTableOp::ProtectRange {
+                region: VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE),
+                perms: VMAPermissions::ro().into(),
+            },
+            MockPageTableOp::ProtectRange {
+                region: VirtMemoryRegion::new(VA::from_value(start + 2 * PAGE_SIZE), PAGE_SIZE),
+                perms: VMAPermissions::ro().into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_mprotect_spanning_merges() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 2 * PAGE_SIZE,
+        PAGE_SIZE,
+        VMAPermissions::ro(),
+    ));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 3 * PAGE_SIZE,
+        2 * PAGE_SIZE,
+        VMAPermissions::rx(),
+    ));
+    assert_eq!(pvm.vma_count(), 3);
+
+    // Partial ranges at either end, covering the middle VMA entirely.
+    let region = VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), 3 * PAGE_SIZE);
+    pvm.mprotect(region, VMAPermissions::rw()).unwrap();
+
+    assert_eq!(pvm.vma_count(), 2);
+    assert_vma_exists(&pvm, start, 4 * PAGE_SIZE);
+    assert_vma_perms(&pvm, start, VMAPermissions::rw());
+    assert_vma_exists(&pvm, start + 4 * PAGE_SIZE, PAGE_SIZE);
+    assert_vma_perms(&pvm, start + 4 * PAGE_SIZE, VMAPermissions::rx());
+}
+
+#[test]
+fn test_mprotect_hole() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 2 * PAGE_SIZE,
+        PAGE_SIZE,
+        VMAPermissions::rw(),
+    ));
+
+    let result = pvm.mprotect(
+        VirtMemoryRegion::new(VA::from_value(start), 3 * PAGE_SIZE),
+        VMAPermissions::ro(),
+    );
+
+    assert!(matches!(result, Err(KernelError::NoMemory)));
+    assert_vma_perms(&pvm, start, VMAPermissions::rw());
+    assert_vma_perms(&pvm, start + 2 * PAGE_SIZE, VMAPermissions::rw());
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+
+    // A range that starts in a hole fails too.
+    let result = pvm.mprotect(
+        VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), 2 * PAGE_SIZE),
+        VMAPermissions::ro(),
+    );
+
+    assert!(matches!(result, Err(KernelError::NoMemory)));
+}
+
+#[test]
+fn test_total_and_data_size() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+
+    assert_eq!(pvm.total_size(), 0);
+    assert_eq!(pvm.data_size(), 0);
+
+    pvm.insert_and_merge(create_anon_vma(
+        0x10000,
+        2 * PAGE_SIZE,
+        VMAPermissions::rx(),
+    ));
+    pvm.insert_and_merge(create_anon_vma(
+        0x20000,
+        3 * PAGE_SIZE,
+        VMAPermissions::rw(),
+    ));
+    pvm.insert_and_merge(create_file_vma(
+        0x30000,
+        PAGE_SIZE,
+        VMAPermissions::ro(),
+        0,
+        new_inode(),
+    ));
+
+    assert_eq!(pvm.total_size(), 6 * PAGE_SIZE);
+    assert_eq!(pvm.data_size(), 3 * PAGE_SIZE);
+
+    // Only the parts of mappings within a region are counted.
+    let region = VirtMemoryRegion::new(VA::from_value(0x11000), 0x20000);
+    assert_eq!(pvm.size_in(region), 5 * PAGE_SIZE);
+    assert_eq!(pvm.data_size_in(region), 3 * PAGE_SIZE);
+
+    // Making part of the writable mapping read-only removes it from the data
+    // size, but not the total.
+    pvm.mprotect(
+        VirtMemoryRegion::new(VA::from_value(0x20000), PAGE_SIZE),
+        VMAPermissions::ro(),
+    )
+    .unwrap();
+
+    assert_eq!(pvm.total_size(), 6 * PAGE_SIZE);
+    assert_eq!(pvm.data_size(), 2 * PAGE_SIZE);
+
+    pvm.munmap(VirtMemoryRegion::new(
+        VA::from_value(0x10000),
+        2 * PAGE_SIZE,
+    ))
+    .unwrap();
+
+    assert_eq!(pvm.total_size(), 4 * PAGE_SIZE);
+}
+
+#[test]
+fn test_merge_shared_contiguous() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let perms = VMAPermissions::rw();
+    let pages: Arc<dyn SharedPages> = Arc::new(DummySharedPages);
+    let addr = MMAP_BASE - 10 * PAGE_SIZE;
+
+    pvm.insert_and_merge(create_shared_vma(
+        addr,
+        2 * PAGE_SIZE,
+        perms,
+        0,
+        pages.clone(),
+    ));
+    pvm.insert_and_merge(create_shared_vma(
+        addr + 2 * PAGE_SIZE,
+        PAGE_SIZE,
+        perms,
+        2 * PAGE_SIZE as u64,
+        pages.clone(),
+    ));
+
+    assert_eq!(pvm.vmas.len(), 1);
+    assert_vma_exists(&pvm, addr, 3 * PAGE_SIZE);
+}
+
+#[test]
+fn test_no_merge_shared_different_objects() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let perms = VMAPermissions::rw();
+    let addr = MMAP_BASE - 10 * PAGE_SIZE;
+
+    pvm.insert_and_merge(create_shared_vma(
+        addr,
+        2 * PAGE_SIZE,
+        perms,
+        0,
+        Arc::new(DummySharedPages),
+    ));
+    pvm.insert_and_merge(create_shared_vma(
+        addr + 2 * PAGE_SIZE,
+        PAGE_SIZE,
+        perms,
+        2 * PAGE_SIZE as u64,
+        Arc::new(DummySharedPages),
+    ));
+
+    // Nor should a shared VMA merge with an adjacent anonymous one.
+    pvm.insert_and_merge(create_anon_vma(addr + 3 * PAGE_SIZE, PAGE_SIZE, perms));
+
+    assert_eq!(pvm.vmas.len(), 3);
+}
+
+#[test]
+fn test_munmap_shared_truncate_start() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let addr = MMAP_BASE - 10 * PAGE_SIZE;
+
+    pvm.insert_and_merge(create_shared_vma(
+        addr,
+        4 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        PAGE_SIZE as u64,
+        Arc::new(DummySharedPages),
+    ));
+
+    pvm.munmap(VirtMemoryRegion::new(VA::from_value(addr), PAGE_SIZE))
+        .unwrap();
+
+    assert_vma_exists(&pvm, addr + PAGE_SIZE, 3 * PAGE_SIZE);
+    let vma = pvm.find_vma(VA::from_value(addr + PAGE_SIZE)).unwrap();
+    match &vma.kind {
+        VMAreaKind::Shared(mapping) => assert_eq!(mapping.offset(), 2 * PAGE_SIZE as u64),
+        _ => panic!("Expected shared VMA"),
+    }
+}
+
+#[test]
+fn test_mprotect_shared_unmaps() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x60000;
+
+    pvm.insert_and_merge(create_shared_vma(
+        start,
+        2 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        Arc::new(DummySharedPages),
+    ));
+
+    let region = VirtMemoryRegion::new(VA::from_value(start), PAGE_SIZE);
+    pvm.mprotect(region, VMAPermissions::ro()).unwrap();
+
+    assert_vma_perms(&pvm, start, VMAPermissions::ro());
+    assert_vma_perms(&pvm, start + PAGE_SIZE, VMAPermissions::rw());
+
+    // The shared pages are unmapped, to be faulted back in with the new
+    // permissions, rather than protected in place.
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[MockPageTableOp::UnmapRange { region }]
+    );
+
+    // The offset of the right-hand VMA within the object is preserved.
+    let right = pvm.find_vma(VA::from_value(start + PAGE_SIZE)).unwrap();
+    match &right.kind {
+        VMAreaKind::Shared(mapping) => assert_eq!(mapping.offset(), PAGE_SIZE as u64),
+        _ => panic!("Expected shared VMA"),
+    }
+}
+
+#[test]
+fn test_mprotect_may_not_write() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x60000;
+    let region = VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE);
+
+    pvm.insert_and_merge(
+        create_shared_vma(
+            start,
+            2 * PAGE_SIZE,
+            VMAPermissions::ro(),
+            0,
+            Arc::new(DummySharedPages),
+        )
+        .with_flags(VMAFlags {
+            may_write: false,
+            ..VMAFlags::default()
+        }),
+    );
+
+    // Write permission can't be added, even to part of the VMA.
+    assert!(matches!(
+        pvm.mprotect(
+            VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), PAGE_SIZE),
+            VMAPermissions::rw()
+        ),
+        Err(KernelError::AccessDenied)
+    ));
+    assert_vma_perms(&pvm, start + PAGE_SIZE, VMAPermissions::ro());
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+
+    // Other permissions can still be changed.
+    pvm.mprotect(region, VMAPermissions::rx()).unwrap();
+    assert_vma_perms(&pvm, start, VMAPermissions::rx());
+}
+
+#[test]
+fn test_unmap_shared_page() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let pages: Arc<dyn SharedPages> = Arc::new(DummySharedPages);
+    let start = 0x60000;
+
+    // Two mappings of the same object, at different offsets, and one of
+    // another object.
+    pvm.insert_and_merge(create_shared_vma(
+        start,
+        2 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        PAGE_SIZE as u64,
+        pages.clone(),
+    ));
+    pvm.insert_and_merge(create_shared_vma(
+        start + 4 * PAGE_SIZE,
+        4 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        pages.clone(),
+    ));
+    pvm.insert_and_merge(create_shared_vma(
+        start + 10 * PAGE_SIZE,
+        4 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        Arc::new(DummySharedPages),
+    ));
+
+    pvm.unmap_shared_page(&*pages, 2 * PAGE_SIZE as u64)
+        .unwrap();
+
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[
+            MockPageTableOp::UnmapRange {
+                region: VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), PAGE_SIZE)
+            },
+            MockPageTableOp::UnmapRange {
+                region: VirtMemoryRegion::new(VA::from_value(start + 6 * PAGE_SIZE), PAGE_SIZE)
+            },
+        ]
+    );
+
+    // A page outside of every mapping isn't unmapped anywhere.
+    pvm.address_space.ops_log.lock().unwrap().clear();
+    pvm.unmap_shared_page(&*pages, 8 * PAGE_SIZE as u64)
+        .unwrap();
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_data_size_excludes_shared() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+
+    pvm.insert_and_merge(create_anon_vma(
+        0x10000,
+        2 * PAGE_SIZE,
+        VMAPermissions::rw(),
+    ));
+    pvm.insert_and_merge(create_shared_vma(
+        0x20000,
+        3 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        Arc::new(DummySharedPages),
+    ));
+
+    assert_eq!(pvm.total_size(), 5 * PAGE_SIZE);
+    assert_eq!(pvm.data_size(), 2 * PAGE_SIZE);
+}
+
+#[test]
+fn test_unmap_all() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let perms = VMAPermissions::rw();
+
+    pvm.insert_and_merge(create_anon_vma(0x10000, 2 * PAGE_SIZE, perms));
+    pvm.insert_and_merge(create_file_vma(
+        0x20000,
+        PAGE_SIZE,
+        VMAPermissions::ro(),
+        0,
+        new_inode(),
+    ));
+
+    pvm.unmap_all().unwrap();
+
+    assert_eq!(pvm.vma_count(), 0);
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[
+            MockPageTableOp::UnmapRange {
+                region: VirtMemoryRegion::new(VA::from_value(0x10000), 2 * PAGE_SIZE)
+            },
+            MockPageTableOp::UnmapRange {
+                region: VirtMemoryRegion::new(VA::from_value(0x20000), PAGE_SIZE)
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_mremap_shrink() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 4 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let addr = pvm
+        .mremap(
+            VirtMemoryRegion::new(VA::from_value(start), 4 * PAGE_SIZE),
+            PAGE_SIZE,
+            RemapRequest::InPlace,
+            false,
+        )
+        .unwrap();
+
+    assert_eq!(addr.value(), start);
+    assert_eq!(pvm.vma_count(), 1);
+    assert_vma_exists(&pvm, start, PAGE_SIZE);
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[MockPageTableOp::UnmapRange {
+            region: VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), 3 * PAGE_SIZE)
+        }]
+    );
+}
+
+#[test]
+fn test_mremap_grow_in_place() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+    let perms = VMAPermissions::rw();
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, perms));
+    // A compatible neighbour after the growth should be merged.
+    pvm.insert_and_merge(create_anon_vma(start + 4 * PAGE_SIZE, PAGE_SIZE, perms));
+
+    let addr = pvm
+        .mremap(
+            VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE),
+            4 * PAGE_SIZE,
+            RemapRequest::InPlace,
+            false,
+        )
+        .unwrap();
+
+    assert_eq!(addr.value(), start);
+    assert_eq!(pvm.vma_count(), 1);
+    assert_vma_exists(&pvm, start, 5 * PAGE_SIZE);
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_mremap_grow_file_in_place() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_file_vma(
+        start,
+        PAGE_SIZE,
+        VMAPermissions::ro(),
+        PAGE_SIZE as u64,
+        new_inode(),
+    ));
+
+    pvm.mremap(
+        VirtMemoryRegion::new(VA::from_value(start), PAGE_SIZE),
+        3 * PAGE_SIZE,
+        RemapRequest::InPlace,
+        false,
+    )
+    .unwrap();
+
+    assert_vma_exists(&pvm, start, 3 * PAGE_SIZE);
+    match pvm.find_vma(VA::from_value(start)).unwrap().kind() {
+        VMAreaKind::File(mapping) => {
+            assert_eq!(mapping.offset(), PAGE_SIZE as u64);
+            assert_eq!(mapping.file_len(), 3 * PAGE_SIZE as u64);
+        }
+        _ => panic!("Expected file VMA"),
+    }
+}
+
+#[test]
+fn test_mremap_grow_blocked() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 3 * PAGE_SIZE,
+        PAGE_SIZE,
+        VMAPermissions::ro(),
+    ));
+
+    let result = pvm.mremap(
+        VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE),
+        4 * PAGE_SIZE,
+        RemapRequest::InPlace,
+        false,
+    );
+
+    assert!(matches!(result, Err(KernelError::NoMemory)));
+    assert_eq!(pvm.vma_count(), 2);
+    assert_vma_exists(&pvm, start, 2 * PAGE_SIZE);
+}
+
+#[test]
+fn test_mremap_not_mapped() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, PAGE_SIZE, VMAPermissions::rw()));
+
+    // The region extends past the end of the VMA.
+    let result = pvm.mremap(
+        VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE),
+        4 * PAGE_SIZE,
+        RemapRequest::MayMove,
+        false,
+    );
+
+    assert!(matches!(result, Err(KernelError::Fault)));
+}
+
+#[test]
+fn test_mremap_maymove() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_file_vma(
+        start,
+        2 * PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        new_inode(),
+    ));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 2 * PAGE_SIZE,
+        PAGE_SIZE,
+        VMAPermissions::ro(),
+    ));
+
+    // Move the second page of the file mapping.
+    let old_region = VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), PAGE_SIZE);
+    let addr = pvm
+        .mremap(old_region, 3 * PAGE_SIZE, RemapRequest::MayMove, false)
+        .unwrap();
+
+    assert_eq!(addr.value(), MMAP_BASE - 3 * PAGE_SIZE);
+    assert_eq!(pvm.vma_count(), 3);
+    assert_vma_exists(&pvm, start, PAGE_SIZE);
+    assert_vma_exists(&pvm, addr.value(), 3 * PAGE_SIZE);
+
+    match pvm.find_vma(addr).unwrap().kind() {
+        VMAreaKind::File(mapping) => {
+            assert_eq!(mapping.offset(), PAGE_SIZE as u64);
+            assert_eq!(mapping.file_len(), 3 * PAGE_SIZE as u64);
+        }
+        _ => panic!("Expected file VMA"),
+    }
+
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[
+            MockPageTableOp::MoveRange {
+                region: old_region,
+                to: addr,
+            },
+            MockPageTableOp::UnmapRange { region: old_region },
+        ]
+    );
+}
+
+#[test]
+fn test_mremap_fixed() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+    let dest = 0x40000;
+    let perms = VMAPermissions::rw();
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, perms));
+    pvm.insert_and_merge(create_anon_vma(dest, 4 * PAGE_SIZE, VMAPermissions::ro()));
+
+    let old_region = VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE);
+    let addr = pvm
+        .mremap(
+            old_region,
+            2 * PAGE_SIZE,
+            RemapRequest::Fixed(VA::from_value(dest)),
+            false,
+        )
+        .unwrap();
+
+    assert_eq!(addr.value(), dest);
+    assert_eq!(pvm.vma_count(), 2);
+    assert!(pvm.find_vma(VA::from_value(start)).is_none());
+    assert_vma_exists(&pvm, dest, 2 * PAGE_SIZE);
+    assert_vma_perms(&pvm, dest, perms);
+    assert_vma_exists(&pvm, dest + 2 * PAGE_SIZE, 2 * PAGE_SIZE);
+    assert_vma_perms(&pvm, dest + 2 * PAGE_SIZE, VMAPermissions::ro());
+
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[
+            MockPageTableOp::UnmapRange {
+                region: VirtMemoryRegion::new(VA::from_value(dest), 2 * PAGE_SIZE)
+            },
+            MockPageTableOp::MoveRange {
+                region: old_region,
+                to: VA::from_value(dest),
+            },
+            MockPageTableOp::UnmapRange { region: old_region },
+        ]
+    );
+}
+
+#[test]
+fn test_mremap_fixed_overlap() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let result = pvm.mremap(
+        VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE),
+        2 * PAGE_SIZE,
+        RemapRequest::Fixed(VA::from_value(start + PAGE_SIZE)),
+        false,
+    );
+
+    assert!(matches!(result, Err(KernelError::InvalidValue)));
+}
+
+#[test]
+fn test_mremap_dontunmap() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let old_region = VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE);
+
+    // The size can't change.
+    assert!(matches!(
+        pvm.mremap(old_region, 3 * PAGE_SIZE, RemapRequest::MayMove, true),
+        Err(KernelError::InvalidValue)
+    ));
+
+    let addr = pvm
+        .mremap(old_region, 2 * PAGE_SIZE, RemapRequest::MayMove, true)
+        .unwrap();
+
+    assert_eq!(pvm.vma_count(), 2);
+    assert_vma_exists(&pvm, start, 2 * PAGE_SIZE);
+    assert_vma_exists(&pvm, addr.value(), 2 * PAGE_SIZE);
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[MockPageTableOp::MoveRange {
+            region: old_region,
+            to: addr,
+        }]
+    );
+}
+
+#[test]
+fn test_madvise_access_hint_splits() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 4 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let region = VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), 2 * PAGE_SIZE);
+    pvm.madvise(region, MemoryAdvice::Access(VMAccessHint::Sequential))
+        .unwrap();
+
+    assert_eq!(pvm.vma_count(), 3);
+    assert_vma_exists(&pvm, start, PAGE_SIZE);
+    assert_vma_exists(&pvm, start + PAGE_SIZE, 2 * PAGE_SIZE);
+    assert_vma_exists(&pvm, start + 3 * PAGE_SIZE, PAGE_SIZE);
+    assert_eq!(
+        pvm.find_vma(VA::from_value(start + PAGE_SIZE))
+            .unwrap()
+            .flags()
+            .access,
+        VMAccessHint::Sequential
+    );
+
+    // Restoring the default hint merges the VMAs back together.
+    pvm.madvise(region, MemoryAdvice::Access(VMAccessHint::Normal))
+        .unwrap();
+
+    assert_eq!(pvm.vma_count(), 1);
+    assert_vma_exists(&pvm, start, 4 * PAGE_SIZE);
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_madvise_spanning_vmas() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 2 * PAGE_SIZE,
+        2 * PAGE_SIZE,
+        VMAPermissions::ro(),
+    ));
+
+    pvm.madvise(
+        VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), 2 * PAGE_SIZE),
+        MemoryAdvice::DontFork,
+    )
+    .unwrap();
+
+    assert_eq!(pvm.vma_count(), 4);
+
+    let dont_fork: Vec<_> = pvm.vmas().map(|vma| vma.flags().dont_fork).collect();
+    assert_eq!(dont_fork, &[false, true, true, false]);
+}
+
+#[test]
+fn test_madvise_unmapped() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_anon_vma(
+        start + 2 * PAGE_SIZE,
+        PAGE_SIZE,
+        VMAPermissions::rw(),
+    ));
+
+    let result = pvm.madvise(
+        VirtMemoryRegion::new(VA::from_value(start), 3 * PAGE_SIZE),
+        MemoryAdvice::DontNeed,
+    );
+
+    assert!(matches!(result, Err(KernelError::NoMemory)));
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_madvise_dontneed() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 4 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let region = VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), PAGE_SIZE);
+    pvm.madvise(region, MemoryAdvice::DontNeed).unwrap();
+
+    // The mapping itself is unchanged.
+    assert_eq!(pvm.vma_count(), 1);
+    assert_vma_exists(&pvm, start, 4 * PAGE_SIZE);
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[MockPageTableOp::UnmapRange { region }]
+    );
+}
+
+#[test]
+fn test_madvise_free_file_backed() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_file_vma(
+        start,
+        PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        new_inode(),
+    ));
+
+    let region = VirtMemoryRegion::new(VA::from_value(start), PAGE_SIZE);
+
+    assert!(matches!(
+        pvm.madvise(region, MemoryAdvice::Free),
+        Err(KernelError::InvalidValue)
+    ));
+
+    // Private file pages may be dropped, to be read back from the file.
+    pvm.madvise(region, MemoryAdvice::DontNeed).unwrap();
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[MockPageTableOp::UnmapRange { region }]
+    );
+}
+
+#[test]
+fn test_madvise_dontneed_locked() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+    let region = VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE);
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+    pvm.mlock(region, true).unwrap();
+
+    assert!(matches!(
+        pvm.madvise(region, MemoryAdvice::DontNeed),
+        Err(KernelError::InvalidValue)
+    ));
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_mlock_accounting() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 4 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let region = VirtMemoryRegion::new(VA::from_value(start + PAGE_SIZE), 2 * PAGE_SIZE);
+    pvm.mlock(region, true).unwrap();
+
+    assert_eq!(pvm.vma_count(), 3);
+    assert_eq!(pvm.locked_size(), 2 * PAGE_SIZE);
+    assert_eq!(
+        pvm.locked_size_in(VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE)),
+        PAGE_SIZE
+    );
+    assert!(
+        pvm.find_vma(VA::from_value(start + PAGE_SIZE))
+            .unwrap()
+            .flags()
+            .locked
+    );
+
+    pvm.mlock(region, false).unwrap();
+
+    assert_eq!(pvm.vma_count(), 1);
+    assert_eq!(pvm.locked_size(), 0);
+
+    assert!(matches!(
+        pvm.mlock(
+            VirtMemoryRegion::new(VA::from_value(start), 5 * PAGE_SIZE),
+            true
+        ),
+        Err(KernelError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_mlock_all() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+
+    pvm.insert_and_merge(create_anon_vma(0x10000, PAGE_SIZE, VMAPermissions::rw()));
+    pvm.mlock_all(true);
+    pvm.set_lock_future(true);
+
+    let addr = pvm
+        .mmap(
+            AddressRequest::Any,
+            2 * PAGE_SIZE,
+            VMAPermissions::rw(),
+            VMAreaKind::Anon,
+        )
+        .unwrap();
+
+    assert!(pvm.find_vma(addr).unwrap().flags().locked);
+    assert_eq!(pvm.locked_size(), 3 * PAGE_SIZE);
+
+    pvm.mlock_all(false);
+    pvm.set_lock_future(false);
+
+    assert_eq!(pvm.locked_size(), 0);
+}
+
+#[test]
+fn test_clone_skips_dontfork() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_shared_vma(
+        start + 4 * PAGE_SIZE,
+        PAGE_SIZE,
+        VMAPermissions::rw(),
+        0,
+        Arc::new(DummySharedPages),
+    ));
+
+    pvm.madvise(
+        VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE),
+        MemoryAdvice::DontFork,
+    )
+    .unwrap();
+    pvm.mlock_all(true);
+
+    let child = pvm.clone_as_cow().unwrap();
+
+    // Only the shared VMA is inherited, and without its lock.
+    assert_eq!(child.vma_count(), 1);
+    assert_vma_exists(&child, start + 4 * PAGE_SIZE, PAGE_SIZE);
+    assert_eq!(child.locked_size(), 0);
+}
+
+// Creates a grows-down stack VMA for testing.
+fn create_stack_vma(start: usize, size: usize) -> VMArea {
+    create_anon_vma(start, size, VMAPermissions::rw()).with_flags(VMAFlags {
+        grows_down: true,
+        ..VMAFlags::default()
+    })
+}
+
+#[test]
+fn test_grow_stack() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let stack_start = 0x100_0000;
+
+    pvm.insert_and_merge(create_stack_vma(stack_start, 2 * PAGE_SIZE));
+
+    // Addresses within the stack need no growth.
+    pvm.grow_stack(VA::from_value(stack_start + 8)).unwrap();
+    assert_vma_exists(&pvm, stack_start, 2 * PAGE_SIZE);
+
+    let fault_addr = VA::from_value(stack_start - 3 * PAGE_SIZE + 0x10);
+    assert_eq!(
+        pvm.find_stack_above(fault_addr)
+            .unwrap()
+            .region()
+            .start_address()
+            .value(),
+        stack_start
+    );
+
+    pvm.grow_stack(fault_addr).unwrap();
+
+    assert_eq!(pvm.vma_count(), 1);
+    assert_vma_exists(&pvm, stack_start - 3 * PAGE_SIZE, 5 * PAGE_SIZE);
+    assert!(pvm.find_stack_above(fault_addr).is_none());
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_grow_stack_not_grows_down() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x100_0000;
+
+    pvm.insert_and_merge(create_anon_vma(start, 2 * PAGE_SIZE, VMAPermissions::rw()));
+
+    let fault_addr = VA::from_value(start - PAGE_SIZE);
+
+    assert!(pvm.find_stack_above(fault_addr).is_none());
+    assert!(matches!(
+        pvm.grow_stack(fault_addr),
+        Err(KernelError::Fault)
+    ));
+    assert!(matches!(
+        pvm.grow_stack(VA::from_value(start)),
+        Err(KernelError::Fault)
+    ));
+}
+
+#[test]
+fn test_grow_stack_guard_gap() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let below = 0x100_0000;
+    let stack_start = below + PAGE_SIZE + STACK_GUARD_GAP + 2 * PAGE_SIZE;
+
+    pvm.insert_and_merge(create_anon_vma(below, PAGE_SIZE, VMAPermissions::rw()));
+    pvm.insert_and_merge(create_stack_vma(stack_start, PAGE_SIZE));
+
+    // Growing down to the edge of the gap is fine...
+    pvm.grow_stack(VA::from_value(stack_start - 2 * PAGE_SIZE))
+        .unwrap();
+    assert_vma_exists(&pvm, stack_start - 2 * PAGE_SIZE, 3 * PAGE_SIZE);
+
+    // ...but not into it.
+    assert!(matches!(
+        pvm.grow_stack(VA::from_value(stack_start - 3 * PAGE_SIZE)),
+        Err(KernelError::NoMemory)
+    ));
+    assert_vma_exists(&pvm, stack_start - 2 * PAGE_SIZE, 3 * PAGE_SIZE);
+}
+
+#[test]
+fn test_mmap_avoids_stack_guard_gap() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let stack_start = MMAP_BASE - 4 * PAGE_SIZE;
+
+    pvm.insert_and_merge(create_stack_vma(stack_start, 4 * PAGE_SIZE));
+
+    let addr = pvm
+        .mmap(
+            AddressRequest::Any,
+            PAGE_SIZE,
+            VMAPermissions::rw(),
+            VMAreaKind::Anon,
+        )
+        .unwrap();
+
+    assert_eq!(addr.value(), stack_start - STACK_GUARD_GAP - PAGE_SIZE);
+
+    // Nor may a fixed mapping be placed in the gap without replacing.
+    let result = pvm.mmap(
+        AddressRequest::Fixed {
+            address: VA::from_value(stack_start - PAGE_SIZE),
+            permit_overlap: false,
+        },
+        PAGE_SIZE,
+        VMAPermissions::rw(),
+        VMAreaKind::Anon,
+    );
+
+    assert!(matches!(result, Err(KernelError::InvalidValue)));
+}
+
+#[test]
+fn test_mmap_grows_down() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let flags = VMAFlags {
+        grows_down: true,
+        ..VMAFlags::default()
+    };
+
+    let addr = pvm
+        .mmap_with_flags(
+            AddressRequest::Any,
+            2 * PAGE_SIZE,
+            VMAPermissions::rw(),
+            VMAreaKind::Anon,
+            flags,
+        )
+        .unwrap();
+
+    assert!(pvm.find_vma(addr).unwrap().flags().grows_down);
+
+    // File mappings can't grow down.
+    let result = pvm.mmap_with_flags(
+        AddressRequest::Any,
+        PAGE_SIZE,
+        VMAPermissions::rw(),
+        VMAreaKind::new_file(new_inode(), 0, PAGE_SIZE as u64),
+        flags,
+    );
+
+    assert!(matches!(result, Err(KernelError::InvalidValue)));
+}
+
+#[test]
+fn test_special_mapping_restrictions() {
+    let mut pvm: MemoryMap<MockAddressSpace> = MemoryMap::new().unwrap();
+    let start = 0x10000;
+    let region = VirtMemoryRegion::new(VA::from_value(start), 2 * PAGE_SIZE);
+
+    pvm.insert_and_merge(VMArea::new(
+        region,
+        VMAreaKind::Special,
+        VMAPermissions::rx(),
+    ));
+
+    // Its pages can't be made writable, dropped or moved.
+    assert!(matches!(
+        pvm.mprotect(region, VMAPermissions::rw()),
+        Err(KernelError::AccessDenied)
+    ));
+    assert!(matches!(
+        pvm.madvise(region, MemoryAdvice::DontNeed),
+        Err(KernelError::InvalidValue)
+    ));
+    assert!(matches!(
+        pvm.mremap(region, 4 * PAGE_SIZE, RemapRequest::MayMove, false),
+        Err(KernelError::InvalidValue)
+    ));
+    assert!(pvm.address_space.ops_log.lock().unwrap().is_empty());
+
+    // Dropping permissions is fine.
+    pvm.mprotect(region, VMAPermissions::ro()).unwrap();
+    assert_vma_perms(&pvm, start, VMAPermissions::ro());
+
+    // Mapping over it drops its pages, rather than keeping them with the new
+    // mapping's permissions.
+    pvm.address_space.ops_log.lock().unwrap().clear();
+    pvm.insert_and_merge(create_anon_vma(start, PAGE_SIZE, VMAPermissions::rw()));
+
+    assert_eq!(
+        *pvm.address_space.ops_log.lock().unwrap(),
+        &[MockPageTableOp::UnmapRange {
+            region: VirtMemoryRegion::new(VA::from_value(start), PAGE_SIZE)
+        }]
+    );
+}
+
+std::thread_local! {
+    /// The allocator that a `FrameAddressSpace` takes its pages from.
+    static FRAMES: core::cell::Cell<Option<&'static FrameAllocator<MockCpuOps>>> =
+        const { core::cell::Cell::new(None) };
+}
+
+/// An address space which keeps its mappings in a map, rather than page
+/// tables, but which holds a reference to each frame it maps, as the real ones
+/// do. See [`UserAddressSpace`] for which operations take and drop them.
+struct FrameAddressSpace {
+    frames: &'static FrameAllocator<MockCpuOps>,
+    ptes: BTreeMap<VA, (PageFrame, PtePermissions)>,
+}
+
+impl FrameAddressSpace {
+    fn in_region(&self, region: VirtMemoryRegion) -> Vec<VA> {
+        self.ptes
+            .range(region.start_address()..region.end_address())
+            .map(|(va, _)| *va)
+            .collect()
+    }
+}
+
+impl UserAddressSpace for FrameAddressSpace {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            frames: FRAMES.get().expect("No allocator for the address space"),
+            ptes: BTreeMap::new(),
+        })
+    }
+
+    fn activate(&self) {}
+
+    fn deactivate(&self) {}
+
+    fn map_page(&mut self, page: PageFrame, va: VA, perms: PtePermissions) -> Result<()> {
+        assert!(self.ptes.insert(va, (page, perms)).is_none());
+        Ok(())
+    }
+
+    fn unmap(&mut self, va: VA) -> Result<PageFrame> {
+        let (page, _) = self.ptes.remove(&va).ok_or(KernelError::InvalidValue)?;
+        Ok(page)
+    }
+
+    fn remap(&mut self, va: VA, new_page: PageFrame, perms: PtePermissions) -> Result<PageFrame> {
+        let old = self.unmap(va)?;
+        self.map_page(new_page, va, perms)?;
+        Ok(old)
+    }
+
+    fn protect_range(&mut self, va_range: VirtMemoryRegion, perms: PtePermissions) -> Result<()> {
+        for va in self.in_region(va_range) {
+            self.ptes.get_mut(&va).unwrap().1 = perms;
+        }
+        Ok(())
+    }
+
+    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<()> {
+        for va in self.in_region(va_range) {
+            let (page, _) = self.ptes.remove(&va).unwrap();
+            self.frames.put_page(page);
+        }
+        Ok(())
+    }
+
+    fn move_range(&mut self, va_range: VirtMemoryRegion, new_start: VA) -> Result<()> {
+        for va in self.in_region(va_range) {
+            let pte = self.ptes.remove(&va).unwrap();
+            let offset = va.value() - va_range.start_address().value();
+            self.ptes.insert(new_start.add_bytes(offset), pte);
+        }
+        Ok(())
+    }
+
+    fn translate(&self, va: VA) -> Option<PageInfo> {
+        self.ptes
+            .get(&va.page_aligned())
+            .map(|&(pfn, perms)| PageInfo { pfn, perms })
+    }
+
+    fn test_and_clear_accessed(&mut self, _va: VA) -> Result<bool> {
+        unreachable!("Not called")
+    }
+
+    fn mark_accessed(&mut self, _va: VA) -> bool {
+        unreachable!("Not called")
+    }
+
+    fn swap_out(&mut self, _va: VA, _entry: SwapEntry) -> Result<PageFrame> {
+        unreachable!("Not called")
+    }
+
+    fn swap_entry(&self, _va: VA) -> Option<SwapEntry> {
+        None
+    }
+
+    fn swap_in(
+        &mut self,
+        _va: VA,
+        _entry: SwapEntry,
+        _page: PageFrame,
+        _perms: PtePermissions,
+    ) -> Result<()> {
+        unreachable!("Not called")
+    }
+
+    fn count_pages(&self, region: VirtMemoryRegion) -> (usize, usize) {
+        (self.in_region(region).len(), 0)
+    }
+
+    fn protect_and_clone_region(
+        &mut self,
+        region: VirtMemoryRegion,
+        other: &mut Self,
+        perms: PtePermissions,
+    ) -> Result<()> {
+        for va in self.in_region(region) {
+            let pte = self.ptes.get_mut(&va).unwrap();
+
+            self.frames.get_page(pte.0);
+            pte.1 = perms;
+            other.map_page(pte.0, va, perms)?;
+        }
+        Ok(())
+    }
+}
+
+/// Faults in the page at `va` of `pvm` with a newly allocated frame, as the
+/// demand pager does.
+fn fault_in(pvm: &mut MemoryMap<FrameAddressSpace>, va: VA) {
+    let perms = PtePermissions::from(pvm.find_vma(va).unwrap().permissions());
+    let page = pvm
+        .address_space
+        .frames
+        .alloc_frames(0)
+        .unwrap()
+        .leak()
+        .start_address()
+        .to_pfn();
+
+    pvm.address_space.map_page(page, va, perms).unwrap();
+}
+
+#[test]
+fn test_frames_freed_after_fork_cow_and_unmap() {
+    let fixture: &'static TestFixture = std::boxed::Box::leak(std::boxed::Box::new(
+        TestFixture::new(&[(0, 16 * 1024 * 1024)], &[]),
+    ));
+    FRAMES.set(Some(&fixture.allocator));
+
+    let baseline = fixture.free_pages();
+
+    let mut parent: MemoryMap<FrameAddressSpace> = MemoryMap::new().unwrap();
+    let addr = parent
+        .mmap(
+            AddressRequest::Any,
+            4 * PAGE_SIZE,
+            VMAPermissions::rw(),
+            VMAreaKind::Anon,
+        )
+        .unwrap();
+
+    for i in 0..4 {
+        fault_in(&mut parent, addr.add_pages(i));
+    }
+
+    assert_eq!(fixture.free_pages(), baseline - 4);
+
+    // Fork: the child shares every page, copy-on-write.
+    let mut child = parent.clone_as_cow().unwrap();
+
+    assert_eq!(fixture.free_pages(), baseline - 4);
+    assert!(child.address_space.translate(addr).unwrap().perms.is_cow());
+
+    // The child writes to its first page, which is copied. The mapping's
+    // reference to the original comes back from the page tables, and is
+    // dropped as the fault handler does.
+    let copy = fixture
+        .allocator
+        .alloc_frames(0)
+        .unwrap()
+        .leak()
+        .start_address()
+        .to_pfn();
+    let original = child
+        .address_space
+        .remap(addr, copy, PtePermissions::from(VMAPermissions::rw()))
+        .unwrap();
+    fixture.allocator.put_page(original);
+
+    assert_eq!(fixture.free_pages(), baseline - 5);
+
+    // The parent unmaps the page the child copied, and another which is still
+    // shared. Only the first is freed.
+    parent
+        .munmap(VirtMemoryRegion::new(addr, 2 * PAGE_SIZE))
+        .unwrap();
+
+    assert_eq!(fixture.free_pages(), baseline - 4);
+
+    // Both processes exit.
+    parent.unmap_all().unwrap();
+    assert_eq!(fixture.free_pages(), baseline - 4);
+
+    child.unmap_all().unwrap();
+    assert_eq!(fixture.free_pages(), baseline);
+}
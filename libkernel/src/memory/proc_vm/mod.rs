@@ -3,7 +3,9 @@
 use crate::{
     UserAddressSpace,
     error::{KernelError, Result},
+    fs::{InodeId, pathbuf::PathBuf},
 };
+use alloc::{collections::btree_map::BTreeMap, vec::Vec};
 use memory_map::{AddressRequest, MemoryMap};
 use vmarea::{AccessKind, FaultValidation, VMAPermissions, VMArea, VMAreaKind};
 
@@ -17,6 +19,13 @@ const BRK_PERMISSIONS: VMAPermissions = VMAPermissions::rw();
 pub struct ProcessVM<AS: UserAddressSpace> {
     pub mm: MemoryMap<AS>,
     brk: VirtMemoryRegion,
+    /// The auxiliary vector handed to the program image, saved for core
+    /// dumps.
+    auxv: Vec<u64>,
+    /// The paths of files mapped when the program image was loaded.
+    file_names: BTreeMap<InodeId, PathBuf>,
+    /// Where the vDSO, preceded by its data page, is mapped.
+    vdso: Option<VirtMemoryRegion>,
 }
 
 impl<AS: UserAddressSpace> ProcessVM<AS> {
@@ -33,7 +42,13 @@ impl<AS: UserAddressSpace> ProcessVM<AS> {
 
         let brk = VirtMemoryRegion::new(vma.region.end_address().align_up(PAGE_SIZE), 0);
 
-        Self { mm, brk }
+        Self {
+            mm,
+            brk,
+            auxv: Vec::new(),
+            file_names: BTreeMap::new(),
+            vdso: None,
+        }
     }
 
     /// Constructs a new Process VM structure from the given VMA. The heap is
@@ -45,13 +60,22 @@ impl<AS: UserAddressSpace> ProcessVM<AS> {
 
         let brk = VirtMemoryRegion::new(vma.region.end_address().align_up(PAGE_SIZE), 0);
 
-        Ok(Self { mm, brk })
+        Ok(Self {
+            mm,
+            brk,
+            auxv: Vec::new(),
+            file_names: BTreeMap::new(),
+            vdso: None,
+        })
     }
 
     pub fn from_map(map: MemoryMap<AS>, brk: VA) -> Self {
         Self {
             mm: map,
             brk: VirtMemoryRegion::new(brk.align_up(PAGE_SIZE), 0),
+            auxv: Vec::new(),
+            file_names: BTreeMap::new(),
+            vdso: None,
         }
     }
 
@@ -59,6 +83,9 @@ impl<AS: UserAddressSpace> ProcessVM<AS> {
         Ok(Self {
             mm: MemoryMap::new()?,
             brk: VirtMemoryRegion::empty(),
+            auxv: Vec::new(),
+            file_names: BTreeMap::new(),
+            vdso: None,
         })
     }
 
@@ -72,6 +99,10 @@ impl<AS: UserAddressSpace> ProcessVM<AS> {
         }
     }
 
+    pub fn mm(&self) -> &MemoryMap<AS> {
+        &self.mm
+    }
+
     pub fn mm_mut(&mut self) -> &mut MemoryMap<AS> {
         &mut self.mm
     }
@@ -148,8 +179,38 @@ impl<AS: UserAddressSpace> ProcessVM<AS> {
         Ok(Self {
             mm: self.mm.clone_as_cow()?,
             brk: self.brk,
+            auxv: self.auxv.clone(),
+            file_names: self.file_names.clone(),
+            vdso: self.vdso,
         })
     }
+
+    pub fn auxv(&self) -> &[u64] {
+        &self.auxv
+    }
+
+    pub fn set_auxv(&mut self, auxv: Vec<u64>) {
+        self.auxv = auxv;
+    }
+
+    /// Records the path of a file mapped into this address space, so that it
+    /// can be reported in a core dump's file list.
+    pub fn set_file_name(&mut self, inode: InodeId, path: PathBuf) {
+        self.file_names.insert(inode, path);
+    }
+
+    pub fn file_name(&self, inode: InodeId) -> Option<&PathBuf> {
+        self.file_names.get(&inode)
+    }
+
+    /// Records the region holding the vDSO's data page and image.
+    pub fn set_vdso(&mut self, region: VirtMemoryRegion) {
+        self.vdso = Some(region);
+    }
+
+    pub fn vdso(&self) -> Option<VirtMemoryRegion> {
+        self.vdso
+    }
 }
 
 #[cfg(test)]
@@ -163,6 +224,7 @@ mod tests {
             region: VirtMemoryRegion::new(VA::from_value(0x1000), PAGE_SIZE),
             kind: VMAreaKind::Anon, // Simplification for test
             permissions: VMAPermissions::rx(),
+            flags: Default::default(),
         };
 
         ProcessVM::from_vma(text_vma).unwrap()
@@ -318,6 +380,7 @@ mod tests {
             region: VirtMemoryRegion::new(obstacle_addr, PAGE_SIZE),
             kind: VMAreaKind::Anon,
             permissions: VMAPermissions::ro(),
+            flags: Default::default(),
         };
         vm.mm.insert_and_merge(obstacle_vma);
         assert_eq!(vm.mm.vma_count(), 2);
@@ -9,7 +9,9 @@
 //!   and initialized data from files, most notably ELF binaries.
 //! - Anonymous (via [`VMAreaKind::Anon`]): Used for demand-zeroed memory like
 //!   the process stack, heap, and BSS sections.
-use core::cmp;
+//! - Shared (via [`VMAreaKind::Shared`]): Used for `MAP_SHARED` mappings, whose
+//!   pages are seen by every process mapping them, rather than copied.
+use core::{any::Any, cmp};
 
 use crate::{
     fs::Inode,
@@ -135,6 +137,43 @@ impl VMFileMapping {
     }
 }
 
+/// The pages backing a shared mapping, indexed by their page offset within it.
+///
+/// Every mapping of the same object sees the same physical pages, rather than
+/// private copies, so they aren't CoW-copied when the address space is cloned.
+/// The kernel provides the implementation, which owns the pages and, for a
+/// shared file mapping, writes them back to the file.
+pub trait SharedPages: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Represents a mapping of a region of a `SharedPages` object that backs a
+/// `VMArea`.
+#[derive(Clone)]
+pub struct VMSharedMapping {
+    pub(super) pages: Arc<dyn SharedPages>,
+    /// The offset, in bytes, of the start of the mapping within the object.
+    pub(super) offset: u64,
+}
+
+impl PartialEq for VMSharedMapping {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.pages, &other.pages) && self.offset == other.offset
+    }
+}
+
+impl VMSharedMapping {
+    /// Returns a clone of the reference-counted pages for this mapping.
+    pub fn pages(&self) -> Arc<dyn SharedPages> {
+        self.pages.clone()
+    }
+
+    /// Returns the offset of the start of the mapping within the object.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
 /// Defines the backing source for a `VMArea`.
 #[derive(Clone, PartialEq)]
 pub enum VMAreaKind {
@@ -152,6 +191,19 @@ pub enum VMAreaKind {
     /// physical page that has been zero-filled. This is used for the heap,
     /// and the stack.
     Anon,
+
+    /// The VMA is a shared mapping, of either a file or anonymous memory.
+    ///
+    /// On a page fault, the page at the corresponding offset of the shared
+    /// object is mapped, so that writes are seen by every other mapping of it.
+    Shared(VMSharedMapping),
+
+    /// The VMA maps pages which the kernel installed when it was created, and
+    /// which are shared by every process, such as the vDSO.
+    ///
+    /// It's never faulted in, and its pages may not be written, dropped or
+    /// moved, since they aren't the process's own.
+    Special,
 }
 
 impl VMAreaKind {
@@ -162,6 +214,59 @@ impl VMAreaKind {
     pub fn new_file(file: Arc<dyn Inode>, offset: u64, len: u64) -> Self {
         Self::File(VMFileMapping { file, offset, len })
     }
+
+    pub fn new_shared(pages: Arc<dyn SharedPages>, offset: u64) -> Self {
+        Self::Shared(VMSharedMapping { pages, offset })
+    }
+}
+
+/// How a VMA's pages are expected to be accessed, as advised by `madvise`.
+/// This determines how far ahead pages of a file mapping are read on a fault.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VMAccessHint {
+    /// No particular access pattern.
+    #[default]
+    Normal,
+    /// Pages are likely to be accessed in order, so read further ahead.
+    Sequential,
+    /// Pages are likely to be accessed in no particular order, so don't read
+    /// ahead at all.
+    Random,
+}
+
+/// The size of the gap kept free below a grows-down VMA, so that the stack
+/// can't silently grow into the mapping below it.
+pub const STACK_GUARD_GAP: usize = 256 * PAGE_SIZE;
+
+/// Attributes of a VMA beyond its permissions and backing. Most are set after
+/// it has been mapped, by `madvise` and the `mlock` family.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VMAFlags {
+    /// The expected access pattern.
+    pub access: VMAccessHint,
+    /// The VMA isn't copied into the child on `fork` (`MADV_DONTFORK`).
+    pub dont_fork: bool,
+    /// The VMA's pages are locked in memory (`mlock`).
+    pub locked: bool,
+    /// The VMA is a stack, which is grown downwards by faults in the guard gap
+    /// below it (`MAP_GROWSDOWN`).
+    pub grows_down: bool,
+    /// The VMA may be made writable. This is clear for a shared mapping of a
+    /// file which wasn't opened for writing, so that `mprotect` can't be used
+    /// to write to the file through it.
+    pub may_write: bool,
+}
+
+impl Default for VMAFlags {
+    fn default() -> Self {
+        Self {
+            access: VMAccessHint::default(),
+            dont_fork: false,
+            locked: false,
+            grows_down: false,
+            may_write: true,
+        }
+    }
 }
 
 /// A Virtual Memory Area (VMA).
@@ -175,6 +280,7 @@ pub struct VMArea {
     pub(super) region: VirtMemoryRegion,
     pub(super) kind: VMAreaKind,
     pub(super) permissions: VMAPermissions,
+    pub(super) flags: VMAFlags,
 }
 
 impl VMArea {
@@ -189,6 +295,7 @@ impl VMArea {
             region,
             kind,
             permissions,
+            flags: VMAFlags::default(),
         }
     }
 
@@ -205,10 +312,13 @@ impl VMArea {
     /// * `f`: A handle to the ELF file's inode.
     /// * `hdr`: The ELF program header (`LOAD` segment) to create the VMA from.
     /// * `endian`: The endianness of the ELF file, for correctly parsing header fields.
+    /// * `load_bias`: The offset added to the segment's link address. This is
+    ///   zero for `ET_EXEC` files and the chosen base address for `ET_DYN` ones.
     pub fn from_pheader<E: Endian>(
         f: Arc<dyn Inode>,
         hdr: ProgramHeader64<E>,
         endian: E,
+        load_bias: usize,
     ) -> VMArea {
         let mut permissions = VMAPermissions {
             read: false,
@@ -229,7 +339,7 @@ impl VMArea {
         }
 
         let mappable_region = VirtMemoryRegion::new(
-            VA::from_value(hdr.p_vaddr(endian) as usize),
+            VA::from_value(hdr.p_vaddr(endian) as usize + load_bias),
             hdr.p_memsz(endian) as usize,
         )
         .to_mappable_region();
@@ -242,6 +352,7 @@ impl VMArea {
                 len: hdr.p_filesz(endian) + mappable_region.offset() as u64,
             }),
             permissions,
+            flags: VMAFlags::default(),
         }
     }
 
@@ -283,6 +394,11 @@ impl VMArea {
         &self.kind
     }
 
+    /// Returns the virtual memory region covered by this VMA.
+    pub fn region(&self) -> VirtMemoryRegion {
+        self.region
+    }
+
     /// Resolves a page fault within this VMA.
     ///
     /// If the fault is in a region backed by a file, this function calculates
@@ -303,7 +419,7 @@ impl VMArea {
     pub fn resolve_fault(&self, faulting_addr: VA) -> Option<VMAFileRead> {
         // Match on the kind of VMA. If it's anonymous, there's no file to read from.
         let mapping = match &self.kind {
-            VMAreaKind::Anon => return None,
+            VMAreaKind::Anon | VMAreaKind::Shared(_) | VMAreaKind::Special => return None,
             VMAreaKind::File(mapping) => mapping,
         };
 
@@ -359,10 +475,57 @@ impl VMArea {
         })
     }
 
+    /// Resolves a page fault within a shared VMA.
+    ///
+    /// # Returns
+    /// * `Some((pages, index))` if the VMA is shared, where `index` is the page
+    ///   offset within `pages` of the page to map at `faulting_addr`.
+    /// * `None` if the VMA isn't shared.
+    pub fn resolve_shared_fault(&self, faulting_addr: VA) -> Option<(Arc<dyn SharedPages>, u64)> {
+        let VMAreaKind::Shared(mapping) = &self.kind else {
+            return None;
+        };
+
+        let offset_in_vma =
+            faulting_addr.page_aligned().value() - self.region.start_address().value();
+
+        Some((
+            mapping.pages.clone(),
+            (mapping.offset + offset_in_vma as u64) / PAGE_SIZE as u64,
+        ))
+    }
+
     pub fn permissions(&self) -> VMAPermissions {
         self.permissions
     }
 
+    /// Returns the VMA's flags.
+    pub fn flags(&self) -> VMAFlags {
+        self.flags
+    }
+
+    /// Returns this VMA with its flags replaced by `flags`.
+    #[must_use]
+    pub fn with_flags(mut self, flags: VMAFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Returns the lowest address that the mapping below this VMA may extend
+    /// to: its start, less the guard gap if it grows down.
+    pub(super) fn start_gap(&self) -> VA {
+        if self.flags.grows_down {
+            VA::from_value(
+                self.region
+                    .start_address()
+                    .value()
+                    .saturating_sub(STACK_GUARD_GAP),
+            )
+        } else {
+            self.region.start_address()
+        }
+    }
+
     pub fn contains_address(&self, addr: VA) -> bool {
         self.region.contains_address(addr)
     }
@@ -370,10 +533,10 @@ impl VMArea {
     /// Checks if this VMA can be merged with an adjacent one.
     ///
     /// This function assumes the other VMA is immediately adjacent in memory.
-    /// Merging is possible if permissions are identical and the backing storage
-    /// is of a compatible and contiguous nature.
+    /// Merging is possible if permissions and flags are identical and the
+    /// backing storage is of a compatible and contiguous nature.
     pub(super) fn can_merge_with(&self, other: &VMArea) -> bool {
-        if self.permissions != other.permissions {
+        if self.permissions != other.permissions || self.flags != other.flags {
             return false;
         }
 
@@ -392,6 +555,11 @@ impl VMArea {
                 same_file && contiguous_offset
             }
 
+            (VMAreaKind::Shared(self_map), VMAreaKind::Shared(other_map)) => {
+                Arc::ptr_eq(&self_map.pages, &other_map.pages)
+                    && other_map.offset == self_map.offset + self.region.size() as u64
+            }
+
             _ => false,
         }
     }
@@ -411,6 +579,18 @@ impl VMArea {
         matches!(self.kind, VMAreaKind::File(_))
     }
 
+    /// Returns true if the VMA is a shared mapping, whose pages belong to a
+    /// `SharedPages` object rather than to the address space.
+    pub fn is_shared(&self) -> bool {
+        matches!(self.kind, VMAreaKind::Shared(_))
+    }
+
+    /// Returns true if the VMA is a special mapping, whose pages were
+    /// installed by the kernel.
+    pub fn is_special(&self) -> bool {
+        matches!(self.kind, VMAreaKind::Special)
+    }
+
     /// Shrink this VMA's region to `new_region`, recalculating file offsets,
     /// for file mappings.
     #[must_use]
@@ -443,7 +623,18 @@ impl VMArea {
 
                 new_vma
             }
-            VMAreaKind::Anon => new_vma,
+            VMAreaKind::Shared(ref mapping) => {
+                let start_offset =
+                    new_region.start_address().value() - self.region.start_address().value();
+
+                new_vma.kind = VMAreaKind::Shared(VMSharedMapping {
+                    pages: mapping.pages.clone(),
+                    offset: mapping.offset + start_offset as u64,
+                });
+
+                new_vma
+            }
+            VMAreaKind::Anon | VMAreaKind::Special => new_vma,
         }
     }
 }
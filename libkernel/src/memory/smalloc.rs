@@ -1028,7 +1028,7 @@ mod tests {
         smalloc
             .add_memory(PhysMemoryRegion::new(
                 PA::from_value(big_buf.as_ptr().expose_provenance()),
-                big_buf.len() * core::mem::size_of::<MaybeUninit<PhysMemoryRegion>>(),
+                core::mem::size_of_val(big_buf),
             ))
             .unwrap();
 
@@ -0,0 +1,318 @@
+//! Swap areas: the on-disk header of a swap area, the map of which of its
+//! slots are in use, and the entries which refer to them from the page tables.
+
+use crate::error::{KernelError, Result};
+use alloc::{vec, vec::Vec};
+
+use super::PAGE_SIZE;
+
+/// The maximum number of swap areas which may be active at once.
+pub const MAX_SWAP_AREAS: usize = 1 << SwapEntry::AREA_BITS;
+
+/// A reference to a page-sized slot in a swap area. A page which has been
+/// swapped out is recorded by storing its entry in the non-present PTE which
+/// mapped it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SwapEntry(u64);
+
+impl SwapEntry {
+    const AREA_BITS: u32 = 5;
+    const AREA_MASK: u64 = (1 << Self::AREA_BITS) - 1;
+
+    /// The number of bits of a raw entry which may be set. Architectures must
+    /// be able to hold this many bits in a non-present PTE.
+    pub const BITS: u32 = 44;
+
+    /// Creates an entry for `slot` of the swap area at index `area`.
+    pub fn new(area: usize, slot: u64) -> Self {
+        debug_assert!(area < MAX_SWAP_AREAS);
+        debug_assert!(slot < 1 << (Self::BITS - Self::AREA_BITS));
+
+        Self((slot << Self::AREA_BITS) | area as u64)
+    }
+
+    /// Returns the index of the swap area which holds the page.
+    pub fn area(self) -> usize {
+        (self.0 & Self::AREA_MASK) as usize
+    }
+
+    /// Returns the slot within the swap area which holds the page.
+    pub fn slot(self) -> u64 {
+        self.0 >> Self::AREA_BITS
+    }
+
+    pub fn as_raw(self) -> u64 {
+        self.0
+    }
+
+    pub fn from_raw(v: u64) -> Self {
+        Self(v & ((1 << Self::BITS) - 1))
+    }
+}
+
+/// The header found in the first page of a swap area, as written by
+/// `mkswap(8)`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SwapHeader {
+    /// The index of the last page of the area which may hold swapped-out
+    /// pages. Page 0 holds the header itself.
+    pub last_page: u64,
+    /// Pages of the area which mustn't be used.
+    pub bad_pages: Vec<u64>,
+}
+
+impl SwapHeader {
+    const MAGIC: &[u8] = b"SWAPSPACE2";
+    const VERSION: u32 = 1;
+    const INFO_OFFSET: usize = 1024;
+    const BAD_PAGES_OFFSET: usize = Self::INFO_OFFSET + 512;
+
+    /// Parses the header from `page`, the first page of a swap area.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidValue` if the page doesn't hold a version 1 swap
+    /// header, or the header describes an area without any usable pages.
+    pub fn parse(page: &[u8]) -> Result<Self> {
+        if page.len() != PAGE_SIZE || !page.ends_with(Self::MAGIC) {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let word = |i: usize| {
+            let offset = Self::INFO_OFFSET + i * 4;
+            u32::from_le_bytes(page[offset..offset + 4].try_into().unwrap())
+        };
+
+        let (version, last_page, nr_bad_pages) = (word(0), word(1), word(2) as usize);
+        let max_bad_pages = (PAGE_SIZE - Self::MAGIC.len() - Self::BAD_PAGES_OFFSET) / 4;
+
+        if version != Self::VERSION || last_page == 0 || nr_bad_pages > max_bad_pages {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let bad_pages = page[Self::BAD_PAGES_OFFSET..]
+            .as_chunks::<4>()
+            .0
+            .iter()
+            .take(nr_bad_pages)
+            .map(|b| u32::from_le_bytes(*b) as u64)
+            .collect();
+
+        Ok(Self {
+            last_page: last_page as u64,
+            bad_pages,
+        })
+    }
+}
+
+/// Tracks which slots of a swap area are in use, and how many references
+/// each has, e.g. from the PTEs of processes which share a swapped-out page
+/// after a fork.
+pub struct SwapMap {
+    counts: Vec<u32>,
+    free: usize,
+    next: usize,
+}
+
+impl SwapMap {
+    /// The count of a slot which can never be allocated: the header page and
+    /// any bad pages.
+    const UNUSABLE: u32 = u32::MAX;
+
+    pub fn new(header: &SwapHeader) -> Self {
+        let mut counts = vec![0; header.last_page as usize + 1];
+
+        counts[0] = Self::UNUSABLE;
+
+        for &bad in &header.bad_pages {
+            if let Some(count) = counts.get_mut(bad as usize) {
+                *count = Self::UNUSABLE;
+            }
+        }
+
+        let free = counts.iter().filter(|&&c| c == 0).count();
+
+        Self {
+            counts,
+            free,
+            next: 1,
+        }
+    }
+
+    /// Allocates a free slot, with a single reference. Slots are handed out
+    /// in ascending order, wrapping around, so that pages swapped out together
+    /// tend to be close together on the device.
+    pub fn alloc(&mut self) -> Option<u64> {
+        if self.free == 0 {
+            return None;
+        }
+
+        let len = self.counts.len();
+        let slot = (self.next..len)
+            .chain(1..self.next)
+            .find(|&slot| self.counts[slot] == 0)?;
+
+        self.counts[slot] = 1;
+        self.free -= 1;
+        self.next = if slot + 1 == len { 1 } else { slot + 1 };
+
+        Some(slot as u64)
+    }
+
+    /// Takes another reference to an allocated slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` isn't allocated.
+    pub fn dup(&mut self, slot: u64) {
+        let count = &mut self.counts[slot as usize];
+
+        assert!(*count != 0 && *count != Self::UNUSABLE);
+
+        *count += 1;
+    }
+
+    /// Drops a reference to an allocated slot, returning `true` if it was the
+    /// last, in which case the slot is free again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` isn't allocated.
+    pub fn put(&mut self, slot: u64) -> bool {
+        let count = &mut self.counts[slot as usize];
+
+        assert!(*count != 0 && *count != Self::UNUSABLE);
+
+        *count -= 1;
+
+        if *count == 0 {
+            self.free += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the number of references to `slot`.
+    pub fn count(&self, slot: u64) -> u32 {
+        match self.counts.get(slot as usize) {
+            Some(&Self::UNUSABLE) | None => 0,
+            Some(&count) => count,
+        }
+    }
+
+    /// Returns the number of slots which can hold pages.
+    pub fn capacity(&self) -> usize {
+        self.counts.iter().filter(|&&c| c != Self::UNUSABLE).count()
+    }
+
+    /// Returns the number of free slots.
+    pub fn free_slots(&self) -> usize {
+        self.free
+    }
+
+    /// Returns the allocated slots.
+    pub fn used_slots(&self) -> impl Iterator<Item = u64> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c != 0 && c != Self::UNUSABLE)
+            .map(|(slot, _)| slot as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_header_page(version: u32, last_page: u32, bad_pages: &[u32]) -> Vec<u8> {
+        let mut page = vec![0; PAGE_SIZE];
+        let info = SwapHeader::INFO_OFFSET;
+
+        page[info..info + 4].copy_from_slice(&version.to_le_bytes());
+        page[info + 4..info + 8].copy_from_slice(&last_page.to_le_bytes());
+        page[info + 8..info + 12].copy_from_slice(&(bad_pages.len() as u32).to_le_bytes());
+
+        for (i, bad) in bad_pages.iter().enumerate() {
+            let offset = SwapHeader::BAD_PAGES_OFFSET + i * 4;
+            page[offset..offset + 4].copy_from_slice(&bad.to_le_bytes());
+        }
+
+        page[PAGE_SIZE - 10..].copy_from_slice(b"SWAPSPACE2");
+        page
+    }
+
+    #[test]
+    fn swap_entry_round_trip() {
+        let entry = SwapEntry::new(3, 0x1234);
+
+        assert_eq!(entry.area(), 3);
+        assert_eq!(entry.slot(), 0x1234);
+        assert_eq!(SwapEntry::from_raw(entry.as_raw()), entry);
+    }
+
+    #[test]
+    fn parse_header() {
+        let header = SwapHeader::parse(&make_header_page(1, 255, &[7, 9])).unwrap();
+
+        assert_eq!(header.last_page, 255);
+        assert_eq!(header.bad_pages, [7, 9]);
+    }
+
+    #[test]
+    fn parse_header_rejects_bad_magic_and_version() {
+        let mut page = make_header_page(1, 255, &[]);
+        page[PAGE_SIZE - 1] = b'1';
+
+        assert_eq!(SwapHeader::parse(&page), Err(KernelError::InvalidValue));
+        assert_eq!(
+            SwapHeader::parse(&make_header_page(2, 255, &[])),
+            Err(KernelError::InvalidValue)
+        );
+        assert_eq!(
+            SwapHeader::parse(&make_header_page(1, 0, &[])),
+            Err(KernelError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn swap_map_skips_header_and_bad_pages() {
+        let header = SwapHeader::parse(&make_header_page(1, 4, &[2])).unwrap();
+        let mut map = SwapMap::new(&header);
+
+        assert_eq!(map.capacity(), 3);
+        assert_eq!(map.alloc(), Some(1));
+        assert_eq!(map.alloc(), Some(3));
+        assert_eq!(map.alloc(), Some(4));
+        assert_eq!(map.alloc(), None);
+        assert_eq!(map.free_slots(), 0);
+    }
+
+    #[test]
+    fn swap_map_counts_references() {
+        let header = SwapHeader::parse(&make_header_page(1, 4, &[])).unwrap();
+        let mut map = SwapMap::new(&header);
+
+        let slot = map.alloc().unwrap();
+        map.dup(slot);
+
+        assert_eq!(map.count(slot), 2);
+        assert!(!map.put(slot));
+        assert_eq!(map.used_slots().collect::<Vec<_>>(), [slot]);
+        assert!(map.put(slot));
+        assert_eq!(map.count(slot), 0);
+        assert_eq!(map.free_slots(), 4);
+    }
+
+    #[test]
+    fn swap_map_wraps_around() {
+        let header = SwapHeader::parse(&make_header_page(1, 3, &[])).unwrap();
+        let mut map = SwapMap::new(&header);
+
+        assert_eq!(map.alloc(), Some(1));
+        assert_eq!(map.alloc(), Some(2));
+        map.put(1);
+        assert_eq!(map.alloc(), Some(3));
+        assert_eq!(map.alloc(), Some(1));
+    }
+}
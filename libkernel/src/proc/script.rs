@@ -0,0 +1,138 @@
+//! Parsing of the `#!` line which starts an interpreter script.
+
+use crate::error::{ExecError, Result};
+use alloc::string::{String, ToString};
+
+/// The number of bytes read from the start of a file to determine its format.
+pub const BINPRM_BUF_SIZE: usize = 256;
+
+/// Parses a `#!` interpreter line from `buf`, the first [`BINPRM_BUF_SIZE`]
+/// bytes of a file (or all of it, if it's shorter).
+///
+/// Returns `None` if the file isn't a script, otherwise the interpreter path
+/// along with its optional argument. As on Linux, everything after the
+/// interpreter path (minus surrounding spaces and tabs) forms a single
+/// argument, and a line which doesn't fit in the buffer is cut short, so long
+/// as the interpreter path itself isn't.
+pub fn parse_shebang(buf: &[u8]) -> Result<Option<(String, Option<String>)>> {
+    let Some(line) = buf.strip_prefix(b"#!") else {
+        return Ok(None);
+    };
+
+    let (line, truncated) = match line.iter().position(|&c| c == b'\n') {
+        Some(end) => (&line[..end], false),
+        None => (line, buf.len() == BINPRM_BUF_SIZE),
+    };
+
+    let line = core::str::from_utf8(line).map_err(|_| ExecError::UnknownFormat)?;
+    let line = line.trim_start_matches([' ', '\t']);
+
+    let (interp, arg) = match line.find([' ', '\t']) {
+        Some(idx) => (&line[..idx], line[idx..].trim_matches([' ', '\t'])),
+        // Running off the end of the buffer may have cut the path short.
+        None if truncated => return Err(ExecError::UnknownFormat.into()),
+        None => (line, ""),
+    };
+
+    if interp.is_empty() {
+        return Err(ExecError::UnknownFormat.into());
+    }
+
+    Ok(Some((
+        interp.to_string(),
+        (!arg.is_empty()).then(|| arg.to_string()),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BINPRM_BUF_SIZE, parse_shebang};
+    use crate::error::{ExecError, KernelError};
+    use alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    };
+
+    fn parse(buf: &[u8]) -> Option<(String, Option<String>)> {
+        parse_shebang(buf).unwrap()
+    }
+
+    fn unknown_format() -> KernelError {
+        ExecError::UnknownFormat.into()
+    }
+
+    #[test]
+    fn not_a_script() {
+        assert_eq!(parse(b"\x7fELF"), None);
+        assert_eq!(parse(b"# !/bin/sh\n"), None);
+        assert_eq!(parse(b""), None);
+    }
+
+    #[test]
+    fn interpreter_only() {
+        assert_eq!(
+            parse(b"#!/bin/sh\necho hi\n"),
+            Some(("/bin/sh".into(), None))
+        );
+        assert_eq!(parse(b"#! \t/bin/sh \t\n"), Some(("/bin/sh".into(), None)));
+        // A script with no newline at all.
+        assert_eq!(parse(b"#!/bin/sh"), Some(("/bin/sh".into(), None)));
+    }
+
+    #[test]
+    fn optional_argument() {
+        assert_eq!(
+            parse(b"#!/usr/bin/env python3\n"),
+            Some(("/usr/bin/env".into(), Some("python3".into())))
+        );
+
+        // Everything after the interpreter is a single argument.
+        assert_eq!(
+            parse(b"#!/bin/awk  -f -v x=1 \t\n"),
+            Some(("/bin/awk".into(), Some("-f -v x=1".into())))
+        );
+    }
+
+    #[test]
+    fn missing_interpreter() {
+        assert_eq!(parse_shebang(b"#!\n"), Err(unknown_format()));
+        assert_eq!(parse_shebang(b"#! \t \n/bin/sh\n"), Err(unknown_format()));
+        assert_eq!(parse_shebang(b"#!"), Err(unknown_format()));
+    }
+
+    #[test]
+    fn crlf_line_ending() {
+        // As on Linux, only the newline ends the line, so a carriage return is
+        // kept as part of the interpreter path or its argument.
+        assert_eq!(parse(b"#!/bin/sh\r\n"), Some(("/bin/sh\r".into(), None)));
+        assert_eq!(
+            parse(b"#!/bin/sh -e\r\n"),
+            Some(("/bin/sh".into(), Some("-e\r".into())))
+        );
+    }
+
+    #[test]
+    fn overlong_argument_is_truncated() {
+        let mut buf: Vec<u8> = b"#!/bin/sh ".to_vec();
+        buf.resize(BINPRM_BUF_SIZE, b'a');
+
+        let (interp, arg) = parse(&buf).unwrap();
+
+        assert_eq!(interp, "/bin/sh");
+        assert_eq!(arg.unwrap(), "a".repeat(BINPRM_BUF_SIZE - 10));
+    }
+
+    #[test]
+    fn overlong_interpreter_is_rejected() {
+        let mut buf: Vec<u8> = b"#!/".to_vec();
+        buf.resize(BINPRM_BUF_SIZE, b'a');
+
+        assert_eq!(parse_shebang(&buf), Err(unknown_format()));
+
+        // Trailing spaces which run off the buffer don't cut the path short.
+        let mut buf: Vec<u8> = b"#!/bin/sh".to_vec();
+        buf.resize(BINPRM_BUF_SIZE, b' ');
+
+        assert_eq!(parse(&buf), Some(("/bin/sh".to_string(), None)));
+    }
+}
@@ -55,6 +55,27 @@ impl<T: ?Sized, CPU: CpuOps> SpinLockIrq<T, CPU> {
             _marker: PhantomData,
         }
     }
+
+    /// Like [`Self::lock_save_irq`], but returns `None` straight away, with
+    /// the original interrupt state restored, if the lock is already held.
+    pub fn try_lock_save_irq(&self) -> Option<SpinLockIrqGuard<'_, T, CPU>> {
+        let saved_irq_flags = CPU::disable_interrupts();
+
+        if self
+            .lock
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            CPU::restore_interrupt_state(saved_irq_flags);
+            return None;
+        }
+
+        Some(SpinLockIrqGuard {
+            lock: self,
+            irq_flags: saved_irq_flags,
+            _marker: PhantomData,
+        })
+    }
 }
 
 /// An RAII guard for an IRQ-safe spinlock.
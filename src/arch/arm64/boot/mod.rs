@@ -1,6 +1,7 @@
 use super::{
     exceptions::{ExceptionState, secondary_exceptions_init},
     memory::{fixmap::FIXMAPS, mmu::setup_kern_addr_space},
+    proc::vdso,
 };
 use crate::{
     arch::{ArchImpl, arm64::exceptions::exceptions_init},
@@ -114,6 +115,7 @@ fn arch_init_stage2(frame: *mut ExceptionState) -> *mut ExceptionState {
 
     // Don't trap wfi/wfe in el0.
     SCTLR_EL1.modify(SCTLR_EL1::NTWE::DontTrap + SCTLR_EL1::NTWI::DontTrap);
+    vdso::cpu_init();
 
     exceptions_init().expect("Failed to initialize exceptions");
     ArchImpl::enable_interrupts();
@@ -145,6 +147,7 @@ fn arch_init_secondary(ctx_frame: *mut ExceptionState) -> *mut ExceptionState {
 
     // Enable interrupts and exceptions.
     secondary_exceptions_init();
+    vdso::cpu_init();
 
     if let Some(ic) = get_interrupt_root() {
         ic.enable_core(ArchImpl::id());
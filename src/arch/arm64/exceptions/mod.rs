@@ -5,12 +5,17 @@ use super::memory::{
 use crate::{
     arch::ArchImpl,
     interrupts::get_interrupt_root,
+    kernel::syscall::handle_syscall,
     ksym_pa,
+    process::thread_group::signal::SigId,
     sched::{current_task, uspc_ret::dispatch_userspace_task},
     spawn_kernel_work,
 };
 use aarch64_cpu::registers::{CPACR_EL1, ReadWriteable, VBAR_EL1};
-use core::{arch::global_asm, fmt::Display};
+use core::{
+    arch::{asm, global_asm},
+    fmt::Display,
+};
 use esr::{Esr, Exception};
 use libkernel::{
     KernAddressSpace, VirtualMemory,
@@ -20,11 +25,9 @@ use libkernel::{
         region::{PhysMemoryRegion, VirtMemoryRegion},
     },
 };
-use syscall::handle_syscall;
 use tock_registers::interfaces::Writeable;
 
 pub mod esr;
-mod syscall;
 
 const EXCEPTION_TBL_SZ: usize = 0x800;
 
@@ -164,6 +167,11 @@ extern "C" fn el0_sync(state_ptr: *mut ExceptionState) -> *const ExceptionState
             // TODO: Flag to start saving FP/SIMD context for this task and,
             // save the state.
         }
+        Exception::SoftwareStepLowerEL(_)
+        | Exception::BreakpointLowerEL(_)
+        | Exception::Brk64(_) => {
+            current_task().raise_task_signal(SigId::SIGTRAP);
+        }
         _ => default_handler(state),
     }
 
@@ -218,4 +226,18 @@ pub fn exceptions_init() -> Result<()> {
 
 pub fn secondary_exceptions_init() {
     VBAR_EL1.set(EXCEPTION_BASE.value() as u64);
+
+    // Clear the OS lock, which otherwise masks debug exceptions, and enable
+    // software step so that setting SPSR_EL1.SS on a return to EL0 single-steps
+    // the task.
+    unsafe {
+        asm!(
+            "msr oslar_el1, xzr",
+            "mrs {tmp}, mdscr_el1",
+            "orr {tmp}, {tmp}, #1",
+            "msr mdscr_el1, {tmp}",
+            "isb",
+            tmp = out(reg) _,
+        )
+    };
 }
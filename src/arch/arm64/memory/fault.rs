@@ -9,12 +9,13 @@ use crate::{
         memory::uaccess::{UACESS_ABORT_DEFERRED, UACESS_ABORT_DENIED},
     },
     memory::fault::{FaultResolution, handle_demand_fault, handle_protection_fault},
+    process::thread_group::signal::SigId,
     sched::{current_task, spawn_kernel_work},
 };
 use alloc::boxed::Box;
 use libkernel::{
     UserAddressSpace,
-    error::Result,
+    error::{KernelError, Result},
     memory::{address::VA, proc_vm::vmarea::AccessKind, region::VirtMemoryRegion},
 };
 
@@ -42,7 +43,8 @@ fn run_mem_fault_handler(exception: Exception, info: AbortIss) -> Result<FaultRe
         let fault_addr = VA::from_value(far as usize);
 
         let task = current_task();
-        let mut vm = task.vm.lock_save_irq();
+        let vm = task.vm();
+        let mut vm = vm.lock_save_irq();
 
         match info.ifsc.category() {
             IfscCategory::TranslationFault => handle_demand_fault(&mut vm, fault_addr, access_kind),
@@ -55,6 +57,12 @@ fn run_mem_fault_handler(exception: Exception, info: AbortIss) -> Result<FaultRe
 
                 handle_protection_fault(&mut vm, fault_addr, access_kind, pg_info)
             }
+            // The access flag was cleared to age the page for reclaim.
+            IfscCategory::AccessFlagFault => {
+                vm.mm_mut().address_space_mut().mark_accessed(fault_addr);
+
+                Ok(FaultResolution::Resolved)
+            }
             _ => panic!("Unhandled memory fault"),
         }
     } else {
@@ -106,19 +114,21 @@ pub fn handle_kernel_mem_fault(exception: Exception, info: AbortIss, state: &mut
 pub fn handle_mem_fault(exception: Exception, info: AbortIss) {
     match run_mem_fault_handler(exception, info) {
         Ok(FaultResolution::Resolved) => {}
-        // TODO: Implement proc signals.
-        Ok(FaultResolution::Denied) => panic!(
-            "SIGSEGV on process {} {:?} PC: {:x}",
-            current_task().process.tgid,
-            exception,
-            current_task().ctx.lock_save_irq().user().elr_el1
-        ),
+        // The signal is delivered on the way back to userspace, on the
+        // alternate signal stack if one is set up, since the fault may have
+        // been a stack overflow.
+        Ok(FaultResolution::Denied) => current_task().force_task_signal(SigId::SIGSEGV),
         // If the page fault involves sleepy kernel work, we can
         // spawn that work on the process, since there is no other
         // kernel work happening.
         Ok(FaultResolution::Deferred(fut)) => spawn_kernel_work(async {
-            if Box::into_pin(fut).await.is_err() {
-                panic!("Page fault defered error, SIGBUS on process");
+            match Box::into_pin(fut).await {
+                Ok(()) => {}
+                // The fault handler has already tried to free memory, so
+                // running out here means this process was picked to be killed.
+                // Make sure that it dies, rather than retrying the fault.
+                Err(KernelError::NoMemory) => current_task().force_task_signal(SigId::SIGKILL),
+                Err(_) => panic!("Page fault defered error, SIGBUS on process"),
             }
         }),
         Err(_) => panic!("Page fault handler error, SIGBUS on process"),
@@ -75,6 +75,7 @@ impl VirtualMemory for Aarch64 {
 
 impl Arch for Aarch64 {
     type UserContext = ExceptionState;
+    type PtraceRegs = proc::ptrace::UserPtRegs;
 
     fn new_user_context(entry_point: VA, stack_top: VA) -> Self::UserContext {
         ExceptionState {
@@ -101,6 +102,44 @@ impl Arch for Aarch64 {
         proc::signal::do_signal_return()
     }
 
+    fn elf_hwcap() -> u64 {
+        proc::hwcap::elf_hwcap()
+    }
+
+    fn vdso_image() -> Option<&'static [u8]> {
+        Some(proc::vdso::image())
+    }
+
+    fn hw_random() -> Option<u64> {
+        proc::rng::random()
+    }
+
+    fn syscall_args(ctx: &Self::UserContext) -> (u32, [u64; 6]) {
+        let [arg1, arg2, arg3, arg4, arg5, arg6, ..] = ctx.x;
+
+        (ctx.x[8] as u32, [arg1, arg2, arg3, arg4, arg5, arg6])
+    }
+
+    fn set_syscall_ret(ctx: &mut Self::UserContext, ret: u64) {
+        ctx.x[0] = ret;
+    }
+
+    fn user_pc(ctx: &Self::UserContext) -> usize {
+        ctx.elr_el1 as usize
+    }
+
+    fn ptrace_get_regs(ctx: &Self::UserContext) -> Self::PtraceRegs {
+        proc::ptrace::get_regs(ctx)
+    }
+
+    fn ptrace_set_regs(ctx: &mut Self::UserContext, regs: &Self::PtraceRegs) {
+        proc::ptrace::set_regs(ctx, regs)
+    }
+
+    fn set_single_step(ctx: &mut Self::UserContext, enable: bool) -> Result<()> {
+        proc::ptrace::set_single_step(ctx, enable)
+    }
+
     fn context_switch(new: Arc<Task>) {
         proc::context_switch(new);
     }
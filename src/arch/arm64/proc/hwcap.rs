@@ -0,0 +1,61 @@
+//! Hardware capabilities reported to userspace via `AT_HWCAP`.
+
+use core::arch::asm;
+
+const HWCAP_FP: u64 = 1 << 0;
+const HWCAP_ASIMD: u64 = 1 << 1;
+const HWCAP_AES: u64 = 1 << 3;
+const HWCAP_PMULL: u64 = 1 << 4;
+const HWCAP_SHA1: u64 = 1 << 5;
+const HWCAP_SHA2: u64 = 1 << 6;
+const HWCAP_CRC32: u64 = 1 << 7;
+const HWCAP_ATOMICS: u64 = 1 << 8;
+
+/// Extracts the 4-bit ID register field starting at `shift`.
+fn field(reg: u64, shift: u32) -> u64 {
+    (reg >> shift) & 0xf
+}
+
+pub fn elf_hwcap() -> u64 {
+    let pfr0: u64;
+    let isar0: u64;
+
+    unsafe {
+        asm!("mrs {}, id_aa64pfr0_el1",
+             out(reg) pfr0,
+             options(nomem, nostack, preserves_flags));
+        asm!("mrs {}, id_aa64isar0_el1",
+             out(reg) isar0,
+             options(nomem, nostack, preserves_flags));
+    }
+
+    let mut hwcap = 0;
+
+    // A value of 0xf in the FP and AdvSIMD fields means "not implemented".
+    if field(pfr0, 16) != 0xf {
+        hwcap |= HWCAP_FP;
+    }
+    if field(pfr0, 20) != 0xf {
+        hwcap |= HWCAP_ASIMD;
+    }
+
+    match field(isar0, 4) {
+        1 => hwcap |= HWCAP_AES,
+        2 => hwcap |= HWCAP_AES | HWCAP_PMULL,
+        _ => {}
+    }
+    if field(isar0, 8) != 0 {
+        hwcap |= HWCAP_SHA1;
+    }
+    if field(isar0, 12) != 0 {
+        hwcap |= HWCAP_SHA2;
+    }
+    if field(isar0, 16) != 0 {
+        hwcap |= HWCAP_CRC32;
+    }
+    if field(isar0, 20) >= 2 {
+        hwcap |= HWCAP_ATOMICS;
+    }
+
+    hwcap
+}
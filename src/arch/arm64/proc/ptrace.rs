@@ -0,0 +1,48 @@
+use crate::{arch::arm64::exceptions::ExceptionState, memory::uaccess::UserCopyable};
+use libkernel::error::Result;
+
+/// SPSR_EL1.SS: when set, and MDSCR_EL1.SS is enabled, a software step
+/// exception is taken after the first instruction following an `eret`.
+const SPSR_SS: u64 = 1 << 21;
+
+/// The condition flags, the only part of PSTATE which userspace may change.
+const SPSR_NZCV: u64 = 0xf << 28;
+
+/// The layout of `struct user_pt_regs`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UserPtRegs {
+    regs: [u64; 31],
+    sp: u64,
+    pc: u64,
+    pstate: u64,
+}
+
+// SAFETY: The register set is made up solely of PoDs with no padding.
+unsafe impl UserCopyable for UserPtRegs {}
+
+pub fn get_regs(ctx: &ExceptionState) -> UserPtRegs {
+    UserPtRegs {
+        regs: ctx.x,
+        sp: ctx.sp_el0,
+        pc: ctx.elr_el1,
+        pstate: ctx.spsr_el1,
+    }
+}
+
+pub fn set_regs(ctx: &mut ExceptionState, regs: &UserPtRegs) {
+    ctx.x = regs.regs;
+    ctx.sp_el0 = regs.sp;
+    ctx.elr_el1 = regs.pc;
+    ctx.spsr_el1 = (ctx.spsr_el1 & !SPSR_NZCV) | (regs.pstate & SPSR_NZCV);
+}
+
+pub fn set_single_step(ctx: &mut ExceptionState, enable: bool) -> Result<()> {
+    if enable {
+        ctx.spsr_el1 |= SPSR_SS;
+    } else {
+        ctx.spsr_el1 &= !SPSR_SS;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! The architectural random number generator, `RNDR`.
+
+use core::arch::asm;
+
+/// The Z flag, which `RNDR` sets when it fails to return a random number.
+const NZCV_Z: u64 = 1 << 30;
+
+pub fn random() -> Option<u64> {
+    let isar0: u64;
+
+    unsafe {
+        asm!("mrs {}, id_aa64isar0_el1",
+             out(reg) isar0,
+             options(nomem, nostack, preserves_flags));
+    }
+
+    // ID_AA64ISAR0_EL1.RNDR is zero if FEAT_RNG isn't implemented.
+    if (isar0 >> 60) & 0xf == 0 {
+        return None;
+    }
+
+    let val: u64;
+    let nzcv: u64;
+
+    unsafe {
+        // RNDR is named by its encoding, as the assembler only knows it when
+        // FEAT_RNG is enabled.
+        asm!("mrs {val}, s3_3_c2_c4_0",
+             "mrs {nzcv}, nzcv",
+             val = out(reg) val,
+             nzcv = out(reg) nzcv,
+             options(nomem, nostack));
+    }
+
+    (nzcv & NZCV_Z == 0).then_some(val)
+}
@@ -1,13 +1,16 @@
 use crate::{
     arch::arm64::exceptions::ExceptionState,
-    memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
+    memory::{
+        uaccess::{UserCopyable, copy_from_user, copy_to_user},
+        vdso,
+    },
     process::thread_group::signal::{
         SigId, ksigaction::UserspaceSigAction, sigaction::SigActionFlags,
     },
     sched::current_task,
 };
 use libkernel::{
-    error::Result,
+    error::{KernelError, Result},
     memory::{
         PAGE_SIZE,
         address::{TUA, UA},
@@ -27,6 +30,17 @@ unsafe impl UserCopyable for RtSigFrame {}
 
 pub async fn do_signal(id: SigId, sa: UserspaceSigAction) -> Result<ExceptionState> {
     let task = current_task();
+
+    // Handlers installed without a restorer return through the vDSO.
+    let restorer = if sa.flags.contains(SigActionFlags::SA_RESTORER) {
+        sa.restorer.ok_or(KernelError::InvalidValue)?.value()
+    } else {
+        vdso::image_base(&task.vm().lock_save_irq())
+            .ok_or(KernelError::Fault)?
+            .value()
+            + super::vdso::sigreturn_offset()
+    };
+
     let mut signal = task.process.signals.lock_save_irq();
 
     let saved_state = *task.ctx.lock_save_irq().user();
@@ -36,10 +50,6 @@ pub async fn do_signal(id: SigId, sa: UserspaceSigAction) -> Result<ExceptionSta
         alt_stack_prev_addr: UA::null(),
     };
 
-    if !sa.flags.contains(SigActionFlags::SA_RESTORER) {
-        panic!("Cannot call non-sa_restorer sig handler");
-    }
-
     let addr: TUA<RtSigFrame> = if sa.flags.contains(SigActionFlags::SA_ONSTACK)
         && let Some(alt_stack) = signal.alt_stack.as_mut()
         && let Some(alloc) = alt_stack.alloc_alt_stack::<RtSigFrame>()
@@ -56,7 +66,7 @@ pub async fn do_signal(id: SigId, sa: UserspaceSigAction) -> Result<ExceptionSta
 
     new_state.sp_el0 = addr.value() as _;
     new_state.elr_el1 = sa.action.value() as _;
-    new_state.x[30] = sa.restorer.unwrap().value() as _;
+    new_state.x[30] = restorer as _;
     new_state.x[0] = id.user_id();
 
     Ok(new_state)
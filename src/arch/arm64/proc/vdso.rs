@@ -0,0 +1,32 @@
+use aarch64_cpu::registers::{CNTKCTL_EL1, ReadWriteable};
+use core::{arch::global_asm, slice};
+
+global_asm!(include_str!("vdso.s"));
+
+unsafe extern "C" {
+    static __vdso_start: u8;
+    static __vdso_end: u8;
+    static __vdso_rt_sigreturn: u8;
+}
+
+/// Returns the vDSO's ELF image.
+pub fn image() -> &'static [u8] {
+    let start = unsafe { &__vdso_start } as *const u8;
+    let end = unsafe { &__vdso_end } as *const u8;
+
+    unsafe { slice::from_raw_parts(start, end.addr() - start.addr()) }
+}
+
+/// Returns the offset of `__kernel_rt_sigreturn` within the vDSO image.
+pub fn sigreturn_offset() -> usize {
+    let start = unsafe { &__vdso_start } as *const u8;
+    let sigreturn = unsafe { &__vdso_rt_sigreturn } as *const u8;
+
+    sigreturn.addr() - start.addr()
+}
+
+/// Allows EL0 to read the physical counter, from which the vDSO derives the
+/// time. This must be called on every CPU.
+pub fn cpu_init() {
+    CNTKCTL_EL1.modify(CNTKCTL_EL1::EL0PCTEN::SET);
+}
@@ -0,0 +1,263 @@
+// The vDSO: a small shared object mapped into every process, providing the
+// time functions without a trip into the kernel along with the signal return
+// trampoline.
+//
+// The image is position independent. The kernel maps its data page (see
+// `memory::vdso::VdsoData`) in the page immediately before it, which the
+// code below reads PC-relative.
+
+.set VDSO_SEQ,          0
+.set VDSO_COUNTER_OK,   4
+.set VDSO_CYCLE_BASE,   8
+.set VDSO_MONO_BASE,    16
+.set VDSO_REAL_OFFSET,  24
+.set VDSO_MULT,         32
+.set VDSO_SHIFT,        40
+.set VDSO_REAL_OK,      44
+.set VDSO_RES,          48
+
+// Bitmasks of the clock IDs handled in userspace, and of those which are
+// derived from the realtime clock.
+.set VDSO_CLOCKS,       0xf3
+.set VDSO_REAL_CLOCKS,  0x21
+
+.set NR_CLOCK_GETRES,   114
+.set NR_CLOCK_GETTIME,  113
+.set NR_GETTIMEOFDAY,   169
+.set NR_RT_SIGRETURN,   139
+
+// Reads the monotonic clock, in nanoseconds, into x6. The offset from it to
+// the realtime clock is left in x10, with w11 non-zero if that is known.
+// Branches to `fallback` if the counter cannot be used from userspace.
+// Clobbers x2-x12.
+.macro vdso_read_mono fallback
+    adr     x2, .Lvdso_data
+1:  ldar    w3, [x2]                    // VDSO_SEQ
+    tbnz    w3, #0, 1b
+    ldr     w4, [x2, #VDSO_COUNTER_OK]
+    cbz     w4, \fallback
+    ldr     x6, [x2, #VDSO_CYCLE_BASE]
+    ldr     x7, [x2, #VDSO_MULT]
+    ldr     w8, [x2, #VDSO_SHIFT]
+    ldr     x9, [x2, #VDSO_MONO_BASE]
+    ldr     x10, [x2, #VDSO_REAL_OFFSET]
+    ldr     w11, [x2, #VDSO_REAL_OK]
+    isb
+    mrs     x5, cntpct_el0
+    dmb     ishld
+    ldr     w12, [x2, #VDSO_SEQ]
+    cmp     w12, w3
+    b.ne    1b
+
+    // ns = mono_base + ((count - cycle_base) * mult) >> shift
+    sub     x5, x5, x6
+    mul     x6, x5, x7
+    umulh   x7, x5, x7
+    lsr     x6, x6, x8
+    neg     x12, x8
+    lsl     x7, x7, x12
+    orr     x6, x6, x7
+    add     x6, x6, x9
+.endm
+
+// Splits the nanosecond count in x6 into seconds (x13) and nanoseconds (x14).
+.macro vdso_split_ns
+    mov     x12, #0xca00
+    movk    x12, #0x3b9a, lsl #16
+    udiv    x13, x6, x12
+    msub    x14, x13, x12, x6
+.endm
+
+    .pushsection .rodata.vdso, "a"
+    .balign 4096
+
+    .global __vdso_start
+__vdso_start:
+.Lvdso_image:
+.set .Lvdso_data, .Lvdso_image - 4096
+
+// ELF header
+    .byte   0x7f, 'E', 'L', 'F'
+    .byte   2                           // ELFCLASS64
+    .byte   1                           // ELFDATA2LSB
+    .byte   1                           // EV_CURRENT
+    .byte   0                           // ELFOSABI_SYSV
+    .zero   8
+    .hword  3                           // ET_DYN
+    .hword  183                         // EM_AARCH64
+    .word   1                           // EV_CURRENT
+    .quad   0                           // e_entry
+    .quad   .Lvdso_phdrs - __vdso_start // e_phoff
+    .quad   0                           // e_shoff
+    .word   0                           // e_flags
+    .hword  64                          // e_ehsize
+    .hword  56                          // e_phentsize
+    .hword  2                           // e_phnum
+    .hword  64                          // e_shentsize
+    .hword  0                           // e_shnum
+    .hword  0                           // e_shstrndx
+
+// Program headers
+.Lvdso_phdrs:
+    .word   1                           // PT_LOAD
+    .word   5                           // PF_R | PF_X
+    .quad   0                           // p_offset
+    .quad   0                           // p_vaddr
+    .quad   0                           // p_paddr
+    .quad   __vdso_end - __vdso_start   // p_filesz
+    .quad   __vdso_end - __vdso_start   // p_memsz
+    .quad   4096                        // p_align
+
+    .word   2                           // PT_DYNAMIC
+    .word   4                           // PF_R
+    .quad   .Lvdso_dynamic - __vdso_start
+    .quad   .Lvdso_dynamic - __vdso_start
+    .quad   .Lvdso_dynamic - __vdso_start
+    .quad   .Lvdso_dynamic_end - .Lvdso_dynamic
+    .quad   .Lvdso_dynamic_end - .Lvdso_dynamic
+    .quad   8
+
+// Dynamic section
+.Lvdso_dynamic:
+    .quad   4,  .Lvdso_hash - __vdso_start              // DT_HASH
+    .quad   5,  .Lvdso_dynstr - __vdso_start            // DT_STRTAB
+    .quad   6,  .Lvdso_dynsym - __vdso_start            // DT_SYMTAB
+    .quad   10, .Lvdso_dynstr_end - .Lvdso_dynstr       // DT_STRSZ
+    .quad   11, 24                                      // DT_SYMENT
+    .quad   14, .Lvdso_soname - .Lvdso_dynstr           // DT_SONAME
+    .quad   0,  0                                       // DT_NULL
+.Lvdso_dynamic_end:
+
+// Symbol hash table: a single bucket chaining every symbol.
+.Lvdso_hash:
+    .word   1                           // nbucket
+    .word   5                           // nchain
+    .word   4                           // bucket[0]
+    .word   0, 0, 1, 2, 3               // chain[]
+
+// Dynamic symbols
+    .balign 8
+.Lvdso_dynsym:
+    .zero   24                          // STN_UNDEF
+
+    .word   .Lvdso_str_clock_gettime - .Lvdso_dynstr
+    .byte   0x12                        // STB_GLOBAL, STT_FUNC
+    .byte   0                           // STV_DEFAULT
+    .hword  1                           // st_shndx
+    .quad   __kernel_clock_gettime - __vdso_start
+    .quad   .Lvdso_clock_gettime_end - __kernel_clock_gettime
+
+    .word   .Lvdso_str_gettimeofday - .Lvdso_dynstr
+    .byte   0x12
+    .byte   0
+    .hword  1
+    .quad   __kernel_gettimeofday - __vdso_start
+    .quad   .Lvdso_gettimeofday_end - __kernel_gettimeofday
+
+    .word   .Lvdso_str_clock_getres - .Lvdso_dynstr
+    .byte   0x12
+    .byte   0
+    .hword  1
+    .quad   __kernel_clock_getres - __vdso_start
+    .quad   .Lvdso_clock_getres_end - __kernel_clock_getres
+
+    .word   .Lvdso_str_rt_sigreturn - .Lvdso_dynstr
+    .byte   0x12
+    .byte   0
+    .hword  1
+    .quad   __kernel_rt_sigreturn - __vdso_start
+    .quad   .Lvdso_rt_sigreturn_end - __kernel_rt_sigreturn
+
+// Dynamic string table
+.Lvdso_dynstr:
+    .byte   0
+.Lvdso_str_clock_gettime:
+    .asciz  "__kernel_clock_gettime"
+.Lvdso_str_gettimeofday:
+    .asciz  "__kernel_gettimeofday"
+.Lvdso_str_clock_getres:
+    .asciz  "__kernel_clock_getres"
+.Lvdso_str_rt_sigreturn:
+    .asciz  "__kernel_rt_sigreturn"
+.Lvdso_soname:
+    .asciz  "linux-vdso.so.1"
+.Lvdso_dynstr_end:
+
+    .balign 16
+
+// int __kernel_clock_gettime(clockid_t clk, struct timespec *ts)
+__kernel_clock_gettime:
+    cmp     w0, #7
+    b.hi    9f
+    mov     w13, #VDSO_CLOCKS
+    lsr     w13, w13, w0
+    tbz     w13, #0, 9f
+    vdso_read_mono 9f
+    mov     w13, #VDSO_REAL_CLOCKS
+    lsr     w13, w13, w0
+    tbz     w13, #0, 2f
+    cbz     w11, 9f
+    add     x6, x6, x10
+2:  vdso_split_ns
+    stp     x13, x14, [x1]
+    mov     x0, #0
+    ret
+9:  mov     x8, #NR_CLOCK_GETTIME
+    svc     #0
+    ret
+.Lvdso_clock_gettime_end:
+
+// int __kernel_gettimeofday(struct timeval *tv, struct timezone *tz)
+__kernel_gettimeofday:
+    cbz     x1, 1f
+    stp     wzr, wzr, [x1]
+1:  cbz     x0, 3f
+    vdso_read_mono 9f
+    cbz     w11, 9f
+    add     x6, x6, x10
+    vdso_split_ns
+    mov     x12, #1000
+    udiv    x14, x14, x12
+    stp     x13, x14, [x0]
+3:  mov     x0, #0
+    ret
+9:  mov     x8, #NR_GETTIMEOFDAY
+    svc     #0
+    ret
+.Lvdso_gettimeofday_end:
+
+// int __kernel_clock_getres(clockid_t clk, struct timespec *res)
+__kernel_clock_getres:
+    cmp     w0, #7
+    b.hi    9f
+    mov     w13, #VDSO_CLOCKS
+    lsr     w13, w13, w0
+    tbz     w13, #0, 9f
+    adr     x2, .Lvdso_data
+    ldr     w4, [x2, #VDSO_COUNTER_OK]
+    cbz     w4, 9f
+    cbz     x1, 1f
+    ldr     x3, [x2, #VDSO_RES]
+    stp     xzr, x3, [x1]
+1:  mov     x0, #0
+    ret
+9:  mov     x8, #NR_CLOCK_GETRES
+    svc     #0
+    ret
+.Lvdso_clock_getres_end:
+
+// void __kernel_rt_sigreturn(void)
+//
+// Signal handlers installed without SA_RESTORER return here.
+    nop
+    .global __vdso_rt_sigreturn
+__vdso_rt_sigreturn:
+__kernel_rt_sigreturn:
+    mov     x8, #NR_RT_SIGRETURN
+    svc     #0
+.Lvdso_rt_sigreturn_end:
+
+    .global __vdso_end
+__vdso_end:
+
+    .popsection
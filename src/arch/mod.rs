@@ -8,9 +8,12 @@
 //! The rest of the kernel should use the `ArchImpl` type alias to access
 //! architecture-specific functions and types.
 
-use crate::process::{
-    Task,
-    thread_group::signal::{SigId, ksigaction::UserspaceSigAction},
+use crate::{
+    memory::uaccess::UserCopyable,
+    process::{
+        Task,
+        thread_group::signal::{SigId, ksigaction::UserspaceSigAction},
+    },
 };
 use alloc::sync::Arc;
 use core::future::Future; // 必须显式引入 Future trait
@@ -26,6 +29,11 @@ pub trait Arch: CpuOps + VirtualMemory {
     /// with this type.
     type UserContext: Sized + Send + Sync + Clone;
 
+    /// The general-purpose register set exposed to debuggers via
+    /// `PTRACE_GETREGSET`/`PTRACE_SETREGSET` with `NT_PRSTATUS`. This must
+    /// match the layout of the architecture's `user_regs_struct`.
+    type PtraceRegs: UserCopyable;
+
     fn name() -> &'static str;
 
     /// Prepares the initial context for a new user-space thread. This sets up
@@ -52,6 +60,47 @@ pub trait Arch: CpuOps + VirtualMemory {
     /// Return from a userspace signal handler.
     fn do_signal_return() -> impl Future<Output = Result<<Self as Arch>::UserContext>>;
 
+    /// Returns the hardware capability bits passed to new programs in
+    /// `AT_HWCAP`.
+    fn elf_hwcap() -> u64;
+
+    /// Returns the ELF image of the vDSO mapped into every process, if the
+    /// architecture provides one. The image expects the vDSO data page to be
+    /// mapped in the page immediately before it.
+    fn vdso_image() -> Option<&'static [u8]>;
+
+    /// Returns a random number from the CPU's architectural random number
+    /// generator, or `None` if it doesn't have one or it failed to produce one.
+    fn hw_random() -> Option<u64>;
+
+    /// Returns the number of the syscall made from a saved user context, and
+    /// its six arguments.
+    fn syscall_args(ctx: &Self::UserContext) -> (u32, [u64; 6]);
+
+    /// Sets the value returned to userspace by a syscall.
+    fn set_syscall_ret(ctx: &mut Self::UserContext, ret: u64);
+
+    /// Returns the address of the instruction at which a saved user context
+    /// resumes.
+    fn user_pc(ctx: &Self::UserContext) -> usize;
+
+    /// Returns the register set of a saved user context, as seen by a debugger.
+    fn ptrace_get_regs(ctx: &Self::UserContext) -> Self::PtraceRegs;
+
+    /// Updates a saved user context from a debugger-supplied register set.
+    /// State which userspace may not control, such as the exception level the
+    /// context returns to, is left untouched.
+    fn ptrace_set_regs(ctx: &mut Self::UserContext, regs: &Self::PtraceRegs);
+
+    /// Arms (or disarms) a hardware single-step trap which fires after one
+    /// instruction has been executed once `ctx` is restored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `KernelError::NotSupported` if the architecture has no
+    /// single-step facility.
+    fn set_single_step(ctx: &mut Self::UserContext, enable: bool) -> Result<()>;
+
     /// Copies a block of memory from userspace to the kernel.
     ///
     /// This is the raw, unsafe primitive for transferring data from a
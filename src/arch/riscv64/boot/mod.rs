@@ -10,7 +10,7 @@ use crate::{
         init::run_initcalls,
     },
     interrupts::{
-        cpu_messenger::{Message, cpu_messenger_init, message_cpu},
+        cpu_messenger::{Message, message_cpu},
         get_interrupt_root,
     },
     kmain,
@@ -19,7 +19,7 @@ use crate::{
 };
 use riscv::{
     asm,
-    register::{sstatus, sie, stvec, satp},
+    register::{sstatus, satp},
 };
 use alloc::string::ToString;
 use core::arch::global_asm;
@@ -52,7 +52,6 @@ fn early_putchar(c: u8) {
         // THR (Transmitter Holding Register) 是偏移 0 的寄存器
         // LSR (Line Status Register) 是偏移 5 的寄存器
         let uart_base = 0x1000_0000 as *mut u8;
-        let lsr = uart_base.add(5);
         
         // 等待发送缓冲区为空 (LSR bit 5: THRE)
         // 这一步在 QEMU 中其实不是必须的，但在真实硬件上很重要
@@ -124,7 +123,7 @@ fn arch_init_stage1(
         early_print(">>> Stage 1 Done\n");
         Ok(stack_addr)
     })()
-    .unwrap_or_else(|e| {
+    .unwrap_or_else(|_| {
         // 打印一点错误码提示（虽然此时还没有格式化输出）
         early_print("\n!!! FATAL ERROR in Stage 1 !!!\n");
         park_cpu()
@@ -205,8 +204,6 @@ fn arch_init_secondary(ctx_frame: *mut TrapFrame) -> *mut TrapFrame {
 #[unsafe(no_mangle)]
 pub extern "C" fn park_cpu() -> ! {
     loop {
-        unsafe {
-            riscv::asm::wfi();
-        }
+        riscv::asm::wfi();
     }
 }
\ No newline at end of file
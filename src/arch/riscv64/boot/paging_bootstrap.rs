@@ -33,13 +33,13 @@ unsafe extern "C" {
 
 #[inline(always)]
 unsafe fn uart_putc(c: u8) {
-    ptr::write_volatile(0x1000_0000 as *mut u8, c);
+    unsafe { ptr::write_volatile(0x1000_0000 as *mut u8, c) };
 }
 
 #[inline(always)]
 unsafe fn uart_puts(s: &str) {
     for b in s.bytes() {
-        uart_putc(b);
+        unsafe { uart_putc(b) };
     }
 }
 
@@ -56,7 +56,7 @@ unsafe fn print_hex(mut val: usize) {
     let mut i = 0;
     
     if val == 0 {
-        uart_putc(b'0');
+        unsafe { uart_putc(b'0') };
         return;
     }
     while val > 0 {
@@ -66,7 +66,7 @@ unsafe fn print_hex(mut val: usize) {
     }
     while i > 0 {
         i -= 1;
-        uart_putc(buf[i]);
+        unsafe { uart_putc(buf[i]) };
     }
 }
 
@@ -243,11 +243,9 @@ fn do_paging_bootstrap(_bad_static_pages: PA, image_addr: PA, fdt_addr: PA) -> R
 pub extern "C" fn enable_mmu(root_table_pa: PA) {
     let ppn = root_table_pa.value() >> 12;
     let satp_value = (SATP_MODE_SV48 << 60) | ppn;
-    
-    unsafe {
-        satp::write(satp_value);
-        asm::sfence_vma_all();
-    }
+
+    satp::write(satp_value);
+    asm::sfence_vma_all();
 }
 
 #[unsafe(no_mangle)]
@@ -148,10 +148,11 @@ fn do_boot_secondary(cpu_node: fdt_parser::Node<'static>) -> Result<()> {
     }
 
     // Check "status" property, skip if disabled
-    if let Some(status) = cpu_node.find_property("status").map(|p| p.str()) {
-        if status != "okay" && status != "ok" {
-            return Ok(());
-        }
+    if let Some(status) = cpu_node.find_property("status").map(|p| p.str())
+        && status != "okay"
+        && status != "ok"
+    {
+        return Ok(());
     }
 
     info!("Starting Hart ID: {}", id);
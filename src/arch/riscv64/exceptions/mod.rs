@@ -1,6 +1,12 @@
+use super::memory::fault::handle_page_fault;
+use crate::{
+    interrupts::get_interrupt_root,
+    kernel::syscall::handle_syscall,
+    sched::{current_task, spawn_kernel_work, uspc_ret::dispatch_userspace_task},
+};
 use core::arch::global_asm;
+use riscv::interrupt::{Exception, ExceptionNumber, Interrupt};
 use riscv::register::scause::{self, Trap};
-use riscv::interrupt::{Exception, Interrupt};
 
 global_asm!(include_str!("entry.S"));
 
@@ -34,28 +40,61 @@ pub fn secondary_exceptions_init() {
     let _ = exceptions_init();
 }
 
+/// The bit of `sstatus` which is set if a trap was taken from S-mode.
+const SSTATUS_SPP: usize = 1 << 8;
+
+/// Handles a trap, returning the frame to be restored, which is that of a
+/// different task if the scheduler switched away from the trapping one.
 #[unsafe(no_mangle)]
-pub extern "C" fn trap_handler(tf: &mut TrapFrame) {
+pub extern "C" fn trap_handler(tf: *mut TrapFrame) -> *mut TrapFrame {
     let scause = scause::read();
     let stval = riscv::register::stval::read();
+    let sepc = unsafe { (*tf).sepc };
+    let from_user = unsafe { (*tf).sstatus } & SSTATUS_SPP == 0;
+
+    if from_user {
+        current_task().ctx.lock_save_irq().save_user_ctx(tf);
+    }
 
     match scause.cause() {
         Trap::Exception(e) if e == Exception::UserEnvCall as usize => {
-            tf.sepc += 4;
+            // Resume after the `ecall` once the syscall completes.
+            current_task().ctx.lock_save_irq().user_mut().sepc += 4;
+
+            spawn_kernel_work(handle_syscall());
         }
-        Trap::Exception(e) if e == Exception::LoadPageFault as usize || e == Exception::StorePageFault as usize => {
-            panic!("Page Fault at {:#x}, addr={:#x}", tf.sepc, stval);
+        Trap::Exception(e)
+            if e == Exception::InstructionPageFault as usize
+                || e == Exception::LoadPageFault as usize
+                || e == Exception::StorePageFault as usize =>
+        {
+            let cause = Exception::from_number(e).unwrap();
+
+            if let Err(err) = handle_page_fault(stval, cause, unsafe { &mut *tf }) {
+                panic!("Page fault handler error at {sepc:#x}, addr={stval:#x}: {err:?}");
+            }
         }
-        Trap::Interrupt(i) if i == Interrupt::SupervisorTimer as usize => {
+        Trap::Interrupt(i) if i == Interrupt::SupervisorExternal as usize => {
+            match get_interrupt_root() {
+                Some(ref im) => im.handle_interrupt(),
+                None => panic!("IRQ handled before root interrupt controller set at {sepc:#x}"),
+            }
         }
+        Trap::Interrupt(i) if i == Interrupt::SupervisorTimer as usize => {}
         _ => {
             panic!(
-                "Unhandled Trap: {:?} (code: {}) at {:#x}, stval={:#x}", 
-                scause.cause(), 
+                "Unhandled Trap: {:?} (code: {}) at {:#x}, stval={:#x}",
+                scause.cause(),
                 scause.code(),
-                tf.sepc, 
+                sepc,
                 stval
             );
         }
     }
-}
\ No newline at end of file
+
+    if from_user {
+        dispatch_userspace_task(tf);
+    }
+
+    tf
+}
@@ -1,4 +1,4 @@
-use crate::memory::PAGE_ALLOC;
+use crate::memory::{PAGE_ALLOC, swap};
 use super::{
     mmu::{page_allocator::PageTableAllocator, page_mapper::PageOffsetPgTableMapper, KERN_ADDR_SPACE},
 };
@@ -8,23 +8,29 @@ use libkernel::{
     arch::riscv64::memory::{
         pg_descriptors::{L3Descriptor, MemoryType, PaMapper, PageTableEntry},
         pg_tables::{
-            RvPageTableRoot, MapAttributes, MappingContext, PageAllocator, PgTableArray, map_range, PgTable
+            RvPageTableRoot, MapAttributes, MappingContext, PageAllocator, PgTableArray, map_range,
+            install_desc,
         },
-        pg_walk::{WalkContext, get_pte, walk_and_modify_region},
-        tlb::AllTlbInvalidator,
+        pg_walk::{WalkContext, free_page_tables, get_pte, walk_and_modify_region},
+        tlb::{AllTlbInvalidator, NullTlbInvalidator},
     },
     error::{KernelError, MapError, Result},
     memory::{
         PAGE_SIZE,
-        address::{TPA, VA, TVA},
+        address::{TPA, VA},
         page::PageFrame,
         permissions::PtePermissions,
         region::{PhysMemoryRegion, VirtMemoryRegion},
         pg_offset::PageOffsetTranslator,
+        swap::SwapEntry,
     },
 };
 use riscv::register::satp;
 use crate::arch::ArchImpl;
+/// The part of the address space that belongs to user space: the lower half
+/// of the L0 table. The upper half is shared with the kernel.
+const USER_REGION: VirtMemoryRegion = VirtMemoryRegion::new(VA::from_value(0), 1 << 47);
+
 pub struct RiscvProcessAddressSpace {
     // 使用 RvPageTableRoot (即 L0Table)
     l0_table: TPA<PgTableArray<RvPageTableRoot>>,
@@ -129,30 +135,30 @@ impl UserAddressSpace for RiscvProcessAddressSpace {
         };
 
         walk_and_modify_region(self.l0_table, va_range, &mut walk_ctx, |_, desc| {
-            // Sv48 PTE 通常硬件不支持显式的 "swapped" 位
-            // 这里利用软件定义位来标记
-            match (perms.is_execute(), perms.is_read(), perms.is_write()) {
-                (false, false, false) => desc.mark_as_swapped(),
-                _ => desc.set_permissions(perms),
+            // A swapped-out page is given the VMA's permissions when it's
+            // swapped back in.
+            if desc.swap_entry().is_some() {
+                return desc;
             }
+
+            protect_desc(desc, perms)
         })
     }
 
-    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<Vec<PageFrame>> {
+    fn unmap_range(&mut self, va_range: VirtMemoryRegion) -> Result<()> {
         let mut walk_ctx = WalkContext {
             mapper: &mut PageOffsetPgTableMapper {},
             invalidator: &AllTlbInvalidator {},
         };
-        let mut claimed_pages = Vec::new();
 
         walk_and_modify_region(self.l0_table, va_range, &mut walk_ctx, |_, desc| {
-            if let Some(addr) = desc.mapped_address() {
-                claimed_pages.push(addr.to_pfn());
+            if let Some(entry) = desc.swap_entry() {
+                swap::put_swap_entry(entry);
+            } else if let Some(addr) = desc.mapped_address() {
+                PAGE_ALLOC.get().unwrap().put_page(addr.to_pfn());
             }
             L3Descriptor::invalid()
-        })?;
-
-        Ok(claimed_pages)
+        })
     }
 
     fn remap(&mut self, va: VA, new_page: PageFrame, perms: PtePermissions) -> Result<PageFrame> {
@@ -164,6 +170,12 @@ impl UserAddressSpace for RiscvProcessAddressSpace {
         let mut old_pte = None;
 
         walk_and_modify_region(self.l0_table, va.page_region(), &mut walk_ctx, |_, pte| {
+            // A swapped-out page has no frame to replace, and its swap entry
+            // mustn't be lost.
+            if pte.swap_entry().is_some() {
+                return pte;
+            }
+
             old_pte = Some(pte);
             L3Descriptor::new_map_pa(new_page.pa(), MemoryType::Normal, perms)
         })?;
@@ -174,6 +186,31 @@ impl UserAddressSpace for RiscvProcessAddressSpace {
             .ok_or(KernelError::MappingError(MapError::NotL3Mapped))
     }
 
+    fn move_range(&mut self, va_range: VirtMemoryRegion, new_start: VA) -> Result<()> {
+        let mut walk_ctx = WalkContext {
+            mapper: &mut PageOffsetPgTableMapper {},
+            invalidator: &AllTlbInvalidator {},
+        };
+        let mut moved = Vec::new();
+
+        walk_and_modify_region(self.l0_table, va_range, &mut walk_ctx, |va, desc| {
+            if desc.swap_entry().is_some() || desc.mapped_address().is_some() {
+                moved.push((va, desc));
+                L3Descriptor::invalid()
+            } else {
+                desc
+            }
+        })?;
+
+        for (va, desc) in moved {
+            let new_va = new_start.add_bytes(va.value() - va_range.start_address().value());
+
+            self.install_desc(new_va, desc)?;
+        }
+
+        Ok(())
+    }
+
     fn translate(&self, va: VA) -> Option<PageInfo> {
         let pte = get_pte(
             self.l0_table,
@@ -188,6 +225,143 @@ impl UserAddressSpace for RiscvProcessAddressSpace {
         })
     }
 
+    fn test_and_clear_accessed(&mut self, va: VA) -> Result<bool> {
+        let mut walk_ctx = WalkContext {
+            mapper: &mut PageOffsetPgTableMapper {},
+            invalidator: &AllTlbInvalidator {},
+        };
+
+        let mut accessed = None;
+
+        walk_and_modify_region(self.l0_table, va.page_region(), &mut walk_ctx, |_, desc| {
+            if desc.swap_entry().is_none() && desc.mapped_address().is_some() {
+                accessed = Some(desc.is_accessed());
+                desc.set_accessed(false)
+            } else {
+                desc
+            }
+        })?;
+
+        accessed.ok_or(KernelError::MappingError(MapError::NotL3Mapped))
+    }
+
+    fn mark_accessed(&mut self, va: VA) -> bool {
+        let mut walk_ctx = WalkContext {
+            mapper: &mut PageOffsetPgTableMapper {},
+            invalidator: &AllTlbInvalidator {},
+        };
+
+        let mut was_clear = false;
+
+        let _ =
+            walk_and_modify_region(self.l0_table, va.page_region(), &mut walk_ctx, |_, desc| {
+                if desc.swap_entry().is_none()
+                    && desc.mapped_address().is_some()
+                    && !desc.is_accessed()
+                {
+                    was_clear = true;
+                    desc.set_accessed(true)
+                } else {
+                    desc
+                }
+            });
+
+        was_clear
+    }
+
+    fn swap_out(&mut self, va: VA, entry: SwapEntry) -> Result<PageFrame> {
+        let mut walk_ctx = WalkContext {
+            mapper: &mut PageOffsetPgTableMapper {},
+            invalidator: &AllTlbInvalidator {},
+        };
+
+        let mut old_page = None;
+
+        walk_and_modify_region(
+            self.l0_table,
+            va.page_region(),
+            &mut walk_ctx,
+            |_, desc| match (desc.swap_entry(), desc.mapped_address()) {
+                (None, Some(addr)) => {
+                    old_page = Some(addr.to_pfn());
+                    L3Descriptor::new_swap_entry(entry)
+                }
+                _ => desc,
+            },
+        )?;
+
+        old_page.ok_or(KernelError::MappingError(MapError::NotL3Mapped))
+    }
+
+    fn swap_entry(&self, va: VA) -> Option<SwapEntry> {
+        get_pte(
+            self.l0_table,
+            va.page_aligned(),
+            &mut PageOffsetPgTableMapper {},
+        )
+        .ok()??
+        .swap_entry()
+    }
+
+    fn swap_in(
+        &mut self,
+        va: VA,
+        entry: SwapEntry,
+        page: PageFrame,
+        perms: PtePermissions,
+    ) -> Result<()> {
+        let mut walk_ctx = WalkContext {
+            mapper: &mut PageOffsetPgTableMapper {},
+            invalidator: &AllTlbInvalidator {},
+        };
+
+        let mut found = false;
+
+        walk_and_modify_region(self.l0_table, va.page_region(), &mut walk_ctx, |_, desc| {
+            if desc.swap_entry() == Some(entry) {
+                found = true;
+                protect_desc(
+                    L3Descriptor::new_map_pa(page.pa(), MemoryType::Normal, perms),
+                    perms,
+                )
+            } else {
+                desc
+            }
+        })?;
+
+        if found {
+            Ok(())
+        } else {
+            Err(KernelError::MappingError(MapError::NotL3Mapped))
+        }
+    }
+
+    fn count_pages(&self, region: VirtMemoryRegion) -> (usize, usize) {
+        let (mut resident, mut swapped) = (0, 0);
+
+        let _ = walk_and_modify_region(
+            self.l0_table,
+            region,
+            &mut WalkContext {
+                mapper: &mut PageOffsetPgTableMapper {},
+                // Safe to not invalidate the TLB, as we are not modifying any
+                // PTEs.
+                invalidator: &NullTlbInvalidator {},
+            },
+            |_, desc| {
+                if desc.swap_entry().is_some() {
+                    swapped += 1;
+                } else if desc.mapped_address().is_some() {
+                    resident += 1;
+                }
+
+                desc
+            },
+        );
+
+        (resident, swapped)
+    }
+
     fn protect_and_clone_region(
         &mut self,
         region: VirtMemoryRegion,
@@ -203,14 +377,13 @@ impl UserAddressSpace for RiscvProcessAddressSpace {
         };
 
         walk_and_modify_region(self.l0_table, region, &mut walk_ctx, |va, pgd| {
-            if let Some(addr) = pgd.mapped_address() {
-                // COW 逻辑：克隆页面，增加引用计数
-                let page_region = PhysMemoryRegion::new(addr, PAGE_SIZE);
-                let alloc1 = unsafe { PAGE_ALLOC.get().unwrap().alloc_from_region(page_region) };
-                
-                // 增加引用计数 (Leak 两次是为了模拟引用计数增加，具体取决于你的 FrameAllocator 实现)
-                alloc1.clone().leak();
-                alloc1.leak();
+            if let Some(entry) = pgd.swap_entry() {
+                swap::dup_swap_entry(entry);
+                other.install_desc(va, pgd).unwrap();
+                pgd
+            } else if let Some(addr) = pgd.mapped_address() {
+                // The new mapping takes its own reference to the page.
+                PAGE_ALLOC.get().unwrap().get_page(addr.to_pfn());
 
                 let mut ctx = MappingContext {
                     allocator: &mut PageTableAllocator::new(),
@@ -236,4 +409,70 @@ impl UserAddressSpace for RiscvProcessAddressSpace {
             }
         })
     }
-}
\ No newline at end of file
+}
+
+impl RiscvProcessAddressSpace {
+    /// Installs `desc` as the PTE for `va`, which must not be mapped,
+    /// creating any tables that are needed.
+    fn install_desc(&mut self, va: VA, desc: L3Descriptor) -> Result<()> {
+        install_desc(
+            self.l0_table,
+            va,
+            desc,
+            &mut MappingContext {
+                allocator: &mut PageTableAllocator::new(),
+                mapper: &mut PageOffsetPgTableMapper {},
+                invalidator: &AllTlbInvalidator {},
+            },
+        )
+    }
+}
+
+/// Applies `perms` to the page descriptor `desc`.
+fn protect_desc(desc: L3Descriptor, perms: PtePermissions) -> L3Descriptor {
+    // Sv48 PTE 通常硬件不支持显式的 "swapped" 位
+    // 这里利用软件定义位来标记
+    match (perms.is_execute(), perms.is_read(), perms.is_write()) {
+        (false, false, false) => desc.mark_as_swapped(),
+        _ => {
+            // A page that's still shared with another address space must stay
+            // CoW, so that the first write to it copies it.
+            let shared = desc.mapped_address().is_some_and(|pa| {
+                !PAGE_ALLOC
+                    .get()
+                    .unwrap()
+                    .is_allocated_exclusive(pa.to_pfn())
+            });
+
+            if perms.is_write() && shared {
+                desc.set_permissions(perms.into_cow())
+            } else {
+                desc.set_permissions(perms)
+            }
+        }
+    }
+}
+
+impl Drop for RiscvProcessAddressSpace {
+    fn drop(&mut self) {
+        // The address space is no longer active on any CPU, so everything that
+        // was mapped in it, and the tables themselves, can be released.
+        if self.unmap_range(USER_REGION).is_err() {
+            // The tables are inconsistent. Leak them, rather than risk freeing
+            // pages that are still in use.
+            return;
+        }
+
+        let page_alloc = PAGE_ALLOC.get().unwrap();
+
+        free_page_tables(
+            self.l0_table,
+            USER_REGION,
+            &mut PageOffsetPgTableMapper {},
+            |table| page_alloc.put_page(table.to_pfn()),
+        )
+        .expect("User region should be page-aligned");
+
+        page_alloc.put_page(self.l0_table.to_untyped().to_pfn());
+    }
+}
@@ -6,11 +6,12 @@ use crate::{
         TrapFrame,
     },
     memory::fault::{FaultResolution, handle_demand_fault, handle_protection_fault},
+    process::thread_group::signal::SigId,
     sched::{current_task, spawn_kernel_work},
 };
 use libkernel::{
     UserAddressSpace,
-    error::Result,
+    error::{KernelError, Result},
     memory::{address::VA, proc_vm::vmarea::AccessKind, region::VirtMemoryRegion},
 };
 //use riscv::register::scause;
@@ -50,14 +51,23 @@ pub fn handle_page_fault(stval: usize, cause: Exception, tf: &mut TrapFrame) ->
     }
     match run_mem_fault_handler(fault_addr, access_kind) {
         Ok(FaultResolution::Resolved) => Ok(()),
+        // The signal is delivered on the way back to userspace, on the
+        // alternate signal stack if one is set up, since the fault may have
+        // been a stack overflow.
         Ok(FaultResolution::Denied) => {
-            panic!("SIGSEGV: Process {} accessed {:?} at {:x}", 
-                current_task().process.tgid, access_kind, fault_addr.value());
-        },
+            current_task().force_task_signal(SigId::SIGSEGV);
+            Ok(())
+        }
         Ok(FaultResolution::Deferred(fut)) => {
             spawn_kernel_work(async {
-                if Box::into_pin(fut).await.is_err() {
-                    panic!("Deferred page fault failed");
+                match Box::into_pin(fut).await {
+                    Ok(()) => {}
+                    // The fault handler has already tried to free memory, so
+                    // running out here means this process was picked to be
+                    // killed. Make sure that it dies, rather than retrying the
+                    // fault.
+                    Err(KernelError::NoMemory) => current_task().force_task_signal(SigId::SIGKILL),
+                    Err(_) => panic!("Deferred page fault failed"),
                 }
             });
             Ok(())
@@ -68,11 +78,17 @@ pub fn handle_page_fault(stval: usize, cause: Exception, tf: &mut TrapFrame) ->
 
 fn run_mem_fault_handler(fault_addr: VA, access_kind: AccessKind) -> Result<FaultResolution> {
     let task = current_task();
-    let mut vm = task.vm.lock_save_irq();
+    let vm = task.vm();
+    let mut vm = vm.lock_save_irq();
 
     let pg_info = vm.mm.address_space_mut().translate(fault_addr);
 
     match pg_info {
+        // Without hardware A-bit updates, an access to a page whose A bit was
+        // cleared to age it for reclaim faults.
+        Some(_) if vm.mm.address_space_mut().mark_accessed(fault_addr) => {
+            Ok(FaultResolution::Resolved)
+        }
         Some(info) => handle_protection_fault(&mut vm, fault_addr, access_kind, info),
         None => handle_demand_fault(&mut vm, fault_addr, access_kind),
     }
@@ -1,6 +1,6 @@
-use super::{FIXMAP_BASE, tlb::SfenceTlbInvalidator as RvTlbInvalidator};
+use super::FIXMAP_BASE;
 use crate::{
-    arch::riscv64::fdt::MAX_FDT_SZ,
+    arch::riscv64::{boot::park_cpu, fdt::MAX_FDT_SZ},
     ksym_pa,
     sync::SpinLock,
 };
@@ -26,12 +26,11 @@ use libkernel::{
     error::{KernelError, Result},
     memory::{
         PAGE_SIZE,
-        address::{IdentityTranslator, PA, TPA, TVA, VA},
+        address::{PA, TPA, TVA, VA},
         permissions::PtePermissions,
         region::PhysMemoryRegion,
     },
 };
-use super::IMAGE_BASE;
 fn debug_uart_putc(c: u8) {
     unsafe {
         let ptr = 0x1000_0000 as *mut u8; 
@@ -147,11 +146,11 @@ pub fn setup_fixmaps(&mut self, root_base: TPA<PgTableArray<RvRoot>>) {
 
         if root_base.value() == 0 {
             debug_print("[FATAL] root_base is 0! Caller passed invalid PA.\n");
-            loop {}
+            park_cpu();
         }
 
         let root_va = TVA::from_value(root_base.value());
-        let root_table = unsafe { RvRoot::from_ptr(root_va) };
+        let root_table = RvRoot::from_ptr(root_va);
         let invalidator = AllTlbInvalidator {};
 
         debug_print("[DEBUG] 2. Checking self.l1 alignment...\n");
@@ -163,7 +162,7 @@ pub fn setup_fixmaps(&mut self, root_base: TPA<PgTableArray<RvRoot>>) {
 
         if l1_ptr & 0xFFF != 0 {
             debug_print("[FATAL] self.l1 is NOT 4KB aligned! Add #[repr(align(4096))] to Fixmap struct.\n");
-            loop {}
+            park_cpu();
         }
 
         debug_print("[DEBUG] 3. Calculating PA safely...\n");
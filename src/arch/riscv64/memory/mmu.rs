@@ -6,7 +6,7 @@ use libkernel::{
         pg_descriptors::{MemoryType, PaMapper},
         pg_tables::{MapAttributes, MappingContext, PgTableArray, RvPageTableRoot, map_range},
         pg_walk::get_pte,
-        tlb::{AllTlbInvalidator, TLBInvalidator},
+        tlb::AllTlbInvalidator,
     },
     error::Result,
     memory::{
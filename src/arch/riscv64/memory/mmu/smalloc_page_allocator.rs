@@ -1,31 +1,18 @@
-use crate::memory::PageOffsetTranslator;
+use crate::{arch::riscv64::boot::park_cpu, memory::PageOffsetTranslator};
 use libkernel::{
     arch::riscv64::memory::pg_tables::{PageAllocator, PgTable, PgTableArray},
-    error::{Result, KernelError}, // 引入 KernelError
+    error::Result,
     memory::{PAGE_SIZE, address::TPA, smalloc::Smalloc},
 };
 
 // 简单的调试打印工具 (因为这里还没法用 println!)
 unsafe fn debug_uart_putc(c: u8) {
     let ptr = 0x1000_0000 as *mut u8;
-    core::ptr::write_volatile(ptr, c);
+    unsafe { core::ptr::write_volatile(ptr, c) };
 }
 
 unsafe fn debug_puts(s: &str) {
-    for c in s.bytes() { debug_uart_putc(c); }
-}
-
-unsafe fn debug_print_hex(mut val: usize) {
-    let hex_chars = b"0123456789abcdef";
-    let mut buf = [0u8; 16];
-    let mut i = 0;
-    if val == 0 { debug_uart_putc(b'0'); return; }
-    while val > 0 {
-        buf[i] = hex_chars[val & 0xf];
-        val >>= 4;
-        i += 1;
-    }
-    while i > 0 { i -= 1; debug_uart_putc(buf[i]); }
+    for c in s.bytes() { unsafe { debug_uart_putc(c) }; }
 }
 
 pub struct SmallocPageAlloc<'a> {
@@ -56,7 +43,7 @@ impl PageAllocator for SmallocPageAlloc<'_> {
                         debug_puts("Check your kmain allocator initialization!\n");
                     }
                     // 死循环以保留现场
-                    loop {}
+                    park_cpu();
                 }
 
                 // 可选：打印分配到的地址，看看是否合理（应该很大，比如 > 0x80000000）
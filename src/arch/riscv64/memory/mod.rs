@@ -9,7 +9,6 @@ pub mod address_space;
 pub mod fault;
 pub mod fixmap;
 pub mod mmu;
-pub mod tlb;
 pub mod uaccess;
 
 // -------------------------------------------------------------------
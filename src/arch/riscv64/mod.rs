@@ -1,10 +1,5 @@
 use alloc::sync::Arc;
 use core::future::Future;
-use riscv::{
-    asm::wfi,
-    register::sstatus,
-};
-
 //use cpu_ops::{local_irq_restore, local_irq_save};
 use exceptions::TrapFrame;
 use libkernel::{
@@ -35,7 +30,6 @@ pub mod cpu_ops;
 pub mod exceptions;
 mod memory;
 mod proc;
-pub mod sbi;
 pub mod fdt;
 
 /// RISC-V 64 Architecture Provider
@@ -58,6 +52,7 @@ impl VirtualMemory for Riscv64 {
 impl Arch for Riscv64 {
     // 使用 TrapFrame 作为用户上下文 (保存通用寄存器 + CSRs)
     type UserContext = TrapFrame;
+    type PtraceRegs = proc::ptrace::UserRegs;
 
     fn new_user_context(entry_point: VA, stack_top: VA) -> Self::UserContext {
         let mut ctx = TrapFrame {
@@ -100,6 +95,45 @@ impl Arch for Riscv64 {
         proc::signal::do_signal_return()
     }
 
+    fn elf_hwcap() -> u64 {
+        proc::hwcap::elf_hwcap()
+    }
+
+    fn vdso_image() -> Option<&'static [u8]> {
+        None
+    }
+
+    fn hw_random() -> Option<u64> {
+        proc::rng::random()
+    }
+
+    fn syscall_args(ctx: &Self::UserContext) -> (u32, [u64; 6]) {
+        // The number is in a7, and the arguments in a0-a5.
+        let args = core::array::from_fn(|i| ctx.regs[10 + i] as u64);
+
+        (ctx.regs[17] as u32, args)
+    }
+
+    fn set_syscall_ret(ctx: &mut Self::UserContext, ret: u64) {
+        ctx.regs[10] = ret as usize;
+    }
+
+    fn user_pc(ctx: &Self::UserContext) -> usize {
+        ctx.sepc
+    }
+
+    fn ptrace_get_regs(ctx: &Self::UserContext) -> Self::PtraceRegs {
+        proc::ptrace::get_regs(ctx)
+    }
+
+    fn ptrace_set_regs(ctx: &mut Self::UserContext, regs: &Self::PtraceRegs) {
+        proc::ptrace::set_regs(ctx, regs)
+    }
+
+    fn set_single_step(ctx: &mut Self::UserContext, enable: bool) -> Result<()> {
+        proc::ptrace::set_single_step(ctx, enable)
+    }
+
     fn context_switch(new: Arc<Task>) {
         proc::context_switch(new);
     }
@@ -112,8 +146,6 @@ impl Arch for Riscv64 {
         // 使用 SBI System Reset Extension 进行关机
         // 0x53525354 = 'SRST' System Reset Extension
         // Type: Shutdown (0), Reason: NoReason (0)
-        // 注意：需要确保 Cargo.toml 中引入了 `sbi-rt`
-        #[cfg(feature = "sbi-rt")]
         sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
 
         // 如果没有 SBI 或返回了，进入死循环
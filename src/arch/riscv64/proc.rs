@@ -2,7 +2,10 @@ use crate::process::Task;
 use alloc::sync::Arc;
 use libkernel::UserAddressSpace;
 
+pub mod hwcap;
 pub mod idle;
+pub mod ptrace;
+pub mod rng;
 pub mod signal;
 
 /// 架构相关的上下文切换
@@ -14,7 +17,7 @@ pub mod signal;
 /// 2. 获取地址空间的可变引用。
 /// 3. 调用 `activate()`，这将写入 `satp` 寄存器并执行 `sfence.vma` 刷新 TLB。
 pub fn context_switch(new: Arc<Task>) {
-    new.vm
+    new.vm()
         .lock_save_irq()
         .mm_mut()
         .address_space_mut()
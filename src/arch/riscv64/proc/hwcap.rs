@@ -0,0 +1,12 @@
+//! Hardware capabilities reported to userspace via `AT_HWCAP`.
+
+/// Returns the bit for a single-letter ISA extension.
+const fn isa_ext(letter: u8) -> u64 {
+    1 << (letter - b'A')
+}
+
+pub fn elf_hwcap() -> u64 {
+    // The kernel itself is built for RV64GC, so every hart we run on
+    // implements at least these base extensions.
+    isa_ext(b'I') | isa_ext(b'M') | isa_ext(b'A') | isa_ext(b'F') | isa_ext(b'D') | isa_ext(b'C')
+}
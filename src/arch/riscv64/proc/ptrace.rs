@@ -0,0 +1,35 @@
+use crate::{arch::riscv64::TrapFrame, memory::uaccess::UserCopyable};
+use libkernel::error::{KernelError, Result};
+
+/// The layout of `struct user_regs_struct`: the PC followed by `x1`-`x31`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UserRegs {
+    pc: usize,
+    regs: [usize; 31],
+}
+
+// SAFETY: The register set is made up solely of PoDs with no padding.
+unsafe impl UserCopyable for UserRegs {}
+
+pub fn get_regs(ctx: &TrapFrame) -> UserRegs {
+    let mut regs = [0; 31];
+    regs.copy_from_slice(&ctx.regs[1..]);
+
+    UserRegs { pc: ctx.sepc, regs }
+}
+
+pub fn set_regs(ctx: &mut TrapFrame, regs: &UserRegs) {
+    ctx.sepc = regs.pc;
+    ctx.regs[1..].copy_from_slice(&regs.regs);
+}
+
+pub fn set_single_step(_ctx: &mut TrapFrame, enable: bool) -> Result<()> {
+    // There is no hardware single-step in the base ISA; debuggers are expected
+    // to fall back to software breakpoints.
+    if enable {
+        Err(KernelError::NotSupported)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+//! The architectural entropy source: the `seed` CSR of the Zkr extension.
+
+use crate::drivers::fdt_prober::get_fdt;
+use core::arch::asm;
+
+/// The operational status, in bits 31:30 of `seed`.
+const OPST_SHIFT: usize = 30;
+const OPST_MASK: usize = 0b11;
+/// 16 bits of entropy are held in the low bits.
+const OPST_ES16: usize = 0b10;
+/// The entropy source has failed, and won't recover.
+const OPST_DEAD: usize = 0b11;
+
+/// How many times `seed` is polled for each 16 bits of entropy before giving
+/// up on it. The other states, BIST and WAIT, are transient.
+const MAX_POLLS: usize = 1000;
+
+/// Returns `true` if the boot hart implements Zkr, according to the device
+/// tree. The firmware is expected to let S-mode access `seed` when it does.
+fn has_zkr() -> bool {
+    let Some(cpu) = get_fdt().all_nodes().find(|node| {
+        node.find_property("device_type")
+            .is_some_and(|prop| prop.str() == "cpu")
+    }) else {
+        return false;
+    };
+
+    if let Some(exts) = cpu.find_property("riscv,isa-extensions") {
+        return exts.str_list().any(|ext| ext == "zkr");
+    }
+
+    cpu.find_property("riscv,isa")
+        .is_some_and(|isa| isa.str().split('_').any(|ext| ext == "zkr"))
+}
+
+/// Reads 16 bits of entropy from `seed`.
+fn read_es16() -> Option<u64> {
+    for _ in 0..MAX_POLLS {
+        let seed: usize;
+
+        unsafe {
+            // `seed` must be accessed with a read-write instruction. It's
+            // named by its number, as the assembler only knows it with Zkr.
+            asm!("csrrw {}, 0x015, zero", out(reg) seed, options(nomem, nostack));
+        }
+
+        match (seed >> OPST_SHIFT) & OPST_MASK {
+            OPST_ES16 => return Some((seed & 0xffff) as u64),
+            OPST_DEAD => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+pub fn random() -> Option<u64> {
+    if !has_zkr() {
+        return None;
+    }
+
+    let mut val = 0;
+
+    for _ in 0..4 {
+        val = (val << 16) | read_es16()?;
+    }
+
+    Some(val)
+}
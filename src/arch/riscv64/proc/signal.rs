@@ -30,7 +30,7 @@ pub async fn do_signal(id: SigId, sa: UserspaceSigAction) -> Result<TrapFrame> {
 
     // 获取当前任务保存的用户态上下文
     let saved_state = *task.ctx.lock_save_irq().user();
-    let mut new_state = saved_state.clone();
+    let mut new_state = saved_state;
     
     let mut frame = RtSigFrame {
         uctx: saved_state,
@@ -1,21 +1,44 @@
+use core::time::Duration;
+
 use libkernel::{
     error::{KernelError, Result},
     memory::address::TUA,
 };
 
-use crate::{drivers::timer::uptime, memory::uaccess::copy_to_user};
+use crate::{
+    drivers::timer::{start_time, uptime},
+    memory::uaccess::copy_to_user,
+    process::creds::caps::CapSet,
+    sched::current_task,
+};
 
-use super::{realtime::date, timespec::TimeSpec};
+use super::{
+    realtime::{date, set_date},
+    timespec::TimeSpec,
+};
 
 pub type ClockId = i32;
 
-const CLOCK_MONOTONIC: ClockId = 0;
-const CLOCK_REALTIME: ClockId = 1;
+const CLOCK_REALTIME: ClockId = 0;
+const CLOCK_MONOTONIC: ClockId = 1;
+const CLOCK_MONOTONIC_RAW: ClockId = 4;
+const CLOCK_REALTIME_COARSE: ClockId = 5;
+const CLOCK_MONOTONIC_COARSE: ClockId = 6;
+const CLOCK_BOOTTIME: ClockId = 7;
+
+/// Returns the resolution of the clocks: the period of the system counter.
+pub fn clock_resolution() -> Duration {
+    start_time().map_or(Duration::from_nanos(1), |t| {
+        Duration::from_nanos(1_000_000_000u64.div_ceil(t.freq()))
+    })
+}
 
 pub async fn sys_clock_gettime(clockid: ClockId, time_spec: TUA<TimeSpec>) -> Result<usize> {
     let time = match clockid {
-        CLOCK_MONOTONIC => uptime(),
-        CLOCK_REALTIME => date(),
+        // There is no clock adjustment or suspend, so all of the monotonic
+        // clocks are the same.
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => uptime(),
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => date(),
         _ => return Err(KernelError::InvalidValue),
     };
 
@@ -23,3 +46,42 @@ pub async fn sys_clock_gettime(clockid: ClockId, time_spec: TUA<TimeSpec>) -> Re
 
     Ok(0)
 }
+
+pub async fn sys_clock_settime(clockid: ClockId, time_spec: TUA<TimeSpec>) -> Result<usize> {
+    // Only the realtime clock can be set; the others count from boot.
+    if clockid != CLOCK_REALTIME {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let time = TimeSpec::copy_from_user(time_spec).await?;
+
+    if !current_task()
+        .creds
+        .lock_save_irq()
+        .has_cap(CapSet::CAP_SYS_TIME)
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    set_date(time.into());
+
+    Ok(0)
+}
+
+pub async fn sys_clock_getres(clockid: ClockId, res: TUA<TimeSpec>) -> Result<usize> {
+    match clockid {
+        CLOCK_REALTIME
+        | CLOCK_MONOTONIC
+        | CLOCK_MONOTONIC_RAW
+        | CLOCK_REALTIME_COARSE
+        | CLOCK_MONOTONIC_COARSE
+        | CLOCK_BOOTTIME => {}
+        _ => return Err(KernelError::InvalidValue),
+    }
+
+    if !res.is_null() {
+        copy_to_user(res, clock_resolution().into()).await?;
+    }
+
+    Ok(0)
+}
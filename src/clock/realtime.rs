@@ -1,5 +1,6 @@
 use crate::{
     drivers::timer::{Instant, now},
+    memory::vdso,
     sync::SpinLock,
 };
 use core::time::Duration;
@@ -18,5 +19,24 @@ pub fn date() -> Duration {
     }
 }
 
+/// Returns a known duration since the epoch, along with the instant at which
+/// it was taken, if the time of day has been set.
+pub fn epoch() -> Option<(Duration, Instant)> {
+    *EPOCH_DURATION.lock_save_irq()
+}
+
+/// Sets the time of day to `date`, a duration since the epoch. This does
+/// nothing if the system timer hasn't been set up.
+pub fn set_date(date: Duration) {
+    let Some(now) = now() else {
+        return;
+    };
+
+    *EPOCH_DURATION.lock_save_irq() = Some((date, now));
+
+    // The vDSO works out the time for itself, so must be told of the change.
+    vdso::clock_changed();
+}
+
 // Represents a known duration since the epoch at the assoicated instant.
 static EPOCH_DURATION: SpinLock<Option<(Duration, Instant)>> = SpinLock::new(None);
@@ -1,7 +1,47 @@
-use super::timespec::TimeSpec;
-use crate::memory::uaccess::{UserCopyable, copy_to_user};
+use super::realtime::{date, set_date};
+use crate::{
+    memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
+    process::creds::caps::CapSet,
+    sched::current_task,
+};
 use core::time::Duration;
-use libkernel::{error::Result, memory::address::TUA};
+use libkernel::{
+    error::{KernelError, Result},
+    memory::address::TUA,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TimeVal {
+    tv_sec: i64,
+    tv_usec: i64,
+}
+
+unsafe impl UserCopyable for TimeVal {}
+
+impl From<Duration> for TimeVal {
+    fn from(value: Duration) -> Self {
+        TimeVal {
+            tv_sec: value.as_secs() as _,
+            tv_usec: value.subsec_micros() as _,
+        }
+    }
+}
+
+impl TryFrom<TimeVal> for Duration {
+    type Error = KernelError;
+
+    fn try_from(value: TimeVal) -> Result<Self> {
+        if value.tv_sec < 0 || !(0..1_000_000).contains(&value.tv_usec) {
+            return Err(KernelError::InvalidValue);
+        }
+
+        Ok(Duration::new(
+            value.tv_sec as _,
+            value.tv_usec as u32 * 1000,
+        ))
+    }
+}
 
 #[derive(Copy, Clone)]
 pub struct TimeZone {
@@ -11,10 +51,10 @@ pub struct TimeZone {
 
 unsafe impl UserCopyable for TimeZone {}
 
-pub async fn sys_gettimeofday(tv: TUA<TimeSpec>, tz: TUA<TimeZone>) -> Result<usize> {
-    let time: TimeSpec = Duration::new(0, 0).into();
-
-    copy_to_user(tv, time).await?;
+pub async fn sys_gettimeofday(tv: TUA<TimeVal>, tz: TUA<TimeZone>) -> Result<usize> {
+    if !tv.is_null() {
+        copy_to_user(tv, date().into()).await?;
+    }
 
     if !tz.is_null() {
         copy_to_user(
@@ -29,3 +69,30 @@ pub async fn sys_gettimeofday(tv: TUA<TimeSpec>, tz: TUA<TimeZone>) -> Result<us
 
     Ok(0)
 }
+
+pub async fn sys_settimeofday(tv: TUA<TimeVal>, tz: TUA<TimeZone>) -> Result<usize> {
+    let time = if tv.is_null() {
+        None
+    } else {
+        Some(Duration::try_from(copy_from_user(tv).await?)?)
+    };
+
+    // The timezone is only used for compatibility, and isn't kept.
+    if !tz.is_null() {
+        copy_from_user(tz).await?;
+    }
+
+    if !current_task()
+        .creds
+        .lock_save_irq()
+        .has_cap(CapSet::CAP_SYS_TIME)
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    if let Some(time) = time {
+        set_date(time);
+    }
+
+    Ok(0)
+}
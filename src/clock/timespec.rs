@@ -33,17 +33,23 @@ impl From<Duration> for TimeSpec {
 
 impl TimeSpec {
     pub async fn copy_from_user(src: TUA<Self>) -> Result<Self> {
-        let timespec = copy_from_user(src).await?;
+        let timespec: Self = copy_from_user(src).await?;
 
-        // Sanity checking.
-        if timespec.tv_nsec > 999_999_999 {
+        timespec.validate()?;
+
+        Ok(timespec)
+    }
+
+    /// Checks that the timespec holds a valid, non-negative, time.
+    pub fn validate(&self) -> Result<()> {
+        if self.tv_nsec > 999_999_999 {
             return Err(KernelError::InvalidValue);
         }
 
-        if timespec.tv_sec < 0 {
+        if self.tv_sec < 0 {
             return Err(KernelError::InvalidValue);
         }
 
-        Ok(timespec)
+        Ok(())
     }
 }
@@ -25,7 +25,6 @@ use meta::{
 };
 
 use super::Console;
-use ringbuf::traits::*;
 
 mod cooker;
 mod meta;
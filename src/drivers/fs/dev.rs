@@ -14,6 +14,7 @@ use libkernel::{
     driver::CharDevDescriptor,
     error::{FsError, KernelError, Result},
     fs::{DEVFS_ID, FileType, Inode, InodeId},
+    proc::ids::{Gid, Uid},
 };
 use log::warn;
 
@@ -40,12 +41,27 @@ impl DevFs {
         })
     }
 
+    /// Creates a character device node called `name` in the root of devfs.
     pub fn mknod(
         &self,
         name: String,
         device_id: CharDevDescriptor,
         mode: FilePermissions,
     ) -> Result<()> {
+        self.insert_node(name, FileType::CharDevice(device_id), mode)
+    }
+
+    /// Creates a block device node called `name` in the root of devfs.
+    pub fn mkblk(
+        &self,
+        name: String,
+        device_id: CharDevDescriptor,
+        mode: FilePermissions,
+    ) -> Result<()> {
+        self.insert_node(name, FileType::BlockDevice(device_id), mode)
+    }
+
+    fn insert_node(&self, name: String, file_type: FileType, mode: FilePermissions) -> Result<()> {
         let InodeKind::Directory(ref children) = self.root.kind else {
             // This should be impossible as the root is always a directory.
             return Err(FsError::InvalidFs.into());
@@ -60,13 +76,14 @@ impl DevFs {
 
         let new_inode = Arc::new(DevFsINode {
             id: InodeId::from_fsid_and_inodeid(DEVFS_ID, id),
+            // This is the crucial part: the file type carries the device
+            // handle.
             attr: SpinLock::new(FileAttr {
-                file_type: FileType::CharDevice(device_id),
+                file_type,
                 mode,
                 ..FileAttr::default()
             }),
-            // This is the crucial part: we store the device handle.
-            kind: InodeKind::CharDevice { device_id },
+            kind: InodeKind::Device,
         });
 
         children.insert(name.to_string(), new_inode);
@@ -89,8 +106,9 @@ impl Filesystem for DevFs {
 enum InodeKind {
     /// A directory, which contains a map of names to child inodes.
     Directory(SpinLock<BTreeMap<String, Arc<DevFsINode>>>),
-    /// A character device, which stores its major/minor handle (`dev_t`).
-    CharDevice { device_id: CharDevDescriptor },
+    /// A character or block device, whose major/minor handle (`dev_t`) is
+    /// stored in its file type.
+    Device,
 }
 
 struct DevDirStreamer {
@@ -138,16 +156,26 @@ impl Inode for DevFsINode {
                     .map(|inode| inode.clone() as Arc<dyn Inode>)
                     .ok_or_else(|| FsError::NotFound.into())
             }
-            InodeKind::CharDevice { .. } => Err(FsError::NotADirectory.into()),
+            InodeKind::Device => Err(FsError::NotADirectory.into()),
         }
     }
 
     async fn getattr(&self) -> Result<FileAttr> {
-        let mut attr = self.attr.lock_save_irq().clone();
-        if let InodeKind::CharDevice { device_id } = self.kind {
-            attr.file_type = FileType::CharDevice(device_id)
+        Ok(self.attr.lock_save_irq().clone())
+    }
+
+    async fn chown(&self, uid: Option<Uid>, gid: Option<Gid>) -> Result<()> {
+        let mut attr = self.attr.lock_save_irq();
+
+        if let Some(uid) = uid {
+            attr.uid = uid;
         }
-        Ok(attr)
+
+        if let Some(gid) = gid {
+            attr.gid = gid;
+        }
+
+        Ok(())
     }
 
     async fn readdir(&self, start_offset: u64) -> Result<Box<dyn DirStream>> {
@@ -159,7 +187,7 @@ impl Inode for DevFsINode {
                     idx: start_offset as usize,
                 }))
             }
-            InodeKind::CharDevice { .. } => Err(FsError::NotADirectory.into()),
+            InodeKind::Device => Err(FsError::NotADirectory.into()),
         }
     }
 }
@@ -10,7 +10,7 @@ use alloc::{
 };
 use libkernel::{
     error::{KernelError, Result},
-    fs::OpenFlags,
+    fs::{BlockDevice, OpenFlags},
 };
 use probe::DeviceDescriptor;
 
@@ -25,11 +25,13 @@ pub mod fs;
 pub mod init;
 pub mod interrupts;
 pub mod probe;
+pub mod ram;
 pub mod timer;
 pub mod uart;
 
 #[repr(u64)]
 pub enum ReservedMajors {
+    Ramdisk = 1,
     Console = 5,
     Uart = 10,
     End = 11,
@@ -59,12 +61,21 @@ pub trait CharDriver: Send + Sync + 'static {
     fn get_device(&self, minor: u64) -> Option<Arc<dyn OpenableDevice>>;
 }
 
+/// A driver for block devices, which may back a filesystem or a swap area.
+pub trait BlkDriver: Send + Sync + 'static {
+    /// Given a minor number, this function returns that specific device
+    /// instance. It can fail if the minor number is invalid for this driver.
+    fn get_device(&self, minor: u64) -> Option<Arc<dyn BlockDevice>>;
+}
+
 pub struct DriverManager {
     /// Every driver instance in the system.
     active_drivers: Vec<Arc<dyn Driver>>,
     _next_major: AtomicU64,
     /// Maps a major number to an instance of a CharDriver.
     char_drivers: BTreeMap<u64, Arc<dyn CharDriver>>,
+    /// Maps a major number to an instance of a BlkDriver.
+    blk_drivers: BTreeMap<u64, Arc<dyn BlkDriver>>,
 }
 
 impl DriverManager {
@@ -73,6 +84,7 @@ impl DriverManager {
             active_drivers: Vec::new(),
             _next_major: AtomicU64::new(ReservedMajors::End as _),
             char_drivers: BTreeMap::new(),
+            blk_drivers: BTreeMap::new(),
         }
     }
 
@@ -107,6 +119,20 @@ impl DriverManager {
     pub fn find_char_driver(&self, major: u64) -> Option<Arc<dyn CharDriver>> {
         self.char_drivers.get(&major).cloned()
     }
+
+    pub fn register_blk_driver(&mut self, major: u64, driver: Arc<dyn BlkDriver>) -> Result<()> {
+        match self.blk_drivers.entry(major) {
+            Entry::Vacant(vacant_entry) => {
+                vacant_entry.insert(driver);
+                Ok(())
+            }
+            Entry::Occupied(_) => Err(KernelError::InUse),
+        }
+    }
+
+    pub fn find_blk_driver(&self, major: u64) -> Option<Arc<dyn BlkDriver>> {
+        self.blk_drivers.get(&major).cloned()
+    }
 }
 
 pub static DM: SpinLock<DriverManager> = SpinLock::new(DriverManager::new());
@@ -0,0 +1,133 @@
+//! A RAM-backed block device, exposed as `/dev/ram0`.
+//!
+//! The device's pages are only allocated the first time they're written, so it
+//! costs nothing until it's used. Blocks which have never been written read
+//! back as zeroes.
+
+use crate::{
+    drivers::{BlkDriver, DriverManager, ReservedMajors, fs::dev::devfs, init::PlatformBus},
+    kernel_driver,
+    memory::page::ClaimedPage,
+    sync::SpinLock,
+};
+use alloc::{
+    boxed::Box,
+    collections::btree_map::{BTreeMap, Entry},
+    string::ToString,
+    sync::Arc,
+};
+use async_trait::async_trait;
+use libkernel::{
+    driver::CharDevDescriptor,
+    error::{IoError, Result},
+    fs::{BlockDevice, attr::FilePermissions},
+    memory::PAGE_SIZE,
+};
+
+/// The size of the ramdisk in bytes.
+const RAM_DISK_SIZE: usize = 16 * 1024 * 1024;
+
+const BLOCK_SIZE: usize = PAGE_SIZE;
+
+struct RamBlkDev {
+    /// The pages which have been written, indexed by block.
+    pages: SpinLock<BTreeMap<u64, ClaimedPage>>,
+}
+
+impl RamBlkDev {
+    fn new() -> Self {
+        Self {
+            pages: SpinLock::new(BTreeMap::new()),
+        }
+    }
+
+    fn check_bounds(&self, block_id: u64, len: usize) -> Result<()> {
+        debug_assert!(len.is_multiple_of(BLOCK_SIZE));
+
+        if block_id + (len / BLOCK_SIZE) as u64 > self.num_blocks() {
+            Err(IoError::OutOfBounds.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl BlockDevice for RamBlkDev {
+    async fn read(&self, block_id: u64, buf: &mut [u8]) -> Result<()> {
+        self.check_bounds(block_id, buf.len())?;
+
+        let pages = self.pages.lock_save_irq();
+
+        for (block, chunk) in (block_id..).zip(buf.chunks_mut(BLOCK_SIZE)) {
+            match pages.get(&block) {
+                Some(page) => chunk.copy_from_slice(page.as_slice()),
+                None => chunk.fill(0),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write(&self, block_id: u64, buf: &[u8]) -> Result<()> {
+        self.check_bounds(block_id, buf.len())?;
+
+        let mut pages = self.pages.lock_save_irq();
+
+        for (block, chunk) in (block_id..).zip(buf.chunks(BLOCK_SIZE)) {
+            let page = match pages.entry(block) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(ClaimedPage::alloc_zeroed()?),
+            };
+
+            page.as_slice_mut().copy_from_slice(chunk);
+        }
+
+        Ok(())
+    }
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        (RAM_DISK_SIZE / BLOCK_SIZE) as u64
+    }
+
+    async fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct RamBlkDriver {
+    ram0: Arc<RamBlkDev>,
+}
+
+impl BlkDriver for RamBlkDriver {
+    fn get_device(&self, minor: u64) -> Option<Arc<dyn BlockDevice>> {
+        match minor {
+            0 => Some(self.ram0.clone()),
+            _ => None,
+        }
+    }
+}
+
+pub fn ram_blkdev_init(_bus: &mut PlatformBus, dm: &mut DriverManager) -> Result<()> {
+    devfs().mkblk(
+        "ram0".to_string(),
+        CharDevDescriptor {
+            major: ReservedMajors::Ramdisk as _,
+            minor: 0,
+        },
+        FilePermissions::from_bits_retain(0o660),
+    )?;
+
+    dm.register_blk_driver(
+        ReservedMajors::Ramdisk as _,
+        Arc::new(RamBlkDriver {
+            ram0: Arc::new(RamBlkDev::new()),
+        }),
+    )
+}
+
+kernel_driver!(ram_blkdev_init);
@@ -0,0 +1,153 @@
+//! One-shot and periodic kernel timers.
+//!
+//! A [`KTimer`] runs an action from the timer interrupt when it expires, and
+//! then, if it has an interval, re-arms itself. It's built on the system
+//! timer's wakeup queue: each expiry is a callback event. Events can't be
+//! removed from the queue, so each carries the generation of the timer at the
+//! time it was scheduled, and is ignored if the timer has since been re-armed.
+
+use super::{Instant, now, schedule_callback};
+use crate::sync::SpinLock;
+use alloc::sync::{Arc, Weak};
+use core::time::Duration;
+
+/// The shortest interval a periodic timer may have, so that a tiny interval
+/// can't flood the system with timer interrupts.
+const MIN_INTERVAL: Duration = Duration::from_micros(100);
+
+type Action = Arc<dyn Fn(u64) + Send + Sync>;
+
+struct KTimerState {
+    /// Incremented whenever the timer is set, invalidating any expiry events
+    /// already queued.
+    generation: u64,
+    expires: Option<Instant>,
+    interval: Duration,
+    /// The number of expiries missed before the last one was handled.
+    overrun: u32,
+    action: Action,
+}
+
+pub struct KTimer {
+    state: Arc<SpinLock<KTimerState>>,
+}
+
+impl KTimer {
+    /// Creates a disarmed timer which runs `action`, from interrupt context,
+    /// each time it expires. The action is passed the number of expiries,
+    /// which is more than one if some were missed.
+    pub fn new(action: impl Fn(u64) + Send + Sync + 'static) -> Self {
+        Self {
+            state: Arc::new(SpinLock::new(KTimerState {
+                generation: 0,
+                expires: None,
+                interval: Duration::ZERO,
+                overrun: 0,
+                action: Arc::new(action),
+            })),
+        }
+    }
+
+    /// Arms the timer to expire after `value`, and then every `interval` if
+    /// that is non-zero. A zero `value` disarms the timer. Returns the time
+    /// remaining and interval of the previous setting.
+    pub fn set(&self, value: Duration, interval: Duration) -> (Duration, Duration) {
+        let mut state = self.state.lock_save_irq();
+        let old = Self::remaining(&state);
+
+        state.generation = state.generation.wrapping_add(1);
+        state.overrun = 0;
+        state.interval = if interval.is_zero() {
+            Duration::ZERO
+        } else {
+            interval.max(MIN_INTERVAL)
+        };
+        state.expires = None;
+
+        if !value.is_zero()
+            && let Some(now) = now()
+        {
+            let when = now + value;
+
+            state.expires = Some(when);
+            Self::schedule(&self.state, state.generation, when);
+        }
+
+        old
+    }
+
+    /// Returns the time until the timer next expires, or zero if it's
+    /// disarmed, along with its interval.
+    pub fn get(&self) -> (Duration, Duration) {
+        Self::remaining(&self.state.lock_save_irq())
+    }
+
+    /// Returns the number of expiries missed before the most recent one was
+    /// handled.
+    pub fn overrun(&self) -> u32 {
+        self.state.lock_save_irq().overrun
+    }
+
+    fn remaining(state: &KTimerState) -> (Duration, Duration) {
+        let remaining = match (state.expires, now()) {
+            // Report an expiry which is due but yet to be handled as imminent,
+            // rather than disarmed.
+            (Some(when), Some(now)) => (when - now).max(Duration::from_nanos(1)),
+            _ => Duration::ZERO,
+        };
+
+        (remaining, state.interval)
+    }
+
+    fn schedule(state: &Arc<SpinLock<KTimerState>>, generation: u64, when: Instant) {
+        let state = Arc::downgrade(state);
+
+        schedule_callback(when, move || Self::expire(&state, generation));
+    }
+
+    fn expire(state: &Weak<SpinLock<KTimerState>>, generation: u64) {
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+
+        let (action, expiries) = {
+            let mut s = state.lock_save_irq();
+
+            if s.generation != generation {
+                return;
+            }
+
+            let Some(expires) = s.expires else {
+                return;
+            };
+
+            let mut expiries = 1;
+
+            if s.interval.is_zero() {
+                s.expires = None;
+            } else {
+                let now = now().expect("Timer expired without a system timer");
+                let interval = s.interval.as_nanos();
+                let missed = (now - expires).as_nanos() / interval;
+                let next = expires + Duration::from_nanos(((missed + 1) * interval) as u64);
+
+                s.overrun = missed.min(i32::MAX as u128) as u32;
+                expiries += missed as u64;
+                s.expires = Some(next);
+                Self::schedule(&state, generation, next);
+            }
+
+            (s.action.clone(), expiries)
+        };
+
+        action(expiries);
+    }
+}
+
+impl Drop for KTimer {
+    fn drop(&mut self) {
+        // Queued expiry events only hold a weak reference, so will find the
+        // timer gone. Disarm it anyway, in case one is being handled now.
+        self.state.lock_save_irq().expires = None;
+    }
+}
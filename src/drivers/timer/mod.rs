@@ -5,7 +5,7 @@ use core::{
     time::Duration,
 };
 
-use alloc::{collections::binary_heap::BinaryHeap, sync::Arc};
+use alloc::{boxed::Box, collections::binary_heap::BinaryHeap, sync::Arc, vec::Vec};
 
 use crate::{
     interrupts::{InterruptDescriptor, InterruptHandler},
@@ -16,6 +16,7 @@ use super::Driver;
 
 #[cfg(any(feature = "arch-aarch64", target_arch = "aarch64"))]
 pub mod armv8_arch;
+pub mod ktimer;
 
 /// Represents a fixed point in monotonic time.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,6 +25,18 @@ pub struct Instant {
     freq: u64,
 }
 
+impl Instant {
+    /// Returns the raw counter value at this instant.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns the frequency, in Hz, of the counter.
+    pub fn freq(&self) -> u64 {
+        self.freq
+    }
+}
+
 impl Ord for Instant {
     fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.ticks.cmp(&other.ticks)
@@ -34,6 +47,10 @@ enum WakeupKind {
     ///  This scheduled wake up is for an async task.
     Task(Waker),
 
+    /// This wake up runs a callback, from interrupt context, with the wakeup
+    /// queue unlocked.
+    Callback(Box<dyn FnOnce() + Send>),
+
     /// This wake up is for the kernel's preemption mechanism.
     _Preempt,
 }
@@ -125,23 +142,36 @@ impl Driver for SysTimer {
 
 impl InterruptHandler for SysTimer {
     fn handle_irq(&self, _desc: InterruptDescriptor) {
-        let mut wake_q = self.wakeup_q.lock_save_irq();
+        let mut callbacks = Vec::new();
 
-        while let Some(next_event) = wake_q.peek() {
-            if next_event.when <= self.driver.now() {
-                let event = wake_q.pop().unwrap(); // We know it's there from peek()
+        {
+            let mut wake_q = self.wakeup_q.lock_save_irq();
 
-                match event.what {
-                    WakeupKind::Task(waker) => waker.wake(),
-                    WakeupKind::_Preempt => todo!(),
+            while let Some(next_event) = wake_q.peek() {
+                if next_event.when <= self.driver.now() {
+                    let event = wake_q.pop().unwrap(); // We know it's there from peek()
+
+                    match event.what {
+                        WakeupKind::Task(waker) => waker.wake(),
+                        WakeupKind::Callback(f) => callbacks.push(f),
+                        WakeupKind::_Preempt => todo!(),
+                    }
+                } else {
+                    // The next event is in the future, so we're done.
+                    break;
                 }
-            } else {
-                // The next event is in the future, so we're done.
-                break;
             }
         }
 
+        // Callbacks may schedule further wakeups, so are run without the queue
+        // locked.
+        for f in callbacks {
+            f();
+        }
+
         // Reschedule based on the new head of the queue.
+        let wake_q = self.wakeup_q.lock_save_irq();
+
         self.driver
             .schedule_interrupt(wake_q.peek().map(|e| e.when));
     }
@@ -152,6 +182,8 @@ impl SysTimer {
         self.driver.now() - self.start_time
     }
 
+    // riscv64 has no timer driver yet.
+    #[cfg_attr(target_arch = "riscv64", allow(dead_code))]
     fn from_driver(driver: Arc<dyn HwTimer>) -> Self {
         Self {
             start_time: driver.now(),
@@ -160,6 +192,21 @@ impl SysTimer {
         }
     }
 
+    /// Schedules `f` to be run from the timer interrupt at, or shortly after,
+    /// `when`.
+    pub fn schedule_callback(&self, when: Instant, f: Box<dyn FnOnce() + Send>) {
+        let mut wake_q = self.wakeup_q.lock_save_irq();
+
+        wake_q.push(WakeupEvent {
+            when,
+            what: WakeupKind::Callback(f),
+        });
+
+        if let Some(next_event) = wake_q.peek() {
+            self.driver.schedule_interrupt(Some(next_event.when));
+        }
+    }
+
     pub async fn sleep(&self, duration: Duration) -> () {
         let when = self.driver.now() + duration;
 
@@ -201,6 +248,24 @@ pub fn now() -> Option<Instant> {
     SYS_TIMER.get().map(|timer| timer.driver.now())
 }
 
+/// Returns the instant at which the system timer was started, from which the
+/// monotonic clock counts.
+pub fn start_time() -> Option<Instant> {
+    SYS_TIMER.get().map(|timer| timer.start_time)
+}
+
+/// Schedules `f` to be run from the timer interrupt at `when`. Returns `false`,
+/// without scheduling it, if the system timer has not been initialised.
+pub fn schedule_callback(when: Instant, f: impl FnOnce() + Send + 'static) -> bool {
+    match SYS_TIMER.get() {
+        Some(timer) => {
+            timer.schedule_callback(when, Box::new(f));
+            true
+        }
+        None => false,
+    }
+}
+
 /// Puts the current task to sleep for `duration`. If no timer driver has yet
 /// been loaded, the funtion returns without sleeping.
 pub async fn sleep(duration: Duration) {
@@ -1,3 +1,7 @@
+// `register_structs!` asserts that each register is aligned, which is trivially
+// true of these byte-wide ones.
+#![allow(clippy::modulo_one)]
+
 use crate::{
     arch::ArchImpl,
     drivers::{
@@ -7,7 +11,7 @@ use crate::{
         uart::{UART_CHAR_DEV, Uart, UartDriver},
     },
 };
-use alloc::{boxed::Box, sync::Arc};
+use alloc::boxed::Box;
 use core::{fmt, hint::spin_loop};
 use libkernel::{
     KernAddressSpace, VirtualMemory,
@@ -19,7 +23,7 @@ use libkernel::{
 };
 use tock_registers::{
     register_bitfields, register_structs,
-    interfaces::{Readable, Writeable, ReadWriteable},
+    interfaces::{Readable, Writeable},
     registers::{ReadOnly, ReadWrite},
 };
 
@@ -182,8 +186,6 @@ pub fn ns16550_init(bus: &mut PlatformBus, _dm: &mut DriverManager) -> Result<()
             // DeviceDescriptor::Fdt 包含 (fdt_parser::Node, FdtFlags)
             let (fdt_node, flags) = match desc {
                 DeviceDescriptor::Fdt(node, flags) => (node, flags),
-                // 如果不是 FDT 设备，返回错误
-                _ => return Err(libkernel::error::KernelError::Probe(ProbeError::NoReg)), 
             };
             
             // 1. 获取寄存器基地址和大小
@@ -0,0 +1,121 @@
+use super::{fops::FileOps, open_file::FileCtx};
+use crate::memory::uaccess::{copy_from_user_slice, copy_to_user, copy_to_user_slice};
+use alloc::{boxed::Box, sync::Arc, vec};
+use async_trait::async_trait;
+use core::cmp::min;
+use libkernel::{
+    error::{FsError, KernelError, Result},
+    fs::{BlockDevice, SeekFrom},
+    memory::address::{TUA, UA},
+};
+
+/// Returns the size of the device in bytes, as a `u64`.
+const BLKGETSIZE64: usize = 0x80081272;
+
+/// An open block device. Its contents are accessed directly through the
+/// device, one block at a time.
+pub struct BlkFile {
+    dev: Arc<dyn BlockDevice>,
+}
+
+impl BlkFile {
+    pub fn new(dev: Arc<dyn BlockDevice>) -> Self {
+        Self { dev }
+    }
+
+    fn size(&self) -> u64 {
+        self.dev.num_blocks() * self.dev.block_size() as u64
+    }
+}
+
+#[async_trait]
+impl FileOps for BlkFile {
+    /// Reads data from the current file position into `buf`. The file's cursor
+    /// is advanced by the number of bytes read.
+    async fn read(&mut self, ctx: &mut FileCtx, mut buf: UA, count: usize) -> Result<usize> {
+        let bs = self.dev.block_size();
+        let count = min(count as u64, self.size().saturating_sub(ctx.pos)) as usize;
+        let mut kbuf = vec![0; bs];
+        let mut total_bytes_read = 0;
+
+        while total_bytes_read < count {
+            let offset = ctx.pos as usize % bs;
+            let chunk_sz = min(bs - offset, count - total_bytes_read);
+
+            self.dev.read(ctx.pos / bs as u64, &mut kbuf).await?;
+            copy_to_user_slice(&kbuf[offset..offset + chunk_sz], buf).await?;
+
+            ctx.pos += chunk_sz as u64;
+            total_bytes_read += chunk_sz;
+            buf = buf.add_bytes(chunk_sz);
+        }
+
+        Ok(total_bytes_read)
+    }
+
+    /// Writes data from `buf` to the current file position. The file's cursor
+    /// is advanced by the number of bytes written.
+    ///
+    /// Writes are truncated at the end of the device; a write starting at or
+    /// beyond it fails.
+    async fn write(&mut self, ctx: &mut FileCtx, mut buf: UA, count: usize) -> Result<usize> {
+        if count > 0 && ctx.pos >= self.size() {
+            return Err(FsError::FileTooLarge.into());
+        }
+
+        let bs = self.dev.block_size();
+        let count = min(count as u64, self.size() - ctx.pos) as usize;
+        let mut kbuf = vec![0; bs];
+        let mut total_bytes_written = 0;
+
+        while total_bytes_written < count {
+            let block = ctx.pos / bs as u64;
+            let offset = ctx.pos as usize % bs;
+            let chunk_sz = min(bs - offset, count - total_bytes_written);
+
+            // Only part of the block is being written, so the rest of it must
+            // be preserved.
+            if chunk_sz != bs {
+                self.dev.read(block, &mut kbuf).await?;
+            }
+
+            copy_from_user_slice(buf, &mut kbuf[offset..offset + chunk_sz]).await?;
+            self.dev.write(block, &kbuf).await?;
+
+            ctx.pos += chunk_sz as u64;
+            total_bytes_written += chunk_sz;
+            buf = buf.add_bytes(chunk_sz);
+        }
+
+        Ok(total_bytes_written)
+    }
+
+    /// Moves the file's cursor to a new position.
+    /// Returns the new position from the start of the file.
+    async fn seek(&mut self, ctx: &mut FileCtx, pos: SeekFrom) -> Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(x) => Some(x),
+            SeekFrom::End(x) => self.size().checked_add_signed(x),
+            SeekFrom::Current(x) => ctx.pos.checked_add_signed(x),
+        };
+
+        ctx.pos = new_pos.ok_or(KernelError::InvalidValue)?;
+
+        Ok(ctx.pos)
+    }
+
+    async fn ioctl(&mut self, _ctx: &mut FileCtx, request: usize, argp: usize) -> Result<usize> {
+        match request {
+            BLKGETSIZE64 => {
+                copy_to_user(TUA::from_value(argp), self.size()).await?;
+
+                Ok(0)
+            }
+            _ => Err(KernelError::NotATty),
+        }
+    }
+
+    async fn flush(&self, _ctx: &FileCtx) -> Result<()> {
+        self.dev.sync().await
+    }
+}
@@ -0,0 +1,580 @@
+//! epoll: I/O event notification for large sets of files.
+//!
+//! Each file registered with an epoll instance has a readiness future, from
+//! [`OpenFile::poll`], which is polled with a waker belonging to that
+//! registration. When the file wakes it, the registration is queued on the
+//! instance's ready list. Waiting only re-polls the queued registrations,
+//! rather than every file of interest.
+
+use super::{
+    fops::{FileOps, PollFuture},
+    open_file::{FileCtx, OpenFile},
+};
+use crate::{
+    drivers::timer::sleep,
+    memory::uaccess::{UserCopyable, copy_from_user, copy_objs_to_user},
+    process::{
+        fd_table::{
+            Fd, FdFlags,
+            select::{PollFlags, copy_sigmask},
+        },
+        thread_group::signal::{SigSet, sigprocmask::replace_sigmask},
+    },
+    sched::current_task,
+    sync::{CondVar, SpinLock},
+};
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    sync::{Arc, Weak},
+    task::Wake,
+    vec::Vec,
+};
+use async_trait::async_trait;
+use core::{
+    any::Any,
+    future::poll_fn,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use libkernel::{
+    error::{FsError, KernelError, Result},
+    fs::OpenFlags,
+    memory::address::{TUA, UA},
+    sync::condvar::WakeupType,
+};
+
+const EPOLL_CLOEXEC: u32 = OpenFlags::O_CLOEXEC.bits();
+
+const EPOLL_CTL_ADD: i32 = 1;
+const EPOLL_CTL_DEL: i32 = 2;
+const EPOLL_CTL_MOD: i32 = 3;
+
+/// The most events which may be returned by a single wait.
+const EP_MAX_EVENTS: usize = i32::MAX as usize / size_of::<EpollEvent>();
+
+bitflags::bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct EpollEvents: u32 {
+        const EPOLLIN        = 0x001;
+        const EPOLLPRI       = 0x002;
+        const EPOLLOUT       = 0x004;
+        const EPOLLERR       = 0x008;
+        const EPOLLHUP       = 0x010;
+        const EPOLLRDNORM    = 0x040;
+        const EPOLLRDBAND    = 0x080;
+        const EPOLLWRNORM    = 0x100;
+        const EPOLLWRBAND    = 0x200;
+        const EPOLLMSG       = 0x400;
+        const EPOLLRDHUP     = 0x2000;
+        const EPOLLEXCLUSIVE = 1 << 28;
+        const EPOLLWAKEUP    = 1 << 29;
+        const EPOLLONESHOT   = 1 << 30;
+        const EPOLLET        = 1 << 31;
+    }
+}
+
+impl EpollEvents {
+    /// The events which may be combined with `EPOLLEXCLUSIVE`.
+    const EXCLUSIVE_OK: Self = Self::EPOLLIN
+        .union(Self::EPOLLOUT)
+        .union(Self::EPOLLERR)
+        .union(Self::EPOLLHUP)
+        .union(Self::EPOLLWAKEUP)
+        .union(Self::EPOLLET)
+        .union(Self::EPOLLEXCLUSIVE);
+
+    /// Events which are always reported, whether or not they were requested.
+    const ALWAYS: Self = Self::EPOLLERR.union(Self::EPOLLHUP);
+
+    fn poll_flags(self) -> PollFlags {
+        PollFlags::from_bits_truncate(self.bits() as u16 as i16)
+    }
+}
+
+impl From<PollFlags> for EpollEvents {
+    fn from(value: PollFlags) -> Self {
+        Self::from_bits_truncate(value.bits() as u16 as u32)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+unsafe impl UserCopyable for EpollEvent {}
+
+struct ItemState {
+    events: EpollEvents,
+    data: u64,
+    /// The file's readiness future, created when the registration is next
+    /// polled.
+    fut: Option<PollFuture>,
+    /// Set once a one-shot registration has reported an event, until it's
+    /// re-armed with `EPOLL_CTL_MOD`.
+    disabled: bool,
+}
+
+/// A file registered with an epoll instance.
+struct EpollItem {
+    file: Weak<OpenFile>,
+    /// The epoll instance the file is, if it is one.
+    nested: Option<Weak<Epoll>>,
+    waker: Waker,
+    state: SpinLock<ItemState>,
+}
+
+impl EpollItem {
+    /// Polls the file for the events of interest, returning the event to
+    /// report if it's ready.
+    async fn poll(&self, file: &OpenFile) -> Option<EpollEvent> {
+        let events = {
+            let state = self.state.lock_save_irq();
+
+            if state.disabled {
+                return None;
+            }
+
+            if state.fut.is_some() {
+                None
+            } else {
+                Some(state.events)
+            }
+        };
+
+        if let Some(events) = events {
+            let fut = Box::pin(file.poll(events.poll_flags()).await);
+
+            self.state.lock_save_irq().fut.get_or_insert(fut);
+        }
+
+        let mut state = self.state.lock_save_irq();
+        let fut = state.fut.as_mut()?;
+
+        let revents = match fut.as_mut().poll(&mut Context::from_waker(&self.waker)) {
+            Poll::Pending => return None,
+            Poll::Ready(flags) => EpollEvents::from(flags),
+        };
+
+        state.fut = None;
+
+        let revents = revents & (state.events | EpollEvents::ALWAYS);
+
+        if state.events.contains(EpollEvents::EPOLLONESHOT) {
+            state.disabled = true;
+        }
+
+        Some(EpollEvent {
+            events: revents.bits(),
+            data: state.data,
+        })
+    }
+}
+
+/// The waker for a registration, which queues it on its instance's ready list.
+struct ItemWaker {
+    epoll: Weak<Epoll>,
+    fd: Fd,
+    exclusive: bool,
+}
+
+impl Wake for ItemWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if let Some(epoll) = self.epoll.upgrade() {
+            epoll.ready.update(|ready| {
+                ready.insert(self.fd);
+
+                // Exclusive registrations wake a single waiter.
+                if self.exclusive {
+                    WakeupType::One
+                } else {
+                    WakeupType::All
+                }
+            });
+        }
+    }
+}
+
+pub struct Epoll {
+    items: SpinLock<BTreeMap<Fd, Arc<EpollItem>>>,
+    /// Registrations which may have become ready since they were last polled.
+    ready: CondVar<BTreeSet<Fd>>,
+}
+
+impl Epoll {
+    fn new() -> Self {
+        Self {
+            items: SpinLock::new(BTreeMap::new()),
+            ready: CondVar::new(BTreeSet::new()),
+        }
+    }
+
+    /// Queues `fds` to be polled by the next wait, without waking any waiters.
+    fn requeue(&self, fds: impl IntoIterator<Item = Fd>) {
+        self.ready.update(|ready| {
+            ready.extend(fds);
+            WakeupType::None
+        });
+    }
+
+    /// Returns whether `target` is reachable from this instance, following
+    /// nested epoll instances.
+    fn reaches(&self, target: &Epoll) -> bool {
+        if core::ptr::eq(self, target) {
+            return true;
+        }
+
+        let nested: Vec<_> = self
+            .items
+            .lock_save_irq()
+            .values()
+            .filter_map(|item| item.nested.as_ref().and_then(Weak::upgrade))
+            .collect();
+
+        nested.iter().any(|epoll| epoll.reaches(target))
+    }
+
+    fn add(
+        self: &Arc<Self>,
+        fd: Fd,
+        file: &Arc<OpenFile>,
+        nested: Option<Arc<Epoll>>,
+        event: EpollEvent,
+    ) -> Result<()> {
+        let events = EpollEvents::from_bits_retain(event.events);
+        let exclusive = events.contains(EpollEvents::EPOLLEXCLUSIVE);
+
+        if exclusive && !EpollEvents::EXCLUSIVE_OK.contains(events) {
+            return Err(KernelError::InvalidValue);
+        }
+
+        if let Some(nested) = &nested
+            && nested.reaches(self)
+        {
+            return Err(KernelError::Loop);
+        }
+
+        let mut items = self.items.lock_save_irq();
+
+        // An entry for a file which has since been closed is stale, and may
+        // be replaced.
+        if let Some(item) = items.get(&fd)
+            && item.file.strong_count() > 0
+        {
+            return Err(FsError::AlreadyExists.into());
+        }
+
+        let waker = Arc::new(ItemWaker {
+            epoll: Arc::downgrade(self),
+            fd,
+            exclusive,
+        });
+
+        items.insert(
+            fd,
+            Arc::new(EpollItem {
+                file: Arc::downgrade(file),
+                nested: nested.as_ref().map(Arc::downgrade),
+                waker: Waker::from(waker),
+                state: SpinLock::new(ItemState {
+                    events,
+                    data: event.data,
+                    fut: None,
+                    disabled: false,
+                }),
+            }),
+        );
+
+        drop(items);
+
+        // Poll the file on the next wait, to find its initial state.
+        self.requeue([fd]);
+
+        Ok(())
+    }
+
+    /// Finds the registration of `file` at `fd`.
+    fn find(&self, fd: Fd, file: &Arc<OpenFile>) -> Result<Arc<EpollItem>> {
+        self.items
+            .lock_save_irq()
+            .get(&fd)
+            .filter(|item| core::ptr::eq(item.file.as_ptr(), Arc::as_ptr(file)))
+            .cloned()
+            .ok_or(FsError::NotFound.into())
+    }
+
+    fn modify(&self, fd: Fd, file: &Arc<OpenFile>, event: EpollEvent) -> Result<()> {
+        let item = self.find(fd, file)?;
+        let events = EpollEvents::from_bits_retain(event.events);
+
+        {
+            let mut state = item.state.lock_save_irq();
+
+            // Exclusive registrations can't be modified.
+            if events.contains(EpollEvents::EPOLLEXCLUSIVE)
+                || state.events.contains(EpollEvents::EPOLLEXCLUSIVE)
+            {
+                return Err(KernelError::InvalidValue);
+            }
+
+            state.events = events;
+            state.data = event.data;
+            state.fut = None;
+            state.disabled = false;
+        }
+
+        self.requeue([fd]);
+
+        Ok(())
+    }
+
+    fn delete(&self, fd: Fd, file: &Arc<OpenFile>) -> Result<()> {
+        self.find(fd, file)?;
+        self.items.lock_save_irq().remove(&fd);
+
+        Ok(())
+    }
+
+    /// Polls the queued registrations, returning up to `max` events.
+    async fn collect(&self, max: usize) -> Vec<EpollEvent> {
+        let mut queued = BTreeSet::new();
+        self.ready.update(|ready| {
+            queued = mem::take(ready);
+            WakeupType::None
+        });
+
+        let mut events = Vec::new();
+        let mut queued = queued.into_iter();
+
+        while events.len() < max
+            && let Some(fd) = queued.next()
+        {
+            let Some(item) = self.items.lock_save_irq().get(&fd).cloned() else {
+                continue;
+            };
+
+            let Some(file) = item.file.upgrade() else {
+                // The file has been closed, so its registration goes with it.
+                let mut items = self.items.lock_save_irq();
+
+                if items.get(&fd).is_some_and(|i| Arc::ptr_eq(i, &item)) {
+                    items.remove(&fd);
+                }
+
+                continue;
+            };
+
+            if let Some(event) = item.poll(&file).await {
+                let flags = item.state.lock_save_irq().events;
+
+                if flags.contains(EpollEvents::EPOLLONESHOT) {
+                    // Disabled until re-armed.
+                } else if flags.contains(EpollEvents::EPOLLET) {
+                    // A readiness future completes while the file is ready,
+                    // rather than when it becomes ready, so it can't be used
+                    // to wait for the next edge until the caller has consumed
+                    // what's available. Wait for the caller's next operation
+                    // on the file, then poll it again.
+                    file.watch(&item.waker);
+                } else {
+                    // Level-triggered registrations are reported for as long
+                    // as the file remains ready.
+                    self.requeue([fd]);
+                }
+
+                events.push(event);
+            }
+        }
+
+        // Leave anything we didn't get to for the next wait.
+        self.requeue(queued);
+
+        events
+    }
+
+    async fn wait(&self, max: usize, timeout: Option<Duration>) -> Vec<EpollEvent> {
+        let nonblocking = timeout.is_some_and(|t| t.is_zero());
+        let mut timeout = timeout.map(|t| Box::pin(sleep(t)));
+
+        loop {
+            let events = self.collect(max).await;
+
+            if !events.is_empty() || nonblocking {
+                return events;
+            }
+
+            let mut ready = Box::pin(
+                self.ready
+                    .wait_until(|ready| if ready.is_empty() { None } else { Some(()) }),
+            );
+
+            let timed_out = poll_fn(|cx| {
+                if ready.as_mut().poll(cx).is_ready() {
+                    Poll::Ready(false)
+                } else if let Some(timeout) = timeout.as_mut()
+                    && timeout.as_mut().poll(cx).is_ready()
+                {
+                    Poll::Ready(true)
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+
+            if timed_out {
+                return Vec::new();
+            }
+        }
+    }
+}
+
+/// The open file of an epoll instance.
+struct EpollFile {
+    epoll: Arc<Epoll>,
+}
+
+#[async_trait]
+impl FileOps for EpollFile {
+    async fn read(&mut self, _ctx: &mut FileCtx, _buf: UA, _count: usize) -> Result<usize> {
+        Err(KernelError::InvalidValue)
+    }
+
+    async fn write(&mut self, _ctx: &mut FileCtx, _buf: UA, _count: usize) -> Result<usize> {
+        Err(KernelError::InvalidValue)
+    }
+
+    fn poll_read_ready(&self) -> Pin<Box<dyn Future<Output = Result<()>> + 'static + Send>> {
+        // An epoll instance is readable when it has registrations queued, which
+        // may be ready.
+        let ready = self
+            .epoll
+            .ready
+            .wait_until(|ready| if ready.is_empty() { None } else { Some(()) });
+
+        Box::pin(async move {
+            ready.await;
+            Ok(())
+        })
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Returns the epoll instance behind `file`, if it's one.
+async fn as_epoll(file: &OpenFile) -> Option<Arc<Epoll>> {
+    let (ops, _) = &*file.lock().await;
+
+    ops.as_any()?
+        .downcast_ref::<EpollFile>()
+        .map(|f| f.epoll.clone())
+}
+
+pub fn sys_epoll_create1(flags: u32) -> Result<usize> {
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let file = OpenFile::new(
+        Box::new(EpollFile {
+            epoll: Arc::new(Epoll::new()),
+        }),
+        OpenFlags::O_RDWR,
+    );
+
+    let fd_flags = if flags & EPOLL_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let fd = current_task()
+        .fd_table
+        .lock_save_irq()
+        .insert_with_flags(Arc::new(file), fd_flags)?;
+
+    Ok(fd.as_raw() as _)
+}
+
+pub async fn sys_epoll_ctl(epfd: Fd, op: i32, fd: Fd, event: TUA<EpollEvent>) -> Result<usize> {
+    let (ep_file, file) = {
+        let task = current_task();
+        let fds = task.fd_table.lock_save_irq();
+
+        (
+            fds.get(epfd).ok_or(KernelError::BadFd)?,
+            fds.get(fd).ok_or(KernelError::BadFd)?,
+        )
+    };
+
+    let epoll = as_epoll(&ep_file).await.ok_or(KernelError::InvalidValue)?;
+
+    if Arc::ptr_eq(&ep_file, &file) {
+        return Err(KernelError::InvalidValue);
+    }
+
+    match op {
+        EPOLL_CTL_ADD => {
+            let event = copy_from_user(event).await?;
+            let nested = as_epoll(&file).await;
+
+            epoll.add(fd, &file, nested, event)?;
+        }
+        EPOLL_CTL_MOD => {
+            let event = copy_from_user(event).await?;
+
+            epoll.modify(fd, &file, event)?;
+        }
+        EPOLL_CTL_DEL => epoll.delete(fd, &file)?,
+        _ => return Err(KernelError::InvalidValue),
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_epoll_pwait(
+    epfd: Fd,
+    events: TUA<EpollEvent>,
+    maxevents: i32,
+    timeout: i32,
+    sigmask: TUA<SigSet>,
+    sigsetsize: usize,
+) -> Result<usize> {
+    if maxevents <= 0 || maxevents as usize > EP_MAX_EVENTS {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let ep_file = current_task()
+        .fd_table
+        .lock_save_irq()
+        .get(epfd)
+        .ok_or(KernelError::BadFd)?;
+
+    let epoll = as_epoll(&ep_file).await.ok_or(KernelError::InvalidValue)?;
+
+    // A negative timeout waits indefinitely.
+    let timeout = (timeout >= 0).then(|| Duration::from_millis(timeout as u64));
+
+    let sigmask = copy_sigmask(sigmask, sigsetsize).await?;
+    let old_sigmask = sigmask.map(replace_sigmask);
+
+    let ready = epoll.wait(maxevents as usize, timeout).await;
+
+    if let Some(old_sigmask) = old_sigmask {
+        replace_sigmask(old_sigmask);
+    }
+
+    copy_objs_to_user(&ready, events).await?;
+
+    Ok(ready.len())
+}
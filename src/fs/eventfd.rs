@@ -0,0 +1,166 @@
+//! eventfd: a file which holds a counter, for event notification between
+//! tasks.
+
+use super::{
+    fops::{FileOps, PollFuture, poll_sources},
+    open_file::{FileCtx, OpenFile},
+};
+use crate::{
+    memory::uaccess::{copy_from_user, copy_to_user},
+    process::fd_table::{FdFlags, select::PollFlags},
+    sched::current_task,
+    sync::CondVar,
+};
+use alloc::{boxed::Box, sync::Arc, vec};
+use async_trait::async_trait;
+use libkernel::{
+    error::{KernelError, Result},
+    fs::OpenFlags,
+    memory::address::UA,
+    sync::condvar::WakeupType,
+};
+
+const EFD_SEMAPHORE: u32 = 1;
+const EFD_CLOEXEC: u32 = OpenFlags::O_CLOEXEC.bits();
+const EFD_NONBLOCK: u32 = OpenFlags::O_NONBLOCK.bits();
+
+/// The largest value the counter may hold.
+const MAX_COUNT: u64 = u64::MAX - 1;
+
+struct EventFd {
+    count: CondVar<u64>,
+    semaphore: bool,
+}
+
+impl EventFd {
+    /// Takes the value to be read from a non-zero counter.
+    fn take(&self, count: &mut u64) -> Option<u64> {
+        if *count == 0 {
+            None
+        } else if self.semaphore {
+            *count -= 1;
+            Some(1)
+        } else {
+            Some(core::mem::take(count))
+        }
+    }
+}
+
+#[async_trait]
+impl FileOps for EventFd {
+    async fn read(&mut self, ctx: &mut FileCtx, buf: UA, count: usize) -> Result<usize> {
+        if count < size_of::<u64>() {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let value = if ctx.flags.contains(OpenFlags::O_NONBLOCK) {
+            let mut value = None;
+
+            self.count.update(|count| {
+                value = self.take(count);
+                WakeupType::None
+            });
+
+            value.ok_or(KernelError::TryAgain)?
+        } else {
+            self.count.wait_until(|count| self.take(count)).await
+        };
+
+        // Taking from the counter makes room for any blocked writers.
+        self.count.update(|_| WakeupType::All);
+
+        copy_to_user(buf.cast(), value).await?;
+
+        Ok(size_of::<u64>())
+    }
+
+    async fn write(&mut self, ctx: &mut FileCtx, buf: UA, count: usize) -> Result<usize> {
+        if count < size_of::<u64>() {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let value: u64 = copy_from_user(buf.cast()).await?;
+
+        if value == u64::MAX {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let add = |count: &mut u64| {
+            if *count <= MAX_COUNT - value {
+                *count += value;
+                Some(())
+            } else {
+                None
+            }
+        };
+
+        if ctx.flags.contains(OpenFlags::O_NONBLOCK) {
+            let mut added = None;
+
+            self.count.update(|count| {
+                added = add(count);
+                WakeupType::None
+            });
+
+            added.ok_or(KernelError::TryAgain)?;
+        } else {
+            self.count.wait_until(add).await;
+        }
+
+        if value != 0 {
+            self.count.update(|_| WakeupType::All);
+        }
+
+        Ok(size_of::<u64>())
+    }
+
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        let readable = self
+            .count
+            .wait_until(|count| if *count > 0 { Some(()) } else { None });
+        let writable = self
+            .count
+            .wait_until(|count| if *count < MAX_COUNT { Some(()) } else { None });
+
+        poll_sources(
+            events,
+            vec![
+                Box::pin(async move {
+                    readable.await;
+                    PollFlags::POLLIN | PollFlags::POLLRDNORM
+                }),
+                Box::pin(async move {
+                    writable.await;
+                    PollFlags::POLLOUT | PollFlags::POLLWRNORM
+                }),
+            ],
+        )
+    }
+}
+
+pub fn sys_eventfd2(initval: u32, flags: u32) -> Result<usize> {
+    if flags & !(EFD_SEMAPHORE | EFD_CLOEXEC | EFD_NONBLOCK) != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let file = OpenFile::new(
+        Box::new(EventFd {
+            count: CondVar::new(initval as u64),
+            semaphore: flags & EFD_SEMAPHORE != 0,
+        }),
+        OpenFlags::O_RDWR | OpenFlags::from_bits_retain(flags & EFD_NONBLOCK),
+    );
+
+    let fd_flags = if flags & EFD_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let fd = current_task()
+        .fd_table
+        .lock_save_irq()
+        .insert_with_flags(Arc::new(file), fd_flags)?;
+
+    Ok(fd.as_raw() as _)
+}
@@ -1,6 +1,6 @@
-use core::pin::Pin;
+use core::{any::Any, future, pin::Pin, task::Poll};
 
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec::Vec};
 use async_trait::async_trait;
 use libkernel::{
     error::{FsError, KernelError, Result},
@@ -8,7 +8,7 @@ use libkernel::{
     memory::address::UA,
 };
 
-use crate::kernel::kpipe::KPipe;
+use crate::{kernel::kpipe::KPipe, process::fd_table::select::PollFlags};
 
 use super::{dir::OpenFileDirIter, open_file::FileCtx, syscalls::iov::IoVec};
 
@@ -38,6 +38,38 @@ macro_rules! process_iovec {
     };
 }
 
+/// A future which resolves with the events which are ready on a file.
+pub type PollFuture = Pin<Box<dyn Future<Output = PollFlags> + 'static + Send>>;
+
+/// Combines `sources`, each of which resolves with events it has found to be
+/// ready, into a [`FileOps::poll`] future for `events`. It resolves once any
+/// event of interest, or an error or hangup, is ready, with all such events
+/// found by then.
+pub fn poll_sources(events: PollFlags, sources: Vec<PollFuture>) -> PollFuture {
+    let interest = events | PollFlags::POLLERR | PollFlags::POLLHUP;
+    let mut sources: Vec<_> = sources.into_iter().map(Some).collect();
+    let mut found = PollFlags::empty();
+
+    Box::pin(future::poll_fn(move |cx| {
+        for source in sources.iter_mut() {
+            if let Some(fut) = source
+                && let Poll::Ready(flags) = fut.as_mut().poll(cx)
+            {
+                found |= flags;
+                *source = None;
+            }
+        }
+
+        let ready = found & interest;
+
+        if ready.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(ready)
+        }
+    }))
+}
+
 #[async_trait]
 pub trait FileOps: Send + Sync {
     /// Reads data from the current file position into `buf`.
@@ -72,12 +104,56 @@ pub trait FileOps: Send + Sync {
         Box::pin(async { Err(KernelError::NotSupported) })
     }
 
+    /// Puts the current task to sleep until any of `events` is ready, or the
+    /// file has an error or hangup, which are reported whether requested or
+    /// not. Resolves with the events which are ready.
+    ///
+    /// By default, readiness comes from `poll_read_ready()` and
+    /// `poll_write_ready()`. A file which supports neither is always ready.
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        fn readiness(
+            fut: Pin<Box<dyn Future<Output = Result<()>> + 'static + Send>>,
+            flags: PollFlags,
+        ) -> PollFuture {
+            Box::pin(async move {
+                match fut.await {
+                    Ok(()) | Err(KernelError::NotSupported) => flags,
+                    Err(_) => PollFlags::POLLERR,
+                }
+            })
+        }
+
+        let mut sources = Vec::new();
+
+        if events.intersects(PollFlags::POLLIN | PollFlags::POLLRDNORM) {
+            sources.push(readiness(
+                self.poll_read_ready(),
+                PollFlags::POLLIN | PollFlags::POLLRDNORM,
+            ));
+        }
+
+        if events.intersects(PollFlags::POLLOUT | PollFlags::POLLWRNORM) {
+            sources.push(readiness(
+                self.poll_write_ready(),
+                PollFlags::POLLOUT | PollFlags::POLLWRNORM,
+            ));
+        }
+
+        poll_sources(events, sources)
+    }
+
     /// Moves the file's cursor to a new position.
     /// Returns the new position from the start of the file.
     async fn seek(&mut self, _ctx: &mut FileCtx, _pos: SeekFrom) -> Result<u64> {
         Err(KernelError::NotSupported)
     }
 
+    /// Returns the file as `Any`, so that syscalls which operate on a special
+    /// kind of file, such as an epoll instance, can recover its concrete type.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
     /// Performs a device-specific control operation.
     async fn ioctl(&mut self, _ctx: &mut FileCtx, _request: usize, _argp: usize) -> Result<usize> {
         // ENOTTY is the standard error for "ioctl not supported by this file type".
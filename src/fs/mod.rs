@@ -1,24 +1,32 @@
 use alloc::boxed::Box;
 use alloc::{collections::btree_map::BTreeMap, sync::Arc};
 use async_trait::async_trait;
+use blk::BlkFile;
 use core::sync::atomic::{AtomicU64, Ordering};
 use dir::DirFile;
 use libkernel::error::{FsError, KernelError, Result};
 use libkernel::fs::path::Path;
+use libkernel::fs::pathbuf::PathBuf;
 use libkernel::fs::{BlockDevice, FS_ID_START, FileType, Filesystem, Inode, InodeId, OpenFlags};
 use open_file::OpenFile;
 use reg::RegFile;
 
 use crate::drivers::{DM, Driver};
-use crate::sync::SpinLock;
+use crate::sched::current_task;
+use crate::sync::{OnceLock, SpinLock};
 use alloc::vec::Vec;
 
+pub mod blk;
 pub mod dir;
+pub mod epoll;
+pub mod eventfd;
 pub mod fops;
 pub mod open_file;
 pub mod pipe;
 pub mod reg;
+pub mod signalfd;
 pub mod syscalls;
+pub mod timerfd;
 
 /// A dummy inode used as a placeholder before the root filesystem is mounted.
 pub struct DummyInode {}
@@ -30,6 +38,7 @@ impl Inode for DummyInode {
 }
 
 /// Represents a mounted filesystem.
+#[derive(Clone)]
 struct Mount {
     fs: Arc<dyn Filesystem>,
     root_inode: Arc<dyn Inode>,
@@ -46,15 +55,11 @@ pub trait FilesystemDriver: Driver + Send + Sync {
     ) -> Result<Arc<dyn Filesystem>>;
 }
 
-/// The internal state of the VFS.
-///
-/// This struct consolidates the filesystem-wide collections (the list of all
-/// registered filesystem instances and the mapping of mount points).
+/// The mount table of a mount namespace.
+#[derive(Clone)]
 struct VfsState {
     /// A map from an InodeId of a directory to the Mount that is mounted there.
     mounts: BTreeMap<InodeId, Mount>,
-    /// A map from a filesystem ID to the corresponding filesystem instance.
-    filesystems: BTreeMap<u64, Arc<dyn Filesystem>>,
 }
 
 impl VfsState {
@@ -62,16 +67,25 @@ impl VfsState {
     const fn new() -> Self {
         Self {
             mounts: BTreeMap::new(),
-            filesystems: BTreeMap::new(),
         }
     }
 
-    /// Registers a new filesystem and its mount point.
+    /// Registers a new mount point.
     fn add_mount(&mut self, mount_point_id: InodeId, mount: Mount) {
-        self.filesystems.insert(mount.fs.id(), mount.fs.clone());
         self.mounts.insert(mount_point_id, mount);
     }
 
+    /// Removes the mount whose root inode is `root_id`, returning it.
+    fn remove_mount(&mut self, root_id: InodeId) -> Option<Mount> {
+        let mount_point_id = *self
+            .mounts
+            .iter()
+            .find(|(_, mount)| mount.root_inode.id() == root_id)?
+            .0;
+
+        self.mounts.remove(&mount_point_id)
+    }
+
     /// Checks if an inode is a mount point and returns the root inode of the
     /// mounted filesystem if it is.
     fn get_mount_root(&self, inode_id: &InodeId) -> Option<Arc<dyn Inode>> {
@@ -81,10 +95,41 @@ impl VfsState {
     }
 }
 
+/// A mount namespace: the set of mounts seen by the tasks within it.
+///
+/// A new namespace starts with a copy of its parent's mounts, after which
+/// mounts made in either are not seen by the other.
+pub struct MountNamespace {
+    state: SpinLock<VfsState>,
+}
+
+impl MountNamespace {
+    /// Returns the initial mount namespace.
+    pub fn init() -> Arc<Self> {
+        INIT_MNT_NS
+            .get_or_init(|| {
+                Arc::new(Self {
+                    state: SpinLock::new(VfsState::new()),
+                })
+            })
+            .clone()
+    }
+
+    /// Returns a new namespace, starting with this one's mounts.
+    pub fn copy(&self) -> Arc<Self> {
+        Arc::new(Self {
+            state: SpinLock::new(self.state.lock_save_irq().clone()),
+        })
+    }
+}
+
+static INIT_MNT_NS: OnceLock<Arc<MountNamespace>> = OnceLock::new();
+
 #[allow(clippy::upper_case_acronyms)]
 pub struct VFS {
     next_fs_id: AtomicU64,
-    state: SpinLock<VfsState>,
+    /// A map from a filesystem ID to the corresponding filesystem instance.
+    filesystems: SpinLock<BTreeMap<u64, Arc<dyn Filesystem>>>,
     root_inode: SpinLock<Option<Arc<dyn Inode>>>,
 }
 
@@ -92,11 +137,37 @@ impl VFS {
     const fn new() -> Self {
         Self {
             next_fs_id: AtomicU64::new(FS_ID_START),
-            state: SpinLock::new(VfsState::new()),
+            filesystems: SpinLock::new(BTreeMap::new()),
             root_inode: SpinLock::new(None),
         }
     }
 
+    /// Returns the mount namespace of the current task.
+    fn mount_ns(&self) -> Arc<MountNamespace> {
+        current_task().namespaces.lock_save_irq().mnt.clone()
+    }
+
+    /// Adds `mount` at `mount_point_id` in the current mount namespace.
+    fn add_mount(&self, mount_point_id: InodeId, mount: Mount) {
+        self.filesystems
+            .lock_save_irq()
+            .insert(mount.fs.id(), mount.fs.clone());
+
+        self.mount_ns()
+            .state
+            .lock_save_irq()
+            .add_mount(mount_point_id, mount);
+    }
+
+    /// Returns the root of the filesystem mounted on `inode` in the current
+    /// mount namespace, if any.
+    fn get_mount_root(&self, inode: &Arc<dyn Inode>) -> Option<Arc<dyn Inode>> {
+        self.mount_ns()
+            .state
+            .lock_save_irq()
+            .get_mount_root(&inode.id())
+    }
+
     /// Creates an instance of a filesystem from a registered driver.
     ///
     /// This does not mount the filesystem, but prepares an instance that can
@@ -132,8 +203,7 @@ impl VFS {
             root_inode: root_inode.clone(),
         };
 
-        // Lock the state to add the new mount and filesystem.
-        self.state.lock_save_irq().add_mount(root_inode.id(), mount);
+        self.add_mount(root_inode.id(), mount);
 
         // Set the global root inode.
         *self.root_inode.lock_save_irq() = Some(root_inode);
@@ -158,14 +228,31 @@ impl VFS {
 
         let new_mount = Mount { fs, root_inode };
 
-        // Lock the state and insert the new mount.
-        self.state
-            .lock_save_irq()
-            .add_mount(mount_point_id, new_mount);
+        self.add_mount(mount_point_id, new_mount);
 
         Ok(())
     }
 
+    /// Unmounts the filesystem whose root is `root` from the current mount
+    /// namespace, flushing it to its device. The root filesystem can't be
+    /// unmounted.
+    pub async fn unmount(&self, root: Arc<dyn Inode>) -> Result<()> {
+        if root.id() == self.root_inode().id() {
+            return Err(KernelError::InUse);
+        }
+
+        let mount = self
+            .mount_ns()
+            .state
+            .lock_save_irq()
+            .remove_mount(root.id())
+            .ok_or(KernelError::InvalidValue)?;
+
+        // The filesystem stays registered, as another mount namespace may
+        // still have it mounted.
+        mount.fs.sync().await
+    }
+
     /// Resolves a path string to an Inode, starting from a given root for
     /// relative paths.
     pub async fn resolve_path(&self, path: &Path, root: Arc<dyn Inode>) -> Result<Arc<dyn Inode>> {
@@ -182,11 +269,7 @@ impl VFS {
         for component in path.components() {
             // Before looking up the component, check if the current inode is a
             // mount point. If so, traverse into the mounted filesystem's root.
-            if let Some(mount_root) = self
-                .state
-                .lock_save_irq()
-                .get_mount_root(&current_inode.id())
-            {
+            if let Some(mount_root) = self.get_mount_root(&current_inode) {
                 current_inode = mount_root;
             }
 
@@ -195,11 +278,7 @@ impl VFS {
         }
 
         // After the final lookup, check if the destination is itself a mount point.
-        if let Some(mount_root) = self
-            .state
-            .lock_save_irq()
-            .get_mount_root(&current_inode.id())
-        {
+        if let Some(mount_root) = self.get_mount_root(&current_inode) {
             current_inode = mount_root;
         }
 
@@ -211,11 +290,17 @@ impl VFS {
         self.root_inode.lock_save_irq().as_ref().unwrap().clone()
     }
 
+    /// Opens the file at `path`, resolved as for [`resolve_path`]. `abs_path`
+    /// is the absolute path that this names, if known, which is recorded on
+    /// the open file.
+    ///
+    /// [`resolve_path`]: Self::resolve_path
     pub async fn open(
         &self,
         path: &Path,
         flags: OpenFlags,
         root: Arc<dyn Inode>,
+        abs_path: Option<PathBuf>,
     ) -> Result<Arc<OpenFile>> {
         // Attempt to resolve the full path first.
         let resolve_result = self.resolve_path(path, root.clone()).await;
@@ -287,6 +372,9 @@ impl VFS {
                 let mut open_file =
                     OpenFile::new(Box::new(RegFile::new(target_inode.clone())), flags);
                 open_file.set_inode(target_inode);
+                if let Some(abs_path) = abs_path {
+                    open_file.set_path(abs_path);
+                }
 
                 Ok(Arc::new(open_file))
             }
@@ -294,11 +382,28 @@ impl VFS {
                 let mut open_file =
                     OpenFile::new(Box::new(DirFile::new(target_inode.clone())), flags);
                 open_file.set_inode(target_inode);
+                if let Some(abs_path) = abs_path {
+                    open_file.set_path(abs_path);
+                }
 
                 Ok(Arc::new(open_file))
             }
             FileType::Symlink => todo!(),
-            FileType::BlockDevice(_) => todo!(),
+            FileType::BlockDevice(blk_dev_descriptor) => {
+                let dev = DM
+                    .lock_save_irq()
+                    .find_blk_driver(blk_dev_descriptor.major)
+                    .and_then(|driver| driver.get_device(blk_dev_descriptor.minor))
+                    .ok_or(FsError::NoDevice)?;
+
+                let mut open_file = OpenFile::new(Box::new(BlkFile::new(dev)), flags);
+                open_file.set_inode(target_inode);
+                if let Some(abs_path) = abs_path {
+                    open_file.set_path(abs_path);
+                }
+
+                Ok(Arc::new(open_file))
+            }
             FileType::CharDevice(char_dev_descriptor) => {
                 let char_driver = DM
                     .lock_save_irq()
@@ -323,10 +428,7 @@ impl VFS {
     /// Any individual error is logged and ignored so that a single faulty
     /// filesystem does not block the shutdown sequence.
     pub async fn sync_all(&self) -> Result<()> {
-        let filesystems: Vec<_> = {
-            let state = self.state.lock_save_irq();
-            state.filesystems.values().cloned().collect()
-        };
+        let filesystems: Vec<_> = self.filesystems.lock_save_irq().values().cloned().collect();
 
         for fs in filesystems {
             // Ignore per-filesystem errors; best-effort
@@ -1,14 +1,11 @@
 use super::fops::FileOps;
 use crate::{
     process::fd_table::select::PollFlags,
-    sync::{AsyncMutexGuard, Mutex},
+    sync::{AsyncMutexGuard, Mutex, SpinLock},
 };
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
-use core::{future, pin::Pin, task::Poll};
-use libkernel::{
-    error::Result,
-    fs::{Inode, OpenFlags},
-};
+use core::task::Waker;
+use libkernel::fs::{Inode, OpenFlags, path::Path, pathbuf::PathBuf};
 
 pub struct FileCtx {
     pub flags: OpenFlags,
@@ -23,7 +20,11 @@ impl FileCtx {
 
 pub struct OpenFile {
     inode: Option<Arc<dyn Inode>>,
+    /// The absolute path that the file was opened by, if known.
+    path: Option<PathBuf>,
     state: Mutex<(Box<dyn FileOps>, FileCtx)>,
+    /// Woken by the next operation on the file. See [`OpenFile::watch`].
+    watchers: SpinLock<Vec<Waker>>,
 }
 
 impl OpenFile {
@@ -31,6 +32,8 @@ impl OpenFile {
         Self {
             state: Mutex::new((ops, FileCtx::new(flags))),
             inode: None,
+            path: None,
+            watchers: SpinLock::new(Vec::new()),
         }
     }
 
@@ -42,62 +45,41 @@ impl OpenFile {
         self.inode.clone()
     }
 
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.path = Some(path)
+    }
+
+    /// Returns the absolute path that the file was opened by, if known.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
     pub async fn flags(&self) -> OpenFlags {
         self.state.lock().await.1.flags
     }
 
     pub async fn lock(&self) -> AsyncMutexGuard<'_, (Box<dyn FileOps>, FileCtx)> {
+        let watchers = core::mem::take(&mut *self.watchers.lock_save_irq());
+        watchers.into_iter().for_each(Waker::wake);
+
         self.state.lock().await
     }
 
-    pub async fn poll(
-        &self,
-        flags: PollFlags,
-    ) -> impl Future<Output = Result<PollFlags>> + Send + use<> {
-        let mut futs = Vec::new();
-
-        {
-            let (ops, _) = &mut *self.lock().await;
+    /// Registers `waker` to be woken by the next operation on the file, such
+    /// as a read or write. Polling the file doesn't count.
+    pub fn watch(&self, waker: &Waker) {
+        let mut watchers = self.watchers.lock_save_irq();
 
-            if flags.contains(PollFlags::POLLIN) {
-                let read_fut = ops.poll_read_ready();
-
-                futs.push(
-                    Box::pin(async move { read_fut.await.map(|_| PollFlags::POLLIN) })
-                        as Pin<Box<dyn Future<Output = _> + Send>>,
-                );
-            }
-
-            if flags.contains(PollFlags::POLLOUT) {
-                let write_fut = ops.poll_write_ready();
-
-                futs.push(Box::pin(async move {
-                    write_fut.await.map(|_| PollFlags::POLLOUT)
-                }));
-            }
+        if !watchers.iter().any(|w| w.will_wake(waker)) {
+            watchers.push(waker.clone());
         }
+    }
 
-        future::poll_fn(move |cx| {
-            let mut flags = PollFlags::empty();
-
-            // If no events were requested, return immediately.
-            if futs.is_empty() {
-                return Poll::Ready(Ok(PollFlags::empty()));
-            }
-
-            for fut in futs.iter_mut() {
-                match fut.as_mut().poll(cx) {
-                    Poll::Ready(Ok(flag)) => flags.insert(flag),
-                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                    Poll::Pending => continue,
-                }
-            }
+    /// Returns a future which resolves once any of `events` is ready on the
+    /// file, or it has an error or hangup. See [`FileOps::poll`].
+    pub async fn poll(&self, events: PollFlags) -> impl Future<Output = PollFlags> + Send + use<> {
+        let (ops, _) = &*self.state.lock().await;
 
-            if flags.is_empty() {
-                Poll::Pending
-            } else {
-                Poll::Ready(Ok(flags))
-            }
-        })
+        ops.poll(events)
     }
 }
@@ -1,13 +1,21 @@
 use crate::{
     kernel::kpipe::KPipe,
     memory::uaccess::copy_to_user,
-    process::{fd_table::Fd, thread_group::signal::SigId},
+    process::{
+        fd_table::{Fd, select::PollFlags},
+        thread_group::signal::SigId,
+    },
     sched::current_task,
     sync::CondVar,
 };
-use alloc::{boxed::Box, sync::Arc};
+use alloc::{boxed::Box, sync::Arc, vec};
 use async_trait::async_trait;
-use core::{future, pin::pin, task::Poll};
+use core::{
+    future,
+    pin::{Pin, pin},
+    task::Poll,
+};
+use futures::future::select;
 use libkernel::{
     error::{KernelError, Result},
     fs::{OpenFlags, SeekFrom},
@@ -16,7 +24,7 @@ use libkernel::{
 };
 //
 use super::{
-    fops::FileOps,
+    fops::{FileOps, PollFuture, poll_sources},
     open_file::{FileCtx, OpenFile},
 };
 
@@ -26,7 +34,37 @@ struct PipeInner {
     other_side_gone: CondVar<bool>,
 }
 
-impl PipeInner {}
+impl PipeInner {
+    /// Returns a poll source which reports `flags` once the other end of the
+    /// pipe has gone.
+    fn gone(&self, flags: PollFlags) -> PollFuture {
+        let gone = self
+            .other_side_gone
+            .wait_until(|gone| if *gone { Some(()) } else { None });
+
+        Box::pin(async move {
+            gone.await;
+            flags
+        })
+    }
+
+    /// Returns a future which completes once `ready` does, or the other end of
+    /// the pipe has gone.
+    fn ready_or_gone(
+        &self,
+        ready: impl Future<Output = ()> + Send + 'static,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + 'static + Send>> {
+        let gone = self
+            .other_side_gone
+            .wait_until(|gone| if *gone { Some(()) } else { None });
+
+        Box::pin(async move {
+            select(pin!(ready), pin!(gone)).await;
+
+            Ok(())
+        })
+    }
+}
 
 struct PipeReader {
     inner: PipeInner,
@@ -73,6 +111,29 @@ impl FileOps for PipeReader {
         Err(KernelError::BadFd)
     }
 
+    fn poll_read_ready(&self) -> Pin<Box<dyn Future<Output = Result<()>> + 'static + Send>> {
+        // Reading is possible once there's data, or will return EOF once the
+        // writer has gone.
+        self.inner.ready_or_gone(self.inner.buf.read_ready())
+    }
+
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        let read_ready = self.inner.buf.read_ready();
+
+        // Data left in the buffer is still readable after the writer has
+        // gone, so both are reported.
+        poll_sources(
+            events,
+            vec![
+                Box::pin(async move {
+                    read_ready.await;
+                    PollFlags::POLLIN | PollFlags::POLLRDNORM
+                }),
+                self.inner.gone(PollFlags::POLLHUP),
+            ],
+        )
+    }
+
     async fn seek(&mut self, _ctx: &mut FileCtx, _pos: SeekFrom) -> Result<u64> {
         Err(KernelError::SeekPipe)
     }
@@ -144,6 +205,27 @@ impl FileOps for PipeWriter {
             .await
     }
 
+    fn poll_write_ready(&self) -> Pin<Box<dyn Future<Output = Result<()>> + 'static + Send>> {
+        // Writing is possible once there's space, or will fail with EPIPE once
+        // the reader has gone.
+        self.inner.ready_or_gone(self.inner.buf.write_ready())
+    }
+
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        let write_ready = self.inner.buf.write_ready();
+
+        poll_sources(
+            events,
+            vec![
+                Box::pin(async move {
+                    write_ready.await;
+                    PollFlags::POLLOUT | PollFlags::POLLWRNORM
+                }),
+                self.inner.gone(PollFlags::POLLERR),
+            ],
+        )
+    }
+
     async fn seek(&mut self, _ctx: &mut FileCtx, _pos: SeekFrom) -> Result<u64> {
         Err(KernelError::SeekPipe)
     }
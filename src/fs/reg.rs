@@ -5,16 +5,20 @@ use crate::{
         page::ClaimedPage,
         uaccess::{copy_from_user_slice, copy_to_user_slice},
     },
+    process::thread_group::{
+        rsrc_lim::{RlimitId, rlimit},
+        signal::SigId,
+    },
+    sched::current_task,
 };
 use alloc::{boxed::Box, sync::Arc};
 use async_trait::async_trait;
 use core::{cmp::min, pin::Pin};
 use libkernel::{
-    error::Result,
+    error::{FsError, Result},
     fs::{Inode, SeekFrom},
     memory::{PAGE_SIZE, address::UA},
 };
-use ringbuf::traits::*;
 
 const SPLICE_BUF_SZ: usize = 32;
 
@@ -65,29 +69,45 @@ impl FileOps for RegFile {
 
     /// Writes data from `buf` to the current file position.
     /// The file's cursor is advanced by the number of bytes written.
+    ///
+    /// Writes are truncated at the process's `RLIMIT_FSIZE`; a write starting
+    /// at or beyond it raises `SIGXFSZ` and fails.
     async fn write(&mut self, ctx: &mut FileCtx, mut buf: UA, mut count: usize) -> Result<usize> {
+        let limit = rlimit(RlimitId::FSIZE);
+
+        if count > 0 && ctx.pos >= limit {
+            current_task()
+                .process
+                .signals
+                .lock_save_irq()
+                .set_pending(SigId::SIGXFSZ);
+
+            return Err(FsError::FileTooLarge.into());
+        }
+
+        count = min(count as u64, limit - ctx.pos) as usize;
+
         let mut pg = ClaimedPage::alloc_zeroed()?;
         let kbuf = pg.as_slice_mut();
-        let mut total_bytes_read = 0;
+        let mut total_bytes_written = 0;
 
         while count > 0 {
             let chunk_sz = min(PAGE_SIZE, count);
+            copy_from_user_slice(buf, &mut kbuf[..chunk_sz]).await?;
 
-            let bytes_read = self.inode.read_at(ctx.pos, &mut kbuf[..chunk_sz]).await?;
+            let bytes_written = self.inode.write_at(ctx.pos, &kbuf[..chunk_sz]).await?;
 
-            if bytes_read == 0 {
-                return Ok(total_bytes_read);
+            if bytes_written == 0 {
+                break;
             }
 
-            copy_to_user_slice(&kbuf[..bytes_read], buf).await?;
-
-            ctx.pos += bytes_read as u64;
-            total_bytes_read += bytes_read;
-            count -= bytes_read;
-            buf = buf.add_bytes(bytes_read);
+            ctx.pos += bytes_written as u64;
+            total_bytes_written += bytes_written;
+            count -= bytes_written;
+            buf = buf.add_bytes(bytes_written);
         }
 
-        Ok(total_bytes_read)
+        Ok(total_bytes_written)
     }
 
     fn poll_read_ready(&self) -> Pin<Box<dyn Future<Output = Result<()>> + 'static + Send>> {
@@ -0,0 +1,260 @@
+//! signalfd: a file from which pending signals are read, rather than being
+//! delivered to a handler.
+
+use super::{
+    fops::{FileOps, PollFuture, poll_sources},
+    open_file::{FileCtx, OpenFile},
+};
+use crate::{
+    memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
+    process::{
+        Task,
+        fd_table::{Fd, FdFlags, select::PollFlags},
+        thread_group::signal::{SigId, SigSet, UNMASKABLE_SIGNALS},
+    },
+    sched::current_task,
+    sync::{CondVar, SpinLock},
+};
+use alloc::{boxed::Box, sync::Arc, vec};
+use async_trait::async_trait;
+use core::any::Any;
+use libkernel::{
+    error::{KernelError, Result},
+    fs::OpenFlags,
+    memory::address::{TUA, UA},
+    sync::condvar::WakeupType,
+};
+
+const SFD_CLOEXEC: u32 = OpenFlags::O_CLOEXEC.bits();
+const SFD_NONBLOCK: u32 = OpenFlags::O_NONBLOCK.bits();
+
+/// `SI_USER`: the signal was sent by `kill()`.
+const SI_USER: i32 = 0;
+
+/// The record read from a signalfd for each signal.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SignalfdSigInfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    ssi_pid: u32,
+    ssi_uid: u32,
+    ssi_fd: i32,
+    ssi_tid: u32,
+    ssi_band: u32,
+    ssi_overrun: u32,
+    ssi_trapno: u32,
+    ssi_status: i32,
+    ssi_int: i32,
+    ssi_ptr: u64,
+    ssi_utime: u64,
+    ssi_stime: u64,
+    ssi_addr: u64,
+    ssi_addr_lsb: u16,
+    _pad2: u16,
+    ssi_syscall: i32,
+    ssi_call_addr: u64,
+    ssi_arch: u32,
+    _pad: [u8; 28],
+}
+
+unsafe impl UserCopyable for SignalfdSigInfo {}
+
+impl SignalfdSigInfo {
+    /// There is no queue of signal information, so only the signal number is
+    /// known.
+    fn new(signal: SigId) -> Self {
+        Self {
+            ssi_signo: signal.user_id() as _,
+            ssi_errno: 0,
+            ssi_code: SI_USER,
+            ssi_pid: 0,
+            ssi_uid: 0,
+            ssi_fd: 0,
+            ssi_tid: 0,
+            ssi_band: 0,
+            ssi_overrun: 0,
+            ssi_trapno: 0,
+            ssi_status: 0,
+            ssi_int: 0,
+            ssi_ptr: 0,
+            ssi_utime: 0,
+            ssi_stime: 0,
+            ssi_addr: 0,
+            ssi_addr_lsb: 0,
+            _pad2: 0,
+            ssi_syscall: 0,
+            ssi_call_addr: 0,
+            ssi_arch: 0,
+            _pad: [0; 28],
+        }
+    }
+}
+
+/// Returns the number of signals raised so far, as counted by `raised`.
+fn raised_count(raised: &CondVar<u64>) -> u64 {
+    let mut n = 0;
+
+    raised.update(|count| {
+        n = *count;
+        WakeupType::None
+    });
+
+    n
+}
+
+/// Removes and returns a signal in `mask` pending for `task`, either for the
+/// task itself or for its process.
+fn take_signal(task: &Task, mask: SigSet) -> Option<SigId> {
+    let mut task_pending = task.pending_signals.lock_save_irq();
+
+    if let Some(signal) = task_pending.intersection(mask).iter().next() {
+        task_pending.remove(signal);
+        return Some(signal.into());
+    }
+
+    drop(task_pending);
+
+    task.process.signals.lock_save_irq().take_pending(mask)
+}
+
+fn is_pending(task: &Task, mask: SigSet) -> bool {
+    task.pending_signals.lock_save_irq().intersects(mask)
+        || task.process.signals.lock_save_irq().is_pending(mask)
+}
+
+struct SignalFd {
+    mask: Arc<SpinLock<SigSet>>,
+}
+
+#[async_trait]
+impl FileOps for SignalFd {
+    async fn read(&mut self, ctx: &mut FileCtx, buf: UA, count: usize) -> Result<usize> {
+        let max = count / size_of::<SignalfdSigInfo>();
+
+        if max == 0 {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let task = current_task();
+        let raised = task.process.signals.lock_save_irq().raised();
+        let mut buf: TUA<SignalfdSigInfo> = buf.cast();
+        let mut n = 0;
+
+        while n < max {
+            // Note the count before looking, so that a signal raised after we
+            // find none pending isn't missed.
+            let seen = raised_count(&raised);
+            let mask = *self.mask.lock_save_irq();
+
+            if let Some(signal) = take_signal(&task, mask) {
+                copy_to_user(buf, SignalfdSigInfo::new(signal)).await?;
+                buf = buf.add_objs(1);
+                n += 1;
+                continue;
+            }
+
+            // Return what we've read, rather than waiting for more.
+            if n > 0 {
+                break;
+            }
+
+            if ctx.flags.contains(OpenFlags::O_NONBLOCK) {
+                return Err(KernelError::TryAgain);
+            }
+
+            raised
+                .wait_until(|count| if *count != seen { Some(()) } else { None })
+                .await;
+        }
+
+        Ok(n * size_of::<SignalfdSigInfo>())
+    }
+
+    async fn write(&mut self, _ctx: &mut FileCtx, _buf: UA, _count: usize) -> Result<usize> {
+        Err(KernelError::InvalidValue)
+    }
+
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        // Readiness is that of the polling task.
+        let task = current_task();
+        let mask = self.mask.clone();
+
+        let readable = Box::pin(async move {
+            let raised = task.process.signals.lock_save_irq().raised();
+
+            loop {
+                let seen = raised_count(&raised);
+
+                if is_pending(&task, *mask.lock_save_irq()) {
+                    return PollFlags::POLLIN | PollFlags::POLLRDNORM;
+                }
+
+                raised
+                    .wait_until(|count| if *count != seen { Some(()) } else { None })
+                    .await;
+            }
+        });
+
+        poll_sources(events, vec![readable])
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+pub async fn sys_signalfd4(
+    fd: Fd,
+    mask: TUA<SigSet>,
+    sizemask: usize,
+    flags: u32,
+) -> Result<usize> {
+    if sizemask != size_of::<SigSet>() || flags & !(SFD_CLOEXEC | SFD_NONBLOCK) != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    // SIGKILL and SIGSTOP can't be read from a signalfd, and are silently
+    // ignored.
+    let mask = copy_from_user(mask).await?.difference(UNMASKABLE_SIGNALS);
+
+    // Given an existing signalfd, only its mask is replaced.
+    if fd.as_raw() != -1 {
+        let file = current_task()
+            .fd_table
+            .lock_save_irq()
+            .get(fd)
+            .ok_or(KernelError::BadFd)?;
+
+        let (ops, _) = &*file.lock().await;
+        let signalfd = ops
+            .as_any()
+            .and_then(|ops| ops.downcast_ref::<SignalFd>())
+            .ok_or(KernelError::InvalidValue)?;
+
+        *signalfd.mask.lock_save_irq() = mask;
+
+        return Ok(fd.as_raw() as _);
+    }
+
+    let file = OpenFile::new(
+        Box::new(SignalFd {
+            mask: Arc::new(SpinLock::new(mask)),
+        }),
+        OpenFlags::O_RDONLY | OpenFlags::from_bits_retain(flags & SFD_NONBLOCK),
+    );
+
+    let fd_flags = if flags & SFD_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let fd = current_task()
+        .fd_table
+        .lock_save_irq()
+        .insert_with_flags(Arc::new(file), fd_flags)?;
+
+    Ok(fd.as_raw() as _)
+}
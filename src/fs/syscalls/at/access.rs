@@ -1,9 +1,17 @@
 use super::{AtFlags, resolve_at_start_node};
-use crate::{fs::VFS, memory::uaccess::cstr::UserCStr, process::fd_table::Fd, sched::current_task};
+use crate::{
+    fs::VFS,
+    memory::uaccess::cstr::UserCStr,
+    process::{creds::caps::CapSet, fd_table::Fd},
+    sched::current_task,
+};
 use core::ffi::c_char;
 use libkernel::{
     error::Result,
-    fs::{attr::AccessMode, path::Path},
+    fs::{
+        attr::{AccessMode, DacCaps},
+        path::Path,
+    },
     memory::address::TUA,
 };
 
@@ -31,12 +39,26 @@ pub async fn sys_faccessat2(dirfd: Fd, path: TUA<c_char>, mode: i32, flags: i32)
     let creds = task.creds.lock_save_irq();
 
     // Determine which user and group IDs to use for the check. By default, use
-    // the real UID and GID. If AT_EACCESS is set, use effective IDs.
-    let (uid, gid) = if at_flags.contains(AtFlags::AT_EACCESS) {
-        (creds.euid(), creds.egid())
+    // the real UID and GID. If AT_EACCESS is set, use effective IDs. Likewise,
+    // the effective capabilities only apply with AT_EACCESS; otherwise a real
+    // UID of root is checked with its permitted capabilities, and any other
+    // with none.
+    let (uid, gid, caps) = if at_flags.contains(AtFlags::AT_EACCESS) {
+        (creds.euid(), creds.egid(), creds.caps().effective)
+    } else if creds.uid().is_root() {
+        (creds.uid(), creds.gid(), creds.caps().permitted)
     } else {
-        (creds.uid(), creds.gid())
+        (creds.uid(), creds.gid(), CapSet::empty())
     };
 
-    attrs.check_access(uid, gid, access_mode).map(|_| 0)
+    let mut dac_caps = DacCaps::empty();
+    dac_caps.set(DacCaps::OVERRIDE, caps.contains(CapSet::CAP_DAC_OVERRIDE));
+    dac_caps.set(
+        DacCaps::READ_SEARCH,
+        caps.contains(CapSet::CAP_DAC_READ_SEARCH),
+    );
+
+    attrs
+        .check_access(uid, gid, dac_caps, access_mode)
+        .map(|_| 0)
 }
@@ -0,0 +1,41 @@
+use crate::{
+    fs::{
+        VFS,
+        syscalls::{at::resolve_at_start_node, chown::chown_inode},
+    },
+    memory::uaccess::cstr::UserCStr,
+    process::fd_table::Fd,
+};
+use core::ffi::c_char;
+use libkernel::{
+    error::{KernelError, Result},
+    fs::path::Path,
+    memory::address::TUA,
+};
+
+use super::AtFlags;
+
+pub async fn sys_fchownat(
+    dirfd: Fd,
+    path: TUA<c_char>,
+    uid: i32,
+    gid: i32,
+    flags: i32,
+) -> Result<usize> {
+    let mut buf = [0; 1024];
+
+    let flags = AtFlags::from_bits(flags).ok_or(KernelError::InvalidValue)?;
+
+    if flags.intersects(!(AtFlags::AT_SYMLINK_NOFOLLOW | AtFlags::AT_EMPTY_PATH)) {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let path = Path::new(UserCStr::from_ptr(path).copy_from_user(&mut buf).await?);
+
+    let start_node = resolve_at_start_node(dirfd, path).await?;
+    let node = VFS.resolve_path(path, start_node).await?;
+
+    chown_inode(node, uid, gid).await?;
+
+    Ok(0)
+}
@@ -2,10 +2,11 @@ use crate::{fs::VFS, process::fd_table::Fd, sched::current_task};
 use alloc::sync::Arc;
 use libkernel::{
     error::{FsError, KernelError, Result},
-    fs::{FileType, Inode, path::Path},
+    fs::{FileType, Inode, path::Path, pathbuf::PathBuf},
 };
 
 pub mod access;
+pub mod chown;
 pub mod open;
 pub mod readlink;
 pub mod stat;
@@ -50,3 +51,21 @@ async fn resolve_at_start_node(dirfd: Fd, path: &Path) -> Result<Arc<dyn Inode>>
 
     Ok(start_node)
 }
+
+/// Returns the absolute path named by `path` when resolved against `dirfd`, as
+/// for [`resolve_at_start_node`], or `None` if the path of `dirfd` isn't known.
+fn resolve_at_path(dirfd: Fd, path: &Path) -> Option<PathBuf> {
+    let task = current_task();
+
+    let base = if path.is_absolute() {
+        PathBuf::from("/")
+    } else if dirfd.is_atcwd() {
+        Path::new("/").join(&task.cwd.lock_save_irq().1)
+    } else {
+        let file = task.fd_table.lock_save_irq().get(dirfd)?;
+
+        PathBuf::from(file.path()?.as_str())
+    };
+
+    Some(base.join(path).normalize())
+}
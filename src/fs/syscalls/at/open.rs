@@ -6,7 +6,7 @@ use libkernel::{
     memory::address::TUA,
 };
 
-use super::resolve_at_start_node;
+use super::{resolve_at_path, resolve_at_start_node};
 
 pub async fn sys_openat(
     dirfd: Fd,
@@ -19,8 +19,9 @@ pub async fn sys_openat(
     let flags = OpenFlags::from_bits_truncate(flags);
     let path = Path::new(UserCStr::from_ptr(path).copy_from_user(&mut buf).await?);
     let start_node = resolve_at_start_node(dirfd, path).await?;
+    let abs_path = resolve_at_path(dirfd, path);
 
-    let file = VFS.open(path, flags, start_node).await?;
+    let file = VFS.open(path, flags, start_node, abs_path).await?;
 
     let fd = current_task().fd_table.lock_save_irq().insert(file)?;
 
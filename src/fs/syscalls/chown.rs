@@ -0,0 +1,39 @@
+use crate::{process::fd_table::Fd, sched::current_task};
+use alloc::sync::Arc;
+use libkernel::{
+    error::{KernelError, Result},
+    fs::Inode,
+    proc::ids::{Gid, Uid},
+};
+
+/// Changes the owner and group of `inode` to `uid` and `gid`, where -1 leaves
+/// that ID unchanged, if the current task's credentials allow it.
+pub async fn chown_inode(inode: Arc<dyn Inode>, uid: i32, gid: i32) -> Result<()> {
+    let uid = (uid != -1).then(|| Uid::new(uid as u32));
+    let gid = (gid != -1).then(|| Gid::new(gid as u32));
+    let attr = inode.getattr().await?;
+
+    if !current_task()
+        .creds
+        .lock_save_irq()
+        .may_chown(attr.uid, attr.gid, uid, gid)
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    inode.chown(uid, gid).await
+}
+
+pub async fn sys_fchown(fd: Fd, uid: i32, gid: i32) -> Result<usize> {
+    let fd = current_task()
+        .fd_table
+        .lock_save_irq()
+        .get(fd)
+        .ok_or(KernelError::BadFd)?;
+
+    let inode = fd.inode().ok_or(KernelError::BadFd)?;
+
+    chown_inode(inode, uid, gid).await?;
+
+    Ok(0)
+}
@@ -1,8 +1,10 @@
 pub mod at;
 pub mod chdir;
+pub mod chown;
 pub mod close;
 pub mod ioctl;
 pub mod iov;
+pub mod mount;
 pub mod open;
 pub mod rw;
 pub mod seek;
@@ -0,0 +1,124 @@
+use crate::{
+    drivers::DM, fs::VFS, memory::uaccess::cstr::UserCStr, process::creds::caps::CapSet,
+    sched::current_task,
+};
+use alloc::boxed::Box;
+use core::ffi::c_char;
+use libkernel::{
+    error::{FsError, KernelError, Result},
+    fs::{BlockDevice, FileType, path::Path},
+    memory::address::{TUA, UA},
+};
+
+const MS_REMOUNT: u64 = 0x20;
+const MS_BIND: u64 = 0x1000;
+const MS_MOVE: u64 = 0x2000;
+const MS_UNBINDABLE: u64 = 1 << 17;
+const MS_PRIVATE: u64 = 1 << 18;
+const MS_SLAVE: u64 = 1 << 19;
+const MS_SHARED: u64 = 1 << 20;
+
+const MNT_FORCE: i32 = 1;
+const MNT_DETACH: i32 = 2;
+const MNT_EXPIRE: i32 = 4;
+const UMOUNT_NOFOLLOW: i32 = 8;
+
+fn check_mount_cap() -> Result<()> {
+    if current_task()
+        .creds
+        .lock_save_irq()
+        .has_cap(CapSet::CAP_SYS_ADMIN)
+    {
+        Ok(())
+    } else {
+        Err(KernelError::NotPermitted)
+    }
+}
+
+/// Returns the block device named by `source`, or `None` if it doesn't name
+/// one, as for a filesystem which has no backing device.
+async fn lookup_source_device(source: &Path) -> Result<Option<Box<dyn BlockDevice>>> {
+    let cwd = current_task().cwd.lock_save_irq().0.clone();
+
+    let inode = match VFS.resolve_path(source, cwd).await {
+        Ok(inode) => inode,
+        Err(KernelError::Fs(FsError::NotFound)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let FileType::BlockDevice(desc) = inode.getattr().await?.file_type else {
+        return Ok(None);
+    };
+
+    let dev = DM
+        .lock_save_irq()
+        .find_blk_driver(desc.major)
+        .and_then(|driver| driver.get_device(desc.minor))
+        .ok_or(FsError::NoDevice)?;
+
+    Ok(Some(Box::new(dev)))
+}
+
+/// Mounts a new instance of the filesystem `fs_type` on `target`. Remounts,
+/// bind mounts, moves and propagation changes aren't supported.
+pub async fn sys_mount(
+    source: TUA<c_char>,
+    target: TUA<c_char>,
+    fs_type: TUA<c_char>,
+    flags: u64,
+    _data: UA,
+) -> Result<usize> {
+    check_mount_cap()?;
+
+    if flags & (MS_REMOUNT | MS_BIND | MS_MOVE | MS_UNBINDABLE | MS_PRIVATE | MS_SLAVE | MS_SHARED)
+        != 0
+    {
+        return Err(KernelError::NotSupported);
+    }
+
+    let mut source_buf = [0; 1024];
+    let mut target_buf = [0; 1024];
+    let mut type_buf = [0; 64];
+
+    let source = Path::new(
+        UserCStr::from_ptr(source)
+            .copy_from_user(&mut source_buf)
+            .await?,
+    );
+    let target = Path::new(
+        UserCStr::from_ptr(target)
+            .copy_from_user(&mut target_buf)
+            .await?,
+    );
+    let fs_type = UserCStr::from_ptr(fs_type)
+        .copy_from_user(&mut type_buf)
+        .await?;
+
+    let cwd = current_task().cwd.lock_save_irq().0.clone();
+    let mount_point = VFS.resolve_path(target, cwd).await?;
+    let blkdev = lookup_source_device(source).await?;
+
+    VFS.mount(mount_point, fs_type, blkdev).await?;
+
+    Ok(0)
+}
+
+pub async fn sys_umount2(target: TUA<c_char>, flags: i32) -> Result<usize> {
+    check_mount_cap()?;
+
+    if flags & !(MNT_FORCE | MNT_DETACH | MNT_EXPIRE | UMOUNT_NOFOLLOW) != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let mut buf = [0; 1024];
+
+    let target = Path::new(UserCStr::from_ptr(target).copy_from_user(&mut buf).await?);
+    let cwd = current_task().cwd.lock_save_irq().0.clone();
+
+    // A mount point resolves to the root of the filesystem mounted on it.
+    let root = VFS.resolve_path(target, cwd).await?;
+
+    VFS.unmount(root).await?;
+
+    Ok(0)
+}
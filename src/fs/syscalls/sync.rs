@@ -1,6 +1,7 @@
-use crate::fs::VFS;
+use crate::{fs::VFS, memory::shared::flush_pending_writeback};
 
 pub async fn sys_sync() -> libkernel::error::Result<usize> {
+    flush_pending_writeback().await?;
     VFS.sync_all().await?;
     Ok(0)
 }
@@ -0,0 +1,206 @@
+//! timerfd: a timer whose expiries are read from a file.
+
+use super::{
+    fops::{FileOps, PollFuture, poll_sources},
+    open_file::{FileCtx, OpenFile},
+};
+use crate::{
+    clock::gettime::ClockId,
+    drivers::timer::ktimer::KTimer,
+    memory::uaccess::{copy_from_user, copy_to_user},
+    process::{
+        fd_table::{Fd, FdFlags, select::PollFlags},
+        thread_group::timer::{CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_REALTIME, ITimerSpec, now},
+    },
+    sched::current_task,
+    sync::CondVar,
+};
+use alloc::{boxed::Box, sync::Arc, vec};
+use async_trait::async_trait;
+use core::{any::Any, time::Duration};
+use libkernel::{
+    error::{KernelError, Result},
+    fs::OpenFlags,
+    memory::address::{TUA, UA},
+    sync::condvar::WakeupType,
+};
+
+const TFD_CLOEXEC: u32 = OpenFlags::O_CLOEXEC.bits();
+const TFD_NONBLOCK: u32 = OpenFlags::O_NONBLOCK.bits();
+
+const TFD_TIMER_ABSTIME: i32 = 1;
+/// Only meaningful for absolute `CLOCK_REALTIME` timers. The real-time clock
+/// is never set, so this is accepted and has no effect.
+const TFD_TIMER_CANCEL_ON_SET: i32 = 2;
+
+struct TimerFd {
+    clock: ClockId,
+    timer: KTimer,
+    /// The number of expiries since the timer was last read or set.
+    expiries: CondVar<u64>,
+}
+
+#[async_trait]
+impl FileOps for TimerFd {
+    async fn read(&mut self, ctx: &mut FileCtx, buf: UA, count: usize) -> Result<usize> {
+        if count < size_of::<u64>() {
+            return Err(KernelError::InvalidValue);
+        }
+
+        let take = |expiries: &mut u64| match core::mem::take(expiries) {
+            0 => None,
+            n => Some(n),
+        };
+
+        let expiries = if ctx.flags.contains(OpenFlags::O_NONBLOCK) {
+            let mut expiries = None;
+
+            self.expiries.update(|n| {
+                expiries = take(n);
+                WakeupType::None
+            });
+
+            expiries.ok_or(KernelError::TryAgain)?
+        } else {
+            self.expiries.wait_until(take).await
+        };
+
+        copy_to_user(buf.cast(), expiries).await?;
+
+        Ok(size_of::<u64>())
+    }
+
+    async fn write(&mut self, _ctx: &mut FileCtx, _buf: UA, _count: usize) -> Result<usize> {
+        Err(KernelError::InvalidValue)
+    }
+
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        let expired = self
+            .expiries
+            .wait_until(|n| if *n > 0 { Some(()) } else { None });
+
+        poll_sources(
+            events,
+            vec![Box::pin(async move {
+                expired.await;
+                PollFlags::POLLIN | PollFlags::POLLRDNORM
+            })],
+        )
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Runs `f` on the timerfd open at `fd`.
+async fn with_timerfd<T>(fd: Fd, f: impl FnOnce(&TimerFd) -> T) -> Result<T> {
+    let file = current_task()
+        .fd_table
+        .lock_save_irq()
+        .get(fd)
+        .ok_or(KernelError::BadFd)?;
+
+    let (ops, _) = &*file.lock().await;
+
+    ops.as_any()
+        .and_then(|ops| ops.downcast_ref::<TimerFd>())
+        .map(f)
+        .ok_or(KernelError::InvalidValue)
+}
+
+pub fn sys_timerfd_create(clock: ClockId, flags: u32) -> Result<usize> {
+    match clock {
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME => {}
+        _ => return Err(KernelError::InvalidValue),
+    }
+
+    if flags & !(TFD_CLOEXEC | TFD_NONBLOCK) != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let expiries = CondVar::new(0u64);
+    let counter = expiries.clone();
+
+    let timer = KTimer::new(move |n| {
+        counter.update(|expiries| {
+            *expiries = expiries.saturating_add(n);
+            WakeupType::All
+        });
+    });
+
+    let file = OpenFile::new(
+        Box::new(TimerFd {
+            clock,
+            timer,
+            expiries,
+        }),
+        OpenFlags::O_RDONLY | OpenFlags::from_bits_retain(flags & TFD_NONBLOCK),
+    );
+
+    let fd_flags = if flags & TFD_CLOEXEC != 0 {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let fd = current_task()
+        .fd_table
+        .lock_save_irq()
+        .insert_with_flags(Arc::new(file), fd_flags)?;
+
+    Ok(fd.as_raw() as _)
+}
+
+pub async fn sys_timerfd_settime(
+    fd: Fd,
+    flags: i32,
+    new_value: TUA<ITimerSpec>,
+    old_value: TUA<ITimerSpec>,
+) -> Result<usize> {
+    if flags & !(TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET) != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let new: ITimerSpec = copy_from_user(new_value).await?;
+    let (mut value, interval) = new.durations()?;
+
+    let (prev_value, prev_interval) = with_timerfd(fd, |timerfd| {
+        // An absolute expiry time which has already passed expires
+        // immediately.
+        if flags & TFD_TIMER_ABSTIME != 0 && !value.is_zero() {
+            value = value
+                .saturating_sub(now(timerfd.clock))
+                .max(Duration::from_nanos(1));
+        }
+
+        // Expiries of the old setting are discarded. The timer is disarmed
+        // first, so the old setting can't expire afterwards, and the count is
+        // cleared before the new setting is armed, as it may expire at once.
+        let prev = timerfd.timer.set(Duration::ZERO, Duration::ZERO);
+
+        timerfd.expiries.update(|n| {
+            *n = 0;
+            WakeupType::None
+        });
+
+        timerfd.timer.set(value, interval);
+
+        prev
+    })
+    .await?;
+
+    if !old_value.is_null() {
+        copy_to_user(old_value, ITimerSpec::new(prev_value, prev_interval)).await?;
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_timerfd_gettime(fd: Fd, curr_value: TUA<ITimerSpec>) -> Result<usize> {
+    let (value, interval) = with_timerfd(fd, |timerfd| timerfd.timer.get()).await?;
+
+    copy_to_user(curr_value, ITimerSpec::new(value, interval)).await?;
+
+    Ok(0)
+}
@@ -19,7 +19,7 @@ use super::{
 
 #[derive(Clone)]
 pub enum Message {
-    // Reschedule,
+    Reschedule,
     // PutTask(Arc<Task>),
     Ping(u32),
 }
@@ -47,7 +47,8 @@ impl InterruptHandler for CpuMessenger {
             .try_pop();
 
         match message {
-            // Some(Message::Reschedule) => return, // We reschedule when returning from an IRQ.
+            // We reschedule when returning from an IRQ.
+            Some(Message::Reschedule) => {}
             // Some(Message::PutTask(task)) => sched::insert_task(task),
             Some(Message::Ping(cpu_id)) => {
                 info!("CPU {} recieved ping from CPU {}", ArchImpl::id(), cpu_id)
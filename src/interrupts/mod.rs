@@ -1,3 +1,6 @@
+// Only the arm64 GIC drivers register an interrupt controller so far.
+#![cfg_attr(target_arch = "riscv64", allow(dead_code))]
+
 use alloc::{
     boxed::Box,
     collections::BTreeMap,
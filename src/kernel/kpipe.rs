@@ -12,7 +12,7 @@ use libkernel::{
     error::Result,
     memory::{PAGE_SIZE, address::UA, kbuf::KBufCore},
 };
-use ringbuf::{storage::Storage, traits::*}; // 引入 traits 以便调用 inner 的 trait 方法
+use ringbuf::storage::Storage;
 
 pub struct PageBackedStorage<T>(ClaimedPage, PhantomData<T>);
 
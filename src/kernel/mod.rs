@@ -1,4 +1,5 @@
 pub mod kpipe;
 pub mod power;
 pub mod rand;
+pub mod syscall;
 pub mod uname;
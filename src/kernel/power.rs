@@ -1,4 +1,4 @@
-use crate::{ArchImpl, arch::Arch};
+use crate::{ArchImpl, arch::Arch, process::creds::caps::CapSet, sched::current_task};
 use libkernel::error::{KernelError, Result};
 
 pub async fn sys_reboot(magic: u32, magic2: u32, op: u32, _arg: usize) -> Result<usize> {
@@ -23,6 +23,13 @@ pub async fn sys_reboot(magic: u32, magic2: u32, op: u32, _arg: usize) -> Result
     {
         return Err(KernelError::InvalidValue);
     }
+    if !current_task()
+        .creds
+        .lock_save_irq()
+        .has_cap(CapSet::CAP_SYS_BOOT)
+    {
+        return Err(KernelError::NotPermitted);
+    }
     match op {
         LINUX_REBOOT_CMD_POWER_OFF => {
             // User is supposed to sync first.
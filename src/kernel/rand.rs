@@ -1,39 +1,81 @@
-// TODO: generate a pool of entropy.
+//! The kernel's random number generator.
+//!
+//! This is a ChaCha-based CSPRNG, seeded on first use from the CPU's
+//! architectural random number generator and the bootloader-provided
+//! `/chosen/rng-seed` and `/chosen/kaslr-seed` device tree properties (when
+//! present), mixed with the boot-time counter. Without either source of entropy
+//! its output, and so the randomisation of address spaces, is predictable.
 
 use crate::{
-    drivers::timer::uptime,
+    arch::{Arch, ArchImpl},
+    drivers::{fdt_prober::get_fdt, timer::uptime},
     memory::uaccess::copy_to_user_slice,
     sync::{OnceLock, SpinLock},
 };
-use alloc::vec::Vec;
+use alloc::vec;
 use libkernel::error::Result;
 use libkernel::memory::address::TUA;
-use rand::{RngCore, SeedableRng, rngs::SmallRng};
+use log::warn;
+use rand::{RngCore, SeedableRng, rngs::StdRng};
 
-pub async fn sys_getrandom(ubuf: TUA<u8>, size: isize, _flags: u32) -> Result<usize> {
-    let buf = {
-        let mut rng = ENTROPY_POOL
-            .get_or_init(|| {
-                let now = uptime();
+fn entropy_pool() -> &'static SpinLock<StdRng> {
+    ENTROPY_POOL.get_or_init(|| {
+        let mut seed = [0u8; 32];
+
+        let mut seeded = false;
+
+        seed[..16].copy_from_slice(&uptime().as_nanos().to_le_bytes());
+
+        for chunk in seed.chunks_mut(8) {
+            if let Some(val) = ArchImpl::hw_random() {
+                for (byte, rand) in chunk.iter_mut().zip(val.to_le_bytes()) {
+                    *byte ^= rand;
+                }
 
-                SpinLock::new(SmallRng::seed_from_u64(
-                    (now.as_micros() & 0xffffffff_ffffffff) as u64,
-                ))
-            })
-            .lock_save_irq();
+                seeded = true;
+            }
+        }
+
+        if let Some(chosen) = get_fdt().find_nodes("/chosen").next() {
+            for prop in ["rng-seed", "kaslr-seed"] {
+                if let Some(prop) = chosen.find_property(prop) {
+                    for (i, byte) in prop.raw_value().iter().enumerate() {
+                        seed[i % seed.len()] ^= byte;
+                    }
 
-        let mut buf = Vec::with_capacity(size as usize);
+                    seeded |= !prop.raw_value().is_empty();
+                }
+            }
+        }
 
-        for _ in 0..size {
-            buf.push((rng.next_u32() & 0xff) as u8);
+        if !seeded {
+            warn!(
+                "rand: no hardware RNG or device tree seed; random numbers and address space randomisation are weak"
+            );
         }
 
-        buf
-    };
+        SpinLock::new(StdRng::from_seed(seed))
+    })
+}
+
+/// Fills `buf` with random bytes.
+pub fn fill_random(buf: &mut [u8]) {
+    entropy_pool().lock_save_irq().fill_bytes(buf);
+}
+
+/// Returns a random `u64`.
+pub fn random_u64() -> u64 {
+    entropy_pool().lock_save_irq().next_u64()
+}
+
+pub async fn sys_getrandom(ubuf: TUA<u8>, size: isize, _flags: u32) -> Result<usize> {
+    let mut buf = vec![0; size as usize];
+
+    fill_random(&mut buf);
 
     copy_to_user_slice(&buf, ubuf.to_untyped()).await?;
 
     Ok(size as _)
 }
 
-static ENTROPY_POOL: OnceLock<SpinLock<SmallRng>> = OnceLock::new();
+static ENTROPY_POOL: OnceLock<SpinLock<StdRng>> = OnceLock::new();
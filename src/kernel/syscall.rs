@@ -1,40 +1,55 @@
 use crate::kernel::power::sys_reboot;
 use crate::kernel::rand::sys_getrandom;
-use crate::memory::mmap::sys_mprotect;
+use crate::memory::mmap::{sys_mprotect, sys_mremap, sys_msync};
 use crate::{
     arch::{Arch, ArchImpl},
-    clock::{gettime::sys_clock_gettime, timeofday::sys_gettimeofday},
+    clock::{
+        gettime::{sys_clock_getres, sys_clock_gettime, sys_clock_settime},
+        timeofday::{sys_gettimeofday, sys_settimeofday},
+    },
     fs::{
         dir::sys_getdents64,
+        epoll::{sys_epoll_create1, sys_epoll_ctl, sys_epoll_pwait},
+        eventfd::sys_eventfd2,
         pipe::sys_pipe2,
+        signalfd::sys_signalfd4,
         syscalls::{
             at::{
                 access::{sys_faccessat, sys_faccessat2},
+                chown::sys_fchownat,
                 open::sys_openat,
                 readlink::sys_readlinkat,
                 stat::sys_newfstatat,
             },
             chdir::{sys_chdir, sys_getcwd},
+            chown::sys_fchown,
             close::sys_close,
             ioctl::sys_ioctl,
             iov::{sys_readv, sys_writev},
+            mount::{sys_mount, sys_umount2},
             rw::{sys_read, sys_write},
             seek::sys_lseek,
             splice::sys_sendfile,
             stat::sys_fstat,
             sync::sys_sync,
         },
+        timerfd::{sys_timerfd_create, sys_timerfd_gettime, sys_timerfd_settime},
     },
-    kernel::uname::sys_uname,
+    kernel::uname::{sys_setdomainname, sys_sethostname, sys_uname},
     memory::{
         brk::sys_brk,
+        madvise::{sys_madvise, sys_mincore},
+        mlock::{sys_mlock, sys_mlock2, sys_mlockall, sys_munlock, sys_munlockall},
         mmap::{sys_mmap, sys_munmap},
+        swap::{sys_swapoff, sys_swapon},
     },
     process::{
         clone::sys_clone,
         creds::{
+            caps::{sys_capget, sys_capset},
             sys_getegid, sys_geteuid, sys_getgid, sys_getresgid, sys_getresuid, sys_gettid,
-            sys_getuid,
+            sys_getuid, sys_setgid, sys_setregid, sys_setresgid, sys_setresuid, sys_setreuid,
+            sys_setuid,
         },
         exec::sys_execve,
         exit::{sys_exit, sys_exit_group},
@@ -43,19 +58,28 @@ use crate::{
             fcntl::sys_fcntl,
             select::{sys_ppoll, sys_pselect6},
         },
+        ns::{sys_setns, sys_unshare},
+        pidfd::{sys_pidfd_getfd, sys_pidfd_open, sys_pidfd_send_signal},
+        prctl::sys_prctl,
+        ptrace::{self, sys_ptrace},
         sleep::sys_nanosleep,
         thread_group::{
-            Pgid,
+            itimer::{sys_getitimer, sys_setitimer},
+            personality::sys_personality,
             pid::{sys_getpgid, sys_getpid, sys_getppid, sys_setpgid},
-            rsrc_lim::sys_prlimit64,
+            rsrc_lim::{sys_getrlimit, sys_prlimit64, sys_setrlimit},
             signal::{
                 kill::{sys_kill, sys_tkill},
                 sigaction::sys_rt_sigaction,
                 sigaltstack::sys_sigaltstack,
                 sigprocmask::sys_rt_sigprocmask,
             },
+            timer::{
+                sys_timer_create, sys_timer_delete, sys_timer_getoverrun, sys_timer_gettime,
+                sys_timer_settime,
+            },
             umask::sys_umask,
-            wait::sys_wait4,
+            wait::{sys_wait4, sys_waitid},
         },
         threading::{sys_set_robust_list, sys_set_tid_address},
     },
@@ -67,32 +91,71 @@ use libkernel::{
     memory::address::{TUA, UA, VA},
 };
 
+/// Handles the syscall made by the current task, whose user context must
+/// already have been saved. The syscall numbers are the generic ones shared by
+/// arm64 and riscv64.
 pub async fn handle_syscall() {
     let task = current_task();
 
-    let (nr, arg1, arg2, arg3, arg4, arg5, arg6) = {
-        let ctx = task.ctx.lock_save_irq();
-        let state = ctx.user();
+    // The tracer may inspect, or rewrite, the syscall's arguments whilst the
+    // task is in its syscall-entry-stop.
+    ptrace::syscall_stop(&task).await;
 
-        (
-            state.x[8] as u32,
-            state.x[0],
-            state.x[1],
-            state.x[2],
-            state.x[3],
-            state.x[4],
-            state.x[5],
-        )
-    };
+    let (nr, [arg1, arg2, arg3, arg4, arg5, arg6]) =
+        ArchImpl::syscall_args(task.ctx.lock_save_irq().user());
 
     let res = match nr {
         0x11 => sys_getcwd(TUA::from_value(arg1 as _), arg2 as _).await,
+        0x13 => sys_eventfd2(arg1 as _, arg2 as _),
+        0x14 => sys_epoll_create1(arg1 as _),
+        0x15 => {
+            sys_epoll_ctl(
+                arg1.into(),
+                arg2 as _,
+                arg3.into(),
+                TUA::from_value(arg4 as _),
+            )
+            .await
+        }
+        0x16 => {
+            sys_epoll_pwait(
+                arg1.into(),
+                TUA::from_value(arg2 as _),
+                arg3 as _,
+                arg4 as _,
+                TUA::from_value(arg5 as _),
+                arg6 as _,
+            )
+            .await
+        }
         0x17 => sys_dup(arg1.into()),
         0x18 => sys_dup3(arg1.into(), arg2.into(), arg3 as _),
         0x19 => sys_fcntl(arg1.into(), arg2 as _, arg3 as _).await,
         0x1d => sys_ioctl(arg1.into(), arg2 as _, arg3 as _).await,
+        0x27 => sys_umount2(TUA::from_value(arg1 as _), arg2 as _).await,
+        0x28 => {
+            sys_mount(
+                TUA::from_value(arg1 as _),
+                TUA::from_value(arg2 as _),
+                TUA::from_value(arg3 as _),
+                arg4 as _,
+                UA::from_value(arg5 as _),
+            )
+            .await
+        }
         0x30 => sys_faccessat(arg1.into(), TUA::from_value(arg2 as _), arg3 as _).await,
         0x31 => sys_chdir(TUA::from_value(arg1 as _)).await,
+        0x36 => {
+            sys_fchownat(
+                arg1.into(),
+                TUA::from_value(arg2 as _),
+                arg3 as _,
+                arg4 as _,
+                arg5 as _,
+            )
+            .await
+        }
+        0x37 => sys_fchown(arg1.into(), arg2 as _, arg3 as _).await,
         0x38 => {
             sys_openat(
                 arg1.into(),
@@ -140,6 +203,15 @@ pub async fn handle_syscall() {
             )
             .await
         }
+        0x4a => {
+            sys_signalfd4(
+                arg1.into(),
+                TUA::from_value(arg2 as _),
+                arg3 as _,
+                arg4 as _,
+            )
+            .await
+        }
         0x4e => {
             sys_readlinkat(
                 arg1.into(),
@@ -160,12 +232,69 @@ pub async fn handle_syscall() {
         }
         0x50 => sys_fstat(arg1.into(), TUA::from_value(arg2 as _)).await,
         0x51 => sys_sync().await,
+        0x55 => sys_timerfd_create(arg1 as _, arg2 as _),
+        0x56 => {
+            sys_timerfd_settime(
+                arg1.into(),
+                arg2 as _,
+                TUA::from_value(arg3 as _),
+                TUA::from_value(arg4 as _),
+            )
+            .await
+        }
+        0x57 => sys_timerfd_gettime(arg1.into(), TUA::from_value(arg2 as _)).await,
+        0x5a => sys_capget(TUA::from_value(arg1 as _), TUA::from_value(arg2 as _)).await,
+        0x5b => sys_capset(TUA::from_value(arg1 as _), TUA::from_value(arg2 as _)).await,
+        0x5c => sys_personality(arg1 as _),
         0x5d => sys_exit(arg1 as _),
         0x5e => sys_exit_group(arg1 as _),
+        0x5f => {
+            sys_waitid(
+                arg1 as _,
+                arg2 as _,
+                TUA::from_value(arg3 as _),
+                arg4 as _,
+                TUA::from_value(arg5 as _),
+            )
+            .await
+        }
         0x60 => sys_set_tid_address(VA::from_value(arg1 as _)).await,
+        0x61 => sys_unshare(arg1 as _),
         0x63 => sys_set_robust_list(TUA::from_value(arg1 as _), arg2 as _).await,
         0x65 => sys_nanosleep(TUA::from_value(arg1 as _), TUA::from_value(arg2 as _)).await,
+        0x66 => sys_getitimer(arg1 as _, TUA::from_value(arg2 as _)).await,
+        0x67 => {
+            sys_setitimer(
+                arg1 as _,
+                TUA::from_value(arg2 as _),
+                TUA::from_value(arg3 as _),
+            )
+            .await
+        }
+        0x6b => {
+            sys_timer_create(
+                arg1 as _,
+                TUA::from_value(arg2 as _),
+                TUA::from_value(arg3 as _),
+            )
+            .await
+        }
+        0x6c => sys_timer_gettime(arg1 as _, TUA::from_value(arg2 as _)).await,
+        0x6d => sys_timer_getoverrun(arg1 as _),
+        0x6e => {
+            sys_timer_settime(
+                arg1 as _,
+                arg2 as _,
+                TUA::from_value(arg3 as _),
+                TUA::from_value(arg4 as _),
+            )
+            .await
+        }
+        0x6f => sys_timer_delete(arg1 as _),
+        0x70 => sys_clock_settime(arg1 as _, TUA::from_value(arg2 as _)).await,
         0x71 => sys_clock_gettime(arg1 as _, TUA::from_value(arg2 as _)).await,
+        0x72 => sys_clock_getres(arg1 as _, TUA::from_value(arg2 as _)).await,
+        0x75 => sys_ptrace(arg1 as _, arg2 as _, arg3 as _, arg4 as _).await,
         0x81 => sys_kill(arg1 as _, arg2.into()),
         0x82 => sys_tkill(arg1 as _, arg2.into()),
         0x84 => sys_sigaltstack(TUA::from_value(arg1 as _), TUA::from_value(arg2 as _)).await,
@@ -196,6 +325,11 @@ pub async fn handle_syscall() {
             return;
         }
         0x8e => sys_reboot(arg1 as _, arg2 as _, arg3 as _, arg4 as _).await,
+        0x8f => sys_setregid(arg1 as _, arg2 as _),
+        0x90 => sys_setgid(arg1 as _),
+        0x91 => sys_setreuid(arg1 as _, arg2 as _),
+        0x92 => sys_setuid(arg1 as _),
+        0x93 => sys_setresuid(arg1 as _, arg2 as _, arg3 as _),
         0x94 => {
             sys_getresuid(
                 TUA::from_value(arg1 as _),
@@ -204,6 +338,7 @@ pub async fn handle_syscall() {
             )
             .await
         }
+        0x95 => sys_setresgid(arg1 as _, arg2 as _, arg3 as _),
         0x96 => {
             sys_getresgid(
                 TUA::from_value(arg1 as _),
@@ -212,12 +347,17 @@ pub async fn handle_syscall() {
             )
             .await
         }
-        0x9a => sys_setpgid(arg1 as _, Pgid(arg2 as _)),
+        0x9a => sys_setpgid(arg1 as _, arg2 as _),
         0x9b => sys_getpgid(arg1 as _),
         0xa0 => sys_uname(TUA::from_value(arg1 as _)).await,
-        0xa3 => Err(KernelError::InvalidValue),
+        0xa1 => sys_sethostname(TUA::from_value(arg1 as _), arg2 as _).await,
+        0xa2 => sys_setdomainname(TUA::from_value(arg1 as _), arg2 as _).await,
+        0xa3 => sys_getrlimit(arg1 as _, TUA::from_value(arg2 as _)).await,
+        0xa4 => sys_setrlimit(arg1 as _, TUA::from_value(arg2 as _)).await,
         0xa6 => sys_umask(arg1 as _).map_err(|e| match e {}),
+        0xa7 => sys_prctl(arg1 as _, arg2 as _, arg3 as _, arg4 as _, arg5 as _).await,
         0xa9 => sys_gettimeofday(TUA::from_value(arg1 as _), TUA::from_value(arg2 as _)).await,
+        0xaa => sys_settimeofday(TUA::from_value(arg1 as _), TUA::from_value(arg2 as _)).await,
         0xac => sys_getpid().map_err(|e| match e {}),
         0xad => sys_getppid().map_err(|e| match e {}),
         0xae => sys_getuid().map_err(|e| match e {}),
@@ -230,6 +370,13 @@ pub async fn handle_syscall() {
             .await
             .map_err(|e| match e {}),
         0xd7 => sys_munmap(VA::from_value(arg1 as usize), arg2 as _).await,
+        0xd8 => sys_mremap(
+            VA::from_value(arg1 as _),
+            arg2 as _,
+            arg3 as _,
+            arg4,
+            VA::from_value(arg5 as _),
+        ),
         0xdc => {
             sys_clone(
                 arg1 as _,
@@ -249,7 +396,23 @@ pub async fn handle_syscall() {
             .await
         }
         0xde => sys_mmap(arg1, arg2, arg3, arg4, arg5.into(), arg6).await,
+        0xe0 => sys_swapon(TUA::from_value(arg1 as _), arg2 as _).await,
+        0xe1 => sys_swapoff(TUA::from_value(arg1 as _)).await,
         0xe2 => sys_mprotect(VA::from_value(arg1 as _), arg2 as _, arg3 as _),
+        0xe3 => sys_msync(VA::from_value(arg1 as _), arg2 as _, arg3 as _).await,
+        0xe4 => sys_mlock(VA::from_value(arg1 as _), arg2 as _).await,
+        0xe5 => sys_munlock(VA::from_value(arg1 as _), arg2 as _),
+        0xe6 => sys_mlockall(arg1 as _).await,
+        0xe7 => sys_munlockall(),
+        0xe8 => {
+            sys_mincore(
+                VA::from_value(arg1 as _),
+                arg2 as _,
+                UA::from_value(arg3 as _),
+            )
+            .await
+        }
+        0xe9 => sys_madvise(VA::from_value(arg1 as _), arg2 as _, arg3 as _).await,
         0x104 => {
             sys_wait4(
                 arg1.cast_signed() as _,
@@ -268,8 +431,16 @@ pub async fn handle_syscall() {
             )
             .await
         }
+        0x10c => sys_setns(arg1.into(), arg2 as _).await,
         0x116 => sys_getrandom(TUA::from_value(arg1 as _), arg2 as _, arg3 as _).await,
+        0x11c => sys_mlock2(VA::from_value(arg1 as _), arg2 as _, arg3 as _).await,
         0x125 => Err(KernelError::NotSupported),
+        0x1a8 => {
+            sys_pidfd_send_signal(arg1.into(), arg2 as _, UA::from_value(arg3 as _), arg4 as _)
+                .await
+        }
+        0x1b2 => sys_pidfd_open(arg1 as _, arg2 as _),
+        0x1b6 => sys_pidfd_getfd(arg1.into(), arg2.into(), arg3 as _).await,
         0x1b7 => {
             sys_faccessat2(
                 arg1.into(),
@@ -281,7 +452,7 @@ pub async fn handle_syscall() {
         }
         _ => panic!(
             "Unhandled syscall 0x{nr:x}, PC: 0x{:x}",
-            current_task().ctx.lock_save_irq().user().elr_el1
+            ArchImpl::user_pc(task.ctx.lock_save_irq().user())
         ),
     };
 
@@ -290,5 +461,12 @@ pub async fn handle_syscall() {
         Err(e) => kern_err_to_syscall(e),
     };
 
-    task.ctx.lock_save_irq().user_mut().x[0] = ret_val.cast_unsigned() as u64;
+    ArchImpl::set_syscall_ret(
+        task.ctx.lock_save_irq().user_mut(),
+        ret_val.cast_unsigned() as u64,
+    );
+
+    if !task.state.lock_save_irq().is_finished() {
+        ptrace::syscall_stop(&task).await;
+    }
 }
@@ -1,23 +1,70 @@
 use crate::{
     arch::{Arch, ArchImpl},
-    memory::uaccess::{UserCopyable, copy_to_user},
+    memory::uaccess::{UserCopyable, copy_from_user_slice, copy_to_user},
+    process::creds::caps::CapSet,
+    sched::current_task,
+    sync::{OnceLock, SpinLock},
 };
-use alloc::ffi::CString;
+use alloc::{ffi::CString, sync::Arc, vec, vec::Vec};
 use core::{ffi::c_char, mem};
-use libkernel::{error::Result, memory::address::TUA};
+use libkernel::{
+    error::{KernelError, Result},
+    memory::address::TUA,
+};
+
+/// The size of each field of `OldUtsname`, including the NUL terminator.
+const UTS_LEN: usize = 65;
 
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct OldUtsname {
-    sysname: [c_char; 65],
-    nodename: [c_char; 65],
-    release: [c_char; 65],
-    version: [c_char; 65],
-    machine: [c_char; 65],
+    sysname: [c_char; UTS_LEN],
+    nodename: [c_char; UTS_LEN],
+    release: [c_char; UTS_LEN],
+    version: [c_char; UTS_LEN],
+    machine: [c_char; UTS_LEN],
+    domainname: [c_char; UTS_LEN],
 }
 
 unsafe impl UserCopyable for OldUtsname {}
 
+/// A UTS namespace, holding the host and domain names seen by the tasks within
+/// it.
+pub struct UtsNamespace {
+    names: SpinLock<UtsNames>,
+}
+
+#[derive(Clone)]
+struct UtsNames {
+    nodename: Vec<u8>,
+    domainname: Vec<u8>,
+}
+
+impl UtsNamespace {
+    /// Returns the initial UTS namespace.
+    pub fn init() -> Arc<Self> {
+        INIT_UTS_NS
+            .get_or_init(|| {
+                Arc::new(Self {
+                    names: SpinLock::new(UtsNames {
+                        nodename: b"moss-machine".to_vec(),
+                        domainname: b"(none)".to_vec(),
+                    }),
+                })
+            })
+            .clone()
+    }
+
+    /// Returns a new namespace, starting with this one's names.
+    pub fn copy(&self) -> Arc<Self> {
+        Arc::new(Self {
+            names: SpinLock::new(self.names.lock_save_irq().clone()),
+        })
+    }
+}
+
+static INIT_UTS_NS: OnceLock<Arc<UtsNamespace>> = OnceLock::new();
+
 fn copy_str_to_c_char_arr(dest: &mut [c_char], src: &[u8]) {
     let len = core::cmp::min(dest.len(), src.len());
 
@@ -37,8 +84,17 @@ pub async fn sys_uname(uts_ptr: TUA<OldUtsname>) -> Result<usize> {
     let sysname = c"Moss".to_bytes_with_nul();
     copy_str_to_c_char_arr(&mut uts.sysname, sysname);
 
-    let nodename = c"moss-machine".to_bytes_with_nul();
-    copy_str_to_c_char_arr(&mut uts.nodename, nodename);
+    let names = current_task()
+        .namespaces
+        .lock_save_irq()
+        .uts
+        .names
+        .lock_save_irq()
+        .clone();
+
+    // The names are at most `UTS_LEN - 1` bytes, leaving room for a NUL.
+    copy_str_to_c_char_arr(&mut uts.nodename, &names.nodename);
+    copy_str_to_c_char_arr(&mut uts.domainname, &names.domainname);
 
     let release = c"4.2.3".to_bytes_with_nul();
     copy_str_to_c_char_arr(&mut uts.release, release);
@@ -54,3 +110,51 @@ pub async fn sys_uname(uts_ptr: TUA<OldUtsname>) -> Result<usize> {
 
     Ok(0)
 }
+
+/// Copies a new host or domain name of `len` bytes from `name`, checking that
+/// the caller may change it.
+async fn copy_name(name: TUA<c_char>, len: usize) -> Result<Vec<u8>> {
+    let task = current_task();
+
+    if !task.creds.lock_save_irq().has_cap(CapSet::CAP_SYS_ADMIN) {
+        return Err(KernelError::NotPermitted);
+    }
+
+    if len >= UTS_LEN {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let mut buf = vec![0; len];
+
+    copy_from_user_slice(name.to_untyped(), &mut buf).await?;
+
+    Ok(buf)
+}
+
+pub async fn sys_sethostname(name: TUA<c_char>, len: usize) -> Result<usize> {
+    let name = copy_name(name, len).await?;
+
+    current_task()
+        .namespaces
+        .lock_save_irq()
+        .uts
+        .names
+        .lock_save_irq()
+        .nodename = name;
+
+    Ok(0)
+}
+
+pub async fn sys_setdomainname(name: TUA<c_char>, len: usize) -> Result<usize> {
+    let name = copy_name(name, len).await?;
+
+    current_task()
+        .namespaces
+        .lock_save_irq()
+        .uts
+        .names
+        .lock_save_irq()
+        .domainname = name;
+
+    Ok(0)
+}
@@ -24,7 +24,6 @@ use libkernel::{
 use log::{error, warn};
 use process::ctx::UserCtx;
 use sched::{current_task, sched_init, spawn_kernel_work, uspc_ret::dispatch_userspace_task};
-use core::arch::asm;
 extern crate alloc;
 
 mod arch;
@@ -38,7 +37,6 @@ mod memory;
 mod process;
 mod sched;
 mod sync;
-use crate::drivers::uart::ns16550::Ns16550;
 #[panic_handler]
 fn on_panic(info: &PanicInfo) -> ! {
     ArchImpl::disable_interrupts();
@@ -118,6 +116,10 @@ async fn launch_init(opts: KOptions) {
         .await
         .expect("Unable to find init");
 
+    if opts.norandmaps {
+        process::exec::aslr::disable();
+    }
+
     let task = current_task();
 
     // Ensure that the exec() call applies to init.
@@ -132,6 +134,7 @@ async fn launch_init(opts: KOptions) {
             Path::new("/dev/console"),
             OpenFlags::O_RDWR,
             VFS.root_inode(),
+            None,
         )
         .await
         .expect("Could not open console for init process");
@@ -151,7 +154,7 @@ async fn launch_init(opts: KOptions) {
             .expect("Could not clone FD");
     }
 
-    process::exec::kernel_exec(inode, vec![init.as_str().to_string()], vec![])
+    process::exec::kernel_exec(&init, inode, vec![init.as_str().to_string()], vec![])
         .await
         .expect("Could not launch init process");
 }
@@ -160,6 +163,7 @@ struct KOptions {
     init: Option<PathBuf>,
     root_fs: Option<String>,
     automounts: Vec<(PathBuf, String)>,
+    norandmaps: bool,
 }
 
 fn parse_args(args: &str) -> KOptions {
@@ -167,6 +171,7 @@ fn parse_args(args: &str) -> KOptions {
         init: None,
         root_fs: None,
         automounts: Vec::new(),
+        norandmaps: false,
     };
 
     let mut opts = Options::new(args.split(" "));
@@ -184,6 +189,7 @@ fn parse_args(args: &str) -> KOptions {
 
                     kopts.automounts.push((PathBuf::from(path), fs.to_string()));
                 }
+                Opt::Long("norandmaps") => kopts.norandmaps = true,
                 Opt::Long(x) => warn!("Unknown option {}", x),
                 Opt::Short(x) => warn!("Unknown option {}", x),
             },
@@ -198,6 +204,10 @@ pub fn kmain(args: String, ctx_frame: *mut UserCtx) {
 
     register_fs_drivers();
 
+    if let Err(e) = memory::vdso::init() {
+        error!("Failed to set up the vDSO: {e}. Continuing without it.");
+    }
+
     let kopts = parse_args(&args);
 
     spawn_kernel_work(launch_init(kopts));
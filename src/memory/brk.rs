@@ -2,6 +2,7 @@ use core::convert::Infallible;
 
 use libkernel::memory::address::VA;
 
+use super::mmap::may_expand_vm;
 use crate::sched::current_task;
 
 /// Handles the `brk` system call.
@@ -21,12 +22,20 @@ use crate::sched::current_task;
 /// - On a failed resize, it returns the current, unchanged break.
 pub async fn sys_brk(addr: VA) -> Result<usize, Infallible> {
     let task = current_task();
-    let mut vm = task.vm.lock_save_irq();
+    let vm = task.vm();
+    let mut vm = vm.lock_save_irq();
 
     // The query case `brk(0)` is special and is handled separately from modifications.
     if addr.is_null() {
         let current_brk_val = vm.current_brk().value();
-        return Ok(current_brk_val as usize);
+        return Ok(current_brk_val);
+    }
+
+    // Growing the heap must not take the process over its memory limits.
+    let growth = addr.value().saturating_sub(vm.current_brk().value());
+
+    if growth != 0 && !may_expand_vm(&vm, growth, true, None) {
+        return Ok(vm.current_brk().value());
     }
 
     // For non-null addresses, attempt to resize the break.
@@ -39,7 +48,7 @@ pub async fn sys_brk(addr: VA) -> Result<usize, Infallible> {
         // The contract is to return the current, unchanged break address.
         Err(_) => {
             let current_brk_val = vm.current_brk().value();
-            Ok(current_brk_val as usize)
+            Ok(current_brk_val)
         }
     }
 }
@@ -1,12 +1,32 @@
-use crate::{process::ProcVM, sched::current_task};
-use alloc::boxed::Box;
+use crate::{
+    process::{
+        ProcVM,
+        thread_group::rsrc_lim::{RlimitId, rlimit},
+    },
+    sched::current_task,
+    sync::SpinLock,
+};
+use alloc::{boxed::Box, sync::Arc, vec};
 use libkernel::{
     PageInfo, UserAddressSpace,
-    error::Result,
-    memory::{address::VA, permissions::PtePermissions, proc_vm::vmarea::AccessKind},
+    error::{KernelError, Result},
+    memory::{
+        address::VA,
+        permissions::PtePermissions,
+        proc_vm::vmarea::{AccessKind, SharedPages, VMAPermissions, VMAccessHint, VMArea},
+        region::VirtMemoryRegion,
+        swap::SwapEntry,
+    },
 };
 
-use super::{PAGE_ALLOC, page::ClaimedPage};
+use super::{
+    PAGE_ALLOC,
+    mmap::may_expand_vm,
+    oom,
+    page::ClaimedPage,
+    shared::SharedMemory,
+    swap::{self, SWAP_CLUSTER},
+};
 
 /// Represents the outcome of a page fault handling attempt.
 ///
@@ -42,26 +62,92 @@ pub fn handle_demand_fault(
     faulting_addr: VA,
     access_kind: AccessKind,
 ) -> Result<FaultResolution> {
+    handle_demand_fault_for(vm, current_task().vm(), faulting_addr, access_kind)
+}
+
+/// Handle a page fault when a PTE is not present in `vm`, which may belong to
+/// a task other than the current one. `vm_ref` must refer to the same VM as
+/// `vm`; it is used to re-take the lock once any deferred I/O has completed.
+pub fn handle_demand_fault_for(
+    vm: &mut ProcVM,
+    vm_ref: Arc<SpinLock<ProcVM>>,
+    faulting_addr: VA,
+    access_kind: AccessKind,
+) -> Result<FaultResolution> {
+    retry_on_oom(do_demand_fault(vm, vm_ref, faulting_addr, access_kind))
+}
+
+fn do_demand_fault(
+    vm: &mut ProcVM,
+    vm_ref: Arc<SpinLock<ProcVM>>,
+    faulting_addr: VA,
+    access_kind: AccessKind,
+) -> Result<FaultResolution> {
+    expand_stack(vm, faulting_addr);
+
     let vma = match vm.find_vma_for_fault(faulting_addr, access_kind) {
         Some(vma) => vma,
         None => return Ok(FaultResolution::Denied),
     }
     .clone();
 
-    let mut new_page = ClaimedPage::alloc_zeroed()?;
+    // The pages of a special mapping are all mapped when it's created, and
+    // there's nothing to fault in.
+    if vma.is_special() {
+        return Ok(FaultResolution::Denied);
+    }
+
+    if let Some(entry) = vm.mm_mut().address_space_mut().swap_entry(faulting_addr) {
+        return handle_swap_fault(vm, vm_ref, entry, faulting_addr);
+    }
+
+    if let Some((pages, index)) = vma.resolve_shared_fault(faulting_addr) {
+        return handle_shared_fault(vm, vm_ref, vma, pages, index, faulting_addr, access_kind);
+    }
+
+    let new_page = ClaimedPage::alloc_zeroed()?;
     let page_va = faulting_addr.page_aligned();
 
     if let Some(vma_read) = vma.resolve_fault(faulting_addr) {
+        let mut reads = vec![(page_va, new_page, vma_read)];
+
+        // Read ahead of the fault as far as the VMA's access hint allows,
+        // stopping at the first page that's already present or has no file
+        // data.
+        for va in (1..readahead_pages(vma.flags().access)).map(|i| page_va.add_pages(i)) {
+            if !vma.contains_address(va) || vm.mm_mut().address_space_mut().translate(va).is_some()
+            {
+                break;
+            }
+
+            let (Some(read), Ok(page)) = (vma.resolve_fault(va), ClaimedPage::alloc_zeroed())
+            else {
+                break;
+            };
+
+            reads.push((va, page, read));
+        }
+
         Ok(FaultResolution::Deferred(Box::new(async move {
-            let pg_buf = &mut new_page.as_slice_mut()
-                [vma_read.page_offset..vma_read.page_offset + vma_read.read_len];
+            for i in 0..reads.len() {
+                let (_, page, read) = &mut reads[i];
+                let pg_buf =
+                    &mut page.as_slice_mut()[read.page_offset..read.page_offset + read.read_len];
+
+                if let Err(e) = read.inode.read_at(read.file_offset, pg_buf).await {
+                    // Only the faulting page itself is needed.
+                    if i == 0 {
+                        return Err(e);
+                    }
 
-            vma_read.inode.read_at(vma_read.file_offset, pg_buf).await?;
+                    reads.truncate(i);
+                    break;
+                }
+            }
 
             // Since the above may have put the task to sleep, revalidate the
             // VMA access.
-            let task = current_task();
-            let mut vm = task.vm.lock_save_irq();
+            let mut vm = vm_ref.lock_save_irq();
 
             // If the handler in the deferred case is no longer valid. Allow
             // the program to back to user-space without touching the page
@@ -74,23 +160,219 @@ pub fn handle_demand_fault(
                 return Ok(());
             }
 
-            vm.mm_mut().address_space_mut().map_page(
-                new_page.leak(),
-                page_va,
-                PtePermissions::from(vma.permissions()),
-            )
+            let address_space = vm.mm_mut().address_space_mut();
+
+            for (va, page, _) in reads {
+                // Another thread may have faulted the page in while we slept.
+                if address_space.translate(va).is_some() {
+                    continue;
+                }
+
+                let pfn = page.leak();
+
+                address_space.map_page(pfn, va, PtePermissions::from(vma.permissions()))?;
+                swap::lru_add(&vm_ref, va, pfn);
+            }
+
+            Ok(())
         })))
     } else {
         // Anonymous mapping, no need to defer.
+        let pfn = new_page.leak();
+
         vm.mm_mut()
             .address_space_mut()
-            .map_page(new_page.leak(), page_va, vma.permissions().into())
-            .unwrap();
+            .map_page(pfn, page_va, vma.permissions().into())?;
+
+        swap::lru_add(&vm_ref, page_va, pfn);
 
         Ok(FaultResolution::Resolved)
     }
 }
 
+/// Handle a page fault on a page which has been swapped out to `entry`, by
+/// reading it back in. A page which is still in the swap cache is mapped
+/// again straight away.
+fn handle_swap_fault(
+    vm: &mut ProcVM,
+    vm_ref: Arc<SpinLock<ProcVM>>,
+    entry: SwapEntry,
+    faulting_addr: VA,
+) -> Result<FaultResolution> {
+    if let Some(page) = swap::cached_page(entry) {
+        swap::swap_in(vm, &vm_ref, faulting_addr, entry, page)?;
+
+        return Ok(FaultResolution::Resolved);
+    }
+
+    Ok(FaultResolution::Deferred(Box::new(async move {
+        let page = swap::read_page(entry).await?;
+
+        // The PTE is checked again once the lock has been re-taken, in case
+        // the page was swapped in, or unmapped, while we slept.
+        swap::swap_in(
+            &mut vm_ref.lock_save_irq(),
+            &vm_ref,
+            faulting_addr,
+            entry,
+            page,
+        )
+    })))
+}
+
+/// Turns a failure to allocate memory while handling a fault, whether straight
+/// away or in deferred work, into an attempt to free some, after which the
+/// faulting access is retried.
+fn retry_on_oom(resolution: Result<FaultResolution>) -> Result<FaultResolution> {
+    match resolution {
+        Err(KernelError::NoMemory) => Ok(FaultResolution::Deferred(Box::new(free_memory()))),
+        Ok(FaultResolution::Deferred(fut)) => Ok(FaultResolution::Deferred(Box::new(async {
+            match Box::into_pin(fut).await {
+                Err(KernelError::NoMemory) => free_memory().await,
+                res => res,
+            }
+        }))),
+        resolution => resolution,
+    }
+}
+
+/// Frees some memory once it has run out, by swapping pages out or, failing
+/// that, by killing a process. Fails with `NoMemory` if the current process
+/// was killed.
+async fn free_memory() -> Result<()> {
+    if swap::is_enabled() && swap::reclaim(SWAP_CLUSTER).await > 0 {
+        return Ok(());
+    }
+
+    oom::out_of_memory().await
+}
+
+/// Grows the stack down to cover `addr`, if it lies below a grows-down VMA and
+/// the growth is within the current task's `RLIMIT_STACK` and other memory
+/// limits. Otherwise, the fault is left to be denied.
+fn expand_stack(vm: &mut ProcVM, addr: VA) {
+    let Some(stack) = vm.mm().find_stack_above(addr) else {
+        return;
+    };
+
+    let growth = stack.region().start_address().value() - addr.page_aligned().value();
+    let size = stack.region().size() + growth;
+
+    if size as u64 > rlimit(RlimitId::STACK)
+        || !may_expand_vm(vm, growth, stack.permissions().write, None)
+    {
+        return;
+    }
+
+    let _ = vm.mm_mut().grow_stack(addr);
+}
+
+/// Returns the number of pages of a private file mapping that are read on a
+/// fault, including the faulting page, for a VMA with the access `hint`.
+fn readahead_pages(hint: VMAccessHint) -> usize {
+    match hint {
+        VMAccessHint::Normal => 4,
+        VMAccessHint::Sequential => 16,
+        VMAccessHint::Random => 1,
+    }
+}
+
+/// Faults in the pages of `region` of `vm` which aren't yet present, as if
+/// they had been read. Pages which can't be read, such as those of a
+/// `PROT_NONE` mapping, are skipped.
+pub async fn populate(vm: &Arc<SpinLock<ProcVM>>, region: VirtMemoryRegion) -> Result<()> {
+    for va in region.iter_pages() {
+        loop {
+            let resolution = {
+                let mut vm_lock = vm.lock_save_irq();
+
+                if vm_lock.mm_mut().address_space_mut().translate(va).is_some() {
+                    break;
+                }
+
+                handle_demand_fault_for(&mut vm_lock, vm.clone(), va, AccessKind::Read)?
+            };
+
+            match resolution {
+                FaultResolution::Resolved => continue,
+                FaultResolution::Denied => break,
+                FaultResolution::Deferred(fut) => Box::into_pin(fut).await?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a page fault when a PTE is not present in a shared VMA, by mapping the
+/// page at `index` of its shared memory object, `pages`.
+fn handle_shared_fault(
+    vm: &mut ProcVM,
+    vm_ref: Arc<SpinLock<ProcVM>>,
+    vma: VMArea,
+    pages: Arc<dyn SharedPages>,
+    index: u64,
+    faulting_addr: VA,
+    access_kind: AccessKind,
+) -> Result<FaultResolution> {
+    let shared = SharedMemory::from_pages(&pages);
+    let page_va = faulting_addr.page_aligned();
+
+    // A page of a shared file mapping is mapped read-only until it's written
+    // to, so that only pages which may be dirty are written back. See
+    // `handle_protection_fault`.
+    let writable =
+        vma.permissions().write && (access_kind == AccessKind::Write || !shared.is_file_backed());
+
+    let perms = PtePermissions::from(VMAPermissions {
+        write: writable,
+        ..vma.permissions()
+    });
+
+    if let Some(page) = shared.get(index, writable) {
+        vm.mm_mut()
+            .address_space_mut()
+            .map_page(page, page_va, perms)?;
+
+        return Ok(FaultResolution::Resolved);
+    }
+
+    if !shared.is_file_backed() {
+        let page = shared.get_or_insert(index, ClaimedPage::alloc_zeroed()?, writable);
+
+        vm.mm_mut()
+            .address_space_mut()
+            .map_page(page, page_va, perms)?;
+
+        return Ok(FaultResolution::Resolved);
+    }
+
+    Ok(FaultResolution::Deferred(Box::new(async move {
+        let shared = SharedMemory::from_pages(&pages);
+        let new_page = shared.read_page(index).await?;
+
+        // As for a private file mapping, revalidate the VMA access after
+        // sleeping.
+        let mut vm = vm_ref.lock_save_irq();
+
+        let is_vma_still_valid = vm
+            .find_vma_for_fault(faulting_addr, access_kind)
+            .is_some_and(|validated_vma| *validated_vma == vma);
+
+        if !is_vma_still_valid {
+            return Ok(());
+        }
+
+        // Another task may have read the page in the meantime, in which case
+        // its copy is used.
+        let page = shared.get_or_insert(index, new_page, writable);
+
+        vm.mm_mut()
+            .address_space_mut()
+            .map_page(page, page_va, perms)
+    })))
+}
+
 /// Handle a page fault when a page is present, but the access kind differ from
 /// permissble accessees defined in the PTE, a 'protection' fault.
 pub fn handle_protection_fault(
@@ -98,6 +380,15 @@ pub fn handle_protection_fault(
     faulting_addr: VA,
     access_kind: AccessKind,
     pg_info: PageInfo,
+) -> Result<FaultResolution> {
+    retry_on_oom(do_protection_fault(vm, faulting_addr, access_kind, pg_info))
+}
+
+fn do_protection_fault(
+    vm: &mut ProcVM,
+    faulting_addr: VA,
+    access_kind: AccessKind,
+    pg_info: PageInfo,
 ) -> Result<FaultResolution> {
     // Detect CoW condition.
     if access_kind == AccessKind::Write && pg_info.perms.is_cow() {
@@ -139,13 +430,31 @@ pub fn handle_protection_fault(
             dst.copy_from_slice(src);
 
             // Remap the existing CoW mapping with the fresh page.
+            let pfn = new_page.leak();
+
             vm.mm_mut()
                 .address_space_mut()
-                .remap(faulting_addr, new_page.leak(), new_pte_perms)
-                .unwrap();
+                .remap(faulting_addr, pfn, new_pte_perms)?;
+
+            swap::lru_add(&current_task().vm(), faulting_addr, pfn);
 
             Ok(FaultResolution::Resolved)
         }
+    } else if let Some(vma) = vm.find_vma_for_fault(faulting_addr, access_kind)
+        && access_kind == AccessKind::Write
+        && let Some((pages, index)) = vma.resolve_shared_fault(faulting_addr)
+    {
+        // The first write to a page of a shared file mapping, which was mapped
+        // read-only until now. It may be dirty from here on.
+        let perms = PtePermissions::from(vma.permissions());
+
+        SharedMemory::from_pages(&pages).mark_dirty(index);
+
+        vm.mm_mut()
+            .address_space_mut()
+            .protect_range(faulting_addr.page_region(), perms)?;
+
+        Ok(FaultResolution::Resolved)
     } else {
         // Any other protection fault *should* be a segmentation fault. Let's
         // just verify.
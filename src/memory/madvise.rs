@@ -0,0 +1,104 @@
+//! The `madvise` and `mincore` system calls.
+
+use super::{fault::populate, shared::SharedMemory, uaccess::copy_to_user_slice};
+use crate::sched::current_task;
+use alloc::vec::Vec;
+use libkernel::{
+    UserAddressSpace,
+    error::{KernelError, Result},
+    memory::{
+        address::{UA, VA},
+        proc_vm::{
+            memory_map::MemoryAdvice,
+            vmarea::{VMAccessHint, VMAreaKind},
+        },
+        region::VirtMemoryRegion,
+    },
+};
+
+const MADV_NORMAL: u32 = 0;
+const MADV_RANDOM: u32 = 1;
+const MADV_SEQUENTIAL: u32 = 2;
+const MADV_WILLNEED: u32 = 3;
+const MADV_DONTNEED: u32 = 4;
+const MADV_FREE: u32 = 8;
+const MADV_DONTFORK: u32 = 10;
+const MADV_DOFORK: u32 = 11;
+
+pub async fn sys_madvise(addr: VA, len: usize, advice: u32) -> Result<usize> {
+    let advice = match advice {
+        MADV_NORMAL => MemoryAdvice::Access(VMAccessHint::Normal),
+        MADV_RANDOM => MemoryAdvice::Access(VMAccessHint::Random),
+        MADV_SEQUENTIAL => MemoryAdvice::Access(VMAccessHint::Sequential),
+        MADV_WILLNEED => MemoryAdvice::WillNeed,
+        MADV_DONTNEED => MemoryAdvice::DontNeed,
+        MADV_FREE => MemoryAdvice::Free,
+        MADV_DONTFORK => MemoryAdvice::DontFork,
+        MADV_DOFORK => MemoryAdvice::DoFork,
+        _ => return Err(KernelError::InvalidValue),
+    };
+
+    let region = VirtMemoryRegion::new(addr, len);
+    let task = current_task();
+    let vm = task.vm();
+
+    let file_regions: Vec<_> = {
+        let mut vm = vm.lock_save_irq();
+
+        vm.mm_mut().madvise(region, advice)?;
+
+        if advice != MemoryAdvice::WillNeed {
+            return Ok(0);
+        }
+
+        // Only file pages are read ahead of time; anonymous memory would just
+        // be zero-filled.
+        vm.mm()
+            .vmas()
+            .filter(|vma| match vma.kind() {
+                VMAreaKind::File(_) => true,
+                VMAreaKind::Shared(mapping) => {
+                    SharedMemory::from_pages(&mapping.pages()).is_file_backed()
+                }
+                VMAreaKind::Anon | VMAreaKind::Special => false,
+            })
+            .filter_map(|vma| vma.region().intersection(region.align_to_page_boundary()))
+            .collect()
+    };
+
+    for region in file_regions {
+        populate(&vm, region).await?;
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_mincore(addr: VA, len: usize, vec: UA) -> Result<usize> {
+    if !addr.is_page_aligned() {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let region = VirtMemoryRegion::new(addr, len).align_to_page_boundary();
+
+    // A page is reported as resident if it's mapped in the page tables.
+    let residency: Vec<u8> = {
+        let task = current_task();
+        let vm = task.vm();
+        let mut vm = vm.lock_save_irq();
+
+        if !vm.mm().is_region_mapped(region) {
+            return Err(KernelError::NoMemory);
+        }
+
+        let address_space = vm.mm_mut().address_space_mut();
+
+        region
+            .iter_pages()
+            .map(|va| address_space.translate(va).is_some() as u8)
+            .collect()
+    };
+
+    copy_to_user_slice(&residency, vec).await?;
+
+    Ok(0)
+}
@@ -0,0 +1,140 @@
+//! The `mlock` family of system calls, which lock pages in memory.
+
+use super::fault::populate;
+use crate::{
+    process::{
+        creds::caps::CapSet,
+        thread_group::rsrc_lim::{RlimitId, rlimit},
+    },
+    sched::current_task,
+};
+use alloc::vec::Vec;
+use libkernel::{
+    error::{KernelError, Result},
+    memory::{PAGE_SIZE, address::VA, region::VirtMemoryRegion},
+};
+
+const MLOCK_ONFAULT: u32 = 1;
+
+const MCL_CURRENT: u32 = 1;
+const MCL_FUTURE: u32 = 2;
+const MCL_ONFAULT: u32 = 4;
+
+/// Returns whether the current task may have `locked` bytes of memory locked,
+/// without exceeding its `RLIMIT_MEMLOCK`.
+pub fn may_lock(locked: usize) -> bool {
+    locked as u64 <= rlimit(RlimitId::MEMLOCK)
+        || current_task()
+            .creds
+            .lock_save_irq()
+            .has_cap(CapSet::CAP_IPC_LOCK)
+}
+
+/// Returns the pages covering `len` bytes from `addr`.
+fn lock_region(addr: VA, len: usize) -> VirtMemoryRegion {
+    VirtMemoryRegion::from_start_end_address(
+        addr.page_aligned(),
+        addr.add_bytes(len).align_up(PAGE_SIZE),
+    )
+}
+
+async fn do_mlock(addr: VA, len: usize, on_fault: bool) -> Result<usize> {
+    let region = lock_region(addr, len);
+
+    if region.size() == 0 {
+        return Ok(0);
+    }
+
+    let task = current_task();
+    let vm = task.vm();
+
+    {
+        let mut vm = vm.lock_save_irq();
+        let mm = vm.mm();
+        let locked = mm.locked_size() - mm.locked_size_in(region) + region.size();
+
+        if !may_lock(locked) {
+            return Err(KernelError::NoMemory);
+        }
+
+        vm.mm_mut().mlock(region, true)?;
+    }
+
+    if !on_fault {
+        populate(&vm, region).await?;
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_mlock(addr: VA, len: usize) -> Result<usize> {
+    do_mlock(addr, len, false).await
+}
+
+pub async fn sys_mlock2(addr: VA, len: usize, flags: u32) -> Result<usize> {
+    if flags & !MLOCK_ONFAULT != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    do_mlock(addr, len, flags & MLOCK_ONFAULT != 0).await
+}
+
+pub fn sys_munlock(addr: VA, len: usize) -> Result<usize> {
+    let region = lock_region(addr, len);
+
+    if region.size() == 0 {
+        return Ok(0);
+    }
+
+    current_task()
+        .vm()
+        .lock_save_irq()
+        .mm_mut()
+        .mlock(region, false)?;
+
+    Ok(0)
+}
+
+pub async fn sys_mlockall(flags: u32) -> Result<usize> {
+    if flags == 0 || flags & !(MCL_CURRENT | MCL_FUTURE | MCL_ONFAULT) != 0 || flags == MCL_ONFAULT
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let task = current_task();
+    let vm = task.vm();
+
+    // As on Linux, the flags replace those of any previous call.
+    let regions: Vec<_> = {
+        let mut vm = vm.lock_save_irq();
+        let mm = vm.mm_mut();
+
+        if flags & MCL_CURRENT != 0 && !may_lock(mm.total_size()) {
+            return Err(KernelError::NoMemory);
+        }
+
+        mm.set_lock_future(flags & MCL_FUTURE != 0);
+        mm.mlock_all(flags & MCL_CURRENT != 0);
+
+        mm.vmas().map(|vma| vma.region()).collect()
+    };
+
+    if flags & MCL_CURRENT != 0 && flags & MCL_ONFAULT == 0 {
+        for region in regions {
+            populate(&vm, region).await?;
+        }
+    }
+
+    Ok(0)
+}
+
+pub fn sys_munlockall() -> Result<usize> {
+    let task = current_task();
+    let vm = task.vm();
+    let mut vm = vm.lock_save_irq();
+
+    vm.mm_mut().set_lock_future(false);
+    vm.mm_mut().mlock_all(false);
+
+    Ok(0)
+}
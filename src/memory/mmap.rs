@@ -1,13 +1,24 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::{process::fd_table::Fd, sched::current_task};
+use super::{mlock::may_lock, shared::SharedMemory};
+use crate::{
+    process::{
+        ProcVM,
+        fd_table::Fd,
+        thread_group::rsrc_lim::{RlimitId, rlimit},
+    },
+    sched::current_task,
+};
+use alloc::{sync::Arc, vec::Vec};
 use libkernel::{
     error::{KernelError, Result},
+    fs::{OpenFlags, pathbuf::PathBuf},
     memory::{
+        PAGE_MASK, PAGE_SIZE,
         address::VA,
         proc_vm::{
-            memory_map::AddressRequest,
-            vmarea::{VMAPermissions, VMAreaKind},
+            memory_map::{AddressRequest, RemapRequest},
+            vmarea::{SharedPages, VMAFlags, VMAPermissions, VMAreaKind},
         },
         region::VirtMemoryRegion,
     },
@@ -17,13 +28,23 @@ const PROT_READ: u64 = 1;
 const PROT_WRITE: u64 = 2;
 const PROT_EXEC: u64 = 4;
 
-const MAP_FILE: u64 = 0x0000;
 const MAP_SHARED: u64 = 0x0001;
 const MAP_PRIVATE: u64 = 0x0002;
+const MAP_SHARED_VALIDATE: u64 = 0x0003;
+const MAP_TYPE: u64 = 0x000f;
 const MAP_FIXED: u64 = 0x0010;
 const MAP_FIXED_NOREPLACE: u64 = 0x100000;
 const MAP_ANON: u64 = 0x0020;
 const MAP_ANONYMOUS: u64 = 0x0020;
+const MAP_GROWSDOWN: u64 = 0x0100;
+
+const MREMAP_MAYMOVE: u64 = 1;
+const MREMAP_FIXED: u64 = 2;
+const MREMAP_DONTUNMAP: u64 = 4;
+
+const MS_ASYNC: u32 = 1;
+const MS_INVALIDATE: u32 = 2;
+const MS_SYNC: u32 = 4;
 
 /// Determines the minimal address that user-space is allowed to specify for
 /// MAP_FIXED{,_NOREPLACE}.
@@ -37,6 +58,40 @@ fn prot_to_perms(prot: u64) -> VMAPermissions {
     }
 }
 
+/// Returns whether `vm` may grow by `len` bytes without exceeding the current
+/// task's `RLIMIT_AS` or, if the new memory is `writable`, `RLIMIT_DATA`. If
+/// new mappings are locked in memory, `RLIMIT_MEMLOCK` applies too.
+///
+/// If the new memory `replaces` a region, whatever is mapped there now is
+/// unmapped to make way for it, and so doesn't count towards the limits.
+pub fn may_expand_vm(
+    vm: &ProcVM,
+    len: usize,
+    writable: bool,
+    replaces: Option<VirtMemoryRegion>,
+) -> bool {
+    let len = len.next_multiple_of(PAGE_SIZE);
+    let mm = vm.mm();
+    let (size, data_size, locked_size) = match replaces {
+        Some(region) => (
+            mm.total_size() - mm.size_in(region),
+            mm.data_size() - mm.data_size_in(region),
+            mm.locked_size() - mm.locked_size_in(region),
+        ),
+        None => (mm.total_size(), mm.data_size(), mm.locked_size()),
+    };
+
+    if (size + len) as u64 > rlimit(RlimitId::AS) {
+        return false;
+    }
+
+    if mm.lock_future() && !may_lock(locked_size + len) {
+        return false;
+    }
+
+    !writable || (data_size + len) as u64 <= rlimit(RlimitId::DATA)
+}
+
 /// Handles the `mmap` system call.
 ///
 /// # Arguments
@@ -58,13 +113,14 @@ pub async fn sys_mmap(
     }
 
     // Ensure mapping sharability has been specified:
-    if (flags & (MAP_SHARED | MAP_PRIVATE)) == 0 {
-        return Err(KernelError::InvalidValue);
-    }
+    let shared = match flags & MAP_TYPE {
+        MAP_SHARED | MAP_SHARED_VALIDATE => true,
+        MAP_PRIVATE => false,
+        _ => return Err(KernelError::InvalidValue),
+    };
 
-    // TODO: Shared Mappings.
-    if (flags & MAP_SHARED) != 0 {
-        return Err(KernelError::NotSupported);
+    if offset & PAGE_MASK as u64 != 0 {
+        return Err(KernelError::InvalidValue);
     }
 
     // `MAP_FIXED` and `MAP_FIXED_NOREPLACE` are mutually exclusive.
@@ -86,9 +142,19 @@ pub async fn sys_mmap(
 
     let requested_len = len as usize;
 
+    let mut file_name = None;
+
+    // Whether the mapping may ever be made writable.
+    let mut may_write = true;
+
     let kind = if flags & (MAP_ANON | MAP_ANONYMOUS) != 0 {
-        VMAreaKind::Anon
-    } else if flags == MAP_FILE {
+        if shared {
+            VMAreaKind::new_shared(SharedMemory::new_anon(), 0)
+        } else {
+            VMAreaKind::Anon
+        }
+    } else {
+        // Without MAP_ANONYMOUS, this is a MAP_FILE mapping.
         let fd = current_task()
             .fd_table
             .lock_save_irq()
@@ -97,10 +163,24 @@ pub async fn sys_mmap(
 
         let inode = fd.inode().ok_or(KernelError::BadFd)?;
 
-        VMAreaKind::new_file(inode, offset, len)
-    } else {
-        // One of MAP_FILE or MAP_ANONYMOUS must be set.
-        return Err(KernelError::InvalidValue);
+        // Remember the file's path, for the file list of a core dump.
+        if let Some(path) = fd.path() {
+            file_name = Some((inode.id(), PathBuf::from(path.as_str())));
+        }
+
+        if shared {
+            // Writes through a shared mapping reach the file, so it must have
+            // been opened for writing, now or if it's made writable later.
+            may_write = fd.flags().await.intersection(OpenFlags::O_ACCMODE) == OpenFlags::O_RDWR;
+
+            if permissions.write && !may_write {
+                return Err(KernelError::AccessDenied);
+            }
+
+            VMAreaKind::new_shared(SharedMemory::for_file(inode), offset)
+        } else {
+            VMAreaKind::new_file(inode, offset, len)
+        }
     };
 
     let address_request = if addr.is_null() {
@@ -123,21 +203,172 @@ pub async fn sys_mmap(
     };
 
     // Lock the task and call the core memory manager to perform the mapping.
-    let new_mapping_addr = current_task().vm.lock_save_irq().mm_mut().mmap(
+    let task = current_task();
+    let vm = task.vm();
+    let mut vm = vm.lock_save_irq();
+
+    // A `MAP_FIXED` mapping replaces anything already mapped in its way.
+    let replaces = match address_request {
+        AddressRequest::Fixed {
+            address,
+            permit_overlap: true,
+        } => Some(VirtMemoryRegion::new(address, requested_len).align_to_page_boundary()),
+        _ => None,
+    };
+
+    // Shared mappings aren't private data, so don't count towards RLIMIT_DATA.
+    if !may_expand_vm(&vm, requested_len, permissions.write && !shared, replaces) {
+        return Err(KernelError::NoMemory);
+    }
+
+    // Only private anonymous mappings may grow down, as stacks: any other
+    // `MAP_GROWSDOWN` mapping is refused.
+    let vma_flags = VMAFlags {
+        grows_down: flags & MAP_GROWSDOWN != 0,
+        may_write,
+        ..VMAFlags::default()
+    };
+
+    let new_mapping_addr = vm.mm_mut().mmap_with_flags(
         address_request,
         requested_len,
         permissions,
         kind,
+        vma_flags,
     )?;
 
+    if let Some((inode, path)) = file_name {
+        vm.set_file_name(inode, path);
+    }
+
     Ok(new_mapping_addr.value())
 }
 
+/// Returns the shared memory objects of the shared file mappings which
+/// overlap `region`.
+fn shared_files_in(vm: &ProcVM, region: VirtMemoryRegion) -> Vec<Arc<dyn SharedPages>> {
+    let mut files: Vec<Arc<dyn SharedPages>> = Vec::new();
+
+    for vma in vm.mm().vmas().filter(|vma| vma.region().overlaps(region)) {
+        if let VMAreaKind::Shared(mapping) = vma.kind()
+            && SharedMemory::from_pages(&mapping.pages()).is_file_backed()
+            && !files.iter().any(|f| Arc::ptr_eq(f, &mapping.pages()))
+        {
+            files.push(mapping.pages());
+        }
+    }
+
+    files
+}
+
 pub async fn sys_munmap(addr: VA, len: usize) -> Result<usize> {
     let region = VirtMemoryRegion::new(addr, len);
 
-    // TODO: reclaim pages.
-    current_task().vm.lock_save_irq().mm_mut().munmap(region)?;
+    let shared_files = {
+        let task = current_task();
+        let vm = task.vm();
+        let mut vm = vm.lock_save_irq();
+        let shared_files = shared_files_in(&vm, region);
+
+        vm.mm_mut().munmap(region)?;
+
+        shared_files
+    };
+
+    // Write back any shared file pages which were written through the
+    // mapping.
+    for pages in shared_files {
+        SharedMemory::from_pages(&pages).writeback().await?;
+    }
+
+    Ok(0)
+}
+
+pub fn sys_mremap(
+    old_addr: VA,
+    old_size: usize,
+    new_size: usize,
+    flags: u64,
+    new_addr: VA,
+) -> Result<usize> {
+    if flags & !(MREMAP_MAYMOVE | MREMAP_FIXED | MREMAP_DONTUNMAP) != 0
+        || (flags & (MREMAP_FIXED | MREMAP_DONTUNMAP) != 0 && flags & MREMAP_MAYMOVE == 0)
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let request = if flags & MREMAP_FIXED != 0 {
+        if new_addr < VA::from_value(MMAP_MIN_ADDR.load(Ordering::SeqCst)) {
+            return Err(KernelError::InvalidValue);
+        }
+
+        RemapRequest::Fixed(new_addr)
+    } else if flags & MREMAP_MAYMOVE != 0 {
+        RemapRequest::MayMove
+    } else {
+        RemapRequest::InPlace
+    };
+
+    let dont_unmap = flags & MREMAP_DONTUNMAP != 0;
+
+    let task = current_task();
+    let vm = task.vm();
+    let mut vm = vm.lock_save_irq();
+
+    // With `MREMAP_DONTUNMAP`, the old mapping is kept alongside the new one.
+    let growth = if dont_unmap {
+        new_size
+    } else {
+        new_size.saturating_sub(old_size)
+    };
+
+    if growth > 0 {
+        let vma = vm.mm().find_vma(old_addr).ok_or(KernelError::Fault)?;
+        let writable = vma.permissions().write && !vma.is_shared();
+
+        if !may_expand_vm(&vm, growth, writable, None) {
+            return Err(KernelError::NoMemory);
+        }
+    }
+
+    let new_addr = vm.mm_mut().mremap(
+        VirtMemoryRegion::new(old_addr, old_size),
+        new_size,
+        request,
+        dont_unmap,
+    )?;
+
+    Ok(new_addr.value())
+}
+
+pub async fn sys_msync(addr: VA, len: usize, flags: u32) -> Result<usize> {
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+        || !addr.is_page_aligned()
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let region = VirtMemoryRegion::new(addr, len).align_to_page_boundary();
+
+    let shared_files = {
+        let task = current_task();
+        let vm = task.vm();
+        let vm = vm.lock_save_irq();
+
+        // The whole range must be mapped.
+        if !vm.mm().is_region_mapped(region) {
+            return Err(KernelError::NoMemory);
+        }
+
+        shared_files_in(&vm, region)
+    };
+
+    // Pages are always written back straight away, so `MS_ASYNC` is no
+    // different from `MS_SYNC`. There's no separate page cache to invalidate.
+    for pages in shared_files {
+        SharedMemory::from_pages(&pages).writeback().await?;
+    }
 
     Ok(0)
 }
@@ -145,12 +376,17 @@ pub async fn sys_munmap(addr: VA, len: usize) -> Result<usize> {
 pub fn sys_mprotect(addr: VA, len: usize, prot: u64) -> Result<usize> {
     let perms = prot_to_perms(prot);
     let region = VirtMemoryRegion::new(addr, len);
+    let task = current_task();
+    let vm = task.vm();
+    let mut vm = vm.lock_save_irq();
+
+    // The vDSO's pages are shared by every process, so must never be made
+    // writable.
+    if perms.write && vm.vdso().is_some_and(|vdso| vdso.overlaps(region)) {
+        return Err(KernelError::AccessDenied);
+    }
 
-    current_task()
-        .vm
-        .lock_save_irq()
-        .mm_mut()
-        .mprotect(region, perms)?;
+    vm.mm_mut().mprotect(region, perms)?;
 
     Ok(0)
 }
@@ -10,9 +10,16 @@ use libkernel::memory::{
 
 pub mod brk;
 pub mod fault;
+pub mod madvise;
+pub mod mlock;
 pub mod mmap;
+pub mod oom;
 pub mod page;
+pub mod remote;
+pub mod shared;
+pub mod swap;
 pub mod uaccess;
+pub mod vdso;
 
 pub type PageOffsetTranslator = libkernel::memory::pg_offset::PageOffsetTranslator<ArchImpl>;
 
@@ -0,0 +1,264 @@
+//! The out-of-memory killer.
+//!
+//! When a page fault can't allocate memory, and swapping pages out hasn't
+//! freed any, a process is killed to make room. Each process is scored by the
+//! pages it has in memory or swapped out, adjusted by its `oom_score_adj`, and
+//! the highest scoring one is sent `SIGKILL`. The faulting access is retried
+//! once the victim has exited and its memory has been freed.
+//!
+//! Signals don't interrupt a task blocked in a syscall, so a victim may never
+//! get as far as exiting. One which hasn't exited within a grace period is
+//! given up on, and another process is killed.
+
+use super::{PAGE_ALLOC, swap};
+use crate::{
+    drivers::timer::{sleep, uptime},
+    process::{
+        ProcVM,
+        creds::caps::CapSet,
+        thread_group::{
+            ProcessState, ThreadGroup,
+            signal::{SigId, SigSet},
+        },
+    },
+    sched::current_task,
+    sync::SpinLock,
+};
+use alloc::{
+    string::ToString,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{pin::pin, time::Duration};
+use futures::future::select;
+use libkernel::{
+    error::{KernelError, Result},
+    memory::{
+        PAGE_SIZE,
+        oom::{OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN, badness, choose_victim},
+    },
+};
+use log::{error, info};
+
+/// How long to wait for a victim to exit before the faulting access is
+/// retried. A victim which hasn't exited by then is waited for again, rather
+/// than another process being killed, until its grace period is up.
+const VICTIM_EXIT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long a victim is given to exit before it's assumed to be stuck.
+const VICTIM_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// The process most recently killed to free memory.
+struct Victim {
+    tg: Weak<ThreadGroup>,
+    /// The uptime at which it was killed.
+    killed_at: Duration,
+}
+
+static VICTIM: SpinLock<Option<Victim>> = SpinLock::new(None);
+
+/// A process which may be killed, along with its memory usage, in pages.
+struct Candidate {
+    tg: Arc<ThreadGroup>,
+    vm: Arc<SpinLock<ProcVM>>,
+    total_vm: usize,
+    resident: usize,
+    swapped: usize,
+    oom_score_adj: i16,
+    /// The process's score, or `None` if it mustn't be killed, or already has
+    /// been.
+    points: Option<u64>,
+}
+
+/// Returns every process which has an address space, along with its score.
+fn candidates() -> Vec<Candidate> {
+    let total_pages = PAGE_ALLOC.get().unwrap().total_page_count() + swap::total_pages();
+
+    let mut candidates: Vec<_> = ThreadGroup::all()
+        .into_iter()
+        .filter(|tg| !tg.tgid.is_idle())
+        .filter(|tg| *tg.state.lock_save_irq() == ProcessState::Running)
+        .filter_map(|tg| {
+            let vm = tg.vm()?;
+            let oom_score_adj = *tg.oom_score_adj.lock_save_irq();
+
+            let (total_vm, (resident, swapped)) = {
+                let vm = vm.lock_save_irq();
+
+                (vm.mm().total_size() / PAGE_SIZE, vm.mm().page_counts())
+            };
+
+            let is_killed = tg.signals.lock_save_irq().is_pending(SigSet::SIGKILL);
+
+            let points = if tg.tgid.is_init() || is_killed {
+                None
+            } else {
+                badness(resident, swapped, oom_score_adj, total_pages)
+            };
+
+            Some(Candidate {
+                tg,
+                vm,
+                total_vm,
+                resident,
+                swapped,
+                oom_score_adj,
+                points,
+            })
+        })
+        .collect();
+
+    // A process's memory can't be freed until every process sharing its
+    // address space has gone too, so none of them may be killed if one of them
+    // mustn't be.
+    let unkillable: Vec<_> = candidates
+        .iter()
+        .filter(|c| c.points.is_none())
+        .map(|c| c.vm.clone())
+        .collect();
+
+    for c in candidates.iter_mut() {
+        if unkillable.iter().any(|vm| Arc::ptr_eq(vm, &c.vm)) {
+            c.points = None;
+        }
+    }
+
+    candidates
+}
+
+/// Logs the memory usage of every candidate, as the kernel saw it when it ran
+/// out of memory.
+fn report(candidates: &[Candidate]) {
+    let task = current_task();
+
+    info!(
+        "{} invoked oom-killer: oom_score_adj={}",
+        *task.comm.lock_save_irq(),
+        *task.process.oom_score_adj.lock_save_irq()
+    );
+    info!(
+        "Mem: {} pages free of {}",
+        PAGE_ALLOC.get().unwrap().free_page_count(),
+        PAGE_ALLOC.get().unwrap().total_page_count()
+    );
+    info!("Tasks state (memory values in pages):");
+    info!("[  pid  ]   uid total_vm      rss swapents oom_score_adj name");
+
+    for c in candidates {
+        info!(
+            "[{:>7}] {:>5} {:>8} {:>8} {:>8} {:>13} {}",
+            c.tg.tgid,
+            c.tg.creds().map_or(0, |creds| u32::from(creds.uid())),
+            c.total_vm,
+            c.resident,
+            c.swapped,
+            c.oom_score_adj,
+            c.tg.comm().map(|comm| comm.to_string()).unwrap_or_default()
+        );
+    }
+}
+
+/// Chooses the process with the highest score and kills it, along with any
+/// other processes sharing its address space, since its memory can't be freed
+/// until they're gone too. Returns the victim.
+///
+/// # Panics
+///
+/// Panics if there is no process which may be killed.
+fn kill_victim() -> Arc<ThreadGroup> {
+    let candidates = candidates();
+
+    report(&candidates);
+
+    let Some(victim) = choose_victim(candidates.iter().map(|c| c.points)).map(|i| &candidates[i])
+    else {
+        panic!("Out of memory and no killable processes");
+    };
+
+    error!(
+        "Out of memory: Killed process {} ({}) total-vm:{}kB, rss:{}kB, swap:{}kB, oom_score_adj:{}",
+        victim.tg.tgid,
+        victim
+            .tg
+            .comm()
+            .map(|comm| comm.to_string())
+            .unwrap_or_default(),
+        victim.total_vm * PAGE_SIZE / 1024,
+        victim.resident * PAGE_SIZE / 1024,
+        victim.swapped * PAGE_SIZE / 1024,
+        victim.oom_score_adj
+    );
+
+    for c in candidates.iter().filter(|c| Arc::ptr_eq(&c.vm, &victim.vm)) {
+        c.tg.signals.lock_save_irq().set_pending(SigId::SIGKILL);
+    }
+
+    victim.tg.clone()
+}
+
+/// Sets the `oom_score_adj` of `tg`, clamped to the valid range. Lowering it,
+/// which makes the process less likely to be killed, requires
+/// `CAP_SYS_RESOURCE`.
+pub fn set_oom_score_adj(tg: &ThreadGroup, oom_score_adj: i64) -> Result<()> {
+    let oom_score_adj =
+        oom_score_adj.clamp(OOM_SCORE_ADJ_MIN as i64, OOM_SCORE_ADJ_MAX as i64) as i16;
+
+    let mut current = tg.oom_score_adj.lock_save_irq();
+
+    if oom_score_adj < *current
+        && !current_task()
+            .creds
+            .lock_save_irq()
+            .has_cap(CapSet::CAP_SYS_RESOURCE)
+    {
+        return Err(KernelError::AccessDenied);
+    }
+
+    *current = oom_score_adj;
+
+    Ok(())
+}
+
+/// Frees memory by killing a process, once memory has run out and can't be
+/// reclaimed. If a process killed by an earlier call hasn't yet exited, it's
+/// waited for instead, so that a single shortage doesn't kill several
+/// processes, unless its grace period is up.
+///
+/// Returns an error if the current process is the victim, in which case it
+/// should give up on whatever it was doing: it will be killed on its way back
+/// to user space.
+pub async fn out_of_memory() -> Result<()> {
+    let victim = {
+        let mut victim = VICTIM.lock_save_irq();
+
+        match victim
+            .as_ref()
+            .filter(|v| uptime() < v.killed_at + VICTIM_GRACE_PERIOD)
+            .and_then(|v| v.tg.upgrade())
+        {
+            Some(tg) if *tg.state.lock_save_irq() == ProcessState::Running => tg,
+            _ => {
+                let tg = kill_victim();
+
+                *victim = Some(Victim {
+                    tg: Arc::downgrade(&tg),
+                    killed_at: uptime(),
+                });
+                tg
+            }
+        }
+    };
+
+    if Arc::ptr_eq(&victim, &current_task().process) {
+        return Err(KernelError::NoMemory);
+    }
+
+    // The victim's memory is freed before it's marked as having exited.
+    let exited = victim
+        .exited
+        .wait_until(|exited| if *exited { Some(()) } else { None });
+
+    select(pin!(exited), pin!(sleep(VICTIM_EXIT_TIMEOUT))).await;
+
+    Ok(())
+}
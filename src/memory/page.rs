@@ -12,7 +12,9 @@ use libkernel::{
     },
 };
 
-/// An conveniance wrapper for dealing with single-page allocaitons.
+/// An conveniance wrapper for dealing with single-page allocaitons. Cloning it
+/// takes another reference to the page.
+#[derive(Clone)]
 pub struct ClaimedPage(PageAllocation<'static, ArchImpl>);
 
 impl Display for ClaimedPage {
@@ -92,6 +94,13 @@ impl ClaimedPage {
         unsafe { slice::from_raw_parts_mut(self.as_ptr_mut(), PAGE_SIZE) }
     }
 
+    /// Takes another reference to the page, returning its frame so that it
+    /// can be mapped elsewhere. The page is only freed once every reference
+    /// has been dropped.
+    pub fn share(&self) -> PageFrame {
+        self.0.clone().leak().start_address().to_pfn()
+    }
+
     pub fn leak(self) -> PageFrame {
         self.0.leak().start_address().to_pfn()
     }
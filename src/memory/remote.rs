@@ -0,0 +1,127 @@
+//! Access to the address space of a task other than the current one, as used
+//! by debuggers via `ptrace`.
+
+use super::{
+    PAGE_ALLOC, PageOffsetTranslator,
+    fault::{FaultResolution, handle_demand_fault_for},
+    page::ClaimedPage,
+};
+use crate::{process::ProcVM, sync::SpinLock};
+use alloc::{boxed::Box, sync::Arc};
+use core::{cmp::min, ptr};
+use libkernel::{
+    UserAddressSpace,
+    error::{KernelError, Result},
+    memory::{
+        PAGE_SIZE, address::VA, page::PageFrame, permissions::PtePermissions,
+        proc_vm::vmarea::AccessKind,
+    },
+};
+
+/// Copies `buf.len()` bytes between `buf` and the address space `vm`, starting
+/// at `addr`. Non-present pages are faulted in as required.
+///
+/// When `write` is set, `buf` is written into the target. Like a debugger's
+/// forced write on Linux, this ignores the mapping's write permission (so that
+/// breakpoints can be placed in text) but never modifies a page which is still
+/// shared with another address space: such pages are copied first.
+pub async fn access_remote_vm(
+    vm: &Arc<SpinLock<ProcVM>>,
+    addr: VA,
+    buf: &mut [u8],
+    write: bool,
+) -> Result<()> {
+    let mut done = 0;
+
+    while done < buf.len() {
+        let va = addr.add_bytes(done);
+        let len = min(PAGE_SIZE - va.page_offset(), buf.len() - done);
+        let chunk = &mut buf[done..done + len];
+
+        loop {
+            let resolution = {
+                let mut vm_lock = vm.lock_save_irq();
+                let pg_info = vm_lock.mm_mut().address_space_mut().translate(va);
+
+                match pg_info {
+                    Some(info) => {
+                        if write && !PAGE_ALLOC.get().unwrap().is_allocated_exclusive(info.pfn) {
+                            break_sharing(&mut vm_lock, va, info.pfn, info.perms)?;
+                        }
+
+                        let info = vm_lock
+                            .mm_mut()
+                            .address_space_mut()
+                            .translate(va)
+                            .ok_or(KernelError::Fault)?;
+
+                        let kva = info
+                            .pfn
+                            .pa()
+                            .to_va::<PageOffsetTranslator>()
+                            .add_bytes(va.page_offset());
+
+                        // SAFETY: The page is mapped in the target's address
+                        // space, which we hold the lock for, so it cannot be
+                        // freed beneath us. The range lies within the page.
+                        unsafe {
+                            if write {
+                                ptr::copy_nonoverlapping(
+                                    chunk.as_ptr(),
+                                    kva.as_ptr_mut() as *mut u8,
+                                    len,
+                                );
+                            } else {
+                                ptr::copy_nonoverlapping(
+                                    kva.as_ptr() as *const u8,
+                                    chunk.as_mut_ptr(),
+                                    len,
+                                );
+                            }
+                        }
+
+                        break;
+                    }
+                    // Populate the page as if it had been read; a forced write
+                    // is then handled above on the next iteration.
+                    None => {
+                        handle_demand_fault_for(&mut vm_lock, vm.clone(), va, AccessKind::Read)?
+                    }
+                }
+            };
+
+            match resolution {
+                FaultResolution::Resolved => continue,
+                FaultResolution::Denied => return Err(KernelError::Fault),
+                FaultResolution::Deferred(fut) => Box::into_pin(fut).await?,
+            }
+        }
+
+        done += len;
+    }
+
+    Ok(())
+}
+
+/// Replaces the shared page mapped at `va` with a private copy.
+fn break_sharing(vm: &mut ProcVM, va: VA, pfn: PageFrame, perms: PtePermissions) -> Result<()> {
+    let mut new_page = ClaimedPage::alloc_zeroed()?;
+
+    // Taking ownership of the shared page here means that our reference is
+    // dropped once the copy has been made.
+    let src_page = unsafe { ClaimedPage::from_pfn(pfn) };
+    new_page.as_slice_mut().copy_from_slice(src_page.as_slice());
+
+    // The page is now private, so a CoW mapping may become writable.
+    let perms = if perms.is_cow() {
+        perms.from_cow()
+    } else {
+        perms
+    };
+
+    vm.mm_mut()
+        .address_space_mut()
+        .remap(va.page_aligned(), new_page.leak(), perms)?;
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+//! Shared memory mappings, created by `mmap` with `MAP_SHARED`.
+//!
+//! The pages of a shared mapping belong to a [`SharedMemory`] object, rather
+//! than to any one address space, so that every mapping of it sees the same
+//! pages. A file has at most one such object, holding those of its pages which
+//! are mapped shared. Pages written through a mapping are written back to the
+//! file by `msync` and `munmap`, or, once the file is no longer mapped, by
+//! `sync`.
+
+use super::page::ClaimedPage;
+use crate::{process::thread_group::ThreadGroup, sync::SpinLock};
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::any::Any;
+use libkernel::{
+    error::Result,
+    fs::{Inode, InodeId},
+    memory::{PAGE_SIZE, page::PageFrame, proc_vm::vmarea::SharedPages},
+};
+
+struct SharedPage {
+    page: ClaimedPage,
+    /// Set once the page has been mapped writable. There's no dirty bit in the
+    /// page tables, so the page may be written to from then on, until it's
+    /// written back and unmapped again.
+    dirty: bool,
+}
+
+/// Shared pages, keyed by their page offset.
+type PageMap = BTreeMap<u64, SharedPage>;
+
+/// The pages of a shared mapping, indexed by their page offset within it.
+pub struct SharedMemory {
+    /// The file whose pages these are, for a shared file mapping.
+    file: Option<Arc<dyn Inode>>,
+    pages: SpinLock<PageMap>,
+}
+
+/// The shared memory object of each file which is mapped shared.
+static SHARED_FILES: SpinLock<BTreeMap<InodeId, Weak<SharedMemory>>> =
+    SpinLock::new(BTreeMap::new());
+
+/// Dirty pages of files which are no longer mapped, waiting to be written back.
+static PENDING_WRITEBACK: SpinLock<Vec<(Arc<dyn Inode>, PageMap)>> = SpinLock::new(Vec::new());
+
+impl SharedPages for SharedMemory {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl SharedMemory {
+    /// Returns a new object for a shared anonymous mapping.
+    pub fn new_anon() -> Arc<Self> {
+        Arc::new(Self {
+            file: None,
+            pages: SpinLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Returns the object for shared mappings of `file`.
+    pub fn for_file(file: Arc<dyn Inode>) -> Arc<Self> {
+        let mut shared_files = SHARED_FILES.lock_save_irq();
+
+        if let Some(shared) = shared_files.get(&file.id()).and_then(Weak::upgrade) {
+            return shared;
+        }
+
+        // Pages which haven't yet been written back are newer than the file's
+        // contents, so are taken back.
+        let mut pages = BTreeMap::new();
+
+        PENDING_WRITEBACK
+            .lock_save_irq()
+            .retain_mut(|(pending, pending_pages)| {
+                if pending.id() != file.id() {
+                    return true;
+                }
+
+                pages.append(pending_pages);
+
+                false
+            });
+
+        let shared = Arc::new(Self {
+            file: Some(file.clone()),
+            pages: SpinLock::new(pages),
+        });
+
+        shared_files.insert(file.id(), Arc::downgrade(&shared));
+
+        shared
+    }
+
+    /// Returns the object backing a shared VMA.
+    pub fn from_pages(pages: &Arc<dyn SharedPages>) -> &Self {
+        pages
+            .as_any()
+            .downcast_ref()
+            .expect("Shared mapping should be backed by shared memory")
+    }
+
+    /// Returns the page at `index`, if it's present, taking a reference to it
+    /// for a new mapping. The page must be marked dirty if it's to be mapped
+    /// `writable`.
+    pub fn get(&self, index: u64, writable: bool) -> Option<PageFrame> {
+        let mut pages = self.pages.lock_save_irq();
+        let page = pages.get_mut(&index)?;
+
+        page.dirty |= writable;
+
+        Some(page.page.share())
+    }
+
+    /// Returns the page at `index`, taking a reference to it for a new mapping
+    /// as for [`get`](Self::get). If there's no such page yet, `page` becomes
+    /// it.
+    pub fn get_or_insert(&self, index: u64, page: ClaimedPage, writable: bool) -> PageFrame {
+        let mut pages = self.pages.lock_save_irq();
+        let page = pages
+            .entry(index)
+            .or_insert(SharedPage { page, dirty: false });
+
+        page.dirty |= writable;
+
+        page.page.share()
+    }
+
+    /// Marks the page at `index` as dirty, as it's about to be made writable.
+    pub fn mark_dirty(&self, index: u64) {
+        if let Some(page) = self.pages.lock_save_irq().get_mut(&index) {
+            page.dirty = true;
+        }
+    }
+
+    /// Returns whether this is the object of a shared file mapping.
+    pub fn is_file_backed(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Returns the file whose pages these are, for a shared file mapping.
+    pub fn file(&self) -> Option<&Arc<dyn Inode>> {
+        self.file.as_ref()
+    }
+
+    /// Returns a new page holding the data of the page at `index`, read from
+    /// the file. Any part of it beyond the end of the file is zeroed.
+    pub async fn read_page(&self, index: u64) -> Result<ClaimedPage> {
+        let mut page = ClaimedPage::alloc_zeroed()?;
+
+        if let Some(file) = &self.file {
+            file.read_at(index * PAGE_SIZE as u64, page.as_slice_mut())
+                .await?;
+        }
+
+        Ok(page)
+    }
+
+    /// Writes the dirty pages back to the file.
+    pub async fn writeback(&self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        // The pages are written without the lock held, so take references to
+        // them first. They're clean from here on, so they're unmapped before
+        // they're written: a write to one after that faults it back in, and
+        // dirties it again.
+        let dirty: Vec<_> = self
+            .pages
+            .lock_save_irq()
+            .iter_mut()
+            .filter(|(_, page)| page.dirty)
+            .map(|(index, page)| {
+                page.dirty = false;
+                (*index, page.page.clone())
+            })
+            .collect();
+
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        for vm in ThreadGroup::all_vms() {
+            let mut vm = vm.lock_save_irq();
+
+            for (index, _) in dirty.iter() {
+                vm.mm_mut()
+                    .unmap_shared_page(self, index * PAGE_SIZE as u64)?;
+            }
+        }
+
+        let res = write_pages(file, dirty.iter().map(|(index, page)| (*index, page))).await;
+
+        // Pages which failed to be written are still dirty.
+        if res.is_err() {
+            for (index, _) in dirty {
+                self.mark_dirty(index);
+            }
+        }
+
+        res
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        let Some(file) = self.file.take() else {
+            return;
+        };
+
+        let mut shared_files = SHARED_FILES.lock_save_irq();
+
+        if shared_files
+            .get(&file.id())
+            .is_some_and(|shared| shared.strong_count() == 0)
+        {
+            shared_files.remove(&file.id());
+        }
+
+        // Dirty pages are kept until they've been written back.
+        let mut pages = core::mem::take(&mut *self.pages.lock_save_irq());
+
+        pages.retain(|_, page| page.dirty);
+
+        if !pages.is_empty() {
+            PENDING_WRITEBACK.lock_save_irq().push((file, pages));
+        }
+    }
+}
+
+/// Writes each page in `pages` to its offset in `file`. The file isn't
+/// extended: only the parts of the pages that lie within it are written.
+async fn write_pages<'a>(
+    file: &Arc<dyn Inode>,
+    pages: impl Iterator<Item = (u64, &'a ClaimedPage)>,
+) -> Result<()> {
+    let size = file.getattr().await?.size;
+
+    for (index, page) in pages {
+        let offset = index * PAGE_SIZE as u64;
+
+        if offset >= size {
+            continue;
+        }
+
+        let len = (size - offset).min(PAGE_SIZE as u64) as usize;
+
+        file.write_at(offset, &page.as_slice()[..len]).await?;
+    }
+
+    Ok(())
+}
+
+/// Writes back the dirty pages of files which are no longer mapped.
+pub async fn flush_pending_writeback() -> Result<()> {
+    let pending = core::mem::take(&mut *PENDING_WRITEBACK.lock_save_irq());
+
+    for (file, pages) in pending.iter() {
+        write_pages(file, pages.iter().map(|(index, page)| (*index, &page.page))).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,702 @@
+//! Swapping of private user pages out to swap areas on block devices when
+//! physical memory runs out, and back in when they're faulted on.
+//!
+//! Pages which may be swapped out are kept on two LRU lists. Newly mapped
+//! pages start on the active list. Pages are moved from there to the inactive
+//! list when their accessed flag is found to be clear, and are swapped out from
+//! the inactive list if it's still clear when they're next looked at. Pages
+//! which are accessed in the meantime are moved back to the active list.
+//! Pages which have since been unmapped are dropped from the lists when
+//! they're next looked at, and the lists are pruned of them as pages are added
+//! once they hold more pages than there are in memory.
+//!
+//! A swapped-out page is recorded by a swap entry in its non-present PTE,
+//! which holds a reference to the slot of the swap area that holds it. While
+//! a page is being written out, it's kept in the swap cache, from which it's
+//! mapped again directly if it's faulted on before the write completes.
+
+use super::{PAGE_ALLOC, page::ClaimedPage, uaccess::cstr::UserCStr};
+use crate::{
+    drivers::{DM, timer::sleep},
+    fs::VFS,
+    process::{ProcVM, creds::caps::CapSet, thread_group::ThreadGroup},
+    sched::current_task,
+    sync::SpinLock,
+};
+use alloc::{
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::{ffi::c_char, time::Duration};
+use libkernel::{
+    UserAddressSpace,
+    error::{FsError, KernelError, Result},
+    fs::{BlockDevice, FileType, InodeId, path::Path},
+    memory::{
+        PAGE_SIZE,
+        address::{TUA, VA},
+        page::PageFrame,
+        permissions::PtePermissions,
+        swap::{MAX_SWAP_AREAS, SwapEntry, SwapHeader, SwapMap},
+    },
+};
+use log::info;
+
+/// The number of pages swapped out at once when memory runs out.
+pub const SWAP_CLUSTER: usize = 32;
+
+/// The number of pages at the front of each LRU list which are looked at for
+/// each page added, once the lists have grown too long.
+const LRU_PRUNE_BATCH: usize = 2;
+
+const SWAP_FLAG_PREFER: i32 = 0x8000;
+const SWAP_FLAG_PRIO_MASK: i32 = 0x7fff;
+const SWAP_FLAG_DISCARD: i32 = 0x10000;
+const SWAP_FLAG_DISCARD_ONCE: i32 = 0x20000;
+const SWAP_FLAG_DISCARD_PAGES: i32 = 0x40000;
+
+const SWAP_FLAGS_VALID: i32 = SWAP_FLAG_PRIO_MASK
+    | SWAP_FLAG_PREFER
+    | SWAP_FLAG_DISCARD
+    | SWAP_FLAG_DISCARD_ONCE
+    | SWAP_FLAG_DISCARD_PAGES;
+
+struct SwapArea {
+    dev: Arc<dyn BlockDevice>,
+    /// The inode of the device special file the area was enabled through.
+    inode: InodeId,
+    map: SwapMap,
+    priority: i16,
+    /// Set while the area is being disabled, so that no more pages are
+    /// swapped out to it.
+    draining: bool,
+}
+
+struct SwapState {
+    areas: Vec<Option<SwapArea>>,
+    /// Pages which are being written out, or whose write failed, by their
+    /// entries.
+    cache: BTreeMap<SwapEntry, ClaimedPage>,
+    /// The priority given to the next area enabled without one.
+    next_priority: i16,
+}
+
+impl SwapState {
+    const fn new() -> Self {
+        Self {
+            areas: Vec::new(),
+            cache: BTreeMap::new(),
+            next_priority: -1,
+        }
+    }
+
+    fn area_mut(&mut self, entry: SwapEntry) -> &mut SwapArea {
+        self.areas[entry.area()]
+            .as_mut()
+            .expect("Swap entry refers to a disabled area")
+    }
+
+    /// Allocates a slot from the area with the highest priority which has a
+    /// free one.
+    fn alloc(&mut self) -> Option<SwapEntry> {
+        let (idx, area) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, area)| Some((idx, area.as_mut()?)))
+            .filter(|(_, area)| !area.draining && area.map.free_slots() > 0)
+            .max_by_key(|(_, area)| area.priority)?;
+
+        Some(SwapEntry::new(idx, area.map.alloc()?))
+    }
+
+    /// Drops a reference to the slot of `entry`. Once the last has gone, the
+    /// slot is free again, and any copy of the page in the cache is dropped.
+    fn put(&mut self, entry: SwapEntry) {
+        if self.area_mut(entry).map.put(entry.slot()) {
+            self.cache.remove(&entry);
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.areas.iter().any(Option::is_some)
+    }
+}
+
+static SWAP: SpinLock<SwapState> = SpinLock::new(SwapState::new());
+
+/// A page on one of the LRU lists, identified by where it's mapped.
+struct LruPage {
+    vm: Weak<SpinLock<ProcVM>>,
+    va: VA,
+    pfn: PageFrame,
+}
+
+struct Lru {
+    active: VecDeque<LruPage>,
+    inactive: VecDeque<LruPage>,
+}
+
+impl Lru {
+    fn list_mut(&mut self, inactive: bool) -> &mut VecDeque<LruPage> {
+        if inactive {
+            &mut self.inactive
+        } else {
+            &mut self.active
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.active.len() + self.inactive.len()
+    }
+}
+
+static LRU: SpinLock<Lru> = SpinLock::new(Lru {
+    active: VecDeque::new(),
+    inactive: VecDeque::new(),
+});
+
+/// Returns `true` if at least one swap area is enabled.
+pub fn is_enabled() -> bool {
+    SWAP.lock_save_irq().is_enabled()
+}
+
+/// Returns the number of pages which may be swapped out to the enabled swap
+/// areas.
+pub fn total_pages() -> usize {
+    SWAP.lock_save_irq()
+        .areas
+        .iter()
+        .flatten()
+        .map(|area| area.map.capacity())
+        .sum()
+}
+
+/// Takes another reference to the swap slot of `entry`, for a copy of the PTE
+/// holding it.
+pub fn dup_swap_entry(entry: SwapEntry) {
+    SWAP.lock_save_irq().area_mut(entry).map.dup(entry.slot());
+}
+
+/// Drops a PTE's reference to the swap slot of `entry`.
+pub fn put_swap_entry(entry: SwapEntry) {
+    SWAP.lock_save_irq().put(entry);
+}
+
+/// Puts the private page `pfn`, which has just been mapped at `va` of `vm`, on
+/// the active list, so that it may be swapped out. Pages are only tracked
+/// while swapping is enabled.
+pub fn lru_add(vm: &Arc<SpinLock<ProcVM>>, va: VA, pfn: PageFrame) {
+    if !is_enabled() {
+        return;
+    }
+
+    let len = {
+        let mut lru = LRU.lock_save_irq();
+
+        lru.active.push_back(LruPage {
+            vm: Arc::downgrade(vm),
+            va: va.page_aligned(),
+            pfn,
+        });
+
+        lru.len()
+    };
+
+    // A page is rarely on the lists more than once while it's mapped, so once
+    // they hold more pages than there are, most of the excess are pages which
+    // have since been unmapped, and which would otherwise only be dropped by
+    // reclaim.
+    if len > PAGE_ALLOC.get().unwrap().total_page_count() {
+        prune_lru(LRU_PRUNE_BATCH);
+    }
+}
+
+/// Looks at up to `nr_pages` pages at the front of each LRU list, dropping
+/// those which are no longer mapped where they were put on it. The others are
+/// moved to the back. As the caller may hold a VM's lock, pages of VMs whose
+/// locks are held are left alone.
+fn prune_lru(nr_pages: usize) {
+    for inactive in [false, true] {
+        for _ in 0..nr_pages {
+            let Some(page) = LRU.lock_save_irq().list_mut(inactive).pop_front() else {
+                break;
+            };
+
+            let is_gone = match page.vm.upgrade() {
+                Some(vm) => vm
+                    .try_lock_save_irq()
+                    .is_some_and(|mut vm| matches!(lru_state(&mut vm, &page), LruState::Gone)),
+                None => true,
+            };
+
+            if !is_gone {
+                LRU.lock_save_irq().list_mut(inactive).push_back(page);
+            }
+        }
+    }
+}
+
+/// Whether a page on the LRU may be swapped out.
+enum LruState {
+    /// The page is no longer mapped where it was put on the LRU, or is in a
+    /// VMA whose pages mustn't be swapped out, so it's dropped from the LRU.
+    Gone,
+    /// The page is shared with another mapping, e.g. after a fork, so it's
+    /// kept on the LRU until it's exclusive again.
+    Shared,
+    Reclaimable,
+}
+
+fn lru_state(vm: &mut ProcVM, page: &LruPage) -> LruState {
+    let is_mapped = vm
+        .mm_mut()
+        .address_space_mut()
+        .translate(page.va)
+        .is_some_and(|info| info.pfn == page.pfn);
+
+    let is_swappable = vm
+        .mm()
+        .find_vma(page.va)
+        .is_some_and(|vma| !vma.is_shared() && !vma.flags().locked);
+
+    if !is_mapped || !is_swappable {
+        LruState::Gone
+    } else if !PAGE_ALLOC.get().unwrap().is_allocated_exclusive(page.pfn) {
+        LruState::Shared
+    } else {
+        LruState::Reclaimable
+    }
+}
+
+/// Moves up to `nr_pages` pages which haven't been accessed recently from the
+/// active list to the inactive list, clearing their accessed flags.
+fn refill_inactive(nr_pages: usize) {
+    let budget = LRU.lock_save_irq().active.len();
+
+    for _ in 0..budget.min(nr_pages * 2) {
+        let Some(page) = LRU.lock_save_irq().active.pop_front() else {
+            return;
+        };
+
+        let Some(vm) = page.vm.upgrade() else {
+            continue;
+        };
+
+        let mut vm = vm.lock_save_irq();
+
+        match lru_state(&mut vm, &page) {
+            LruState::Gone => continue,
+            LruState::Shared => {
+                LRU.lock_save_irq().active.push_back(page);
+                continue;
+            }
+            LruState::Reclaimable => {}
+        }
+
+        match vm
+            .mm_mut()
+            .address_space_mut()
+            .test_and_clear_accessed(page.va)
+        {
+            Ok(true) => LRU.lock_save_irq().active.push_back(page),
+            Ok(false) => LRU.lock_save_irq().inactive.push_back(page),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Unmaps up to `nr_pages` pages from the inactive list which haven't been
+/// accessed since they were put there, replacing their PTEs with swap
+/// entries. The pages are put in the swap cache, to be written out.
+fn isolate_inactive(nr_pages: usize) -> Vec<(SwapEntry, ClaimedPage)> {
+    let mut victims = Vec::new();
+    let budget = LRU.lock_save_irq().inactive.len();
+
+    for _ in 0..budget {
+        if victims.len() == nr_pages {
+            break;
+        }
+
+        let Some(page) = LRU.lock_save_irq().inactive.pop_front() else {
+            break;
+        };
+
+        let Some(vm) = page.vm.upgrade() else {
+            continue;
+        };
+
+        let mut vm = vm.lock_save_irq();
+
+        match lru_state(&mut vm, &page) {
+            LruState::Gone => continue,
+            LruState::Shared => {
+                LRU.lock_save_irq().active.push_back(page);
+                continue;
+            }
+            LruState::Reclaimable => {}
+        }
+
+        let address_space = vm.mm_mut().address_space_mut();
+
+        match address_space.test_and_clear_accessed(page.va) {
+            Ok(true) => {
+                LRU.lock_save_irq().active.push_back(page);
+                continue;
+            }
+            Ok(false) => {}
+            Err(_) => continue,
+        }
+
+        let mut swap = SWAP.lock_save_irq();
+
+        let Some(entry) = swap.alloc() else {
+            // Swap is full.
+            drop(swap);
+            LRU.lock_save_irq().inactive.push_front(page);
+            break;
+        };
+
+        match address_space.swap_out(page.va, entry) {
+            Ok(pfn) => {
+                // The cache takes over the mapping's reference to the page,
+                // and the write takes another reference to the slot, so that
+                // it isn't reused until the write has completed.
+                let page = unsafe { ClaimedPage::from_pfn(pfn) };
+
+                swap.area_mut(entry).map.dup(entry.slot());
+                swap.cache.insert(entry, page.clone());
+                victims.push((entry, page));
+            }
+            Err(_) => swap.put(entry),
+        }
+    }
+
+    victims
+}
+
+/// Returns the area holding `entry`, along with the first block of its slot.
+fn slot_location(entry: SwapEntry) -> (Arc<dyn BlockDevice>, u64) {
+    let mut swap = SWAP.lock_save_irq();
+    let dev = swap.area_mut(entry).dev.clone();
+    let block = entry.slot() * (PAGE_SIZE / dev.block_size()) as u64;
+
+    (dev, block)
+}
+
+/// Writes the page at `entry` out, dropping it from the swap cache once it's
+/// safely on disk. Returns `true` if the write succeeded.
+async fn write_out(entry: SwapEntry, page: ClaimedPage) -> bool {
+    let (dev, block) = slot_location(entry);
+    let written = dev.write(block, page.as_slice()).await.is_ok();
+
+    let mut swap = SWAP.lock_save_irq();
+
+    // If the page was swapped back in, and its slot reused, while it was
+    // being written, the cache now holds another page.
+    if written && swap.cache.get(&entry).is_some_and(|p| p.pa() == page.pa()) {
+        swap.cache.remove(&entry);
+    }
+
+    swap.put(entry);
+
+    written
+}
+
+/// Frees up to `nr_pages` pages of memory by swapping out pages which haven't
+/// been accessed recently. Returns the number of pages freed.
+pub async fn reclaim(nr_pages: usize) -> usize {
+    refill_inactive(nr_pages);
+
+    let mut freed = 0;
+
+    for (entry, page) in isolate_inactive(nr_pages) {
+        if write_out(entry, page).await {
+            freed += 1;
+        }
+    }
+
+    freed
+}
+
+/// Allocates a page, swapping pages out to make room if memory has run out.
+async fn alloc_page() -> Result<ClaimedPage> {
+    loop {
+        match ClaimedPage::alloc_zeroed() {
+            Err(KernelError::NoMemory) if reclaim(SWAP_CLUSTER).await > 0 => continue,
+            page => return page,
+        }
+    }
+}
+
+/// Returns the page at `entry` if it's in the swap cache.
+pub fn cached_page(entry: SwapEntry) -> Option<ClaimedPage> {
+    SWAP.lock_save_irq().cache.get(&entry).cloned()
+}
+
+/// Reads the page at `entry` back in, from the swap cache if it's there.
+pub async fn read_page(entry: SwapEntry) -> Result<ClaimedPage> {
+    if let Some(page) = cached_page(entry) {
+        return Ok(page);
+    }
+
+    let (dev, block) = slot_location(entry);
+    let mut page = alloc_page().await?;
+
+    dev.read(block, page.as_slice_mut()).await?;
+
+    Ok(page)
+}
+
+/// Maps `page`, which has been read back in from `entry`, at `va` of `vm`, in
+/// place of the swap entry, dropping the PTE's reference to the slot. If the
+/// PTE no longer holds `entry`, e.g. because the page has been swapped in by
+/// another fault, or unmapped, `page` is dropped.
+pub fn swap_in(
+    vm: &mut ProcVM,
+    vm_ref: &Arc<SpinLock<ProcVM>>,
+    va: VA,
+    entry: SwapEntry,
+    page: ClaimedPage,
+) -> Result<()> {
+    let va = va.page_aligned();
+
+    if vm.mm_mut().address_space_mut().swap_entry(va) != Some(entry) {
+        return Ok(());
+    }
+
+    let Some(vma) = vm.mm().find_vma(va) else {
+        return Ok(());
+    };
+
+    let mut perms = PtePermissions::from(vma.permissions());
+
+    // After a fork, other PTEs may hold `entry` too, and map the page from the
+    // swap cache, or may have already done so. Unless it's ours alone, it's
+    // mapped CoW, so that writing to it doesn't change it under them.
+    if perms.is_write() && !is_exclusive(entry, &page) {
+        perms = perms.into_cow();
+    }
+
+    let pfn = page.leak();
+
+    vm.mm_mut()
+        .address_space_mut()
+        .swap_in(va, entry, pfn, perms)?;
+
+    put_swap_entry(entry);
+    lru_add(vm_ref, va, pfn);
+
+    Ok(())
+}
+
+/// Returns `true` if the PTE being swapped in holds the only reference to the
+/// slot of `entry`, and `page`, which has been read back in from it, is
+/// referenced by nothing else, including the swap cache.
+fn is_exclusive(entry: SwapEntry, page: &ClaimedPage) -> bool {
+    let mut swap = SWAP.lock_save_irq();
+
+    swap.area_mut(entry).map.count(entry.slot()) == 1
+        && PAGE_ALLOC
+            .get()
+            .unwrap()
+            .is_allocated_exclusive(page.pa().to_pfn())
+}
+
+/// Swaps every page of `vm` which has been swapped out to the area at `idx`
+/// back in.
+async fn unuse_vm(vm: &Arc<SpinLock<ProcVM>>, idx: usize) -> Result<()> {
+    let regions: Vec<_> = vm.lock_save_irq().mm().vmas().map(|v| v.region()).collect();
+
+    for va in regions.iter().flat_map(|r| r.iter_pages()) {
+        let entry = vm
+            .lock_save_irq()
+            .mm_mut()
+            .address_space_mut()
+            .swap_entry(va);
+
+        if let Some(entry) = entry
+            && entry.area() == idx
+        {
+            let page = read_page(entry).await?;
+
+            swap_in(&mut vm.lock_save_irq(), vm, va, entry, page)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Swaps every page which has been swapped out to the area at `idx` back in,
+/// waiting for any writes to it to complete.
+async fn unuse_area(idx: usize) -> Result<()> {
+    loop {
+        for vm in ThreadGroup::all_vms() {
+            unuse_vm(&vm, idx).await?;
+        }
+
+        let in_use = SWAP.lock_save_irq().areas[idx]
+            .as_ref()
+            .is_some_and(|area| area.map.used_slots().next().is_some());
+
+        if !in_use {
+            return Ok(());
+        }
+
+        // Pages are still being written out to the area.
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Resolves `path` to a block device special file, returning its inode's ID
+/// and the device.
+async fn lookup_swap_device(path: TUA<c_char>) -> Result<(InodeId, Arc<dyn BlockDevice>)> {
+    let mut buf = [0; 1024];
+
+    let path = Path::new(UserCStr::from_ptr(path).copy_from_user(&mut buf).await?);
+    let cwd = current_task().cwd.lock_save_irq().0.clone();
+    let inode = VFS.resolve_path(path, cwd).await?;
+
+    let FileType::BlockDevice(desc) = inode.getattr().await?.file_type else {
+        return Err(KernelError::InvalidValue);
+    };
+
+    let dev = DM
+        .lock_save_irq()
+        .find_blk_driver(desc.major)
+        .and_then(|driver| driver.get_device(desc.minor))
+        .ok_or(FsError::NoDevice)?;
+
+    Ok((inode.id(), dev))
+}
+
+fn check_swap_cap() -> Result<()> {
+    if current_task()
+        .creds
+        .lock_save_irq()
+        .has_cap(CapSet::CAP_SYS_ADMIN)
+    {
+        Ok(())
+    } else {
+        Err(KernelError::NotPermitted)
+    }
+}
+
+pub async fn sys_swapon(path: TUA<c_char>, flags: i32) -> Result<usize> {
+    check_swap_cap()?;
+
+    if flags & !SWAP_FLAGS_VALID != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let (inode, dev) = lookup_swap_device(path).await?;
+
+    if dev.block_size() > PAGE_SIZE || !PAGE_SIZE.is_multiple_of(dev.block_size()) {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let mut header = vec![0; PAGE_SIZE];
+    dev.read(0, &mut header).await?;
+
+    let mut header = SwapHeader::parse(&header)?;
+
+    // An area which claims to be larger than its device is truncated to fit.
+    let dev_pages = dev.num_blocks() * dev.block_size() as u64 / PAGE_SIZE as u64;
+
+    if dev_pages < 2 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    header.last_page = header.last_page.min(dev_pages - 1);
+
+    let map = SwapMap::new(&header);
+
+    let mut swap = SWAP.lock_save_irq();
+
+    if swap.areas.iter().flatten().any(|area| area.inode == inode) {
+        return Err(KernelError::InUse);
+    }
+
+    let priority = if flags & SWAP_FLAG_PREFER != 0 {
+        (flags & SWAP_FLAG_PRIO_MASK) as i16
+    } else {
+        swap.next_priority -= 1;
+        swap.next_priority
+    };
+
+    let idx = match swap.areas.iter().position(Option::is_none) {
+        Some(idx) => idx,
+        None if swap.areas.len() < MAX_SWAP_AREAS => {
+            swap.areas.push(None);
+            swap.areas.len() - 1
+        }
+        None => return Err(KernelError::NotPermitted),
+    };
+
+    info!(
+        "Adding {}k swap, priority {priority}",
+        map.capacity() * PAGE_SIZE / 1024
+    );
+
+    swap.areas[idx] = Some(SwapArea {
+        dev,
+        inode,
+        map,
+        priority,
+        draining: false,
+    });
+
+    Ok(0)
+}
+
+pub async fn sys_swapoff(path: TUA<c_char>) -> Result<usize> {
+    check_swap_cap()?;
+
+    let (inode, _) = lookup_swap_device(path).await?;
+
+    let idx = {
+        let mut swap = SWAP.lock_save_irq();
+
+        let (idx, area) = swap
+            .areas
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, area)| Some((idx, area.as_mut()?)))
+            .find(|(_, area)| area.inode == inode && !area.draining)
+            .ok_or(KernelError::InvalidValue)?;
+
+        let in_use = area.map.capacity() - area.map.free_slots();
+
+        // There must be room for the pages to come back.
+        if in_use > PAGE_ALLOC.get().unwrap().free_page_count() {
+            return Err(KernelError::NoMemory);
+        }
+
+        area.draining = true;
+        idx
+    };
+
+    if let Err(e) = unuse_area(idx).await {
+        if let Some(area) = SWAP.lock_save_irq().areas[idx].as_mut() {
+            area.draining = false;
+        }
+
+        return Err(e);
+    }
+
+    let area = SWAP.lock_save_irq().areas[idx].take();
+
+    if !is_enabled() {
+        let mut lru = LRU.lock_save_irq();
+        lru.active.clear();
+        lru.inactive.clear();
+    }
+
+    if let Some(area) = area {
+        area.dev.sync().await?;
+    }
+
+    Ok(0)
+}
@@ -0,0 +1,202 @@
+//! The virtual dynamic shared object (vDSO).
+//!
+//! The vDSO is a small ELF image, supplied by the architecture, which is mapped
+//! into every process so that frequently used calls such as `clock_gettime`
+//! can be serviced without entering the kernel. It's backed by a data page,
+//! mapped read-only immediately before the image, which holds the parameters
+//! for converting the system counter into the time.
+
+use super::page::ClaimedPage;
+use crate::{
+    arch::{Arch, ArchImpl},
+    clock::{gettime::clock_resolution, realtime::epoch},
+    drivers::timer::start_time,
+    sync::{OnceLock, SpinLock},
+};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering, fence};
+use libkernel::{
+    UserAddressSpace, VirtualMemory,
+    error::{ExecError, KernelError, Result},
+    memory::{
+        PAGE_SIZE,
+        permissions::PtePermissions,
+        proc_vm::{
+            memory_map::{AddressRequest, MemoryMap},
+            vmarea::{VMAPermissions, VMAreaKind},
+        },
+        region::VirtMemoryRegion,
+    },
+};
+use object::{LittleEndian, elf, read::elf::FileHeader};
+
+/// The layout of the vDSO data page. This is read by the vDSO image, so the
+/// field offsets must not change.
+///
+/// Readers retry whilst `seq` is odd, or if it changed whilst they read the
+/// other fields.
+#[repr(C)]
+struct VdsoData {
+    seq: AtomicU32,
+    /// Non-zero if userspace can derive the time from the counter.
+    counter_ok: AtomicU32,
+    /// The counter value at which the monotonic clock read `mono_base`.
+    cycle_base: AtomicU64,
+    mono_base: AtomicU64,
+    /// The offset, in nanoseconds, from the monotonic to the realtime clock.
+    real_offset: AtomicU64,
+    /// Counter ticks are scaled to nanoseconds by `(ticks * mult) >> shift`.
+    mult: AtomicU64,
+    shift: AtomicU32,
+    /// Non-zero if `real_offset` is valid.
+    real_ok: AtomicU32,
+    /// The clock resolution, in nanoseconds.
+    res: AtomicU64,
+}
+
+const SHIFT: u32 = 32;
+
+struct Vdso {
+    data: ClaimedPage,
+    image: Vec<ClaimedPage>,
+}
+
+// SAFETY: The pages are only written during initialisation, or through the
+// atomics of `VdsoData`.
+unsafe impl Send for Vdso {}
+unsafe impl Sync for Vdso {}
+
+impl Vdso {
+    fn data(&self) -> &VdsoData {
+        // SAFETY: The data page is owned by the vDSO for the lifetime of the
+        // kernel, and `VdsoData` fits within it.
+        unsafe { &*self.data.as_ptr().cast() }
+    }
+}
+
+static VDSO: OnceLock<Vdso> = OnceLock::new();
+
+/// Serialises updates to the data page, since its sequence count only allows
+/// for one writer at a time.
+static UPDATE_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// Loads the architecture's vDSO image, if it has one, and fills in its data
+/// page. This must be called once the system timer has been set up.
+///
+/// Fails if the image isn't a shared object, in which case processes run
+/// without a vDSO.
+pub fn init() -> Result<()> {
+    let Some(image) = ArchImpl::vdso_image() else {
+        return Ok(());
+    };
+
+    let ehdr =
+        elf::FileHeader64::<LittleEndian>::parse(image).map_err(|_| ExecError::InvalidElfFormat)?;
+
+    if ehdr.e_type.get(LittleEndian) != elf::ET_DYN {
+        return Err(ExecError::InvalidElfFormat.into());
+    }
+
+    let mut pages = Vec::new();
+
+    for chunk in image.chunks(PAGE_SIZE) {
+        let mut page = ClaimedPage::alloc_zeroed()?;
+        page.as_slice_mut()[..chunk.len()].copy_from_slice(chunk);
+        pages.push(page);
+    }
+
+    let vdso = Vdso {
+        data: ClaimedPage::alloc_zeroed()?,
+        image: pages,
+    };
+
+    update_clock(vdso.data());
+
+    VDSO.set(vdso).map_err(|_| KernelError::InUse)
+}
+
+/// Publishes the new time to the vDSO, once the realtime clock has been set.
+pub fn clock_changed() {
+    if let Some(vdso) = VDSO.get() {
+        let _guard = UPDATE_LOCK.lock_save_irq();
+
+        update_clock(vdso.data());
+    }
+}
+
+/// Publishes the current counter-to-time conversion to the data page.
+fn update_clock(data: &VdsoData) {
+    let seq = data.seq.load(Ordering::Relaxed);
+
+    data.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+    fence(Ordering::Release);
+
+    if let Some(start) = start_time() {
+        let mult = ((1_000_000_000u128 << SHIFT) / start.freq() as u128) as u64;
+
+        data.cycle_base.store(start.ticks(), Ordering::Relaxed);
+        data.mono_base.store(0, Ordering::Relaxed);
+        data.mult.store(mult, Ordering::Relaxed);
+        data.shift.store(SHIFT, Ordering::Relaxed);
+        data.res
+            .store(clock_resolution().as_nanos() as u64, Ordering::Relaxed);
+
+        // realtime = since_epoch + (now - taken) = monotonic + since_epoch -
+        // (taken - start).
+        if let Some((since_epoch, taken)) = epoch() {
+            let offset = since_epoch.as_nanos() as i128 - (taken - start).as_nanos() as i128;
+
+            data.real_offset.store(offset as u64, Ordering::Relaxed);
+            data.real_ok.store(1, Ordering::Relaxed);
+        }
+
+        data.counter_ok.store(1, Ordering::Relaxed);
+    }
+
+    data.seq.store(seq.wrapping_add(2), Ordering::Release);
+}
+
+/// Maps the vDSO, and its data page, into `mm`. Returns the region spanning
+/// both, or `None` if the architecture has no vDSO.
+pub fn map_vdso(
+    mm: &mut MemoryMap<<ArchImpl as VirtualMemory>::ProcessAddressSpace>,
+) -> Result<Option<VirtMemoryRegion>> {
+    let Some(vdso) = VDSO.get() else {
+        return Ok(None);
+    };
+
+    let len = (vdso.image.len() + 1) * PAGE_SIZE;
+    let base = mm.mmap(
+        AddressRequest::Any,
+        len,
+        VMAPermissions::ro(),
+        VMAreaKind::Special,
+    )?;
+
+    mm.address_space_mut()
+        .map_page(vdso.data.share(), base, PtePermissions::ro(true))?;
+
+    let image_base = base.add_bytes(PAGE_SIZE);
+
+    for (i, page) in vdso.image.iter().enumerate() {
+        mm.address_space_mut().map_page(
+            page.share(),
+            image_base.add_bytes(i * PAGE_SIZE),
+            PtePermissions::rx(true),
+        )?;
+    }
+
+    mm.mprotect(
+        VirtMemoryRegion::new(image_base, vdso.image.len() * PAGE_SIZE),
+        VMAPermissions::rx(),
+    )?;
+
+    Ok(Some(VirtMemoryRegion::new(base, len)))
+}
+
+/// Returns the address of the vDSO image mapped into `vm`, if any.
+#[cfg(target_arch = "aarch64")]
+pub fn image_base(vm: &crate::process::ProcVM) -> Option<libkernel::memory::address::VA> {
+    vm.vdso()
+        .map(|region| region.start_address().add_bytes(PAGE_SIZE))
+}
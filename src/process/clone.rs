@@ -1,16 +1,34 @@
 use crate::{
-    process::{TASK_LIST, Task, TaskState},
+    memory::uaccess::copy_to_user,
+    process::{
+        TASK_LIST, Task, TaskState,
+        creds::caps::CapSet,
+        ns::clone_namespaces,
+        pidfd::open_pidfd,
+        thread_group::{
+            ThreadGroup,
+            pid::tid_vnr,
+            rsrc_lim::{RlimitId, rlimit},
+        },
+    },
     sched::{self, current_task},
-    sync::SpinLock,
+    sync::{CondVar, SpinLock},
 };
+use alloc::sync::Weak;
 use bitflags::bitflags;
 use libkernel::{
     error::{KernelError, Result},
+    fs::OpenFlags,
     memory::address::UA,
+    sync::condvar::WakeupType,
 };
 use ringbuf::Arc;
 
-use super::{ctx::Context, thread_group::signal::SigSet};
+use super::{
+    ctx::Context,
+    ptrace::{self, Ptrace},
+    thread_group::signal::SigSet,
+};
 
 bitflags! {
     #[derive(Debug, Clone, Copy)]
@@ -19,6 +37,7 @@ bitflags! {
         const CLONE_FS = 0x200;
         const CLONE_FILES = 0x400;
         const CLONE_SIGHAND = 0x800;
+        const CLONE_PIDFD = 0x1000;
         const CLONE_PTRACE = 0x2000;
         const CLONE_VFORK = 0x4000;
         const CLONE_PARENT = 0x8000;
@@ -44,14 +63,72 @@ bitflags! {
 pub async fn sys_clone(
     flags: u32,
     newsp: usize,
-    _parent_tidptr: UA,
-    _child_tidptr: UA,
+    parent_tidptr: UA,
+    child_tidptr: UA,
     tls: usize,
 ) -> Result<usize> {
     let flags = CloneFlags::from_bits_truncate(flags);
 
+    // The pidfd is returned through the parent TID pointer, and only a new
+    // process may be referred to by one.
+    if flags.contains(CloneFlags::CLONE_PIDFD)
+        && flags.intersects(CloneFlags::CLONE_PARENT_SETTID | CloneFlags::CLONE_THREAD)
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    // A new mount namespace can't share the filesystem context with its
+    // parent, and threads share their PID namespace.
+    if flags.contains(CloneFlags::CLONE_NEWNS | CloneFlags::CLONE_FS)
+        || flags.contains(CloneFlags::CLONE_NEWPID | CloneFlags::CLONE_THREAD)
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let namespaces = clone_namespaces(flags)?;
+
+    // A vfork parent is suspended until the child execs or exits, so that the
+    // child may borrow its address space.
+    let vfork_done = flags
+        .contains(CloneFlags::CLONE_VFORK)
+        .then(|| Arc::new(CondVar::new(false)));
+
     let new_task = {
         let current_task = current_task();
+        let creds = current_task.creds.lock_save_irq().clone();
+
+        // A user may not own more tasks than their RLIMIT_NPROC permits.
+        if !creds.has_cap(CapSet::CAP_SYS_RESOURCE)
+            && !creds.has_cap(CapSet::CAP_SYS_ADMIN)
+            && ThreadGroup::count_user_tasks(creds.uid()) as u64 >= rlimit(RlimitId::NPROC)
+        {
+            return Err(KernelError::TryAgain);
+        }
+
+        // 处理虚拟内存 (VM)
+        let vm = if flags.contains(CloneFlags::CLONE_VM) {
+            current_task.vm()
+        } else {
+            Arc::new(SpinLock::new(
+                current_task.vm().lock_save_irq().clone_as_cow()?,
+            ))
+        };
+
+        // 处理文件描述符表
+        let files = if flags.contains(CloneFlags::CLONE_FILES) {
+            current_task.fd_table.clone()
+        } else {
+            Arc::new(SpinLock::new(
+                current_task.fd_table.lock_save_irq().clone_for_exec(),
+            ))
+        };
+
+        // 处理当前工作目录
+        let cwd = if flags.contains(CloneFlags::CLONE_FS) {
+            current_task.cwd.clone()
+        } else {
+            Arc::new(SpinLock::new(current_task.cwd.lock_save_irq().clone()))
+        };
 
         // 处理线程组和父子进程关系
         let (tg, tid) = if flags.contains(CloneFlags::CLONE_THREAD) {
@@ -79,36 +156,12 @@ pub async fn sys_clone(
                 current_task.process.clone()
             };
 
-            tgid_parent.new_child(flags.contains(CloneFlags::CLONE_SIGHAND))
-        };
-
-        // 处理虚拟内存 (VM)
-        let vm = if flags.contains(CloneFlags::CLONE_VM) {
-            current_task.vm.clone()
-        } else {
-            Arc::new(SpinLock::new(
-                current_task.vm.lock_save_irq().clone_as_cow()?,
-            ))
-        };
-
-        // 处理文件描述符表
-        let files = if flags.contains(CloneFlags::CLONE_FILES) {
-            current_task.fd_table.clone()
-        } else {
-            Arc::new(SpinLock::new(
-                current_task.fd_table.lock_save_irq().clone_for_exec(),
-            ))
-        };
-
-        // 处理当前工作目录
-        let cwd = if flags.contains(CloneFlags::CLONE_FS) {
-            current_task.cwd.clone()
-        } else {
-            Arc::new(SpinLock::new(current_task.cwd.lock_save_irq().clone()))
+            tgid_parent.new_child(
+                flags.contains(CloneFlags::CLONE_SIGHAND),
+                namespaces.pid_for_children.clone(),
+            )
         };
 
-        let creds = current_task.creds.lock_save_irq().clone();
-
         // ====================================================================
         // 关键修改：架构相关的上下文设置 (Context Setup)
         // ====================================================================
@@ -126,13 +179,13 @@ pub async fn sys_clone(
             if newsp != 0 {
                 // ARM64 TrapFrame 通常有独立的 sp 字段，或者是 regs[31]
                 // 这里假设你的 TrapFrame 定义中有 sp 字段
-                user_ctx.sp = newsp as u64; 
+                user_ctx.sp_el0 = newsp as u64;
             }
 
             // 3. 设置 TLS (Thread Local Storage)
             if flags.contains(CloneFlags::CLONE_SETTLS) {
                 // ARM64 使用 tpidr_el0 系统寄存器
-                user_ctx.tpidr = tls as u64;
+                user_ctx.tpid_el0 = tls as u64;
             }
         }
 
@@ -160,7 +213,7 @@ pub async fn sys_clone(
         Task {
             tid,
             process: tg,
-            vm,
+            vm: SpinLock::new(vm),
             fd_table: files,
             cwd,
             creds: SpinLock::new(creds),
@@ -173,17 +226,98 @@ pub async fn sys_clone(
             deadline: SpinLock::new(*current_task.deadline.lock_save_irq()),
             state: Arc::new(SpinLock::new(TaskState::Runnable)),
             last_run: SpinLock::new(None),
+            on_cpu: SpinLock::new(None),
             robust_list: SpinLock::new(None),
+            ptrace: Ptrace::new(),
+            comm: SpinLock::new(*current_task.comm.lock_save_irq()),
+            // The parent-death signal isn't inherited.
+            pdeath_signal: SpinLock::new(None),
+            no_new_privs: SpinLock::new(*current_task.no_new_privs.lock_save_irq()),
+            namespaces: SpinLock::new(namespaces),
+            tid_address: SpinLock::new(
+                flags
+                    .contains(CloneFlags::CLONE_CHILD_CLEARTID)
+                    .then_some(child_tidptr),
+            ),
+            vfork_done: SpinLock::new(vfork_done.clone()),
         }
     };
 
+    // The pidfd is allocated and reported before the child is published, so
+    // that a failure can be undone by forgetting the child.
+    if flags.contains(CloneFlags::CLONE_PIDFD)
+        && let Err(e) = report_pidfd(new_task.process.clone(), parent_tidptr).await
+    {
+        abandon_child(&new_task.process);
+        return Err(e);
+    }
+
+    // A new thread is given a TID in each of its process's PID namespaces.
+    new_task.process.pid_ns.attach_thread(new_task.descriptor());
+
     TASK_LIST
         .lock_save_irq()
         .insert(new_task.descriptor(), Arc::downgrade(&new_task.state));
 
     let tid = new_task.tid;
+    let new_task = Arc::new(new_task);
+
+    new_task
+        .process
+        .threads
+        .lock_save_irq()
+        .insert(tid, Arc::downgrade(&new_task));
+
+    if flags.contains(CloneFlags::CLONE_PTRACE) {
+        ptrace::trace_child(&current_task(), &new_task);
+    }
+
+    let vtid = tid_vnr(&new_task);
+
+    sched::insert_task(new_task);
+
+    if let Some(vfork_done) = vfork_done {
+        vfork_done
+            .wait_until(|done| if *done { Some(()) } else { None })
+            .await;
+    }
+
+    Ok(vtid as _)
+}
+
+/// Opens a pidfd for the new process `process`, and reports it through
+/// `parent_tidptr`.
+async fn report_pidfd(process: Arc<ThreadGroup>, parent_tidptr: UA) -> Result<()> {
+    let pidfd = open_pidfd(process, OpenFlags::empty())?;
+
+    if let Err(e) = copy_to_user(parent_tidptr.cast::<i32>(), pidfd.as_raw()).await {
+        current_task().fd_table.lock_save_irq().remove(pidfd);
+        return Err(e);
+    }
+
+    Ok(())
+}
 
-    sched::insert_task(Arc::new(new_task));
+/// Forgets a new process which failed to be created before it ran. It's freed,
+/// along with its PID, once the last reference to it is dropped.
+fn abandon_child(process: &ThreadGroup) {
+    if let Some(parent) = process
+        .parent
+        .lock_save_irq()
+        .as_ref()
+        .and_then(Weak::upgrade)
+    {
+        parent.children.lock_save_irq().remove(&process.tgid);
+    }
+}
 
-    Ok(tid.value() as _)
+/// Wakes the parent of `task` if it was created by `vfork`, as it no longer
+/// uses the parent's address space.
+pub fn complete_vfork(task: &Task) {
+    if let Some(vfork_done) = task.vfork_done.lock_save_irq().take() {
+        vfork_done.update(|done| {
+            *done = true;
+            WakeupType::One
+        });
+    }
 }
\ No newline at end of file
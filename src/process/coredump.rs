@@ -0,0 +1,476 @@
+//! ELF core dump generation for signals whose default action is `Core`.
+//!
+//! The dump is written to a file named `core` in the process's current working
+//! directory and consists of:
+//!
+//! - A `PT_NOTE` segment holding an `NT_PRSTATUS` note for every thread (the
+//!   thread which took the signal first), the auxiliary vector (`NT_AUXV`) and
+//!   the list of file-backed mappings (`NT_FILE`).
+//! - One `PT_LOAD` segment per VMA. The contents of read-only file mappings are
+//!   omitted, since they can be recovered from the file itself.
+//!
+//! The file is cut short at the soft `RLIMIT_CORE`.
+
+use super::{
+    Task,
+    exit::kill_other_threads,
+    prctl::SUID_DUMP_DISABLE,
+    thread_group::{ProcessState, rsrc_lim::RlimitId, signal::SigId},
+};
+use crate::{
+    arch::{Arch, ArchImpl},
+    memory::{remote::access_remote_vm, shared::SharedMemory},
+};
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::{cmp::min, mem, slice};
+use libkernel::{
+    UserAddressSpace,
+    error::{FsError, KernelError, Result},
+    fs::{FileType, Inode},
+    memory::{
+        PAGE_SIZE,
+        proc_vm::vmarea::{VMAPermissions, VMAreaKind},
+        region::VirtMemoryRegion,
+    },
+};
+use object::{
+    LittleEndian as LE, U16, U32, U64,
+    elf::{self, FileHeader64, Ident, ProgramHeader64},
+    pod::bytes_of,
+};
+
+#[cfg(target_arch = "aarch64")]
+const ELF_MACHINE: u16 = elf::EM_AARCH64;
+#[cfg(not(target_arch = "aarch64"))]
+const ELF_MACHINE: u16 = elf::EM_RISCV;
+
+const CORE_NAME: &str = "core";
+const NOTE_NAME: &[u8] = b"CORE\0";
+
+/// The `elf_prstatus` structure found in an `NT_PRSTATUS` note.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ElfPrStatus {
+    /// `si_signo`, `si_code` and `si_errno` of the signal being handled.
+    info: [i32; 3],
+    cursig: i16,
+    _pad: i16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    /// User, system and cumulative child times, as `timeval`s.
+    times: [i64; 8],
+    reg: <ArchImpl as Arch>::PtraceRegs,
+    fpvalid: i32,
+    _pad2: i32,
+}
+
+/// A snapshot of a VMA, taken so that the VM lock needn't be held whilst the
+/// dump is written.
+struct CoreVma {
+    region: VirtMemoryRegion,
+    permissions: VMAPermissions,
+    /// Whether the contents of this VMA are included in the dump.
+    dump: bool,
+    /// Whether pages which have never been faulted in have contents elsewhere,
+    /// rather than reading as zero.
+    backed: bool,
+}
+
+/// Tracks the write position within the core file, enforcing `RLIMIT_CORE`.
+struct CoreWriter {
+    inode: Arc<dyn Inode>,
+    pos: u64,
+    limit: u64,
+}
+
+impl CoreWriter {
+    /// Returns whether the file has reached `RLIMIT_CORE`.
+    fn is_full(&self) -> bool {
+        self.pos >= self.limit
+    }
+
+    /// Appends `buf` to the file. Anything beyond `RLIMIT_CORE` is discarded.
+    async fn write(&mut self, buf: &[u8]) -> Result<()> {
+        let len = min(buf.len() as u64, self.limit.saturating_sub(self.pos)) as usize;
+        let buf = &buf[..len];
+        let mut done = 0;
+
+        while done < buf.len() {
+            let written = self
+                .inode
+                .write_at(self.pos + done as u64, &buf[done..])
+                .await?;
+
+            if written == 0 {
+                return Err(KernelError::TooLarge);
+            }
+
+            done += written;
+        }
+
+        self.pos += buf.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Appends a note with the given type and descriptor to `notes`.
+fn push_note(notes: &mut Vec<u8>, n_type: u32, desc: &[u8]) {
+    notes.extend_from_slice(&(NOTE_NAME.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&n_type.to_le_bytes());
+    notes.extend_from_slice(NOTE_NAME);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+}
+
+fn prstatus_for(task: &Task, sig: SigId) -> ElfPrStatus {
+    let process = &task.process;
+
+    let ppid = process
+        .parent
+        .lock_save_irq()
+        .as_ref()
+        .and_then(|x| x.upgrade())
+        .map(|x| x.tgid.value())
+        .unwrap_or(0);
+
+    ElfPrStatus {
+        info: [sig.user_id() as i32, 0, 0],
+        cursig: sig.user_id() as i16,
+        _pad: 0,
+        sigpend: task.pending_signals.lock_save_irq().bits(),
+        sighold: task.sig_mask.lock_save_irq().bits(),
+        pid: task.tid.value() as _,
+        ppid: ppid as _,
+        pgrp: process.pgid.lock_save_irq().value() as _,
+        sid: process.sid.lock_save_irq().value() as _,
+        // We don't yet account per-task CPU time.
+        times: [0; 8],
+        reg: ArchImpl::ptrace_get_regs(task.ctx.lock_save_irq().user()),
+        fpvalid: 0,
+        _pad2: 0,
+    }
+}
+
+/// Builds the contents of the `PT_NOTE` segment, along with a snapshot of the
+/// VMAs to be dumped.
+fn collect(task: &Arc<Task>, sig: SigId) -> (Vec<u8>, Vec<CoreVma>) {
+    let mut notes = Vec::new();
+
+    let mut threads = vec![task.clone()];
+    threads.extend(
+        task.process
+            .threads
+            .lock_save_irq()
+            .values()
+            .filter_map(|t| t.upgrade())
+            .filter(|t| t.tid != task.tid),
+    );
+
+    for thread in threads {
+        let prstatus = prstatus_for(&thread, sig);
+
+        // SAFETY: `ElfPrStatus` is `repr(C)`, contains only plain data and has
+        // explicit padding fields.
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                (&prstatus as *const ElfPrStatus).cast::<u8>(),
+                mem::size_of::<ElfPrStatus>(),
+            )
+        };
+
+        push_note(&mut notes, elf::NT_PRSTATUS, bytes);
+    }
+
+    let vm = task.vm();
+
+    let vm = vm.lock_save_irq();
+
+    let auxv: Vec<u8> = vm.auxv().iter().flat_map(|x| x.to_le_bytes()).collect();
+    push_note(&mut notes, elf::NT_AUXV, &auxv);
+
+    let mut vmas = Vec::new();
+    let mut files = Vec::new();
+    let mut names = Vec::new();
+
+    for vma in vm.mm().vmas() {
+        let permissions = vma.permissions();
+
+        let file = match vma.kind() {
+            VMAreaKind::File(mapping) => Some((mapping.file().id(), mapping.offset())),
+            VMAreaKind::Shared(mapping) => SharedMemory::from_pages(&mapping.pages())
+                .file()
+                .map(|file| (file.id(), mapping.offset())),
+            _ => None,
+        };
+
+        if let Some((inode, offset)) = file
+            && let Some(name) = vm.file_name(inode)
+        {
+            files.push((
+                vma.region().start_address().value() as u64,
+                vma.region().end_address().value() as u64,
+                offset / PAGE_SIZE as u64,
+            ));
+            names.extend_from_slice(name.as_str().as_bytes());
+            names.push(0);
+        }
+
+        vmas.push(CoreVma {
+            region: vma.region(),
+            permissions,
+            dump: !vma.is_file_backed() || permissions.write,
+            backed: !matches!(vma.kind(), VMAreaKind::Anon),
+        });
+    }
+
+    let mut nt_file = Vec::new();
+    nt_file.extend_from_slice(&(files.len() as u64).to_le_bytes());
+    nt_file.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    for (start, end, ofs) in files {
+        nt_file.extend_from_slice(&start.to_le_bytes());
+        nt_file.extend_from_slice(&end.to_le_bytes());
+        nt_file.extend_from_slice(&ofs.to_le_bytes());
+    }
+    nt_file.extend_from_slice(&names);
+    push_note(&mut notes, elf::NT_FILE, &nt_file);
+
+    (notes, vmas)
+}
+
+/// Opens (creating or truncating) the core file in the task's cwd.
+///
+/// As on Linux, an existing file is only reused if it's a regular file with a
+/// single link, owned by the dumping process's effective user.
+async fn open_core_file(task: &Task) -> Result<Arc<dyn Inode>> {
+    let cwd = task.cwd.lock_save_irq().0.clone();
+
+    match cwd.lookup(CORE_NAME).await {
+        Ok(inode) => {
+            let attr = inode.getattr().await?;
+
+            if attr.file_type != FileType::File || attr.nlinks > 1 {
+                return Err(KernelError::NotPermitted);
+            }
+
+            if attr.uid != task.creds.lock_save_irq().euid() {
+                return Err(KernelError::NotPermitted);
+            }
+
+            inode.truncate(0).await?;
+            Ok(inode)
+        }
+        Err(KernelError::Fs(FsError::NotFound)) => {
+            cwd.create(CORE_NAME, FileType::File, 0o600).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn phdr(
+    p_type: u32,
+    p_flags: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+    memsz: u64,
+) -> ProgramHeader64<LE> {
+    ProgramHeader64 {
+        p_type: U32::new(LE, p_type),
+        p_flags: U32::new(LE, p_flags),
+        p_offset: U64::new(LE, offset),
+        p_vaddr: U64::new(LE, vaddr),
+        p_paddr: U64::new(LE, 0),
+        p_filesz: U64::new(LE, filesz),
+        p_memsz: U64::new(LE, memsz),
+        p_align: U64::new(
+            LE,
+            if p_type == elf::PT_LOAD {
+                PAGE_SIZE as u64
+            } else {
+                4
+            },
+        ),
+    }
+}
+
+async fn write_core(task: &Arc<Task>, sig: SigId, limit: u64) -> Result<()> {
+    let (notes, vmas) = collect(task, sig);
+
+    let ehdr_sz = mem::size_of::<FileHeader64<LE>>();
+    let phdr_sz = mem::size_of::<ProgramHeader64<LE>>();
+    let phnum = vmas.len() + 1;
+
+    if phnum >= elf::PN_XNUM as usize {
+        return Err(KernelError::TooLarge);
+    }
+
+    let notes_offset = ehdr_sz + phnum * phdr_sz;
+    let data_offset = (notes_offset + notes.len()).next_multiple_of(PAGE_SIZE);
+
+    let ehdr = FileHeader64::<LE> {
+        e_ident: Ident {
+            magic: elf::ELFMAG,
+            class: elf::ELFCLASS64,
+            data: elf::ELFDATA2LSB,
+            version: elf::EV_CURRENT,
+            os_abi: elf::ELFOSABI_NONE,
+            abi_version: 0,
+            padding: [0; 7],
+        },
+        e_type: U16::new(LE, elf::ET_CORE),
+        e_machine: U16::new(LE, ELF_MACHINE),
+        e_version: U32::new(LE, elf::EV_CURRENT as u32),
+        e_entry: U64::new(LE, 0),
+        e_phoff: U64::new(LE, ehdr_sz as u64),
+        e_shoff: U64::new(LE, 0),
+        e_flags: U32::new(LE, 0),
+        e_ehsize: U16::new(LE, ehdr_sz as u16),
+        e_phentsize: U16::new(LE, phdr_sz as u16),
+        e_phnum: U16::new(LE, phnum as u16),
+        e_shentsize: U16::new(LE, 0),
+        e_shnum: U16::new(LE, 0),
+        e_shstrndx: U16::new(LE, 0),
+    };
+
+    let mut headers = Vec::with_capacity(data_offset);
+    headers.extend_from_slice(bytes_of(&ehdr));
+    headers.extend_from_slice(bytes_of(&phdr(
+        elf::PT_NOTE,
+        0,
+        notes_offset as u64,
+        0,
+        notes.len() as u64,
+        0,
+    )));
+
+    let mut offset = data_offset as u64;
+
+    for vma in vmas.iter() {
+        let size = vma.region.size() as u64;
+        let filesz = if vma.dump { size } else { 0 };
+        let mut flags = 0;
+
+        if vma.permissions.read {
+            flags |= elf::PF_R;
+        }
+        if vma.permissions.write {
+            flags |= elf::PF_W;
+        }
+        if vma.permissions.execute {
+            flags |= elf::PF_X;
+        }
+
+        headers.extend_from_slice(bytes_of(&phdr(
+            elf::PT_LOAD,
+            flags,
+            offset,
+            vma.region.start_address().value() as u64,
+            filesz,
+            size,
+        )));
+
+        offset += filesz;
+    }
+
+    headers.extend_from_slice(&notes);
+    headers.resize(data_offset, 0);
+
+    // As on Linux, a limit of less than a page means no dump at all.
+    if limit < PAGE_SIZE as u64 {
+        return Err(KernelError::TooLarge);
+    }
+
+    let mut writer = CoreWriter {
+        inode: open_core_file(task).await?,
+        pos: 0,
+        limit,
+    };
+
+    writer.write(&headers).await?;
+
+    let mut page = vec![0u8; PAGE_SIZE];
+    let vm = task.vm();
+
+    'dump: for vma in vmas.iter().filter(|vma| vma.dump) {
+        for va in vma.region.iter_pages() {
+            if writer.is_full() {
+                break 'dump;
+            }
+
+            let populated = {
+                let mut vm = vm.lock_save_irq();
+                let address_space = vm.mm_mut().address_space_mut();
+
+                address_space.translate(va).is_some() || address_space.swap_entry(va).is_some()
+            };
+
+            // Anonymous pages which have never been touched read as zero; don't
+            // fault them in just to dump them. Any other page is read as the
+            // task would see it, from swap or its file if need be. A page that
+            // can't be read, such as one beyond the end of its file, is dumped
+            // as zeroes.
+            if !(populated || vma.backed)
+                || access_remote_vm(&vm, va, &mut page, false).await.is_err()
+            {
+                page.fill(0);
+            }
+
+            writer.write(&page).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a core dump for `task`'s process, which is being killed by `sig`.
+///
+/// Returns `true` if a core file was written, even if it was cut short at the
+/// soft `RLIMIT_CORE`. No dump is attempted if that limit is zero, or if the
+/// process isn't dumpable.
+pub async fn do_coredump(task: &Arc<Task>, sig: SigId) -> bool {
+    if *task.process.dumpable.lock_save_irq() == SUID_DUMP_DISABLE {
+        return false;
+    }
+
+    let limit = task
+        .process
+        .rsrc_lim
+        .lock_save_irq()
+        .get(RlimitId::CORE)
+        .rlim_cur;
+
+    if limit == 0 {
+        return false;
+    }
+
+    {
+        let mut state = task.process.state.lock_save_irq();
+
+        // If the process is exiting, or another thread is dumping it or
+        // exec'ing, then this thread is being killed.
+        if *state != ProcessState::Running {
+            return false;
+        }
+
+        *state = ProcessState::CoreDump;
+    }
+
+    // The other threads are killed by the exit which follows the dump anyway;
+    // do it first, so that neither their registers nor the memory being
+    // dumped can change beneath us.
+    kill_other_threads(task);
+
+    let dumped = write_core(task, sig, limit).await.is_ok();
+
+    // Let the exit which follows tear the process down.
+    *task.process.state.lock_save_irq() = ProcessState::Running;
+
+    dumped
+}
@@ -2,14 +2,18 @@ use core::convert::Infallible;
 
 use crate::{
     memory::uaccess::{UserCopyable, copy_to_user},
+    process::thread_group::pid::tid_vnr,
     sched::current_task,
 };
+use caps::{CapSet, Capabilities};
 use libkernel::{
-    error::Result,
+    error::{KernelError, Result},
     memory::address::TUA,
     proc::ids::{Gid, Uid},
 };
 
+pub mod caps;
+
 unsafe impl UserCopyable for Uid {}
 unsafe impl UserCopyable for Gid {}
 
@@ -21,6 +25,7 @@ pub struct Credentials {
     gid: Gid,
     egid: Gid,
     sgid: Gid,
+    caps: Capabilities,
 }
 
 impl Credentials {
@@ -32,6 +37,7 @@ impl Credentials {
             gid: Gid::new_root_group(),
             egid: Gid::new_root_group(),
             sgid: Gid::new_root_group(),
+            caps: Capabilities::new_full(),
         }
     }
 
@@ -58,6 +64,153 @@ impl Credentials {
     pub fn sgid(&self) -> Gid {
         self.sgid
     }
+
+    pub fn caps(&self) -> Capabilities {
+        self.caps
+    }
+
+    pub fn set_caps(&mut self, caps: Capabilities) {
+        self.caps = caps;
+    }
+
+    /// Returns true if `cap` is in the effective capability set.
+    pub fn has_cap(&self, cap: CapSet) -> bool {
+        self.caps.effective.contains(cap)
+    }
+
+    /// Returns true if a task with these credentials may send a signal to a
+    /// task with the `target` credentials.
+    pub fn may_signal(&self, target: &Credentials) -> bool {
+        self.has_cap(CapSet::CAP_KILL)
+            || self.uid == target.uid
+            || self.uid == target.suid
+            || self.euid == target.uid
+            || self.euid == target.suid
+    }
+
+    /// Returns true if a task with these credentials may attach to, and so
+    /// inspect and modify, a task with the `target` credentials.
+    ///
+    /// Without `CAP_SYS_PTRACE`, the target must run with exactly our real
+    /// user and group IDs, and must not hold capabilities that we lack.
+    pub fn may_trace(&self, target: &Credentials) -> bool {
+        if self.has_cap(CapSet::CAP_SYS_PTRACE) {
+            return true;
+        }
+
+        [target.uid, target.euid, target.suid] == [self.uid; 3]
+            && [target.gid, target.egid, target.sgid] == [self.gid; 3]
+            && self.caps.permitted.contains(target.caps.permitted)
+    }
+
+    /// Returns true if a task with these credentials may give a file owned by
+    /// `owner`/`group` the new `uid` and `gid`, where `None` leaves an ID
+    /// unchanged.
+    ///
+    /// Without `CAP_CHOWN`, only the owner may change the file's group, and
+    /// only to its own effective group; the owner can never be changed.
+    pub fn may_chown(&self, owner: Uid, group: Gid, uid: Option<Uid>, gid: Option<Gid>) -> bool {
+        if self.has_cap(CapSet::CAP_CHOWN) {
+            return true;
+        }
+
+        let uid_ok = uid.is_none_or(|uid| uid == owner);
+        let gid_ok = gid.is_none_or(|gid| gid == group || gid == self.egid);
+
+        self.euid == owner && uid_ok && gid_ok
+    }
+
+    fn root_uids(&self) -> [bool; 3] {
+        [self.uid.is_root(), self.euid.is_root(), self.suid.is_root()]
+    }
+
+    /// Applies the credential changes of an `execve` of a file, where
+    /// `setuid`/`setgid` are the file's owner IDs if the corresponding mode
+    /// bits are set.
+    pub fn exec_transition(&mut self, setuid: Option<Uid>, setgid: Option<Gid>) {
+        if let Some(uid) = setuid {
+            self.euid = uid;
+        }
+
+        if let Some(gid) = setgid {
+            self.egid = gid;
+        }
+
+        // The saved IDs always take a copy of the effective IDs on exec.
+        self.suid = self.euid;
+        self.sgid = self.egid;
+
+        self.caps.exec_transition(
+            self.uid.is_root(),
+            self.euid.is_root(),
+            setuid.is_some() || setgid.is_some(),
+        );
+    }
+
+    /// Changes the user IDs, where `None` leaves the ID unchanged.
+    ///
+    /// Without `CAP_SETUID`, each new ID must match one of the current real,
+    /// effective or saved user IDs.
+    pub fn set_resuid(
+        &mut self,
+        uid: Option<Uid>,
+        euid: Option<Uid>,
+        suid: Option<Uid>,
+    ) -> Result<()> {
+        let current = [self.uid, self.euid, self.suid];
+
+        if !self.has_cap(CapSet::CAP_SETUID)
+            && [uid, euid, suid]
+                .into_iter()
+                .flatten()
+                .any(|id| !current.contains(&id))
+        {
+            return Err(KernelError::NotPermitted);
+        }
+
+        let old_root = self.root_uids();
+
+        self.uid = uid.unwrap_or(self.uid);
+        self.euid = euid.unwrap_or(self.euid);
+        self.suid = suid.unwrap_or(self.suid);
+
+        self.caps.setuid_transition(old_root, self.root_uids());
+
+        Ok(())
+    }
+
+    /// Changes the group IDs, where `None` leaves the ID unchanged.
+    ///
+    /// Without `CAP_SETGID`, each new ID must match one of the current real,
+    /// effective or saved group IDs.
+    pub fn set_resgid(
+        &mut self,
+        gid: Option<Gid>,
+        egid: Option<Gid>,
+        sgid: Option<Gid>,
+    ) -> Result<()> {
+        let current = [self.gid, self.egid, self.sgid];
+
+        if !self.has_cap(CapSet::CAP_SETGID)
+            && [gid, egid, sgid]
+                .into_iter()
+                .flatten()
+                .any(|id| !current.contains(&id))
+        {
+            return Err(KernelError::NotPermitted);
+        }
+
+        self.gid = gid.unwrap_or(self.gid);
+        self.egid = egid.unwrap_or(self.egid);
+        self.sgid = sgid.unwrap_or(self.sgid);
+
+        Ok(())
+    }
+}
+
+/// Converts a user-supplied ID argument, where -1 means "unchanged".
+fn id_arg(id: u32) -> Option<u32> {
+    if id == u32::MAX { None } else { Some(id) }
 }
 
 pub fn sys_getuid() -> core::result::Result<usize, Infallible> {
@@ -85,9 +238,7 @@ pub fn sys_getegid() -> core::result::Result<usize, Infallible> {
 }
 
 pub fn sys_gettid() -> core::result::Result<usize, Infallible> {
-    let tid: u32 = current_task().tid.0;
-
-    Ok(tid as _)
+    Ok(tid_vnr(&current_task()) as _)
 }
 
 pub async fn sys_getresuid(ruid: TUA<Uid>, euid: TUA<Uid>, suid: TUA<Uid>) -> Result<usize> {
@@ -111,3 +262,105 @@ pub async fn sys_getresgid(rgid: TUA<Gid>, egid: TUA<Gid>, sgid: TUA<Gid>) -> Re
 
     Ok(0)
 }
+
+pub fn sys_setuid(uid: u32) -> Result<usize> {
+    let task = current_task();
+    let mut creds = task.creds.lock_save_irq();
+    let uid = Uid::new(uid);
+
+    if creds.has_cap(CapSet::CAP_SETUID) {
+        creds.set_resuid(Some(uid), Some(uid), Some(uid))?;
+    } else if uid == creds.uid || uid == creds.suid {
+        creds.set_resuid(None, Some(uid), None)?;
+    } else {
+        return Err(KernelError::NotPermitted);
+    }
+
+    Ok(0)
+}
+
+pub fn sys_setgid(gid: u32) -> Result<usize> {
+    let task = current_task();
+    let mut creds = task.creds.lock_save_irq();
+    let gid = Gid::new(gid);
+
+    if creds.has_cap(CapSet::CAP_SETGID) {
+        creds.set_resgid(Some(gid), Some(gid), Some(gid))?;
+    } else if gid == creds.gid || gid == creds.sgid {
+        creds.set_resgid(None, Some(gid), None)?;
+    } else {
+        return Err(KernelError::NotPermitted);
+    }
+
+    Ok(0)
+}
+
+pub fn sys_setreuid(ruid: u32, euid: u32) -> Result<usize> {
+    let task = current_task();
+    let mut creds = task.creds.lock_save_irq();
+    let ruid = id_arg(ruid).map(Uid::new);
+    let euid = id_arg(euid).map(Uid::new);
+
+    if !creds.has_cap(CapSet::CAP_SETUID) {
+        // The real UID may only be swapped with the effective UID.
+        if ruid.is_some_and(|id| id != creds.uid && id != creds.euid) {
+            return Err(KernelError::NotPermitted);
+        }
+    }
+
+    // If the real UID is set, or the effective UID is set to something other
+    // than the previous real UID, the saved UID follows the new effective UID.
+    let suid = if ruid.is_some() || euid.is_some_and(|id| id != creds.uid) {
+        Some(euid.unwrap_or(creds.euid))
+    } else {
+        None
+    };
+
+    creds.set_resuid(ruid, euid, suid).map(|_| 0)
+}
+
+pub fn sys_setregid(rgid: u32, egid: u32) -> Result<usize> {
+    let task = current_task();
+    let mut creds = task.creds.lock_save_irq();
+    let rgid = id_arg(rgid).map(Gid::new);
+    let egid = id_arg(egid).map(Gid::new);
+
+    if !creds.has_cap(CapSet::CAP_SETGID) {
+        // The real GID may only be swapped with the effective GID.
+        if rgid.is_some_and(|id| id != creds.gid && id != creds.egid) {
+            return Err(KernelError::NotPermitted);
+        }
+    }
+
+    let sgid = if rgid.is_some() || egid.is_some_and(|id| id != creds.gid) {
+        Some(egid.unwrap_or(creds.egid))
+    } else {
+        None
+    };
+
+    creds.set_resgid(rgid, egid, sgid).map(|_| 0)
+}
+
+pub fn sys_setresuid(ruid: u32, euid: u32, suid: u32) -> Result<usize> {
+    current_task()
+        .creds
+        .lock_save_irq()
+        .set_resuid(
+            id_arg(ruid).map(Uid::new),
+            id_arg(euid).map(Uid::new),
+            id_arg(suid).map(Uid::new),
+        )
+        .map(|_| 0)
+}
+
+pub fn sys_setresgid(rgid: u32, egid: u32, sgid: u32) -> Result<usize> {
+    current_task()
+        .creds
+        .lock_save_irq()
+        .set_resgid(
+            id_arg(rgid).map(Gid::new),
+            id_arg(egid).map(Gid::new),
+            id_arg(sgid).map(Gid::new),
+        )
+        .map(|_| 0)
+}
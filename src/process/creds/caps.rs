@@ -0,0 +1,284 @@
+use crate::{
+    memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
+    process::{
+        find_task_by_pid,
+        thread_group::pid::{PidT, tid_vnr},
+    },
+    sched::current_task,
+};
+use bitflags::bitflags;
+use libkernel::{
+    error::{KernelError, Result},
+    memory::address::TUA,
+};
+
+bitflags! {
+    /// A set of POSIX capabilities, using the Linux bit numbering.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct CapSet: u64 {
+        const CAP_CHOWN              = 1 << 0;
+        const CAP_DAC_OVERRIDE       = 1 << 1;
+        const CAP_DAC_READ_SEARCH    = 1 << 2;
+        const CAP_FOWNER             = 1 << 3;
+        const CAP_FSETID             = 1 << 4;
+        const CAP_KILL               = 1 << 5;
+        const CAP_SETGID             = 1 << 6;
+        const CAP_SETUID             = 1 << 7;
+        const CAP_SETPCAP            = 1 << 8;
+        const CAP_LINUX_IMMUTABLE    = 1 << 9;
+        const CAP_NET_BIND_SERVICE   = 1 << 10;
+        const CAP_NET_BROADCAST      = 1 << 11;
+        const CAP_NET_ADMIN          = 1 << 12;
+        const CAP_NET_RAW            = 1 << 13;
+        const CAP_IPC_LOCK           = 1 << 14;
+        const CAP_IPC_OWNER          = 1 << 15;
+        const CAP_SYS_MODULE         = 1 << 16;
+        const CAP_SYS_RAWIO          = 1 << 17;
+        const CAP_SYS_CHROOT         = 1 << 18;
+        const CAP_SYS_PTRACE         = 1 << 19;
+        const CAP_SYS_PACCT          = 1 << 20;
+        const CAP_SYS_ADMIN          = 1 << 21;
+        const CAP_SYS_BOOT           = 1 << 22;
+        const CAP_SYS_NICE           = 1 << 23;
+        const CAP_SYS_RESOURCE       = 1 << 24;
+        const CAP_SYS_TIME           = 1 << 25;
+        const CAP_SYS_TTY_CONFIG     = 1 << 26;
+        const CAP_MKNOD              = 1 << 27;
+        const CAP_LEASE              = 1 << 28;
+        const CAP_AUDIT_WRITE        = 1 << 29;
+        const CAP_AUDIT_CONTROL      = 1 << 30;
+        const CAP_SETFCAP            = 1 << 31;
+        const CAP_MAC_OVERRIDE       = 1 << 32;
+        const CAP_MAC_ADMIN          = 1 << 33;
+        const CAP_SYSLOG             = 1 << 34;
+        const CAP_WAKE_ALARM         = 1 << 35;
+        const CAP_BLOCK_SUSPEND      = 1 << 36;
+        const CAP_AUDIT_READ         = 1 << 37;
+        const CAP_PERFMON            = 1 << 38;
+        const CAP_BPF                = 1 << 39;
+        const CAP_CHECKPOINT_RESTORE = 1 << 40;
+    }
+}
+
+impl CapSet {
+    fn low(self) -> u32 {
+        self.bits() as u32
+    }
+
+    fn high(self) -> u32 {
+        (self.bits() >> 32) as u32
+    }
+
+    fn from_halves(low: u32, high: u32) -> Self {
+        Self::from_bits_truncate(low as u64 | ((high as u64) << 32))
+    }
+}
+
+/// The per-task capability sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub permitted: CapSet,
+    pub effective: CapSet,
+    pub inheritable: CapSet,
+    pub bounding: CapSet,
+    pub ambient: CapSet,
+}
+
+impl Capabilities {
+    /// The capability sets of a task running as root from boot.
+    pub fn new_full() -> Self {
+        Self {
+            permitted: CapSet::all(),
+            effective: CapSet::all(),
+            inheritable: CapSet::empty(),
+            bounding: CapSet::all(),
+            ambient: CapSet::empty(),
+        }
+    }
+
+    /// Applies the capability transformation rules for `execve`.
+    ///
+    /// There are no file capabilities, so a binary is only "privileged" if it
+    /// runs as root, in which case it is treated as having full permitted and
+    /// inheritable file sets. `root_ruid`/`root_euid` are the user IDs after
+    /// any set-user-ID transition has been applied.
+    pub fn exec_transition(&mut self, root_ruid: bool, root_euid: bool, setid_exec: bool) {
+        if setid_exec {
+            // Ambient capabilities never survive a set-user/group-ID exec.
+            self.ambient = CapSet::empty();
+        }
+
+        if root_ruid || root_euid {
+            self.permitted = self.inheritable | self.bounding;
+            self.effective = if root_euid {
+                self.permitted
+            } else {
+                CapSet::empty()
+            };
+            self.ambient = CapSet::empty();
+        } else {
+            self.permitted = self.ambient;
+            self.effective = self.ambient;
+        }
+    }
+
+    /// Applies the capability fixups performed when a task changes its user
+    /// IDs via the `set*uid` family of syscalls.
+    pub fn setuid_transition(&mut self, old: [bool; 3], new: [bool; 3]) {
+        let [old_r, old_e, old_s] = old;
+        let [new_r, new_e, new_s] = new;
+
+        // Dropping root from all of the user IDs drops all capabilities.
+        if (old_r || old_e || old_s) && !(new_r || new_e || new_s) {
+            self.permitted = CapSet::empty();
+            self.effective = CapSet::empty();
+            self.ambient = CapSet::empty();
+        }
+
+        if old_e && !new_e {
+            self.effective = CapSet::empty();
+        }
+
+        if !old_e && new_e {
+            self.effective = self.permitted;
+        }
+    }
+}
+
+const LINUX_CAPABILITY_VERSION_1: u32 = 0x1998_0330;
+const LINUX_CAPABILITY_VERSION_2: u32 = 0x2007_1026;
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct UserCapHeader {
+    version: u32,
+    pid: PidT,
+}
+
+unsafe impl UserCopyable for UserCapHeader {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UserCapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+unsafe impl UserCopyable for UserCapData {}
+
+/// Validates the header's version, returning the number of `UserCapData`
+/// elements that the caller has passed. On a version mismatch, the preferred
+/// version is written back into the header as Linux does.
+async fn check_cap_header(hdr_ptr: TUA<UserCapHeader>) -> Result<(UserCapHeader, usize)> {
+    let mut hdr = copy_from_user(hdr_ptr).await?;
+
+    match hdr.version {
+        LINUX_CAPABILITY_VERSION_1 => Ok((hdr, 1)),
+        LINUX_CAPABILITY_VERSION_2 | LINUX_CAPABILITY_VERSION_3 => Ok((hdr, 2)),
+        _ => {
+            hdr.version = LINUX_CAPABILITY_VERSION_3;
+            copy_to_user(hdr_ptr, hdr).await?;
+            Err(KernelError::InvalidValue)
+        }
+    }
+}
+
+fn find_task_caps(pid: PidT) -> Result<Capabilities> {
+    let current = current_task();
+
+    if pid == 0 || pid == tid_vnr(&current) {
+        return Ok(current.creds.lock_save_irq().caps());
+    }
+
+    if pid < 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let task = find_task_by_pid(pid).ok_or(KernelError::NoProcess)?;
+
+    Ok(task.creds.lock_save_irq().caps())
+}
+
+pub async fn sys_capget(hdr_ptr: TUA<UserCapHeader>, data_ptr: TUA<UserCapData>) -> Result<usize> {
+    let (hdr, nr_elems) = check_cap_header(hdr_ptr).await?;
+
+    // A NULL data pointer is used by userspace to probe the kernel's
+    // preferred version.
+    if data_ptr.is_null() {
+        return Ok(0);
+    }
+
+    let caps = find_task_caps(hdr.pid)?;
+
+    let data = [
+        UserCapData {
+            effective: caps.effective.low(),
+            permitted: caps.permitted.low(),
+            inheritable: caps.inheritable.low(),
+        },
+        UserCapData {
+            effective: caps.effective.high(),
+            permitted: caps.permitted.high(),
+            inheritable: caps.inheritable.high(),
+        },
+    ];
+
+    for (i, elem) in data.iter().take(nr_elems).enumerate() {
+        copy_to_user(data_ptr.add_objs(i), *elem).await?;
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_capset(hdr_ptr: TUA<UserCapHeader>, data_ptr: TUA<UserCapData>) -> Result<usize> {
+    let (hdr, nr_elems) = check_cap_header(hdr_ptr).await?;
+    let task = current_task();
+
+    // Only the calling thread's capabilities may be changed.
+    if hdr.pid != 0 && hdr.pid != tid_vnr(&task) {
+        return Err(KernelError::NotPermitted);
+    }
+
+    let mut data = [UserCapData::default(); 2];
+
+    for (i, elem) in data.iter_mut().take(nr_elems).enumerate() {
+        *elem = copy_from_user(data_ptr.add_objs(i)).await?;
+    }
+
+    let effective = CapSet::from_halves(data[0].effective, data[1].effective);
+    let permitted = CapSet::from_halves(data[0].permitted, data[1].permitted);
+    let inheritable = CapSet::from_halves(data[0].inheritable, data[1].inheritable);
+
+    let mut creds = task.creds.lock_save_irq();
+    let old = creds.caps();
+
+    // The inheritable set may only gain capabilities that are already
+    // permitted, unless the caller holds CAP_SETPCAP. In either case, it can
+    // never exceed the bounding set.
+    let inheritable_limit = if old.effective.contains(CapSet::CAP_SETPCAP) {
+        old.inheritable | old.bounding
+    } else {
+        old.inheritable | old.permitted
+    };
+
+    if !inheritable_limit.contains(inheritable)
+        || !(old.inheritable | old.bounding).contains(inheritable)
+        || !old.permitted.contains(permitted)
+        || !permitted.contains(effective)
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    creds.set_caps(Capabilities {
+        permitted,
+        effective,
+        inheritable,
+        bounding: old.bounding,
+        // Ambient capabilities must remain both permitted and inheritable.
+        ambient: old.ambient & permitted & inheritable,
+    });
+
+    Ok(0)
+}
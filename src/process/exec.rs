@@ -1,52 +1,157 @@
 use crate::{
     arch::{Arch, ArchImpl},
     fs::VFS,
+    kernel::rand::fill_random,
     memory::{
         page::ClaimedPage,
         uaccess::{copy_from_user, cstr::UserCStr},
+        vdso,
+    },
+    process::{
+        Task, TaskState,
+        clone::complete_vfork,
+        ctx::Context,
+        exit::kill_other_threads,
+        prctl::{SUID_DUMP_DISABLE, SUID_DUMP_USER, set_comm_from_path},
+        ptrace,
+        thread_group::{
+            ProcessState,
+            personality::PER_CLEAR_ON_SETID,
+            rsrc_lim::{RlimitId, rlimit},
+            signal::SignalState,
+        },
     },
-    process::{TaskState, ctx::Context, thread_group::signal::SignalState},
     sched::current_task,
 };
 use alloc::{string::String, vec};
 use alloc::{string::ToString, sync::Arc, vec::Vec};
-use auxv::{AT_NULL, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM, AT_RANDOM};
-use core::{ffi::c_char, mem, slice};
+use aslr::{Aslr, BRK_RND_BITS, MMAP_RND_BITS, STACK_RND_BITS};
+use auxv::{
+    AT_BASE, AT_EGID, AT_ENTRY, AT_EUID, AT_EXECFN, AT_GID, AT_HWCAP, AT_NULL, AT_PAGESZ, AT_PHDR,
+    AT_PHENT, AT_PHNUM, AT_RANDOM, AT_SECURE, AT_SYSINFO_EHDR, AT_UID,
+};
+use core::{ffi::c_char, iter, mem, slice};
 use libkernel::{
     UserAddressSpace, VirtualMemory,
     error::{ExecError, KernelError, Result},
-    fs::{Inode, path::Path},
+    fs::{Inode, attr::FilePermissions, path::Path, pathbuf::PathBuf},
     memory::{
-        PAGE_SIZE,
+        PAGE_MASK, PAGE_SIZE,
         address::{TUA, VA},
         permissions::PtePermissions,
         proc_vm::{
             ProcessVM,
-            memory_map::MemoryMap,
-            vmarea::{VMAPermissions, VMArea, VMAreaKind},
+            memory_map::{MMAP_BASE, MemoryMap},
+            vmarea::{VMAFlags, VMAPermissions, VMArea, VMAreaKind},
         },
         region::VirtMemoryRegion,
     },
+    proc::script::{BINPRM_BUF_SIZE, parse_shebang},
 };
 use object::{
     LittleEndian,
-    elf::{self, PT_LOAD},
+    elf::{self, ET_DYN, ET_EXEC, PT_INTERP, PT_LOAD, PT_PHDR},
     read::elf::{FileHeader, ProgramHeader},
 };
 
+pub mod aslr;
 mod auxv;
 
 const STACK_END: usize = 0x0000_8000_0000_0000;
-const STACK_SZ: usize = 0x2000 * 0x400;
-const STACK_START: usize = STACK_END - STACK_SZ;
 
+/// The bounds on the size of the stack of a new program, which is otherwise
+/// taken from `RLIMIT_STACK`. The stack is mapped at the minimum size, and grows
+/// on demand.
+const MIN_STACK_SZ: usize = 0x20000;
+const MAX_STACK_SZ: usize = 0x4000_0000;
+
+/// Where position-independent (`ET_DYN`) executables are loaded.
+const ET_DYN_BASE: usize = 0x5555_5555_0000;
+
+/// Where the program interpreter (dynamic linker) is loaded.
+const INTERP_BASE: usize = 0x7f00_0000_0000;
+
+/// The number of random bytes pointed to by `AT_RANDOM`.
+const AT_RANDOM_SZ: usize = 16;
+
+/// The longest `PT_INTERP` path we'll accept, including its NUL terminator.
+const PATH_MAX: usize = 4096;
+
+/// The maximum number of nested interpreters (e.g. a script whose interpreter
+/// is itself a script) permitted for a single exec.
+const MAX_INTERP_DEPTH: usize = 4;
+
+/// Replaces the current process image with the program at `path`.
+///
+/// ELF executables are loaded directly. Scripts beginning with a `#!` line are
+/// run by executing their interpreter instead, with the interpreter's optional
+/// argument and the script's path inserted before the original arguments.
 pub async fn kernel_exec(
-    inode: Arc<dyn Inode>,
-    argv: Vec<String>,
+    path: &Path,
+    mut inode: Arc<dyn Inode>,
+    mut argv: Vec<String>,
     envp: Vec<String>,
 ) -> Result<()> {
+    let execfn = path;
+    let mut path = PathBuf::from(path.as_str());
+
+    // A relative path, whether of the program or of a script's interpreter, is
+    // resolved against the cwd.
+    let (cwd, cwd_path) = current_task().cwd.lock_save_irq().clone();
+    let cwd_path = Path::new("/").join(&cwd_path);
+
+    for _ in 0..=MAX_INTERP_DEPTH {
+        let mut buf = [0u8; BINPRM_BUF_SIZE];
+        let len = inode.read_at(0, &mut buf).await?;
+        let buf = &buf[..len];
+
+        if buf.starts_with(&elf::ELFMAG) {
+            return exec_elf(&cwd_path.join(&path).normalize(), execfn, inode, argv, envp).await;
+        }
+
+        let Some((interp, arg)) = parse_shebang(buf)? else {
+            return Err(ExecError::UnknownFormat.into());
+        };
+
+        let mut new_argv = Vec::with_capacity(argv.len() + 2);
+        new_argv.push(interp.clone());
+        new_argv.extend(arg);
+        new_argv.push(path.as_str().to_string());
+        new_argv.extend(argv.into_iter().skip(1));
+
+        path = PathBuf::from(interp.as_str());
+        inode = VFS.resolve_path(&path, cwd.clone()).await?;
+        argv = new_argv;
+    }
+
+    Err(ExecError::TooManyInterpreters.into())
+}
+
+/// An ELF image whose segments have been added to a new address space.
+struct LoadedElf {
+    /// The offset applied to the image's link addresses.
+    bias: usize,
+    /// The relocated entry point.
+    entry: usize,
+    /// The end of the highest segment.
+    end: usize,
+    /// The user address of the program headers, if they are mapped.
+    phdr: Option<usize>,
+    phent: usize,
+    phnum: usize,
+    /// The program interpreter requested via `PT_INTERP`.
+    interp: Option<String>,
+}
+
+/// Builds VMAs for each `PT_LOAD` segment of the ELF file `inode`, appending
+/// them to `vmas`. `ET_DYN` images are relocated so that their lowest segment
+/// starts at `base`; `ET_EXEC` images are mapped at their link addresses.
+async fn load_elf(
+    inode: &Arc<dyn Inode>,
+    base: usize,
+    vmas: &mut Vec<VMArea>,
+) -> Result<LoadedElf> {
     let mut buf = [0u8; core::mem::size_of::<elf::FileHeader64<LittleEndian>>()];
-    let mut auxv = Vec::new();
 
     inode.read_at(0, &mut buf).await?;
 
@@ -54,12 +159,6 @@ pub async fn kernel_exec(
         .map_err(|_| ExecError::InvalidElfFormat)?;
     let endian = elf.endian().unwrap();
 
-    // Push program header params.
-    auxv.push(AT_PHNUM);
-    auxv.push(elf.e_phnum.get(endian) as _);
-    auxv.push(AT_PHENT);
-    auxv.push(elf.e_phentsize(endian) as _);
-
     let mut ph_buf = vec![
         0u8;
         elf.e_phnum.get(endian) as usize * elf.e_phentsize.get(endian) as usize
@@ -72,43 +171,226 @@ pub async fn kernel_exec(
         .program_headers(endian, ph_buf.as_slice())
         .map_err(|_| ExecError::InvalidPHdrFormat)?;
 
-    let mut vmas = Vec::new();
-    let mut highest_addr = 0;
+    let bias = match elf.e_type(endian) {
+        ET_EXEC => 0,
+        ET_DYN => {
+            let lowest = hdrs
+                .iter()
+                .filter(|hdr| hdr.p_type(endian) == PT_LOAD)
+                .map(|hdr| hdr.p_vaddr(endian) as usize)
+                .min()
+                .ok_or(ExecError::InvalidPHdrFormat)?;
+
+            // Images linked above `base` are simply loaded where they were
+            // linked.
+            base.saturating_sub(lowest & !(PAGE_SIZE - 1))
+        }
+        _ => return Err(ExecError::InvalidElfFormat.into()),
+    };
+
+    let phoff = elf.e_phoff(endian);
+    let mut phdr = None;
+    let mut interp = None;
+    let mut end = 0;
 
     for hdr in hdrs {
-        let kind = hdr.p_type(endian);
+        match hdr.p_type(endian) {
+            PT_LOAD => {
+                vmas.push(VMArea::from_pheader(inode.clone(), *hdr, endian, bias));
+
+                // Fall back to locating the program headers within the
+                // segment which maps them if there's no `PT_PHDR`.
+                let offset = hdr.p_offset(endian);
+                if phdr.is_none() && (offset..offset + hdr.p_filesz(endian)).contains(&phoff) {
+                    phdr = Some((hdr.p_vaddr(endian) + phoff - offset) as usize + bias);
+                }
+
+                end = end.max((hdr.p_vaddr(endian) + hdr.p_memsz(endian)) as usize + bias);
+            }
+            PT_PHDR => phdr = Some(hdr.p_vaddr(endian) as usize + bias),
+            PT_INTERP => {
+                let len = hdr.p_filesz(endian) as usize;
 
-        if kind == PT_LOAD {
-            vmas.push(VMArea::from_pheader(inode.clone(), *hdr, endian));
+                if len > PATH_MAX {
+                    return Err(ExecError::InvalidPHdrFormat.into());
+                }
 
-            if hdr.p_offset.get(endian) == 0 {
-                // TODO: poteintally more validation that this VA will contain
-                // the program headers.
-                auxv.push(AT_PHDR);
-                auxv.push(hdr.p_vaddr.get(endian) + elf.e_phoff.get(endian));
-            }
+                let mut path = vec![0u8; len];
+                inode.read_at(hdr.p_offset(endian), &mut path).await?;
 
-            let mapping_end = hdr.p_vaddr(endian) + hdr.p_memsz(endian);
+                let path = core::str::from_utf8(&path)
+                    .map_err(|_| ExecError::InvalidPHdrFormat)?
+                    .trim_end_matches('\0');
 
-            if mapping_end > highest_addr {
-                highest_addr = mapping_end;
+                if path.is_empty() {
+                    return Err(ExecError::InvalidPHdrFormat.into());
+                }
+
+                interp = Some(path.to_string());
             }
+            _ => {}
         }
     }
 
-    vmas.push(VMArea::new(
-        VirtMemoryRegion::new(VA::from_value(STACK_START), STACK_SZ),
-        VMAreaKind::Anon,
-        VMAPermissions::rw(),
-    ));
+    Ok(LoadedElf {
+        bias,
+        entry: elf.e_entry(endian) as usize + bias,
+        end,
+        phdr,
+        phent: elf.e_phentsize(endian) as usize,
+        phnum: elf.e_phnum(endian) as usize,
+        interp,
+    })
+}
+
+/// Executes the ELF file `inode`, found at the absolute path `path`.
+async fn exec_elf(
+    path: &Path,
+    execfn: &Path,
+    inode: Arc<dyn Inode>,
+    argv: Vec<String>,
+    envp: Vec<String>,
+) -> Result<()> {
+    let attr = inode.getattr().await?;
+    let current_task = current_task();
+
+    // A task with no_new_privs set ignores the set-ID bits of what it runs.
+    let no_new_privs = *current_task.no_new_privs.lock_save_irq();
+    let setid_mode = |mode| !no_new_privs && attr.mode.contains(mode);
+    let is_setid = setid_mode(FilePermissions::S_ISUID) || setid_mode(FilePermissions::S_ISGID);
+
+    // Executing a set-ID program drops flags which could be used to subvert it.
+    let personality = {
+        let mut personality = current_task.process.personality.lock_save_irq();
+
+        if is_setid {
+            *personality &= !PER_CLEAR_ON_SETID;
+        }
+
+        *personality
+    };
+
+    let aslr = Aslr::new(personality);
+    let stack_end = STACK_END - aslr.offset(STACK_RND_BITS);
+    let mmap_base = MMAP_BASE - aslr.offset(MMAP_RND_BITS);
+    let mut vmas = Vec::new();
+
+    let exe = load_elf(&inode, ET_DYN_BASE + aslr.offset(MMAP_RND_BITS), &mut vmas).await?;
+
+    let ld = match exe.interp.as_deref() {
+        Some(interp) => {
+            let (cwd, cwd_path) = current_task.cwd.lock_save_irq().clone();
+            let ld_inode = VFS.resolve_path(Path::new(interp), cwd).await?;
+            let ld = load_elf(
+                &ld_inode,
+                INTERP_BASE - aslr.offset(MMAP_RND_BITS),
+                &mut vmas,
+            )
+            .await?;
+
+            // The dynamic linker must be self-contained.
+            if ld.interp.is_some() {
+                return Err(ExecError::InvalidElfFormat.into());
+            }
+
+            let interp = Path::new("/")
+                .join(&cwd_path)
+                .join(Path::new(interp))
+                .normalize();
+
+            Some((ld, ld_inode, interp))
+        }
+        None => None,
+    };
+
+    let stack_sz = stack_size();
+
+    // The stack starts small, and grows down on demand up to `RLIMIT_STACK`.
+    vmas.push(
+        VMArea::new(
+            VirtMemoryRegion::new(VA::from_value(stack_end - MIN_STACK_SZ), MIN_STACK_SZ),
+            VMAreaKind::Anon,
+            VMAPermissions::rw(),
+        )
+        .with_flags(VMAFlags {
+            grows_down: true,
+            ..VMAFlags::default()
+        }),
+    );
+
+    // Apply any set-user-ID and set-group-ID bits, along with the capability
+    // transformation, to the new program image.
+    let mut creds = current_task.creds.lock_save_irq().clone();
+    creds.exec_transition(
+        setid_mode(FilePermissions::S_ISUID).then_some(attr.uid),
+        setid_mode(FilePermissions::S_ISGID).then_some(attr.gid),
+    );
+
+    let mut auxv = vec![
+        AT_HWCAP,
+        ArchImpl::elf_hwcap(),
+        AT_PHENT,
+        exe.phent as _,
+        AT_PHNUM,
+        exe.phnum as _,
+        AT_BASE,
+        ld.as_ref().map_or(0, |(ld, _, _)| ld.bias as _),
+        AT_ENTRY,
+        exe.entry as _,
+        AT_UID,
+        u32::from(creds.uid()) as _,
+        AT_EUID,
+        u32::from(creds.euid()) as _,
+        AT_GID,
+        u32::from(creds.gid()) as _,
+        AT_EGID,
+        u32::from(creds.egid()) as _,
+        AT_SECURE,
+        (creds.uid() != creds.euid() || creds.gid() != creds.egid()) as _,
+    ];
+
+    if let Some(phdr) = exe.phdr {
+        auxv.push(AT_PHDR);
+        auxv.push(phdr as _);
+    }
 
     let mut mem_map = MemoryMap::from_vmas(vmas)?;
+    mem_map.set_mmap_base(VA::from_value(mmap_base));
 
-    let stack_ptr = setup_user_stack(&mut mem_map, &argv, &envp, auxv)?;
+    let vdso = vdso::map_vdso(&mut mem_map)?;
+    if let Some(vdso) = vdso {
+        auxv.push(AT_SYSINFO_EHDR);
+        auxv.push(vdso.start_address().add_bytes(PAGE_SIZE).value() as _);
+    }
 
-    let user_ctx =
-        ArchImpl::new_user_context(VA::from_value(elf.e_entry(endian) as usize), stack_ptr);
-    let mut vm = ProcessVM::from_map(mem_map, VA::from_value(highest_addr as usize));
+    let (stack_ptr, auxv) = setup_user_stack(
+        &mut mem_map,
+        stack_end,
+        stack_sz,
+        &argv,
+        &envp,
+        execfn,
+        auxv,
+    )?;
+
+    let entry = ld.as_ref().map_or(exe.entry, |(ld, _, _)| ld.entry);
+    let user_ctx = ArchImpl::new_user_context(VA::from_value(entry), stack_ptr);
+    let brk = exe.end.next_multiple_of(PAGE_SIZE) + aslr.offset(BRK_RND_BITS);
+    let mut vm = ProcessVM::from_map(mem_map, VA::from_value(brk));
+
+    vm.set_auxv(auxv);
+    if let Some(vdso) = vdso {
+        vm.set_vdso(vdso);
+    }
+    // Both paths were made absolute when they were resolved.
+    vm.set_file_name(inode.id(), PathBuf::from(path.as_str()));
+    if let Some((_, ld_inode, interp)) = ld {
+        vm.set_file_name(ld_inode.id(), interp);
+    }
+
+    // This is the point of no return. The other threads must be gone before
+    // the program image that they're running is replaced.
+    de_thread(&current_task)?;
 
     // We don't have to worry about actually calling for a full context switch
     // here. Parts of the old process that are replaced will go out of scope and
@@ -116,53 +398,123 @@ pub async fn kernel_exec(
     // state. Simply activate the new process's address space.
     vm.mm_mut().address_space_mut().activate();
 
-    let current_task = current_task();
-
+    *current_task.creds.lock_save_irq() = creds;
     *current_task.ctx.lock_save_irq() = Context::from_user_ctx(user_ctx);
     *current_task.state.lock_save_irq() = TaskState::Runnable;
-    *current_task.vm.lock_save_irq() = vm;
+    // Any other process sharing the old address space, such as a vfork
+    // parent, keeps it.
+    current_task.set_vm(vm);
+
+    // A vfork parent may now run again.
+    complete_vfork(&current_task);
     *current_task.process.signals.lock_save_irq() = SignalState::new_default();
 
+    // Interval timers survive exec, but POSIX timers do not.
+    current_task.process.posix_timers.lock_save_irq().clear();
+
+    // A set-ID program can't be dumped, or told of its parent's death by a
+    // signal chosen by its unprivileged caller.
+    *current_task.process.dumpable.lock_save_irq() = if is_setid {
+        SUID_DUMP_DISABLE
+    } else {
+        SUID_DUMP_USER
+    };
+
+    if is_setid {
+        *current_task.pdeath_signal.lock_save_irq() = None;
+    }
+
+    set_comm_from_path(execfn);
+
     Ok(())
 }
 
+/// Kills every other thread in `task`'s process, leaving it as the only one.
+fn de_thread(task: &Task) -> Result<()> {
+    {
+        let mut state = task.process.state.lock_save_irq();
+
+        // If the process is exiting, or another thread got here first, then
+        // this thread is being killed.
+        if *state != ProcessState::Running {
+            return Err(KernelError::NoProcess);
+        }
+
+        *state = ProcessState::Exec;
+    }
+
+    kill_other_threads(task);
+
+    task.process
+        .threads
+        .lock_save_irq()
+        .retain(|tid, _| *tid == task.tid);
+
+    *task.process.state.lock_save_irq() = ProcessState::Running;
+
+    Ok(())
+}
+
+/// Returns the size that the stack of a new program is assumed to reach, for
+/// limiting its arguments: the current `RLIMIT_STACK`, clamped to sensible
+/// bounds.
+fn stack_size() -> usize {
+    let limit = rlimit(RlimitId::STACK).min(MAX_STACK_SZ as u64) as usize;
+
+    limit.max(MIN_STACK_SZ) & !PAGE_MASK
+}
+
 // Sets up the user stack according to the System V ABI.
 //
-// The stack layout from high addresses to low addresses is:
+// The stack layout from `stack_end` downwards is:
+// - The executable's filename, as pointed to by `AT_EXECFN`
 // - Argument and Environment strings
+// - Random bytes, as pointed to by `AT_RANDOM`
 // - Padding to 16-byte boundary
 // - Auxiliary Vector (auxv)
 // - Environment pointers (envp)
 // - Argument pointers (argv)
 // - Argument count (argc)
 //
-// The final stack pointer will point to `argc`.
+// The final stack pointer will point to `argc`. The complete auxiliary vector
+// is returned alongside it.
 fn setup_user_stack(
     mm: &mut MemoryMap<<ArchImpl as VirtualMemory>::ProcessAddressSpace>,
+    stack_end: usize,
+    stack_sz: usize,
     argv: &[String],
     envp: &[String],
+    execfn: &Path,
     mut auxv: Vec<u64>,
-) -> Result<VA> {
+) -> Result<(VA, Vec<u64>)> {
+    let strings: Vec<&str> = envp
+        .iter()
+        .chain(argv.iter())
+        .map(String::as_str)
+        .chain(iter::once(execfn.as_str()))
+        .collect();
+
     // Calculate the space needed and the virtual addresses for all strings and
     // pointers.
     let mut string_addrs = Vec::new();
     let mut total_string_size = 0;
 
     // We add strings to the stack from top-down.
-    for s in envp.iter().chain(argv.iter()) {
+    for s in strings.iter() {
         let len = s.len() + 1; // +1 for null terminator
         total_string_size += len;
         string_addrs.push(len); // Temporarily store length
     }
 
-    let mut current_va = STACK_END;
+    let mut current_va = stack_end;
     for len in string_addrs.iter_mut().rev() {
         // Now calculate the final virtual address of each string.
         current_va -= *len;
         *len = current_va; // Replace length with the VA
     }
 
-    let (envp_addrs, argv_addrs) = string_addrs.split_at(envp.len());
+    let (envp_addrs, rest) = string_addrs.split_at(envp.len());
+    let (argv_addrs, execfn_addr) = rest.split_at(argv.len());
 
     let mut info_block = Vec::<u64>::new();
     info_block.push(argv.len() as u64); // argc
@@ -174,42 +526,54 @@ fn setup_user_stack(
     // Add auxiliary vectors
     auxv.push(AT_PAGESZ);
     auxv.push(PAGE_SIZE as u64);
+    let strings_base_va = stack_end - total_string_size;
+    let random_va = strings_base_va - AT_RANDOM_SZ;
+
     auxv.push(AT_RANDOM);
-    // TODO: SECURITY: Actually make this a random value.
-    auxv.push(STACK_END as u64 - 0x10);
+    auxv.push(random_va as u64);
+    auxv.push(AT_EXECFN);
+    auxv.push(execfn_addr[0] as u64);
     auxv.push(AT_NULL);
     auxv.push(0);
 
-    info_block.append(&mut auxv);
+    info_block.extend_from_slice(&auxv);
 
     let info_block_size = info_block.len() * mem::size_of::<u64>();
 
     // The top of the info block must be 16-byte aligned. The stack pointer on
     // entry to the new process must also be 16-byte aligned.
-    let strings_base_va = STACK_END - total_string_size;
-    let final_sp_unaligned = strings_base_va - info_block_size;
+    let final_sp_unaligned = random_va - info_block_size;
     let final_sp_val = final_sp_unaligned & !0xF; // Align down to 16 bytes
 
-    let total_stack_size = STACK_END - final_sp_val;
-    if total_stack_size > STACK_SZ {
+    // As on Linux, the arguments and environment may use at most a quarter of
+    // the stack.
+    let total_stack_size = stack_end - final_sp_val;
+    if total_stack_size > stack_sz / 4 {
         return Err(KernelError::TooLarge);
     }
 
+    // Grow the stack to hold everything, if it isn't already large enough.
+    mm.grow_stack(VA::from_value(final_sp_val))?;
+
     let mut stack_image = vec![0u8; total_stack_size];
 
     // Write strings into the image
-    let mut string_cursor = STACK_END;
-    for s in envp.iter().chain(argv.iter()).rev() {
+    let mut string_cursor = stack_end;
+    for s in strings.iter().rev() {
         string_cursor -= s.len() + 1;
-        let offset = total_stack_size - (STACK_END - string_cursor);
+        let offset = total_stack_size - (stack_end - string_cursor);
         stack_image[offset..offset + s.len()].copy_from_slice(s.as_bytes());
         // Null terminator is already there from vec![0;...].
     }
 
+    // Write the random bytes into the image
+    let random_offset = total_stack_size - (stack_end - random_va);
+    fill_random(&mut stack_image[random_offset..random_offset + AT_RANDOM_SZ]);
+
     // Write info block into the image
     let info_block_bytes: &[u8] =
         unsafe { slice::from_raw_parts(info_block.as_ptr().cast(), info_block_size) };
-    let info_block_offset = total_stack_size - (STACK_END - final_sp_val);
+    let info_block_offset = total_stack_size - (stack_end - final_sp_val);
     stack_image[info_block_offset..info_block_offset + info_block_size]
         .copy_from_slice(info_block_bytes);
 
@@ -229,12 +593,12 @@ fn setup_user_stack(
         page_slice[PAGE_SIZE - image_slice.len()..].copy_from_slice(image_slice);
 
         // Map the page to the correct virtual address
-        let page_va = VA::from_value(STACK_END - (i + 1) * PAGE_SIZE);
+        let page_va = VA::from_value(stack_end - (i + 1) * PAGE_SIZE);
         mm.address_space_mut()
             .map_page(page.leak(), page_va, PtePermissions::rw(true))?;
     }
 
-    Ok(VA::from_value(final_sp_val))
+    Ok((VA::from_value(final_sp_val), auxv))
 }
 
 pub async fn sys_execve(
@@ -271,9 +635,12 @@ pub async fn sys_execve(
     }
 
     let path = Path::new(UserCStr::from_ptr(path).copy_from_user(&mut buf).await?);
-    let inode = VFS.resolve_path(path, VFS.root_inode()).await?;
+    let cwd = current_task().cwd.lock_save_irq().0.clone();
+    let inode = VFS.resolve_path(path, cwd).await?;
+
+    kernel_exec(path, inode, argv, envp).await?;
 
-    kernel_exec(inode, argv, envp).await?;
+    ptrace::exec_stop(&current_task()).await;
 
     Ok(0)
 }
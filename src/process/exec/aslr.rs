@@ -0,0 +1,52 @@
+//! Address-space layout randomisation.
+//!
+//! Each exec places the stack, mmap base, program break and any PIE or
+//! dynamic linker image at a random offset from their nominal addresses.
+//! Randomisation can be turned off for the whole system with the `norandmaps`
+//! command-line option, or for a single process with
+//! `personality(ADDR_NO_RANDOMIZE)`.
+
+use crate::{kernel::rand::random_u64, process::thread_group::personality::ADDR_NO_RANDOMIZE};
+use core::sync::atomic::{AtomicBool, Ordering};
+use libkernel::memory::PAGE_SIZE;
+
+/// The number of bits of page-granular entropy applied to the stack top.
+pub const STACK_RND_BITS: u32 = 18;
+
+/// The number of bits of page-granular entropy applied to the mmap base and
+/// to `ET_DYN` load addresses.
+pub const MMAP_RND_BITS: u32 = 28;
+
+/// The number of bits of page-granular entropy applied to the program break.
+pub const BRK_RND_BITS: u32 = 18;
+
+static RANDOMIZE_VA_SPACE: AtomicBool = AtomicBool::new(true);
+
+/// Disables randomisation for all subsequent execs.
+pub fn disable() {
+    RANDOMIZE_VA_SPACE.store(false, Ordering::Relaxed);
+}
+
+/// Generates the random offsets for a single exec.
+pub struct Aslr {
+    enabled: bool,
+}
+
+impl Aslr {
+    pub fn new(personality: u32) -> Self {
+        Self {
+            enabled: RANDOMIZE_VA_SPACE.load(Ordering::Relaxed)
+                && personality & ADDR_NO_RANDOMIZE == 0,
+        }
+    }
+
+    /// Returns a random, page-aligned offset of fewer than `2^bits` pages, or
+    /// zero if randomisation is disabled.
+    pub fn offset(&self, bits: u32) -> usize {
+        if !self.enabled {
+            return 0;
+        }
+
+        (random_u64() as usize & ((1 << bits) - 1)) * PAGE_SIZE
+    }
+}
@@ -1,10 +1,15 @@
-use crate::sched::current_task;
-use alloc::vec::Vec;
-use libkernel::error::Result;
+use crate::{
+    interrupts::cpu_messenger::{Message, message_cpu},
+    sched::current_task,
+};
+use alloc::{sync::Weak, vec::Vec};
+use libkernel::{error::Result, sync::condvar::WakeupType};
 use ringbuf::Arc;
 
 use super::{
-    TaskState,
+    Task, TaskState,
+    clone::complete_vfork,
+    ptrace,
     thread_group::{ProcessState, Tgid, ThreadGroup, signal::SigId, wait::ChildState},
 };
 
@@ -27,7 +32,7 @@ pub fn do_exit_group(exit_code: ChildState) {
         let mut process_state = process.state.lock_save_irq();
 
         // Check if we're already exiting (e.g., two threads call exit_group at
-        // once)
+        // once), or another thread is exec'ing, which kills us anyway.
         if *process_state != ProcessState::Running {
             // We're already on our way out. Just kill this thread.
             drop(process_state);
@@ -39,47 +44,77 @@ pub fn do_exit_group(exit_code: ChildState) {
         *process_state = ProcessState::Exiting;
     }
 
-    // Signal all other threads in the group to terminate. We iterate over Weak
-    // pointers and upgrade them.
-    for thread_weak in process.threads.lock_save_irq().values() {
-        if let Some(other_thread) = thread_weak.upgrade() {
-            // Don't signal ourselves
-            if other_thread.tid != task.tid {
-                // TODO: Send an IPI/Signal to halt execution now. For now, just
-                // wait for the scheduler to never schdule any of it's tasks
-                // again.
-                *other_thread.state.lock_save_irq() = TaskState::Finished;
+    // Stop the process's timers, which would otherwise go on signalling it.
+    process.itimers.lock_save_irq().disarm();
+    process.posix_timers.lock_save_irq().clear();
+
+    // Stop all other threads in the group before anything they might be using
+    // is torn down.
+    kill_other_threads(&task);
+
+    // Wake any vfork parents which are waiting on our threads.
+    let threads: Vec<_> = process
+        .threads
+        .lock_save_irq()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect();
+
+    for thread in threads.iter() {
+        complete_vfork(thread);
+    }
+
+    release_vm(&task, &threads);
+
+    // A PID namespace doesn't outlive its init process: everything else in it
+    // is killed.
+    if process
+        .pid_ns
+        .child_reaper()
+        .is_some_and(|init| Arc::ptr_eq(&init, &process))
+    {
+        for tgid in process.pid_ns.members() {
+            if tgid != process.tgid
+                && let Some(tg) = ThreadGroup::get(tgid)
+            {
+                tg.signals.lock_save_irq().set_pending(SigId::SIGKILL);
             }
         }
     }
 
-    // TODO: For a UMP system, the above is sufficient, however on SMP, we need
-    // to wait for all the processes to have stopped execution before tearing
-    // down the address-space, etc.
-
-    // Reparent children to `init`
+    // Reparent children to the nearest subreaper, or to `init`.
     {
         let mut our_children = process.children.lock_save_irq();
 
-        let init = ThreadGroup::get(Tgid::init()).expect("Could not find init process");
+        let reaper = find_reaper(&process);
 
-        let mut init_children = init.children.lock_save_irq();
+        let mut reaper_children = reaper.children.lock_save_irq();
 
         let mut our_children: Vec<_> = core::mem::take(&mut *our_children).into_iter().collect();
 
         for (tgid, our_child) in our_children.drain(..) {
-            *our_child.parent.lock_save_irq() = Some(Arc::downgrade(&init));
+            *our_child.parent.lock_save_irq() = Some(Arc::downgrade(&reaper));
 
-            init_children.insert(tgid, our_child);
+            send_pdeath_signals(&our_child);
+
+            reaper_children.insert(tgid, our_child);
         }
     }
 
+    // Release our tracees, and tell the tracers of any of our threads.
+    ptrace::exit_ptrace(&process, &parent, exit_code);
+
     parent.children.lock_save_irq().remove(&process.tgid);
 
-    parent.child_notifiers.child_update(process.tgid, exit_code);
+    parent.child_notifiers.child_update(&process, exit_code);
 
     parent.signals.lock_save_irq().set_pending(SigId::SIGCHLD);
 
+    process.exited.update(|exited| {
+        *exited = true;
+        WakeupType::All
+    });
+
     // 5. This thread is now finished.
     *task.state.lock_save_irq() = TaskState::Finished;
 
@@ -87,6 +122,113 @@ pub fn do_exit_group(exit_code: ChildState) {
     // state is set to Finished.
 }
 
+/// Releases the memory of an exiting process straight away, rather than once
+/// it's been reaped, unless its address space is shared with another process
+/// (e.g. a vfork parent).
+fn release_vm(task: &Task, threads: &[Arc<Task>]) {
+    let vm = task.vm();
+
+    let sharers = threads
+        .iter()
+        .filter(|thread| Arc::ptr_eq(&thread.vm(), &vm))
+        .count();
+
+    // Every other reference is held by one of our threads.
+    if Arc::strong_count(&vm) == sharers + 1 {
+        let _ = vm.lock_save_irq().mm_mut().unmap_all();
+    }
+}
+
+/// Kills every thread in `task`'s process other than `task` itself, returning
+/// once none of them is running on any CPU. After this, none of them will touch
+/// the process's address space again, so it may be torn down or replaced.
+///
+/// The caller must ensure that no other thread is doing the same, which is
+/// what the process's `state` is used for.
+pub fn kill_other_threads(task: &Task) {
+    let others: Vec<_> = task
+        .process
+        .threads
+        .lock_save_irq()
+        .values()
+        .filter_map(Weak::upgrade)
+        .filter(|t| t.tid != task.tid)
+        .collect();
+
+    for other in others.iter() {
+        // The scheduler checks a task's state when claiming it for a CPU, so
+        // with the state lock held, `on_cpu` can't change from `None`.
+        let mut state = other.state.lock_save_irq();
+
+        *state = TaskState::Finished;
+
+        // A thread running on another CPU is interrupted, forcing it into the
+        // scheduler, which won't return it to userspace. If the mailbox is
+        // full, an IPI is already on its way.
+        if let Some(cpu) = *other.on_cpu.lock_save_irq() {
+            let _ = message_cpu(cpu, Message::Reschedule);
+        }
+    }
+
+    // Wait for them all to be switched away from. No locks may be held here,
+    // since the other CPUs may need them to get to the scheduler.
+    for other in others.iter() {
+        while other.on_cpu.lock_save_irq().is_some() {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Returns the process that the orphaned children of `process` are adopted by:
+/// its nearest living ancestor within its PID namespace which has set
+/// `PR_SET_CHILD_SUBREAPER`, or else the init process of that namespace.
+fn find_reaper(process: &ThreadGroup) -> Arc<ThreadGroup> {
+    let is_live = |tg: &ThreadGroup| *tg.state.lock_save_irq() != ProcessState::Exiting;
+
+    let mut ancestor = process
+        .parent
+        .lock_save_irq()
+        .as_ref()
+        .and_then(Weak::upgrade);
+
+    while let Some(tg) = ancestor {
+        // Orphans are never adopted by a process outside of their namespace.
+        if process.pid_ns.pid_of(tg.tgid).is_none() {
+            break;
+        }
+
+        if *tg.child_subreaper.lock_save_irq() && is_live(&tg) {
+            return tg;
+        }
+
+        ancestor = tg.parent.lock_save_irq().as_ref().and_then(Weak::upgrade);
+    }
+
+    // The orphans of a namespace's exiting init are adopted by the global
+    // init.
+    process
+        .pid_ns
+        .child_reaper()
+        .filter(|init| is_live(init))
+        .unwrap_or_else(|| ThreadGroup::get(Tgid::init()).expect("Could not find init process"))
+}
+
+/// Sends `child` the signals requested by its threads via `PR_SET_PDEATHSIG`,
+/// now that its parent has exited.
+fn send_pdeath_signals(child: &ThreadGroup) {
+    let signals: Vec<_> = child
+        .threads
+        .lock_save_irq()
+        .values()
+        .filter_map(Weak::upgrade)
+        .filter_map(|t| *t.pdeath_signal.lock_save_irq())
+        .collect();
+
+    for signal in signals {
+        child.signals.lock_save_irq().set_pending(signal);
+    }
+}
+
 pub fn kernel_exit_with_signal(signal: SigId, core: bool) {
     do_exit_group(ChildState::SignalExit { signal, core });
 }
@@ -131,6 +273,8 @@ pub fn sys_exit(exit_code: usize) -> Result<usize> {
         // Remove ourself from the process's thread list.
         thread_lock.remove(&task.tid);
 
+        complete_vfork(&task);
+
         // 3. This thread stops executing forever. The task struct will be
         // deallocated when the last Arc<Task> is dropped (e.g., by the
         // scheduler).
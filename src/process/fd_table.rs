@@ -1,4 +1,8 @@
-use crate::{fs::open_file::OpenFile, memory::uaccess::UserCopyable};
+use crate::{
+    fs::open_file::OpenFile,
+    memory::uaccess::UserCopyable,
+    process::thread_group::rsrc_lim::{RlimitId, rlimit},
+};
 use alloc::{sync::Arc, vec::Vec};
 use libkernel::error::{FsError, Result};
 
@@ -76,12 +80,15 @@ impl FileDescriptorTable {
 
     /// Inserts a new file into the table, returning the new file descriptor.
     pub fn insert(&mut self, file: Arc<OpenFile>) -> Result<Fd> {
+        self.insert_with_flags(file, FdFlags::default())
+    }
+
+    /// Inserts a new file into the table with the descriptor flags `flags`,
+    /// returning the new file descriptor.
+    pub fn insert_with_flags(&mut self, file: Arc<OpenFile>, flags: FdFlags) -> Result<Fd> {
         let fd = self.find_free_fd()?;
 
-        let entry = FileDescriptorEntry {
-            file,
-            flags: FdFlags::default(),
-        };
+        let entry = FileDescriptorEntry { file, flags };
 
         self.insert_at(fd, entry);
 
@@ -141,10 +148,19 @@ impl FileDescriptorTable {
         }
     }
 
-    /// Finds the lowest-numbered available file descriptor.
+    /// Returns the number of file descriptors the current process may have
+    /// open, from `RLIMIT_NOFILE`.
+    pub fn fd_limit() -> usize {
+        rlimit(RlimitId::NOFILE).min(MAX_FDS as u64) as usize
+    }
+
+    /// Finds the lowest-numbered available file descriptor below the
+    /// `RLIMIT_NOFILE` limit.
     fn find_free_fd(&mut self) -> Result<Fd> {
+        let limit = Self::fd_limit();
+
         // Start searching from our hint.
-        for i in self.next_fd_hint..self.entries.len() {
+        for i in self.next_fd_hint..self.entries.len().min(limit) {
             if self.entries[i].is_none() {
                 self.next_fd_hint = i + 1;
                 return Ok(Fd(i as i32));
@@ -154,7 +170,7 @@ impl FileDescriptorTable {
         // We didn't find a free slot in the existing capacity
         let next = self.entries.len();
 
-        if next >= MAX_FDS {
+        if next >= limit {
             Err(FsError::TooManyFiles.into())
         } else {
             self.next_fd_hint = next + 1;
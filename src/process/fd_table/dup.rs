@@ -4,7 +4,7 @@ use libkernel::{
     fs::OpenFlags,
 };
 
-use super::{Fd, FdFlags, FileDescriptorEntry};
+use super::{Fd, FdFlags, FileDescriptorEntry, FileDescriptorTable};
 
 pub fn sys_dup(fd: Fd) -> Result<usize> {
     let task = current_task();
@@ -22,6 +22,10 @@ pub fn sys_dup3(oldfd: Fd, newfd: Fd, flags: u32) -> Result<usize> {
         return Err(KernelError::InvalidValue);
     }
 
+    if newfd.as_raw() < 0 || newfd.as_raw() as usize >= FileDescriptorTable::fd_limit() {
+        return Err(KernelError::BadFd);
+    }
+
     let flags = OpenFlags::from_bits_retain(flags);
 
     if !flags.difference(OpenFlags::O_CLOEXEC).is_empty() {
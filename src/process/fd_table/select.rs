@@ -1,5 +1,5 @@
 use alloc::{boxed::Box, vec::Vec};
-use core::{future::poll_fn, iter, pin::pin, task::Poll, time::Duration};
+use core::{future::poll_fn, task::Poll, time::Duration};
 use libkernel::{
     error::{KernelError, Result},
     memory::address::TUA,
@@ -7,15 +7,16 @@ use libkernel::{
 
 use crate::{
     clock::timespec::TimeSpec,
-    drivers::timer::sleep,
+    drivers::timer::{sleep, uptime},
+    fs::fops::PollFuture,
     memory::uaccess::{
         UserCopyable, copy_from_user, copy_obj_array_from_user, copy_objs_to_user, copy_to_user,
     },
-    process::thread_group::signal::SigSet,
+    process::thread_group::signal::{SigSet, sigprocmask::replace_sigmask},
     sched::current_task,
 };
 
-use super::Fd;
+use super::{Fd, FileDescriptorTable};
 
 const SET_SIZE: usize = 1024;
 
@@ -25,29 +26,14 @@ pub struct FdSet {
 }
 
 impl FdSet {
-    fn iter_fds(&self, max: i32) -> impl Iterator<Item = Fd> {
-        let mut candidate_fd = 0;
-
-        iter::from_fn(move || {
-            loop {
-                if candidate_fd == max {
-                    return None;
-                }
-
-                if self.set[candidate_fd as usize / 64] & (1 << (candidate_fd % 64)) != 0 {
-                    let ret = Fd(candidate_fd);
-                    candidate_fd += 1;
-
-                    return Some(ret);
-                }
-
-                candidate_fd += 1;
-            }
-        })
+    fn empty() -> Self {
+        Self { set: [0; _] }
     }
 
-    fn zero(&mut self) {
-        self.set = [0; _];
+    fn is_set(&self, fd: Fd) -> bool {
+        let fd = fd.as_raw();
+
+        self.set[fd as usize / 64] & (1 << (fd % 64)) != 0
     }
 
     fn set_fd(&mut self, fd: Fd) {
@@ -59,79 +45,199 @@ impl FdSet {
 
 unsafe impl UserCopyable for FdSet {}
 
-// TODO: writefds, exceptfds, timeout.
+/// The events which mark a file as ready in each of `select`'s sets.
+const SELECT_READ: PollFlags = PollFlags::POLLIN
+    .union(PollFlags::POLLRDNORM)
+    .union(PollFlags::POLLHUP)
+    .union(PollFlags::POLLERR);
+const SELECT_WRITE: PollFlags = PollFlags::POLLOUT
+    .union(PollFlags::POLLWRNORM)
+    .union(PollFlags::POLLERR);
+const SELECT_EXCEPT: PollFlags = PollFlags::POLLPRI;
+
+/// The `sigmask` argument of `pselect6`, which passes the set and its size
+/// indirectly, as there aren't enough argument registers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PSelectSigmask {
+    ss: TUA<SigSet>,
+    ss_len: usize,
+}
+
+unsafe impl UserCopyable for PSelectSigmask {}
+
+/// Copies in the signal mask argument of a waiting syscall, if it was given.
+pub async fn copy_sigmask(set: TUA<SigSet>, sigsetsize: usize) -> Result<Option<SigSet>> {
+    if set.is_null() {
+        return Ok(None);
+    }
+
+    if sigsetsize != size_of::<SigSet>() {
+        return Err(KernelError::InvalidValue);
+    }
+
+    Ok(Some(copy_from_user(set).await?))
+}
+
+/// Waits until any of `polls` resolves, or `timeout` elapses, returning the
+/// events each has found ready. A `None` entry is ignored.
+///
+/// If `sigmask` is given, it's installed as the signal mask for the duration
+/// of the wait. Waits aren't interrupted by signals, so this only affects
+/// which signals are delivered once the syscall returns.
+async fn do_poll(
+    mut polls: Vec<Option<PollFuture>>,
+    timeout: Option<Duration>,
+    sigmask: Option<SigSet>,
+) -> Vec<PollFlags> {
+    let mut revents = alloc::vec![PollFlags::empty(); polls.len()];
+
+    // A zero timeout only checks whether anything is ready.
+    let nonblocking = timeout.is_some_and(|t| t.is_zero());
+    let mut timeout = timeout.map(|t| Box::pin(sleep(t)));
+
+    let old_sigmask = sigmask.map(replace_sigmask);
+
+    poll_fn(|cx| {
+        let mut ready = false;
+
+        for (poll, revents) in polls.iter_mut().zip(revents.iter_mut()) {
+            if let Some(fut) = poll
+                && let Poll::Ready(flags) = fut.as_mut().poll(cx)
+            {
+                *revents = flags;
+                *poll = None;
+                ready = true;
+            }
+        }
+
+        if ready || nonblocking {
+            Poll::Ready(())
+        } else if let Some(timeout) = timeout.as_mut() {
+            timeout.as_mut().poll(cx)
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+
+    if let Some(old_sigmask) = old_sigmask {
+        replace_sigmask(old_sigmask);
+    }
+
+    revents
+}
+
+/// Writes the time left of a `timeout` which began at `start` back to `dst`.
+async fn write_remaining(dst: TUA<TimeSpec>, timeout: Option<Duration>, start: Duration) {
+    if let Some(timeout) = timeout {
+        let remaining = timeout.saturating_sub(uptime() - start);
+
+        // As on Linux, failing to report the remaining time isn't an error;
+        // the wait has already happened.
+        let _ = copy_to_user(dst, TimeSpec::from(remaining)).await;
+    }
+}
+
 pub async fn sys_pselect6(
     max: i32,
     readfds: TUA<FdSet>,
-    _writefds: TUA<FdSet>,
-    _exceptfds: TUA<FdSet>,
+    writefds: TUA<FdSet>,
+    exceptfds: TUA<FdSet>,
     timeout: TUA<TimeSpec>,
-    _mask: TUA<SigSet>,
+    sigmask: TUA<PSelectSigmask>,
 ) -> Result<usize> {
-    let task = current_task();
+    if max < 0 {
+        return Err(KernelError::InvalidValue);
+    }
 
-    let mut read_fd_set = copy_from_user(readfds).await?;
+    let max = max.min(SET_SIZE as i32);
 
-    let mut read_fds = Vec::new();
+    let copy_set = async |set: TUA<FdSet>| {
+        if set.is_null() {
+            Ok(FdSet::empty())
+        } else {
+            copy_from_user(set).await
+        }
+    };
 
+    let sets = [
+        (copy_set(readfds).await?, PollFlags::POLLIN, SELECT_READ),
+        (copy_set(writefds).await?, PollFlags::POLLOUT, SELECT_WRITE),
+        (
+            copy_set(exceptfds).await?,
+            PollFlags::POLLPRI,
+            SELECT_EXCEPT,
+        ),
+    ];
+
+    let timeout_ptr = timeout;
     let timeout: Option<Duration> = if timeout.is_null() {
         None
     } else {
-        Some(copy_from_user(timeout).await?.into())
+        Some(TimeSpec::copy_from_user(timeout).await?.into())
     };
 
-    for fd in read_fd_set.iter_fds(max) {
-        let file = task
+    let sigmask = if sigmask.is_null() {
+        None
+    } else {
+        let sigmask = copy_from_user(sigmask).await?;
+
+        copy_sigmask(sigmask.ss, sigmask.ss_len).await?
+    };
+
+    let mut fds = Vec::new();
+
+    for fd in (0..max).map(Fd) {
+        let events = sets
+            .iter()
+            .filter(|(set, _, _)| set.is_set(fd))
+            .fold(PollFlags::empty(), |events, (_, e, _)| events | *e);
+
+        if events.is_empty() {
+            continue;
+        }
+
+        let file = current_task()
             .fd_table
             .lock_save_irq()
             .get(fd)
             .ok_or(KernelError::BadFd)?;
 
-        read_fds.push((
-            Box::pin(async move {
-                let (ops, _) = &mut *file.lock().await;
+        fds.push((fd, file, events));
+    }
+
+    let mut polls = Vec::new();
 
-                ops.poll_read_ready().await
-            }),
-            fd,
-        ));
+    for (_, file, events) in fds.iter() {
+        polls.push(Some(Box::pin(file.poll(*events).await) as PollFuture));
     }
 
-    read_fd_set.zero();
+    let start = uptime();
+    let revents = do_poll(polls, timeout, sigmask).await;
 
-    let n = poll_fn(|cx| {
-        let mut num_ready: usize = 0;
+    let mut num_ready = 0;
 
-        for (fut, fd) in read_fds.iter_mut() {
-            if fut.as_mut().poll(cx).is_ready() {
-                // Mark the is_ready bool. Don't break out of the loop just
-                // yet, we may aswell check all fds while we're here.
-                read_fd_set.set_fd(*fd);
-                num_ready += 1;
-            }
+    for ((set, _, ready), dst) in sets.iter().zip([readfds, writefds, exceptfds]) {
+        if dst.is_null() {
+            continue;
         }
 
-        if num_ready == 0 {
-            // Check for the case where both timeout fields are zero:
-            //
-            // If both fields of the timeval structure are zero, then select()
-            // returns immediately.
-            if let Some(timeout) = timeout
-                && timeout.is_zero()
-            {
-                Poll::Ready(0)
-            } else {
-                Poll::Pending
+        let mut result = FdSet::empty();
+
+        for ((fd, _, _), revents) in fds.iter().zip(revents.iter()) {
+            if set.is_set(*fd) && revents.intersects(*ready) {
+                result.set_fd(*fd);
+                num_ready += 1;
             }
-        } else {
-            Poll::Ready(num_ready)
         }
-    })
-    .await;
 
-    copy_to_user(readfds, read_fd_set).await?;
+        copy_to_user(dst, result).await?;
+    }
+
+    write_remaining(timeout_ptr, timeout, start).await;
 
-    Ok(n)
+    Ok(num_ready)
 }
 
 bitflags::bitflags! {
@@ -165,67 +271,62 @@ pub async fn sys_ppoll(
     ufds: TUA<PollFd>,
     nfds: u32,
     timeout: TUA<TimeSpec>,
-    _sigmask: TUA<SigSet>,
-    _sigset_len: usize,
+    sigmask: TUA<SigSet>,
+    sigsetsize: usize,
 ) -> Result<usize> {
-    let task = current_task();
+    if nfds as usize > FileDescriptorTable::fd_limit() {
+        return Err(KernelError::InvalidValue);
+    }
 
     let mut poll_fds = copy_obj_array_from_user(ufds, nfds as _).await?;
 
-    let mut timeout_fut = if timeout.is_null() {
+    let timeout_ptr = timeout;
+    let timeout: Option<Duration> = if timeout.is_null() {
         None
     } else {
-        let duration = copy_from_user(timeout).await?.into();
-        Some(pin!(sleep(duration)))
+        Some(TimeSpec::copy_from_user(timeout).await?.into())
     };
 
-    let fds = {
-        let fd_table = task.fd_table.lock_save_irq();
+    let sigmask = copy_sigmask(sigmask, sigsetsize).await?;
 
-        poll_fds
-            .iter()
-            .map(|poll_fd| fd_table.get(poll_fd.fd).ok_or(KernelError::BadFd))
-            .collect::<Result<Vec<_>>>()?
-    };
+    let mut polls = Vec::new();
 
-    let mut futs = Vec::new();
+    for poll_fd in poll_fds.iter_mut() {
+        poll_fd.revents = PollFlags::empty();
+
+        // Negative fds are ignored, so that an entry can be disabled without
+        // removing it.
+        if poll_fd.fd.as_raw() < 0 {
+            polls.push(None);
+            continue;
+        }
 
-    for (poll_fd, open_file) in poll_fds.iter_mut().zip(fds) {
-        let poll_fut = open_file.poll(poll_fd.events).await;
+        let file = current_task().fd_table.lock_save_irq().get(poll_fd.fd);
 
-        futs.push(Box::pin(async {
-            poll_fd.revents = poll_fut.await?;
+        let poll: PollFuture = match file {
+            Some(file) => Box::pin(file.poll(poll_fd.events).await),
+            None => Box::pin(async { PollFlags::POLLNVAL }),
+        };
 
-            Ok(())
-        }));
+        polls.push(Some(poll));
     }
 
-    let num_ready = poll_fn(|cx| {
-        let mut num_ready = 0;
+    let start = uptime();
+    let revents = do_poll(polls, timeout, sigmask).await;
 
-        for fut in futs.iter_mut() {
-            match fut.as_mut().poll(cx) {
-                Poll::Ready(Ok(())) => num_ready += 1,
-                Poll::Ready(Err(e)) => return Poll::Ready(Err::<_, KernelError>(e)),
-                Poll::Pending => continue,
-            }
-        }
+    let mut num_ready = 0;
 
-        if num_ready == 0 {
-            if let Some(ref mut timeout) = timeout_fut {
-                timeout.as_mut().poll(cx).map(|_| Ok(0))
-            } else {
-                Poll::Pending
-            }
-        } else {
-            Poll::Ready(Ok(num_ready))
-        }
-    })
-    .await?;
+    for (poll_fd, revents) in poll_fds.iter_mut().zip(revents) {
+        poll_fd.revents = revents;
 
-    drop(futs);
+        if !revents.is_empty() {
+            num_ready += 1;
+        }
+    }
 
     copy_objs_to_user(&poll_fds, ufds).await?;
 
+    write_remaining(timeout_ptr, timeout, start).await;
+
     Ok(num_ready)
 }
@@ -3,16 +3,17 @@ use crate::process::threading::RobustListHead;
 use crate::{
     arch::{Arch, ArchImpl},
     fs::DummyInode,
-    sync::SpinLock,
+    sync::{CondVar, SpinLock},
 };
 use alloc::{
     collections::btree_map::BTreeMap,
     sync::{Arc, Weak},
 };
+use core::mem;
 use creds::Credentials;
 use ctx::{Context, UserCtx};
 use fd_table::FileDescriptorTable;
-use libkernel::memory::address::TUA;
+use libkernel::memory::address::{TUA, UA};
 use libkernel::{VirtualMemory, fs::Inode};
 use libkernel::{
     fs::pathbuf::PathBuf,
@@ -21,18 +22,27 @@ use libkernel::{
         proc_vm::{ProcessVM, vmarea::VMArea},
     },
 };
+use ns::Namespaces;
+use prctl::Comm;
+use ptrace::Ptrace;
 use thread_group::{
     Tgid, ThreadGroup,
     builder::ThreadGroupBuilder,
+    pid::{PidT, find_vpid, tid_from_vtid},
     signal::{SigId, SigSet, SignalState},
 };
 
 pub mod clone;
+pub mod coredump;
 pub mod creds;
 pub mod ctx;
 pub mod exec;
 pub mod exit;
 pub mod fd_table;
+pub mod ns;
+pub mod pidfd;
+pub mod prctl;
+pub mod ptrace;
 pub mod sleep;
 pub mod thread_group;
 pub mod threading;
@@ -63,6 +73,14 @@ impl TaskDescriptor {
         Self { tid, tgid }
     }
 
+    pub fn tgid(self) -> Tgid {
+        self.tgid
+    }
+
+    pub fn tid(self) -> Tid {
+        self.tid
+    }
+
     /// Returns a descriptor for the idle task.
     pub fn this_cpus_idle() -> Self {
         Self {
@@ -120,7 +138,9 @@ pub type ProcVM = ProcessVM<<ArchImpl as VirtualMemory>::ProcessAddressSpace>;
 pub struct Task {
     pub tid: Tid,
     pub process: Arc<ThreadGroup>,
-    pub vm: Arc<SpinLock<ProcVM>>,
+    /// The task's address space, shared with any tasks cloned with
+    /// `CLONE_VM`. It's replaced, rather than modified, on exec.
+    vm: SpinLock<Arc<SpinLock<ProcVM>>>,
     pub cwd: Arc<SpinLock<(Arc<dyn Inode>, PathBuf)>>,
     pub creds: SpinLock<Credentials>,
     pub fd_table: Arc<SpinLock<FileDescriptorTable>>,
@@ -133,7 +153,23 @@ pub struct Task {
     pub priority: i8,
     pub last_run: SpinLock<Option<Instant>>,
     pub state: Arc<SpinLock<TaskState>>,
+    /// The CPU the task is currently running on, if any. Its address space is
+    /// active there until this is cleared.
+    pub on_cpu: SpinLock<Option<usize>>,
     pub robust_list: SpinLock<Option<TUA<RobustListHead>>>,
+    pub ptrace: Ptrace,
+    /// The task's command name.
+    pub comm: SpinLock<Comm>,
+    /// The signal sent to the process when its parent exits, if any.
+    pub pdeath_signal: SpinLock<Option<SigId>>,
+    /// Set once the task may no longer gain privileges through `execve`.
+    pub no_new_privs: SpinLock<bool>,
+    pub namespaces: SpinLock<Namespaces>,
+    /// The address passed to `set_tid_address` or via `CLONE_CHILD_CLEARTID`.
+    pub tid_address: SpinLock<Option<UA>>,
+    /// Signalled when a child created by `vfork` stops sharing its parent's
+    /// address space, by exec'ing or exiting.
+    pub vfork_done: SpinLock<Option<Arc<CondVar<bool>>>>,
 }
 
 impl Task {
@@ -156,7 +192,7 @@ impl Task {
             cwd: Arc::new(SpinLock::new((Arc::new(DummyInode {}), PathBuf::new()))),
             creds: SpinLock::new(Credentials::new_root()),
             ctx: SpinLock::new(Context::from_user_ctx(user_ctx)),
-            vm: Arc::new(SpinLock::new(vm)),
+            vm: SpinLock::new(Arc::new(SpinLock::new(vm))),
             sig_mask: SpinLock::new(SigSet::empty()),
             pending_signals: SpinLock::new(SigSet::empty()),
             vruntime: SpinLock::new(0),
@@ -164,7 +200,15 @@ impl Task {
             deadline: SpinLock::new(None),
             fd_table: Arc::new(SpinLock::new(FileDescriptorTable::new())),
             last_run: SpinLock::new(None),
+            on_cpu: SpinLock::new(None),
             robust_list: SpinLock::new(None),
+            vfork_done: SpinLock::new(None),
+            ptrace: Ptrace::new(),
+            comm: SpinLock::new(Comm::new("idle")),
+            pdeath_signal: SpinLock::new(None),
+            no_new_privs: SpinLock::new(false),
+            namespaces: SpinLock::new(Namespaces::init()),
+            tid_address: SpinLock::new(None),
         }
     }
 
@@ -175,9 +219,9 @@ impl Task {
             state: Arc::new(SpinLock::new(TaskState::Runnable)),
             cwd: Arc::new(SpinLock::new((Arc::new(DummyInode {}), PathBuf::new()))),
             creds: SpinLock::new(Credentials::new_root()),
-            vm: Arc::new(SpinLock::new(
+            vm: SpinLock::new(Arc::new(SpinLock::new(
                 ProcessVM::empty().expect("Could not create init process's VM"),
-            )),
+            ))),
             fd_table: Arc::new(SpinLock::new(FileDescriptorTable::new())),
             pending_signals: SpinLock::new(SigSet::empty()),
             vruntime: SpinLock::new(0),
@@ -189,7 +233,15 @@ impl Task {
                 <ArchImpl as Arch>::new_user_context(VA::null(), VA::null()),
             )),
             last_run: SpinLock::new(None),
+            on_cpu: SpinLock::new(None),
             robust_list: SpinLock::new(None),
+            vfork_done: SpinLock::new(None),
+            ptrace: Ptrace::new(),
+            comm: SpinLock::new(Comm::new("init")),
+            pdeath_signal: SpinLock::new(None),
+            no_new_privs: SpinLock::new(false),
+            namespaces: SpinLock::new(Namespaces::init()),
+            tid_address: SpinLock::new(None),
         }
     }
 
@@ -219,9 +271,74 @@ impl Task {
         TaskDescriptor::from_tgid_tid(self.process.tgid, self.tid)
     }
 
+    /// Returns the task's address space.
+    pub fn vm(&self) -> Arc<SpinLock<ProcVM>> {
+        self.vm.lock_save_irq().clone()
+    }
+
+    /// Replaces the task's address space with `vm`, returning the old one.
+    pub fn set_vm(&self, vm: ProcVM) -> Arc<SpinLock<ProcVM>> {
+        mem::replace(&mut *self.vm.lock_save_irq(), Arc::new(SpinLock::new(vm)))
+    }
+
     pub fn raise_task_signal(&self, signal: SigId) {
         self.pending_signals.lock_save_irq().insert(signal.into());
+        self.process.signals.lock_save_irq().notify_raised();
+    }
+
+    /// Raises `signal` on the task because of a fault that it can't continue
+    /// past, such as a stack overflow. The signal is unblocked, and reset to
+    /// its default action if it's ignored, so that it can't be lost.
+    pub fn force_task_signal(&self, signal: SigId) {
+        self.sig_mask.lock_save_irq().remove(signal.into());
+        self.process.signals.lock_save_irq().unignore(signal);
+        self.raise_task_signal(signal);
+    }
+}
+
+/// Finds the task referred to by `pid` from the perspective of the current
+/// task: either a thread in the caller's own thread group, or the main thread
+/// of the process with that PID.
+pub fn find_task_by_pid(pid: PidT) -> Option<Arc<Task>> {
+    if pid <= 0 {
+        return None;
     }
+
+    let current = crate::sched::current_task();
+
+    let task = tid_from_vtid(pid).and_then(|tid| {
+        current
+            .process
+            .threads
+            .lock_save_irq()
+            .get(&tid)
+            .and_then(Weak::upgrade)
+    });
+
+    task.or_else(|| {
+        find_vpid(pid).and_then(|tg| {
+            tg.threads
+                .lock_save_irq()
+                .get(&Tid::from_tgid(tg.tgid))
+                .and_then(Weak::upgrade)
+        })
+    })
+}
+
+/// Finds the thread with TID `tid` in the current task's PID namespace, in
+/// any process.
+pub fn find_task_by_tid(tid: PidT) -> Option<Arc<Task>> {
+    if tid <= 0 {
+        return None;
+    }
+
+    let task = crate::sched::current_task().process.pid_ns.task_of(tid)?;
+
+    ThreadGroup::get(task.tgid())?
+        .threads
+        .lock_save_irq()
+        .get(&task.tid())
+        .and_then(Weak::upgrade)
 }
 
 pub static TASK_LIST: SpinLock<BTreeMap<TaskDescriptor, Weak<SpinLock<TaskState>>>> =
@@ -229,3 +346,9 @@ pub static TASK_LIST: SpinLock<BTreeMap<TaskDescriptor, Weak<SpinLock<TaskState>
 
 unsafe impl Send for Task {}
 unsafe impl Sync for Task {}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        self.process.pid_ns.detach_thread(self.descriptor());
+    }
+}
@@ -0,0 +1,163 @@
+//! Namespaces: per-task views of the system's host name, mounts and PIDs.
+//!
+//! UTS, mount and PID namespaces are supported. A task's namespaces are
+//! shared with its children unless new ones are requested via `clone` or
+//! `unshare`.
+
+use super::{
+    clone::CloneFlags, creds::caps::CapSet, fd_table::Fd, pidfd::pidfd_process,
+    thread_group::pid::PidNamespace,
+};
+use crate::{
+    fs::{MountNamespace, VFS},
+    kernel::uname::UtsNamespace,
+    sched::current_task,
+};
+use alloc::sync::Arc;
+use libkernel::{
+    error::{KernelError, Result},
+    fs::pathbuf::PathBuf,
+};
+
+/// The namespace types which can be created and joined.
+const NS_FLAGS: CloneFlags = CloneFlags::CLONE_NEWNS
+    .union(CloneFlags::CLONE_NEWUTS)
+    .union(CloneFlags::CLONE_NEWPID);
+
+/// The namespaces of a task.
+#[derive(Clone)]
+pub struct Namespaces {
+    pub uts: Arc<UtsNamespace>,
+    pub mnt: Arc<MountNamespace>,
+    /// The PID namespace which the task's children are created in. The task's
+    /// own PID namespace is that of its process.
+    pub pid_for_children: Arc<PidNamespace>,
+}
+
+impl Namespaces {
+    /// Returns the initial namespaces.
+    pub fn init() -> Self {
+        Self {
+            uts: UtsNamespace::init(),
+            mnt: MountNamespace::init(),
+            pid_for_children: PidNamespace::init(),
+        }
+    }
+
+    /// Returns a copy of these namespaces, with a new namespace in place of
+    /// each of the types selected by `flags`.
+    pub fn copy(&self, flags: CloneFlags) -> Self {
+        let mut ns = self.clone();
+
+        if flags.contains(CloneFlags::CLONE_NEWUTS) {
+            ns.uts = self.uts.copy();
+        }
+
+        if flags.contains(CloneFlags::CLONE_NEWNS) {
+            ns.mnt = self.mnt.copy();
+        }
+
+        if flags.contains(CloneFlags::CLONE_NEWPID) {
+            ns.pid_for_children = self.pid_for_children.new_child();
+        }
+
+        ns
+    }
+}
+
+/// Checks that the current task may create, or join, the namespaces selected
+/// by `flags`.
+fn check_ns_flags(flags: CloneFlags) -> Result<()> {
+    if flags.intersects(NS_FLAGS)
+        && !current_task()
+            .creds
+            .lock_save_irq()
+            .has_cap(CapSet::CAP_SYS_ADMIN)
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    Ok(())
+}
+
+/// Returns the namespaces for a new task cloned from the current one with
+/// `flags`.
+pub fn clone_namespaces(flags: CloneFlags) -> Result<Namespaces> {
+    check_ns_flags(flags)?;
+
+    Ok(current_task().namespaces.lock_save_irq().copy(flags))
+}
+
+pub fn sys_unshare(flags: u32) -> Result<usize> {
+    let flags = CloneFlags::from_bits(flags).ok_or(KernelError::InvalidValue)?;
+
+    if !NS_FLAGS.contains(flags) {
+        return Err(KernelError::InvalidValue);
+    }
+
+    check_ns_flags(flags)?;
+
+    let task = current_task();
+    let mut namespaces = task.namespaces.lock_save_irq();
+
+    *namespaces = namespaces.copy(flags);
+
+    Ok(0)
+}
+
+/// Joins the namespaces, selected by `nstype`, of the process referred to by
+/// the pidfd `fd`. With no procfs, there are no namespace files, so only
+/// pidfds are accepted.
+pub async fn sys_setns(fd: Fd, nstype: u32) -> Result<usize> {
+    let flags = CloneFlags::from_bits(nstype).ok_or(KernelError::InvalidValue)?;
+
+    if flags.is_empty() || !NS_FLAGS.contains(flags) {
+        return Err(KernelError::InvalidValue);
+    }
+
+    check_ns_flags(flags)?;
+
+    let process = pidfd_process(fd).await?;
+
+    let target = process
+        .threads
+        .lock_save_irq()
+        .values()
+        .find_map(|t| t.upgrade())
+        .ok_or(KernelError::NoProcess)?;
+
+    let target_ns = target.namespaces.lock_save_irq().clone();
+    let task = current_task();
+
+    // A PID namespace may only be joined from it or one of its ancestors.
+    if flags.contains(CloneFlags::CLONE_NEWPID)
+        && !task.process.pid_ns.is_ancestor_of(&process.pid_ns)
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    {
+        let mut namespaces = task.namespaces.lock_save_irq();
+
+        if flags.contains(CloneFlags::CLONE_NEWUTS) {
+            namespaces.uts = target_ns.uts;
+        }
+
+        if flags.contains(CloneFlags::CLONE_NEWNS) {
+            namespaces.mnt = target_ns.mnt;
+        }
+
+        // As with unshare, our own PID namespace is unchanged; only our
+        // children are created in the target's.
+        if flags.contains(CloneFlags::CLONE_NEWPID) {
+            namespaces.pid_for_children = process.pid_ns.clone();
+        }
+    }
+
+    // Paths resolved in the old mount namespace mean nothing in the new one.
+    if flags.contains(CloneFlags::CLONE_NEWNS) {
+        *task.cwd.lock_save_irq() = (VFS.root_inode(), PathBuf::new());
+    }
+
+    Ok(0)
+}
@@ -0,0 +1,174 @@
+//! pidfd: file descriptors which refer to a process.
+//!
+//! Unlike a PID, a pidfd can't come to refer to a different process if the
+//! one it was opened for exits and its PID is reused, so signalling or waiting
+//! through one is free of races.
+
+use super::{
+    fd_table::{Fd, FdFlags, select::PollFlags},
+    thread_group::{
+        ProcessState, ThreadGroup,
+        pid::{PidT, find_vpid},
+        signal::{SigId, kill::signal_tg_checked, uaccess::UserSigId},
+    },
+};
+use crate::{
+    fs::{
+        fops::{FileOps, PollFuture, poll_sources},
+        open_file::{FileCtx, OpenFile},
+    },
+    sched::current_task,
+};
+use alloc::{boxed::Box, sync::Arc, vec};
+use async_trait::async_trait;
+use core::any::Any;
+use libkernel::{
+    error::{KernelError, Result},
+    fs::OpenFlags,
+    memory::address::UA,
+};
+
+const PIDFD_NONBLOCK: u32 = OpenFlags::O_NONBLOCK.bits();
+
+struct PidFd {
+    process: Arc<ThreadGroup>,
+}
+
+#[async_trait]
+impl FileOps for PidFd {
+    async fn read(&mut self, _ctx: &mut FileCtx, _buf: UA, _count: usize) -> Result<usize> {
+        Err(KernelError::InvalidValue)
+    }
+
+    async fn write(&mut self, _ctx: &mut FileCtx, _buf: UA, _count: usize) -> Result<usize> {
+        Err(KernelError::InvalidValue)
+    }
+
+    fn poll(&self, events: PollFlags) -> PollFuture {
+        // A pidfd becomes readable once its process has exited.
+        let exited = self
+            .process
+            .exited
+            .wait_until(|exited| if *exited { Some(()) } else { None });
+
+        poll_sources(
+            events,
+            vec![Box::pin(async move {
+                exited.await;
+                PollFlags::POLLIN | PollFlags::POLLRDNORM
+            })],
+        )
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Opens a pidfd for `process` in the current task's file table. pidfds are
+/// always close-on-exec.
+pub fn open_pidfd(process: Arc<ThreadGroup>, flags: OpenFlags) -> Result<Fd> {
+    let file = OpenFile::new(Box::new(PidFd { process }), OpenFlags::O_RDWR | flags);
+
+    current_task()
+        .fd_table
+        .lock_save_irq()
+        .insert_with_flags(Arc::new(file), FdFlags::CLOEXEC)
+}
+
+/// Returns the process referred to by the pidfd open at `fd`.
+pub async fn pidfd_process(fd: Fd) -> Result<Arc<ThreadGroup>> {
+    let file = current_task()
+        .fd_table
+        .lock_save_irq()
+        .get(fd)
+        .ok_or(KernelError::BadFd)?;
+
+    let (ops, _) = &*file.lock().await;
+
+    ops.as_any()
+        .and_then(|ops| ops.downcast_ref::<PidFd>())
+        .map(|pidfd| pidfd.process.clone())
+        .ok_or(KernelError::InvalidValue)
+}
+
+pub fn sys_pidfd_open(pid: PidT, flags: u32) -> Result<usize> {
+    if pid <= 0 || flags & !PIDFD_NONBLOCK != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let process = find_vpid(pid).ok_or(KernelError::NoProcess)?;
+    let fd = open_pidfd(process, OpenFlags::from_bits_retain(flags))?;
+
+    Ok(fd.as_raw() as _)
+}
+
+/// There is no queue of signal information, so the `info` argument is
+/// ignored, as it is for `timer_create`'s `sigev_value`.
+pub async fn sys_pidfd_send_signal(pidfd: Fd, signal: i32, _info: UA, flags: u32) -> Result<usize> {
+    if flags != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let process = pidfd_process(pidfd).await?;
+
+    if *process.state.lock_save_irq() == ProcessState::Exiting {
+        return Err(KernelError::NoProcess);
+    }
+
+    let task = current_task();
+    let creds = task.creds.lock_save_irq().clone();
+
+    // Signal 0 only checks that the process may be signalled.
+    if signal == 0 {
+        if let Some(target) = process.creds()
+            && !creds.may_signal(&target)
+        {
+            return Err(KernelError::NotPermitted);
+        }
+    } else {
+        let signal = SigId::try_from(UserSigId::from(signal as u64))?;
+
+        signal_tg_checked(&creds, &task.process, &process, signal)?;
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_pidfd_getfd(pidfd: Fd, targetfd: Fd, flags: u32) -> Result<usize> {
+    if flags != 0 {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let process = pidfd_process(pidfd).await?;
+
+    let target = process
+        .threads
+        .lock_save_irq()
+        .values()
+        .find_map(|t| t.upgrade())
+        .ok_or(KernelError::NoProcess)?;
+
+    // Taking another process's file needs the same permission as tracing it.
+    let task = current_task();
+    let creds = task.creds.lock_save_irq().clone();
+
+    if !Arc::ptr_eq(&task.process, &process)
+        && !creds.may_trace(&target.creds.lock_save_irq().clone())
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    let file = target
+        .fd_table
+        .lock_save_irq()
+        .get(targetfd)
+        .ok_or(KernelError::BadFd)?;
+
+    let fd = task
+        .fd_table
+        .lock_save_irq()
+        .insert_with_flags(file, FdFlags::CLOEXEC)?;
+
+    Ok(fd.as_raw() as _)
+}
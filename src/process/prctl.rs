@@ -0,0 +1,192 @@
+//! `prctl`: operations on the calling task and its process.
+
+use crate::{
+    arch::{Arch, ArchImpl},
+    memory::{oom::set_oom_score_adj, uaccess::copy_to_user},
+    sched::current_task,
+};
+use alloc::string::String;
+use core::{ffi::c_char, fmt::Display};
+use libkernel::{
+    error::{KernelError, Result},
+    fs::path::Path,
+    memory::address::TUA,
+};
+
+use super::thread_group::signal::{SigId, uaccess::UserSigId};
+
+/// The size of a task's command name, including its NUL terminator.
+pub const TASK_COMM_LEN: usize = 16;
+
+/// The process may not be dumped or traced by unprivileged users.
+pub const SUID_DUMP_DISABLE: u32 = 0;
+/// The process may be dumped and traced as normal.
+pub const SUID_DUMP_USER: u32 = 1;
+
+const PR_SET_PDEATHSIG: i32 = 1;
+const PR_GET_PDEATHSIG: i32 = 2;
+const PR_GET_DUMPABLE: i32 = 3;
+const PR_SET_DUMPABLE: i32 = 4;
+const PR_SET_NAME: i32 = 15;
+const PR_GET_NAME: i32 = 16;
+const PR_SET_CHILD_SUBREAPER: i32 = 36;
+const PR_GET_CHILD_SUBREAPER: i32 = 37;
+const PR_SET_NO_NEW_PRIVS: i32 = 38;
+const PR_GET_NO_NEW_PRIVS: i32 = 39;
+const PR_GET_TID_ADDRESS: i32 = 40;
+// There's no procfs to expose `/proc/<pid>/oom_score_adj` through, so the
+// process's own adjustment is set and read with these options, which are
+// specific to this kernel.
+const PR_SET_OOM_SCORE_ADJ: i32 = 0x4f4f_4d01;
+const PR_GET_OOM_SCORE_ADJ: i32 = 0x4f4f_4d02;
+
+/// A task's command name, as set on exec and by `PR_SET_NAME`. Names longer
+/// than `TASK_COMM_LEN - 1` bytes are truncated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Comm([u8; TASK_COMM_LEN]);
+
+impl Comm {
+    pub fn new(name: &str) -> Self {
+        let mut comm = [0; TASK_COMM_LEN];
+        let len = name.len().min(TASK_COMM_LEN - 1);
+
+        comm[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        Self(comm)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; TASK_COMM_LEN] {
+        &self.0
+    }
+}
+
+impl Display for Comm {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(TASK_COMM_LEN);
+
+        f.write_str(&String::from_utf8_lossy(&self.0[..len]))
+    }
+}
+
+async fn set_name(name: TUA<c_char>) -> Result<()> {
+    // Names which don't fit are truncated, rather than rejected. The name
+    // needn't be valid UTF-8, so it's copied as bytes.
+    let mut comm = [0u8; TASK_COMM_LEN];
+
+    // SAFETY: `comm` is `TASK_COMM_LEN` bytes long.
+    let len = unsafe {
+        ArchImpl::copy_strn_from_user(name.to_untyped(), comm.as_mut_ptr(), TASK_COMM_LEN)
+    }
+    .await?;
+
+    comm[len.min(TASK_COMM_LEN - 1)..].fill(0);
+
+    *current_task().comm.lock_save_irq() = Comm(comm);
+
+    Ok(())
+}
+
+pub async fn sys_prctl(
+    option: i32,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+    arg5: usize,
+) -> Result<usize> {
+    let task = current_task();
+
+    match option {
+        PR_SET_PDEATHSIG => {
+            let signal = match arg2 {
+                0 => None,
+                n => Some(SigId::try_from(UserSigId::from(n as u64))?),
+            };
+
+            *task.pdeath_signal.lock_save_irq() = signal;
+
+            Ok(0)
+        }
+        PR_GET_PDEATHSIG => {
+            let signal = task
+                .pdeath_signal
+                .lock_save_irq()
+                .map_or(0, |s| s.user_id() as i32);
+
+            copy_to_user(TUA::<i32>::from_value(arg2), signal).await?;
+
+            Ok(0)
+        }
+        PR_GET_DUMPABLE => Ok(*task.process.dumpable.lock_save_irq() as _),
+        PR_SET_DUMPABLE => match arg2 as u32 {
+            d @ (SUID_DUMP_DISABLE | SUID_DUMP_USER) => {
+                *task.process.dumpable.lock_save_irq() = d;
+                Ok(0)
+            }
+            _ => Err(KernelError::InvalidValue),
+        },
+        PR_SET_NAME => set_name(TUA::from_value(arg2)).await.map(|_| 0),
+        PR_GET_NAME => {
+            let comm = *task.comm.lock_save_irq();
+
+            copy_to_user(
+                TUA::<[u8; TASK_COMM_LEN]>::from_value(arg2),
+                *comm.as_bytes(),
+            )
+            .await?;
+
+            Ok(0)
+        }
+        PR_SET_CHILD_SUBREAPER => {
+            *task.process.child_subreaper.lock_save_irq() = arg2 != 0;
+            Ok(0)
+        }
+        PR_GET_CHILD_SUBREAPER => {
+            let subreaper = *task.process.child_subreaper.lock_save_irq();
+
+            copy_to_user(TUA::<i32>::from_value(arg2), subreaper as i32).await?;
+
+            Ok(0)
+        }
+        PR_SET_NO_NEW_PRIVS => {
+            // Once set, no_new_privs can't be cleared.
+            if arg2 != 1 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(KernelError::InvalidValue);
+            }
+
+            *task.no_new_privs.lock_save_irq() = true;
+
+            Ok(0)
+        }
+        PR_GET_NO_NEW_PRIVS => {
+            if arg2 != 0 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(KernelError::InvalidValue);
+            }
+
+            Ok(*task.no_new_privs.lock_save_irq() as _)
+        }
+        PR_GET_TID_ADDRESS => {
+            let addr = task.tid_address.lock_save_irq().map_or(0, |a| a.value());
+
+            copy_to_user(TUA::<usize>::from_value(arg2), addr).await?;
+
+            Ok(0)
+        }
+        PR_SET_OOM_SCORE_ADJ => set_oom_score_adj(&task.process, arg2 as i32 as i64).map(|_| 0),
+        PR_GET_OOM_SCORE_ADJ => {
+            let oom_score_adj = *task.process.oom_score_adj.lock_save_irq() as i32;
+
+            copy_to_user(TUA::<i32>::from_value(arg2), oom_score_adj).await?;
+
+            Ok(0)
+        }
+        _ => Err(KernelError::InvalidValue),
+    }
+}
+
+/// Sets the name of the current task to the file name of the program at
+/// `path`, which it is executing.
+pub fn set_comm_from_path(path: &Path) {
+    let name = path.file_name().unwrap_or(path.as_str());
+
+    *current_task().comm.lock_save_irq() = Comm::new(name);
+}
@@ -0,0 +1,542 @@
+//! Process tracing, as used by debuggers and `strace`.
+//!
+//! A tracee enters a *ptrace-stop* at syscall entry and exit (when resumed
+//! with `PTRACE_SYSCALL`), before a signal is delivered or ignored, and after a
+//! successful `execve`. While stopped, it sleeps on its own [`Ptrace`] state
+//! and its tracer is notified through its `wait4` child state. The tracer may
+//! then inspect and modify the tracee's registers and memory before resuming
+//! it, optionally injecting a signal.
+
+use super::{
+    Task,
+    creds::caps::CapSet,
+    find_task_by_tid,
+    prctl::SUID_DUMP_DISABLE,
+    thread_group::{
+        ThreadGroup,
+        pid::PidT,
+        signal::{SigId, uaccess::UserSigId},
+        wait::ChildState,
+    },
+};
+use crate::{
+    arch::{Arch, ArchImpl},
+    fs::syscalls::iov::IoVec,
+    memory::{
+        remote::access_remote_vm,
+        uaccess::{copy_from_user, copy_to_user, copy_to_user_slice},
+    },
+    sched::current_task,
+    sync::CondVar,
+};
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use bitflags::bitflags;
+use core::mem::size_of;
+use libkernel::{
+    error::{KernelError, Result},
+    memory::address::{TUA, VA},
+    sync::condvar::WakeupType,
+};
+
+const PTRACE_TRACEME: i64 = 0;
+const PTRACE_PEEKTEXT: i64 = 1;
+const PTRACE_PEEKDATA: i64 = 2;
+const PTRACE_POKETEXT: i64 = 4;
+const PTRACE_POKEDATA: i64 = 5;
+const PTRACE_CONT: i64 = 7;
+const PTRACE_SINGLESTEP: i64 = 9;
+const PTRACE_ATTACH: i64 = 16;
+const PTRACE_DETACH: i64 = 17;
+const PTRACE_SYSCALL: i64 = 24;
+const PTRACE_SETOPTIONS: i64 = 0x4200;
+const PTRACE_GETREGSET: i64 = 0x4204;
+const PTRACE_SETREGSET: i64 = 0x4205;
+const PTRACE_SEIZE: i64 = 0x4206;
+
+const PTRACE_EVENT_EXEC: u32 = 4;
+
+/// The `NT_PRSTATUS` register set: the general-purpose registers.
+const NT_PRSTATUS: usize = 1;
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct PtraceOptions: u32 {
+        const PTRACE_O_TRACESYSGOOD = 0x1;
+        const PTRACE_O_TRACEEXEC = 0x10;
+        const PTRACE_O_EXITKILL = 0x100000;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ResumeMode {
+    Continue,
+    Syscall,
+    SingleStep,
+}
+
+struct PtraceState {
+    tracer: Option<Weak<ThreadGroup>>,
+    /// Attached with `PTRACE_SEIZE` rather than `PTRACE_ATTACH`/`TRACEME`.
+    seized: bool,
+    options: PtraceOptions,
+    mode: ResumeMode,
+    /// Set whilst the tracee is in a ptrace-stop.
+    stopped: bool,
+    /// A resume request from the tracer, carrying any signal to inject.
+    resume: Option<Option<SigId>>,
+    /// A signal injected by the tracer, to be delivered on the way back to
+    /// userspace.
+    injected: Option<SigId>,
+}
+
+/// The per-task tracing state.
+pub struct Ptrace {
+    inner: CondVar<PtraceState>,
+}
+
+impl Default for Ptrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ptrace {
+    pub fn new() -> Self {
+        Self {
+            inner: CondVar::new(PtraceState {
+                tracer: None,
+                seized: false,
+                options: PtraceOptions::empty(),
+                mode: ResumeMode::Continue,
+                stopped: false,
+                resume: None,
+                injected: None,
+            }),
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut PtraceState) -> T) -> T {
+        let mut ret = None;
+
+        self.inner.update(|s| {
+            ret = Some(f(s));
+            WakeupType::None
+        });
+
+        ret.unwrap()
+    }
+
+    /// Returns the tracing process, if this task is being traced.
+    pub fn tracer(&self) -> Option<Arc<ThreadGroup>> {
+        self.with_state(|s| s.tracer.as_ref().and_then(Weak::upgrade))
+    }
+
+    pub fn is_traced(&self) -> bool {
+        self.tracer().is_some()
+    }
+
+    /// Takes any signal that the tracer asked to be delivered when it last
+    /// resumed this task.
+    pub fn take_injected_signal(&self) -> Option<SigId> {
+        self.with_state(|s| s.injected.take())
+    }
+
+    fn is_traced_by(&self, tracer: &Arc<ThreadGroup>) -> bool {
+        self.tracer().is_some_and(|t| Arc::ptr_eq(&t, tracer))
+    }
+
+    fn resume(&self, mode: ResumeMode, signal: Option<SigId>) {
+        self.inner.update(|s| {
+            s.mode = mode;
+            s.resume = Some(signal);
+            WakeupType::All
+        });
+    }
+
+    fn detach(&self, signal: Option<SigId>) {
+        self.inner.update(|s| {
+            s.tracer = None;
+            s.seized = false;
+            s.options = PtraceOptions::empty();
+            s.mode = ResumeMode::Continue;
+            s.injected = signal;
+            WakeupType::All
+        });
+    }
+}
+
+/// Makes `tracer` the tracer of `tracee`.
+fn attach(
+    tracee: &Arc<Task>,
+    tracer: &Arc<ThreadGroup>,
+    seized: bool,
+    options: PtraceOptions,
+) -> Result<()> {
+    tracee.ptrace.with_state(|s| {
+        if s.tracer.as_ref().is_some_and(|t| t.strong_count() > 0) {
+            return Err(KernelError::NotPermitted);
+        }
+
+        s.tracer = Some(Arc::downgrade(tracer));
+        s.seized = seized;
+        s.options = options;
+        s.mode = ResumeMode::Continue;
+        s.stopped = false;
+        s.resume = None;
+        s.injected = None;
+
+        Ok(())
+    })?;
+
+    tracer.tracees.lock_save_irq().push(Arc::downgrade(tracee));
+
+    Ok(())
+}
+
+fn remove_tracee(tracer: &ThreadGroup, tracee: &Arc<Task>) {
+    let tracee = Arc::as_ptr(tracee);
+    let mut tracees = tracer.tracees.lock_save_irq();
+
+    tracees.retain(|t| t.strong_count() > 0 && !core::ptr::eq(t.as_ptr(), tracee));
+}
+
+/// Checks whether the current task may attach to `tracee`.
+fn may_attach(current: &Arc<Task>, tracee: &Arc<Task>) -> Result<()> {
+    if Arc::ptr_eq(&current.process, &tracee.process) || tracee.process.tgid.is_init() {
+        return Err(KernelError::NotPermitted);
+    }
+
+    let creds = current.creds.lock_save_irq().clone();
+    let target = tracee.creds.lock_save_irq().clone();
+
+    // A process that isn't dumpable may only be traced with CAP_SYS_PTRACE.
+    if *tracee.process.dumpable.lock_save_irq() == SUID_DUMP_DISABLE
+        && !creds.has_cap(CapSet::CAP_SYS_PTRACE)
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
+    if creds.may_trace(&target) {
+        Ok(())
+    } else {
+        Err(KernelError::NotPermitted)
+    }
+}
+
+/// Puts `task` into a ptrace-stop and waits for its tracer to resume it.
+///
+/// `stop_sig` and `event` are reported to the tracer in bits 8-15 and 16-23 of
+/// the `wait4` status respectively. Returns the signal that the tracer asked to
+/// be delivered, if any.
+async fn ptrace_stop(task: &Arc<Task>, stop_sig: u32, event: u32) -> Option<SigId> {
+    let tracer = task.ptrace.with_state(|s| {
+        let tracer = s.tracer.as_ref().and_then(Weak::upgrade)?;
+        s.stopped = true;
+        s.resume = None;
+        Some(tracer)
+    })?;
+
+    tracer
+        .child_notifiers
+        .tracee_update(task, ChildState::TraceStop { stop_sig, event });
+    tracer.signals.lock_save_irq().set_pending(SigId::SIGCHLD);
+    drop(tracer);
+
+    task.ptrace
+        .inner
+        .wait_until(|s| {
+            // A detach also releases the tracee.
+            let signal = if s.tracer.is_none() {
+                None
+            } else {
+                s.resume.take()?
+            };
+
+            s.stopped = false;
+            Some(signal)
+        })
+        .await
+}
+
+/// Reports a syscall-entry or syscall-exit stop for the current task, if its
+/// tracer resumed it with `PTRACE_SYSCALL`.
+pub async fn syscall_stop(task: &Arc<Task>) {
+    let stop_sig = task.ptrace.with_state(|s| {
+        if s.tracer.is_none() || s.mode != ResumeMode::Syscall {
+            return None;
+        }
+
+        let sig = SigId::SIGTRAP.user_id() as u32;
+
+        if s.options.contains(PtraceOptions::PTRACE_O_TRACESYSGOOD) {
+            Some(sig | 0x80)
+        } else {
+            Some(sig)
+        }
+    });
+
+    if let Some(stop_sig) = stop_sig
+        && let Some(signal) = ptrace_stop(task, stop_sig, 0).await
+    {
+        task.raise_task_signal(signal);
+    }
+}
+
+/// Reports a signal-delivery stop for `signal` to the tracer of `task`. Any
+/// signal the tracer chooses to deliver in its place is queued to be actioned
+/// by the caller on the next pass through the return-to-userspace path.
+pub async fn signal_delivery_stop(task: Arc<Task>, signal: SigId) {
+    let injected = ptrace_stop(&task, signal.user_id() as u32, 0).await;
+
+    task.ptrace.with_state(|s| s.injected = injected);
+}
+
+/// Notifies the tracer of the current task of a successful `execve`.
+pub async fn exec_stop(task: &Arc<Task>) {
+    let (traced, seized, options) = task
+        .ptrace
+        .with_state(|s| (s.tracer.is_some(), s.seized, s.options));
+
+    if !traced {
+        return;
+    }
+
+    if options.contains(PtraceOptions::PTRACE_O_TRACEEXEC) {
+        if let Some(signal) =
+            ptrace_stop(task, SigId::SIGTRAP.user_id() as u32, PTRACE_EVENT_EXEC).await
+        {
+            task.raise_task_signal(signal);
+        }
+    } else if !seized {
+        // Legacy behaviour: the tracee stops with a SIGTRAP after exec.
+        task.raise_task_signal(SigId::SIGTRAP);
+    }
+}
+
+/// Starts tracing a new child created with `CLONE_PTRACE` if its creator is
+/// itself traced. The child begins in a stop so that the tracer can take
+/// control of it.
+pub fn trace_child(parent: &Task, child: &Arc<Task>) {
+    let (tracer, seized, options) = parent.ptrace.with_state(|s| {
+        (
+            s.tracer.as_ref().and_then(Weak::upgrade),
+            s.seized,
+            s.options,
+        )
+    });
+
+    if let Some(tracer) = tracer
+        && attach(child, &tracer, seized, options).is_ok()
+    {
+        child.raise_task_signal(SigId::SIGSTOP);
+    }
+}
+
+/// Tears down any tracing relationships of an exiting process: it stops
+/// tracing its tracees, and its traced threads are reported to their tracers.
+pub fn exit_ptrace(process: &Arc<ThreadGroup>, parent: &Arc<ThreadGroup>, exit: ChildState) {
+    let tracees: Vec<_> = core::mem::take(&mut *process.tracees.lock_save_irq());
+
+    for tracee in tracees.iter().filter_map(Weak::upgrade) {
+        let kill = tracee
+            .ptrace
+            .with_state(|s| s.options.contains(PtraceOptions::PTRACE_O_EXITKILL));
+
+        tracee.ptrace.detach(None);
+
+        if kill {
+            tracee
+                .process
+                .signals
+                .lock_save_irq()
+                .set_pending(SigId::SIGKILL);
+        }
+    }
+
+    let threads: Vec<_> = process
+        .threads
+        .lock_save_irq()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect();
+
+    for thread in threads {
+        let Some(tracer) = thread.ptrace.tracer() else {
+            continue;
+        };
+
+        remove_tracee(&tracer, &thread);
+        tracer.child_notifiers.forget_tracee(&thread);
+        thread.ptrace.detach(None);
+
+        // The parent is notified separately by the exit path.
+        if !Arc::ptr_eq(&tracer, parent) {
+            tracer.child_notifiers.child_update(process, exit);
+            tracer.signals.lock_save_irq().set_pending(SigId::SIGCHLD);
+        }
+    }
+}
+
+fn signal_arg(data: usize) -> Result<Option<SigId>> {
+    if data == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(SigId::try_from(UserSigId::from(data as u64))?))
+    }
+}
+
+/// Returns the register set of the stopped `tracee`.
+async fn get_regset(tracee: &Task, iov_ptr: TUA<IoVec>) -> Result<usize> {
+    let mut iov = copy_from_user(iov_ptr).await?;
+    let regs = ArchImpl::ptrace_get_regs(tracee.ctx.lock_save_irq().user());
+
+    // SAFETY: `PtraceRegs` is `UserCopyable`, so it is plain data.
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &regs as *const _ as *const u8,
+            size_of::<<ArchImpl as Arch>::PtraceRegs>(),
+        )
+    };
+
+    let len = iov.iov_len.min(bytes.len());
+    copy_to_user_slice(&bytes[..len], iov.iov_base).await?;
+
+    // As on Linux, report back how much of the register set was written.
+    iov.iov_len = len;
+    copy_to_user(iov_ptr, iov).await?;
+
+    Ok(0)
+}
+
+/// Replaces the register set of the stopped `tracee`.
+async fn set_regset(tracee: &Task, iov_ptr: TUA<IoVec>) -> Result<usize> {
+    let iov = copy_from_user(iov_ptr).await?;
+
+    if iov.iov_len < size_of::<<ArchImpl as Arch>::PtraceRegs>() {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let regs: <ArchImpl as Arch>::PtraceRegs = copy_from_user(iov.iov_base.cast()).await?;
+
+    ArchImpl::ptrace_set_regs(tracee.ctx.lock_save_irq().user_mut(), &regs);
+
+    Ok(0)
+}
+
+pub async fn sys_ptrace(request: i64, pid: PidT, addr: usize, data: usize) -> Result<usize> {
+    let current = current_task();
+
+    match request {
+        PTRACE_TRACEME => {
+            let parent = current
+                .process
+                .parent
+                .lock_save_irq()
+                .as_ref()
+                .and_then(Weak::upgrade)
+                .ok_or(KernelError::NotPermitted)?;
+
+            attach(&current, &parent, false, PtraceOptions::empty())?;
+
+            return Ok(0);
+        }
+        PTRACE_ATTACH | PTRACE_SEIZE => {
+            if request == PTRACE_SEIZE && addr != 0 {
+                return Err(KernelError::InvalidValue);
+            }
+
+            let tracee = find_task_by_tid(pid).ok_or(KernelError::NoProcess)?;
+
+            may_attach(&current, &tracee)?;
+
+            let options = if request == PTRACE_SEIZE {
+                PtraceOptions::from_bits(data as u32).ok_or(KernelError::InvalidValue)?
+            } else {
+                PtraceOptions::empty()
+            };
+
+            attach(&tracee, &current.process, request == PTRACE_SEIZE, options)?;
+
+            if request == PTRACE_ATTACH {
+                tracee.raise_task_signal(SigId::SIGSTOP);
+            }
+
+            return Ok(0);
+        }
+        _ => {}
+    }
+
+    let tracee = find_task_by_tid(pid).ok_or(KernelError::NoProcess)?;
+
+    // All other requests require the tracee to be traced by us, and stopped.
+    if !tracee.ptrace.is_traced_by(&current.process) || !tracee.ptrace.with_state(|s| s.stopped) {
+        return Err(KernelError::NoProcess);
+    }
+
+    match request {
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+            let mut word = [0; size_of::<usize>()];
+
+            access_remote_vm(&tracee.vm(), VA::from_value(addr), &mut word, false).await?;
+            copy_to_user(TUA::from_value(data), usize::from_ne_bytes(word)).await?;
+
+            Ok(0)
+        }
+        PTRACE_POKETEXT | PTRACE_POKEDATA => {
+            let mut word = data.to_ne_bytes();
+
+            access_remote_vm(&tracee.vm(), VA::from_value(addr), &mut word, true).await?;
+
+            Ok(0)
+        }
+        PTRACE_GETREGSET | PTRACE_SETREGSET => {
+            if addr != NT_PRSTATUS {
+                return Err(KernelError::InvalidValue);
+            }
+
+            if request == PTRACE_GETREGSET {
+                get_regset(&tracee, TUA::from_value(data)).await
+            } else {
+                set_regset(&tracee, TUA::from_value(data)).await
+            }
+        }
+        PTRACE_SETOPTIONS => {
+            let options = PtraceOptions::from_bits(data as u32).ok_or(KernelError::InvalidValue)?;
+
+            tracee.ptrace.with_state(|s| s.options = options);
+
+            Ok(0)
+        }
+        PTRACE_CONT | PTRACE_SYSCALL | PTRACE_SINGLESTEP => {
+            let signal = signal_arg(data)?;
+            let mode = match request {
+                PTRACE_CONT => ResumeMode::Continue,
+                PTRACE_SYSCALL => ResumeMode::Syscall,
+                _ => ResumeMode::SingleStep,
+            };
+
+            ArchImpl::set_single_step(
+                tracee.ctx.lock_save_irq().user_mut(),
+                mode == ResumeMode::SingleStep,
+            )?;
+
+            tracee.ptrace.resume(mode, signal);
+
+            Ok(0)
+        }
+        PTRACE_DETACH => {
+            let signal = signal_arg(data)?;
+
+            ArchImpl::set_single_step(tracee.ctx.lock_save_irq().user_mut(), false)?;
+            remove_tracee(&current.process, &tracee);
+            current.process.child_notifiers.forget_tracee(&tracee);
+            tracee.ptrace.detach(signal);
+
+            Ok(0)
+        }
+        _ => Err(KernelError::InvalidValue),
+    }
+}
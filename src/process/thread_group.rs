@@ -1,23 +1,33 @@
-use super::{Task, Tid};
-use crate::{memory::uaccess::UserCopyable, sync::SpinLock};
+use super::{ProcVM, Task, Tid, creds::Credentials, prctl::Comm};
+use crate::{
+    memory::uaccess::UserCopyable,
+    sync::{CondVar, SpinLock},
+};
 use alloc::{
     collections::btree_map::BTreeMap,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use builder::ThreadGroupBuilder;
 use core::{
     fmt::Display,
-    sync::atomic::{AtomicU32, Ordering},
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
 };
-use pid::PidT;
-use rsrc_lim::ResourceLimits;
-use signal::{SigSet, SignalState};
+use itimer::ITimers;
+use libkernel::proc::ids::Uid;
+use pid::{PidNamespace, PidT};
+use rsrc_lim::{RLimit, ResourceLimits, RlimitId};
+use signal::{SigId, SigSet, SignalState};
+use timer::PosixTimers;
 use wait::ChildNotifiers;
 
 pub mod builder;
+pub mod itimer;
+pub mod personality;
 pub mod pid;
 pub mod rsrc_lim;
 pub mod signal;
+pub mod timer;
 pub mod umask;
 pub mod wait;
 
@@ -82,8 +92,10 @@ impl Sid {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProcessState {
-    Running, // Actively running
-    Exiting, // In the middle of being torn down
+    Running,  // Actively running
+    Exec,     // Killing its other threads, to replace its program image
+    CoreDump, // Killing its other threads, to dump its memory
+    Exiting,  // In the middle of being torn down
 }
 
 pub struct ThreadGroup {
@@ -92,6 +104,8 @@ pub struct ThreadGroup {
     pub sid: SpinLock<Sid>,
     pub state: SpinLock<ProcessState>,
     pub umask: SpinLock<u32>,
+    /// The execution domain and flags set via `personality`.
+    pub personality: SpinLock<u32>,
     pub parent: SpinLock<Option<Weak<ThreadGroup>>>,
     pub children: SpinLock<BTreeMap<Tgid, Arc<ThreadGroup>>>,
     pub threads: SpinLock<BTreeMap<Tid, Weak<Task>>>,
@@ -99,7 +113,27 @@ pub struct ThreadGroup {
     pub rsrc_lim: Arc<SpinLock<ResourceLimits>>,
     pub pending_signals: SpinLock<SigSet>,
     pub child_notifiers: ChildNotifiers,
+    /// Tasks that this process is tracing via `ptrace`.
+    pub tracees: SpinLock<Vec<Weak<Task>>>,
+    pub itimers: SpinLock<ITimers>,
+    pub posix_timers: SpinLock<PosixTimers>,
+    /// Set once the process has exited, waking anything, such as a pidfd
+    /// poller, waiting for it to do so.
+    pub exited: CondVar<bool>,
+    /// Whether the process may be core dumped or traced by an unprivileged
+    /// user, as set via `PR_SET_DUMPABLE`.
+    pub dumpable: SpinLock<u32>,
+    /// Set if orphaned descendants should be reparented to this process,
+    /// rather than to init.
+    pub child_subreaper: SpinLock<bool>,
+    /// Added to the process's score when choosing which process to kill when
+    /// memory runs out. See `memory::oom`.
+    pub oom_score_adj: SpinLock<i16>,
+    /// The PID namespace that the process belongs to.
+    pub pid_ns: Arc<PidNamespace>,
     next_tid: AtomicU32,
+    /// The CPU time, in nanoseconds, consumed by all threads of the process.
+    cpu_time: AtomicU64,
 }
 
 unsafe impl Send for ThreadGroup {}
@@ -122,8 +156,17 @@ impl ThreadGroup {
         Tgid(NEXT_TGID.fetch_add(1, Ordering::SeqCst))
     }
 
-    pub fn new_child(self: Arc<Self>, share_state: bool) -> (Arc<ThreadGroup>, Tid) {
-        let mut builder = ThreadGroupBuilder::new(Self::next_tgid()).with_parent(self.clone());
+    pub fn new_child(
+        self: Arc<Self>,
+        share_state: bool,
+        pid_ns: Arc<PidNamespace>,
+    ) -> (Arc<ThreadGroup>, Tid) {
+        let mut builder = ThreadGroupBuilder::new(Self::next_tgid())
+            .with_parent(self.clone())
+            .with_pid_ns(pid_ns)
+            .with_personality(*self.personality.lock_save_irq())
+            .with_dumpable(*self.dumpable.lock_save_irq())
+            .with_oom_score_adj(*self.oom_score_adj.lock_save_irq());
 
         if share_state {
             builder = builder
@@ -151,11 +194,127 @@ impl ThreadGroup {
     pub fn get(id: Tgid) -> Option<Arc<Self>> {
         TG_LIST.lock_save_irq().get(&id).and_then(|x| x.upgrade())
     }
+
+    /// Returns every live process in the system.
+    pub fn all() -> Vec<Arc<Self>> {
+        TG_LIST
+            .lock_save_irq()
+            .values()
+            .filter_map(|tg| tg.upgrade())
+            .collect()
+    }
+
+    /// Returns the address space of every live process, once each, though it
+    /// may be shared by several of them.
+    pub fn all_vms() -> Vec<Arc<SpinLock<ProcVM>>> {
+        let mut vms: Vec<Arc<SpinLock<ProcVM>>> = Vec::new();
+
+        for vm in Self::all().iter().filter_map(|tg| tg.vm()) {
+            if !vms.iter().any(|v| Arc::ptr_eq(v, &vm)) {
+                vms.push(vm);
+            }
+        }
+
+        vms
+    }
+
+    /// Returns the address space of the process, as held by any of its live
+    /// threads.
+    pub fn vm(&self) -> Option<Arc<SpinLock<ProcVM>>> {
+        self.threads
+            .lock_save_irq()
+            .values()
+            .find_map(|t| t.upgrade())
+            .map(|t| t.vm())
+    }
+
+    /// Returns the main thread of this process, or any other live thread if
+    /// the main thread has exited.
+    fn main_thread(&self) -> Option<Arc<Task>> {
+        let threads = self.threads.lock_save_irq();
+
+        threads
+            .get(&Tid(self.tgid.value()))
+            .and_then(|t| t.upgrade())
+            .or_else(|| threads.values().find_map(|t| t.upgrade()))
+    }
+
+    /// Returns a copy of the credentials of this process, as held by the main
+    /// thread, or by any other live thread if the main thread has exited.
+    pub fn creds(&self) -> Option<Credentials> {
+        self.main_thread().map(|t| t.creds.lock_save_irq().clone())
+    }
+
+    /// Returns the command name of this process's main thread, or of any other
+    /// live thread if the main thread has exited.
+    pub fn comm(&self) -> Option<Comm> {
+        self.main_thread().map(|t| *t.comm.lock_save_irq())
+    }
+
+    /// Returns the CPU time consumed by the process, in nanoseconds.
+    pub fn cpu_time(&self) -> u64 {
+        self.cpu_time.load(Ordering::Relaxed)
+    }
+
+    /// Charges `ns` nanoseconds of CPU time to the process, expiring its
+    /// CPU-time interval timers and enforcing `RLIMIT_CPU`. As on Linux,
+    /// exceeding the soft limit sends `SIGXCPU` and raises the soft limit by a
+    /// second, so that the signal is repeated each second until the hard limit
+    /// is reached, at which point the process is killed.
+    pub fn charge_cpu_time(&self, ns: u64) {
+        let cpu_time = self.cpu_time.fetch_add(ns, Ordering::Relaxed) + ns;
+        let secs = cpu_time / 1_000_000_000;
+
+        self.itimers
+            .lock_save_irq()
+            .expire_cpu_timers(cpu_time, &mut self.signals.lock_save_irq());
+
+        let mut limits = self.rsrc_lim.lock_save_irq();
+        let limit = limits.get(RlimitId::CPU);
+
+        if secs >= limit.rlim_max {
+            self.signals.lock_save_irq().set_pending(SigId::SIGKILL);
+        } else if secs >= limit.rlim_cur {
+            self.signals.lock_save_irq().set_pending(SigId::SIGXCPU);
+
+            let _ = limits.set(
+                RlimitId::CPU,
+                RLimit {
+                    rlim_cur: secs + 1,
+                    rlim_max: limit.rlim_max,
+                },
+                true,
+            );
+        }
+    }
+
+    /// Returns the number of live tasks, across all processes, whose real user
+    /// ID is `uid`. This is the quantity limited by `RLIMIT_NPROC`.
+    pub fn count_user_tasks(uid: Uid) -> usize {
+        let groups: Vec<_> = TG_LIST
+            .lock_save_irq()
+            .values()
+            .filter_map(|tg| tg.upgrade())
+            .collect();
+
+        groups
+            .iter()
+            .flat_map(|tg| {
+                tg.threads
+                    .lock_save_irq()
+                    .values()
+                    .filter_map(|t| t.upgrade())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|t| t.creds.lock_save_irq().uid() == uid)
+            .count()
+    }
 }
 
 impl Drop for ThreadGroup {
     fn drop(&mut self) {
         TG_LIST.lock_save_irq().remove(&self.tgid);
+        self.pid_ns.detach(self.tgid);
     }
 }
 
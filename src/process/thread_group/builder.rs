@@ -1,13 +1,19 @@
-use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicU32, AtomicU64};
 
-use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 
-use crate::sync::SpinLock;
+use crate::{
+    process::prctl::SUID_DUMP_USER,
+    sync::{CondVar, SpinLock},
+};
 
 use super::{
     Pgid, ProcessState, Sid, TG_LIST, Tgid, ThreadGroup,
+    itimer::ITimers,
+    pid::PidNamespace,
     rsrc_lim::ResourceLimits,
     signal::{SigSet, SignalState},
+    timer::PosixTimers,
     wait::ChildNotifiers,
 };
 
@@ -16,6 +22,10 @@ pub struct ThreadGroupBuilder {
     tgid: Tgid,
     parent: Option<Arc<ThreadGroup>>,
     umask: Option<u32>,
+    personality: Option<u32>,
+    dumpable: Option<u32>,
+    oom_score_adj: Option<i16>,
+    pid_ns: Option<Arc<PidNamespace>>,
     sigstate: Option<Arc<SpinLock<SignalState>>>,
     rsrc_lim: Option<Arc<SpinLock<ResourceLimits>>>,
 }
@@ -27,6 +37,10 @@ impl ThreadGroupBuilder {
             tgid,
             parent: None,
             umask: None,
+            personality: None,
+            dumpable: None,
+            oom_score_adj: None,
+            pid_ns: None,
             sigstate: None,
             rsrc_lim: None,
         }
@@ -44,6 +58,30 @@ impl ThreadGroupBuilder {
         self
     }
 
+    /// Sets the personality of the thread group.
+    pub fn with_personality(mut self, personality: u32) -> Self {
+        self.personality = Some(personality);
+        self
+    }
+
+    /// Sets whether the thread group may be dumped.
+    pub fn with_dumpable(mut self, dumpable: u32) -> Self {
+        self.dumpable = Some(dumpable);
+        self
+    }
+
+    /// Sets the OOM killer score adjustment of the thread group.
+    pub fn with_oom_score_adj(mut self, oom_score_adj: i16) -> Self {
+        self.oom_score_adj = Some(oom_score_adj);
+        self
+    }
+
+    /// Sets the PID namespace that the thread group is created in.
+    pub fn with_pid_ns(mut self, pid_ns: Arc<PidNamespace>) -> Self {
+        self.pid_ns = Some(pid_ns);
+        self
+    }
+
     pub fn with_rsrc_lim(mut self, rsrc_lim: Arc<SpinLock<ResourceLimits>>) -> Self {
         self.rsrc_lim = Some(rsrc_lim);
         self
@@ -53,12 +91,17 @@ impl ThreadGroupBuilder {
     ///
     /// If a sigstate has not been provided, a default one will be created.
     pub fn build(self) -> Arc<ThreadGroup> {
+        let pid_ns = self.pid_ns.unwrap_or_else(PidNamespace::init);
+
+        pid_ns.attach(self.tgid);
+
         let ret = Arc::new(ThreadGroup {
             tgid: self.tgid,
             pgid: SpinLock::new(Pgid(self.tgid.value())),
             sid: SpinLock::new(Sid(self.tgid.value())),
             parent: SpinLock::new(self.parent.as_ref().map(Arc::downgrade)),
             umask: SpinLock::new(self.umask.unwrap_or(0)),
+            personality: SpinLock::new(self.personality.unwrap_or(0)),
             children: SpinLock::new(BTreeMap::new()),
             signals: self
                 .sigstate
@@ -68,7 +111,16 @@ impl ThreadGroupBuilder {
                 .unwrap_or_else(|| Arc::new(SpinLock::new(ResourceLimits::default()))),
             pending_signals: SpinLock::new(SigSet::empty()),
             child_notifiers: ChildNotifiers::new(),
+            tracees: SpinLock::new(Vec::new()),
+            itimers: SpinLock::new(ITimers::default()),
+            posix_timers: SpinLock::new(PosixTimers::default()),
+            exited: CondVar::new(false),
+            dumpable: SpinLock::new(self.dumpable.unwrap_or(SUID_DUMP_USER)),
+            child_subreaper: SpinLock::new(false),
+            oom_score_adj: SpinLock::new(self.oom_score_adj.unwrap_or(0)),
+            pid_ns,
             next_tid: AtomicU32::new(0),
+            cpu_time: AtomicU64::new(0),
             state: SpinLock::new(ProcessState::Running),
             threads: SpinLock::new(BTreeMap::new()),
         });
@@ -0,0 +1,175 @@
+use super::{
+    ThreadGroup,
+    signal::{SigId, SignalState},
+};
+use crate::{
+    clock::timeofday::TimeVal,
+    drivers::timer::ktimer::KTimer,
+    memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
+    sched::current_task,
+};
+use alloc::sync::{Arc, Weak};
+use core::time::Duration;
+use libkernel::{
+    error::{KernelError, Result},
+    memory::address::TUA,
+};
+
+const ITIMER_REAL: i32 = 0;
+const ITIMER_VIRTUAL: i32 = 1;
+const ITIMER_PROF: i32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ITimerVal {
+    it_interval: TimeVal,
+    it_value: TimeVal,
+}
+
+unsafe impl UserCopyable for ITimerVal {}
+
+impl ITimerVal {
+    fn new(value: Duration, interval: Duration) -> Self {
+        Self {
+            it_interval: interval.into(),
+            it_value: value.into(),
+        }
+    }
+}
+
+/// A timer which counts the CPU time consumed by the process.
+#[derive(Default)]
+struct CpuTimer {
+    /// The process CPU time, in nanoseconds, at which the timer expires.
+    expires: Option<u64>,
+    interval: u64,
+}
+
+impl CpuTimer {
+    fn set(&mut self, value: Duration, interval: Duration, cpu_time: u64) -> (Duration, Duration) {
+        let old = self.get(cpu_time);
+
+        self.expires = (!value.is_zero()).then(|| cpu_time + value.as_nanos() as u64);
+        self.interval = interval.as_nanos() as u64;
+
+        old
+    }
+
+    fn get(&self, cpu_time: u64) -> (Duration, Duration) {
+        let remaining = self
+            .expires
+            .map_or(0, |expires| expires.saturating_sub(cpu_time).max(1));
+
+        (
+            Duration::from_nanos(remaining),
+            Duration::from_nanos(self.interval),
+        )
+    }
+
+    /// Returns whether the timer has expired at `cpu_time`, re-arming it if
+    /// it's periodic.
+    fn expire(&mut self, cpu_time: u64) -> bool {
+        match self.expires {
+            Some(expires) if cpu_time >= expires => {
+                self.expires = (self.interval != 0)
+                    .then(|| expires + ((cpu_time - expires) / self.interval + 1) * self.interval);
+
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The interval timers of a process, set by `setitimer`.
+///
+/// There is no separate accounting of user and system time, so the virtual
+/// and profiling timers both count the process's total CPU time.
+#[derive(Default)]
+pub struct ITimers {
+    /// Created on first use, as it needs a reference to the process.
+    real: Option<KTimer>,
+    virt: CpuTimer,
+    prof: CpuTimer,
+}
+
+impl ITimers {
+    /// Raises `SIGVTALRM` and `SIGPROF` for any CPU-time timers which have
+    /// expired now that the process has used `cpu_time` nanoseconds.
+    pub fn expire_cpu_timers(&mut self, cpu_time: u64, signals: &mut SignalState) {
+        if self.virt.expire(cpu_time) {
+            signals.set_pending(SigId::SIGVTALRM);
+        }
+
+        if self.prof.expire(cpu_time) {
+            signals.set_pending(SigId::SIGPROF);
+        }
+    }
+
+    /// Disarms all of the timers.
+    pub fn disarm(&mut self) {
+        *self = Self::default();
+    }
+
+    fn real(&mut self, process: &Arc<ThreadGroup>) -> &KTimer {
+        self.real.get_or_insert_with(|| {
+            let process: Weak<ThreadGroup> = Arc::downgrade(process);
+
+            KTimer::new(move |_| {
+                if let Some(process) = process.upgrade() {
+                    process.signals.lock_save_irq().set_pending(SigId::SIGALRM);
+                }
+            })
+        })
+    }
+}
+
+pub async fn sys_getitimer(which: i32, curr_value: TUA<ITimerVal>) -> Result<usize> {
+    let process = current_task().process.clone();
+
+    let (value, interval) = {
+        let timers = process.itimers.lock_save_irq();
+
+        match which {
+            ITIMER_REAL => timers
+                .real
+                .as_ref()
+                .map_or((Duration::ZERO, Duration::ZERO), KTimer::get),
+            ITIMER_VIRTUAL => timers.virt.get(process.cpu_time()),
+            ITIMER_PROF => timers.prof.get(process.cpu_time()),
+            _ => return Err(KernelError::InvalidValue),
+        }
+    };
+
+    copy_to_user(curr_value, ITimerVal::new(value, interval)).await?;
+
+    Ok(0)
+}
+
+pub async fn sys_setitimer(
+    which: i32,
+    new_value: TUA<ITimerVal>,
+    old_value: TUA<ITimerVal>,
+) -> Result<usize> {
+    let new: ITimerVal = copy_from_user(new_value).await?;
+    let value = new.it_value.try_into()?;
+    let interval = new.it_interval.try_into()?;
+    let process = current_task().process.clone();
+
+    let (prev_value, prev_interval) = {
+        let mut timers = process.itimers.lock_save_irq();
+
+        match which {
+            ITIMER_REAL => timers.real(&process).set(value, interval),
+            ITIMER_VIRTUAL => timers.virt.set(value, interval, process.cpu_time()),
+            ITIMER_PROF => timers.prof.set(value, interval, process.cpu_time()),
+            _ => return Err(KernelError::InvalidValue),
+        }
+    };
+
+    if !old_value.is_null() {
+        copy_to_user(old_value, ITimerVal::new(prev_value, prev_interval)).await?;
+    }
+
+    Ok(0)
+}
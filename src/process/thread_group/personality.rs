@@ -0,0 +1,60 @@
+use crate::sched::current_task;
+use libkernel::error::{KernelError, Result};
+
+/// The execution domain, in the low byte of a persona. Only the native Linux
+/// domain is supported.
+const PER_MASK: u32 = 0xff;
+const PER_LINUX: u32 = 0x0000;
+
+/// Report a 2.6.x kernel version from `uname`.
+const UNAME26: u32 = 0x0020000;
+/// Disable address-space layout randomisation.
+pub const ADDR_NO_RANDOMIZE: u32 = 0x0040000;
+const FDPIC_FUNCPTRS: u32 = 0x0080000;
+const MMAP_PAGE_ZERO: u32 = 0x0100000;
+const ADDR_COMPAT_LAYOUT: u32 = 0x0200000;
+const READ_IMPLIES_EXEC: u32 = 0x0400000;
+const ADDR_LIMIT_32BIT: u32 = 0x0800000;
+const SHORT_INODE: u32 = 0x1000000;
+const WHOLE_SECONDS: u32 = 0x2000000;
+const STICKY_TIMEOUTS: u32 = 0x4000000;
+const ADDR_LIMIT_3GB: u32 = 0x8000000;
+
+/// The persona flags defined by Linux. Most are accepted only so that they can
+/// be queried back.
+const PER_FLAGS: u32 = UNAME26
+    | ADDR_NO_RANDOMIZE
+    | FDPIC_FUNCPTRS
+    | MMAP_PAGE_ZERO
+    | ADDR_COMPAT_LAYOUT
+    | READ_IMPLIES_EXEC
+    | ADDR_LIMIT_32BIT
+    | SHORT_INODE
+    | WHOLE_SECONDS
+    | STICKY_TIMEOUTS
+    | ADDR_LIMIT_3GB;
+
+/// Flags which are cleared when executing a set-user-ID or set-group-ID
+/// program, as they could be used to subvert it.
+pub const PER_CLEAR_ON_SETID: u32 = ADDR_NO_RANDOMIZE;
+
+/// Passing this value to `personality` queries the current persona without
+/// changing it.
+const PERSONALITY_QUERY: u32 = 0xffffffff;
+
+pub fn sys_personality(persona: u32) -> Result<usize> {
+    let task = current_task();
+    let mut personality = task.process.personality.lock_save_irq();
+
+    let old = *personality;
+
+    if persona != PERSONALITY_QUERY {
+        if persona & PER_MASK != PER_LINUX || persona & !(PER_MASK | PER_FLAGS) != 0 {
+            return Err(KernelError::InvalidValue);
+        }
+
+        *personality = persona;
+    }
+
+    Ok(old as _)
+}
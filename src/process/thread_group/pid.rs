@@ -1,48 +1,337 @@
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 use libkernel::error::{KernelError, Result};
 
-use crate::sched::current_task;
-use core::convert::Infallible;
+use crate::{
+    sched::current_task,
+    sync::{OnceLock, SpinLock},
+};
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use super::{Pgid, Tgid, ThreadGroup};
+use crate::process::{TASK_LIST, Task, TaskDescriptor, Tid};
 
 /// Userspace `pid_t` type.
 pub type PidT = i32;
 
+/// A PID namespace.
+///
+/// Processes are identified globally by their TGID, which is also their PID
+/// in the initial namespace. A process created within a child namespace is
+/// also given a PID in that namespace and in each of its ancestors, and only
+/// processes with a PID in a task's namespace are visible to it. The other
+/// threads of such a process are likewise given a TID in each namespace, from
+/// the same range as PIDs.
+pub struct PidNamespace {
+    parent: Option<Arc<PidNamespace>>,
+    next_pid: AtomicU32,
+    pids: SpinLock<NsPids>,
+}
+
+/// The translations between the PIDs of a namespace and global TGIDs.
+#[derive(Default)]
+struct NsPids {
+    to_tgid: BTreeMap<PidT, Tgid>,
+    to_pid: BTreeMap<Tgid, PidT>,
+    /// The TIDs of threads other than main threads, whose TID is their
+    /// process's PID.
+    to_task: BTreeMap<PidT, TaskDescriptor>,
+    to_tid: BTreeMap<TaskDescriptor, PidT>,
+}
+
+impl PidNamespace {
+    /// Returns the initial PID namespace.
+    pub fn init() -> Arc<Self> {
+        INIT_PID_NS
+            .get_or_init(|| {
+                Arc::new(Self {
+                    parent: None,
+                    next_pid: AtomicU32::new(1),
+                    pids: SpinLock::new(NsPids::default()),
+                })
+            })
+            .clone()
+    }
+
+    /// Creates a new namespace nested within this one.
+    pub fn new_child(self: &Arc<Self>) -> Arc<Self> {
+        Arc::new(Self {
+            parent: Some(self.clone()),
+            next_pid: AtomicU32::new(1),
+            pids: SpinLock::new(NsPids::default()),
+        })
+    }
+
+    fn is_init(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    /// Returns true if `other` is this namespace, or is nested within it.
+    pub fn is_ancestor_of(&self, other: &PidNamespace) -> bool {
+        let mut ns = Some(other);
+
+        while let Some(n) = ns {
+            if core::ptr::eq(n, self) {
+                return true;
+            }
+
+            ns = n.parent.as_deref();
+        }
+
+        false
+    }
+
+    /// Returns the PID of the process `tgid` within this namespace, if it is
+    /// visible here.
+    pub fn pid_of(&self, tgid: Tgid) -> Option<PidT> {
+        if self.is_init() {
+            return Some(tgid.value() as _);
+        }
+
+        self.pids.lock_save_irq().to_pid.get(&tgid).copied()
+    }
+
+    /// Returns the TGID of the process with `pid` in this namespace.
+    pub fn tgid_of(&self, pid: PidT) -> Option<Tgid> {
+        if self.is_init() {
+            return (pid > 0).then(|| Tgid::from_pid_t(pid));
+        }
+
+        self.pids.lock_save_irq().to_tgid.get(&pid).copied()
+    }
+
+    /// Returns the TID of the thread `task` within this namespace, if it is
+    /// visible here.
+    pub fn tid_of(&self, task: TaskDescriptor) -> Option<PidT> {
+        if task.tid() == Tid::from_tgid(task.tgid()) {
+            return self.pid_of(task.tgid());
+        }
+
+        if self.is_init() {
+            return Some(task.tid().value() as _);
+        }
+
+        self.pids.lock_save_irq().to_tid.get(&task).copied()
+    }
+
+    /// Returns the thread of the process `tgid` with `tid` in this namespace.
+    pub fn thread_of(&self, tgid: Tgid, tid: PidT) -> Option<Tid> {
+        if self.tgid_of(tid) == Some(tgid) {
+            return Some(Tid::from_tgid(tgid));
+        }
+
+        if self.is_init() {
+            return (tid > 0).then_some(Tid(tid as _));
+        }
+
+        self.pids
+            .lock_save_irq()
+            .to_task
+            .get(&tid)
+            .filter(|task| task.tgid() == tgid)
+            .map(|task| task.tid())
+    }
+
+    /// Returns the thread with `tid` in this namespace, of any process. A
+    /// process's PID names its main thread, even once it has exited.
+    pub fn task_of(&self, tid: PidT) -> Option<TaskDescriptor> {
+        let main_thread = |tgid| TaskDescriptor::from_tgid_tid(tgid, Tid::from_tgid(tgid));
+
+        if self.is_init() {
+            // Every TID is also a valid TGID here, so the running threads are
+            // searched first.
+            return TASK_LIST
+                .lock_save_irq()
+                .keys()
+                .find(|task| task.tid() == Tid(tid as _))
+                .copied()
+                .or_else(|| self.tgid_of(tid).map(main_thread));
+        }
+
+        self.tgid_of(tid)
+            .map(main_thread)
+            .or_else(|| self.pids.lock_save_irq().to_task.get(&tid).copied())
+    }
+
+    /// Returns the process with `pid` in this namespace.
+    pub fn find(&self, pid: PidT) -> Option<Arc<ThreadGroup>> {
+        self.tgid_of(pid).and_then(ThreadGroup::get)
+    }
+
+    /// Returns the init process of this namespace, which adopts orphans within
+    /// it.
+    pub fn child_reaper(&self) -> Option<Arc<ThreadGroup>> {
+        self.find(1)
+    }
+
+    /// Returns the TGIDs of the processes in this namespace. The initial
+    /// namespace doesn't track its processes, so this is empty for it.
+    pub fn members(&self) -> Vec<Tgid> {
+        self.pids.lock_save_irq().to_pid.keys().copied().collect()
+    }
+
+    /// Allocates PIDs for the new process `tgid` in this namespace and its
+    /// ancestors.
+    pub(super) fn attach(&self, tgid: Tgid) {
+        if self.is_init() {
+            return;
+        }
+
+        let pid = self.next_pid.fetch_add(1, Ordering::SeqCst) as PidT;
+        let mut pids = self.pids.lock_save_irq();
+
+        pids.to_tgid.insert(pid, tgid);
+        pids.to_pid.insert(tgid, pid);
+        drop(pids);
+
+        if let Some(parent) = &self.parent {
+            parent.attach(tgid);
+        }
+    }
+
+    /// Allocates TIDs for the new thread `task` in this namespace and its
+    /// ancestors. A main thread uses its process's PIDs instead.
+    pub fn attach_thread(&self, task: TaskDescriptor) {
+        if self.is_init() || task.tid() == Tid::from_tgid(task.tgid()) {
+            return;
+        }
+
+        let tid = self.next_pid.fetch_add(1, Ordering::SeqCst) as PidT;
+        let mut pids = self.pids.lock_save_irq();
+
+        pids.to_task.insert(tid, task);
+        pids.to_tid.insert(task, tid);
+        drop(pids);
+
+        if let Some(parent) = &self.parent {
+            parent.attach_thread(task);
+        }
+    }
+
+    /// Releases the TIDs of the thread `task` in this namespace and its
+    /// ancestors.
+    pub fn detach_thread(&self, task: TaskDescriptor) {
+        if self.is_init() {
+            return;
+        }
+
+        let mut pids = self.pids.lock_save_irq();
+
+        if let Some(tid) = pids.to_tid.remove(&task) {
+            pids.to_task.remove(&tid);
+        }
+
+        drop(pids);
+
+        if let Some(parent) = &self.parent {
+            parent.detach_thread(task);
+        }
+    }
+
+    /// Releases the PIDs of `tgid` in this namespace and its ancestors.
+    pub(super) fn detach(&self, tgid: Tgid) {
+        if self.is_init() {
+            return;
+        }
+
+        let mut pids = self.pids.lock_save_irq();
+
+        if let Some(pid) = pids.to_pid.remove(&tgid) {
+            pids.to_tgid.remove(&pid);
+        }
+
+        drop(pids);
+
+        if let Some(parent) = &self.parent {
+            parent.detach(tgid);
+        }
+    }
+}
+
+static INIT_PID_NS: OnceLock<Arc<PidNamespace>> = OnceLock::new();
+
+/// Returns the PID of the process `tgid` as seen from the current task's PID
+/// namespace, or 0 if it isn't visible there.
+pub fn pid_vnr(tgid: Tgid) -> PidT {
+    current_task().process.pid_ns.pid_of(tgid).unwrap_or(0)
+}
+
+/// Returns the TID of `task` as seen from the current task's PID namespace,
+/// or 0 if it isn't visible there.
+pub fn tid_vnr(task: &Task) -> PidT {
+    task_vnr(task.descriptor())
+}
+
+/// Returns the TID of the thread `task` as seen from the current task's PID
+/// namespace, or 0 if it isn't visible there.
+pub fn task_vnr(task: TaskDescriptor) -> PidT {
+    current_task().process.pid_ns.tid_of(task).unwrap_or(0)
+}
+
+/// Returns the thread of the current process with `tid` in the current task's
+/// PID namespace.
+pub fn tid_from_vtid(tid: PidT) -> Option<Tid> {
+    let process = &current_task().process;
+
+    process.pid_ns.thread_of(process.tgid, tid)
+}
+
+/// Returns the TGID of the process with `pid` in the current task's PID
+/// namespace.
+pub fn tgid_from_vpid(pid: PidT) -> Option<Tgid> {
+    current_task().process.pid_ns.tgid_of(pid)
+}
+
+/// Returns the process with `pid` in the current task's PID namespace.
+pub fn find_vpid(pid: PidT) -> Option<Arc<ThreadGroup>> {
+    current_task().process.pid_ns.find(pid)
+}
+
 pub fn sys_getpid() -> core::result::Result<usize, Infallible> {
-    Ok(current_task().process.tgid.value() as _)
+    Ok(pid_vnr(current_task().process.tgid) as _)
 }
 
 pub fn sys_getppid() -> core::result::Result<usize, Infallible> {
+    // The parent of a namespace's init process is outside of it, and so is
+    // reported as 0.
     Ok(current_task()
         .process
         .parent
         .lock_save_irq()
         .as_ref()
         .and_then(|x| x.upgrade())
-        .map(|x| x.tgid.value())
+        .map(|x| pid_vnr(x.tgid))
         .unwrap_or(0) as _)
 }
 
 pub fn sys_getpgid(pid: PidT) -> Result<usize> {
     let pgid = if pid == 0 {
         *current_task().process.pgid.lock_save_irq()
-    } else if let Some(tg) = ThreadGroup::get(Tgid::from_pid_t(pid)) {
+    } else if let Some(tg) = find_vpid(pid) {
         *tg.pgid.lock_save_irq()
     } else {
         return Err(KernelError::NoProcess);
     };
 
-    Ok(pgid.value() as _)
+    Ok(pid_vnr(Tgid(pgid.value())) as _)
 }
 
-pub fn sys_setpgid(pid: PidT, pgid: Pgid) -> Result<usize> {
-    if pid == 0 {
-        *current_task().process.pgid.lock_save_irq() = pgid;
-    } else if let Some(tg) = ThreadGroup::get(Tgid::from_pid_t(pid)) {
-        *tg.pgid.lock_save_irq() = pgid;
+pub fn sys_setpgid(pid: PidT, pgid: PidT) -> Result<usize> {
+    let tg = if pid == 0 {
+        current_task().process.clone()
     } else {
-        return Err(KernelError::NoProcess);
+        find_vpid(pid).ok_or(KernelError::NoProcess)?
+    };
+
+    let pgid = match pgid {
+        0 => Pgid(tg.tgid.value()),
+        p if p < 0 => return Err(KernelError::InvalidValue),
+        p => Pgid(tgid_from_vpid(p).ok_or(KernelError::NotPermitted)?.value()),
     };
 
+    *tg.pgid.lock_save_irq() = pgid;
+
     Ok(0)
 }
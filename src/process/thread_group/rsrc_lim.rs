@@ -1,3 +1,4 @@
+use alloc::sync::Arc;
 use libkernel::{
     error::{KernelError, Result},
     memory::address::TUA,
@@ -5,11 +6,11 @@ use libkernel::{
 
 use crate::{
     memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
-    process::thread_group::{TG_LIST, Tgid},
+    process::creds::caps::CapSet,
     sched::current_task,
 };
 
-use super::pid::PidT;
+use super::pid::{PidT, find_vpid};
 
 #[repr(u32)]
 #[derive(Clone, Copy, Debug)]
@@ -156,12 +157,24 @@ impl Default for ResourceLimits {
     }
 }
 
+/// Returns the current task's soft limit for the resource `id`.
+pub fn rlimit(id: RlimitId) -> u64 {
+    current_task()
+        .process
+        .rsrc_lim
+        .lock_save_irq()
+        .get(id)
+        .rlim_cur
+}
+
 impl ResourceLimits {
     pub fn get(&self, id: RlimitId) -> RLimit {
         self.limits[id.as_usize()]
     }
 
     /// Attempt to set a new resource limit, returning the old value if changed.
+    ///
+    /// `is_privileged` should be true if the caller holds `CAP_SYS_RESOURCE`.
     pub fn set(&mut self, id: RlimitId, new_limit: RLimit, is_privileged: bool) -> Result<RLimit> {
         let old_limit = self.get(id);
 
@@ -194,11 +207,7 @@ pub async fn sys_prlimit64(
     let task = if pid == 0 {
         current_task().process.clone()
     } else {
-        TG_LIST
-            .lock_save_irq()
-            .get(&Tgid::from_pid_t(pid))
-            .and_then(|x| x.upgrade())
-            .ok_or(KernelError::NoProcess)?
+        find_vpid(pid).ok_or(KernelError::NoProcess)?
     };
 
     let new_limit = if !new_rlim.is_null() {
@@ -207,10 +216,29 @@ pub async fn sys_prlimit64(
         None
     };
 
+    let current = current_task();
+    let creds = current.creds.lock_save_irq().clone();
+    let is_privileged = creds.has_cap(CapSet::CAP_SYS_RESOURCE);
+
+    // Accessing another process's limits requires that all of its user and
+    // group IDs match our real IDs.
+    if !is_privileged
+        && !Arc::ptr_eq(&task, &current.process)
+        && let Some(target) = task.creds()
+        && !([target.uid(), target.euid(), target.suid()]
+            .iter()
+            .all(|&id| id == creds.uid())
+            && [target.gid(), target.egid(), target.sgid()]
+                .iter()
+                .all(|&id| id == creds.gid()))
+    {
+        return Err(KernelError::NotPermitted);
+    }
+
     let old_lim = if let Some(new_limit) = new_limit {
         task.rsrc_lim
             .lock_save_irq()
-            .set(resource, new_limit, true)?
+            .set(resource, new_limit, is_privileged)?
     } else {
         task.rsrc_lim.lock_save_irq().get(resource)
     };
@@ -221,3 +249,11 @@ pub async fn sys_prlimit64(
 
     Ok(0)
 }
+
+pub async fn sys_getrlimit(resource: u32, rlim: TUA<RLimit>) -> Result<usize> {
+    sys_prlimit64(0, resource, TUA::null(), rlim).await
+}
+
+pub async fn sys_setrlimit(resource: u32, rlim: TUA<RLimit>) -> Result<usize> {
+    sys_prlimit64(0, resource, rlim, TUA::null()).await
+}
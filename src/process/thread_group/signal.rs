@@ -7,17 +7,24 @@ use core::{
 
 use bitflags::bitflags;
 use ksigaction::{KSignalAction, UserspaceSigAction};
-use libkernel::memory::{address::UA, region::UserMemoryRegion};
+use libkernel::{
+    memory::{address::UA, region::UserMemoryRegion},
+    sync::condvar::WakeupType,
+};
 use ringbuf::Arc;
 
-use crate::{memory::uaccess::UserCopyable, sync::SpinLock};
+use crate::{
+    memory::uaccess::UserCopyable,
+    sync::{CondVar, SpinLock},
+};
 
 pub mod kill;
 pub mod ksigaction;
 pub mod sigaction;
 pub mod sigaltstack;
 pub mod sigprocmask;
-mod uaccess;
+pub mod stop;
+pub mod uaccess;
 
 bitflags! {
     #[repr(C)]
@@ -133,7 +140,13 @@ impl Display for SigId {
 }
 
 // SIGKILL and SIGSTOP
-const UNMASKABLE_SIGNALS: SigSet = SigSet::SIGKILL.union(SigSet::SIGSTOP);
+pub const UNMASKABLE_SIGNALS: SigSet = SigSet::SIGKILL.union(SigSet::SIGSTOP);
+
+/// The signals whose default action is to stop the process.
+const STOP_SIGNALS: SigSet = SigSet::SIGSTOP
+    .union(SigSet::SIGTSTP)
+    .union(SigSet::SIGTTIN)
+    .union(SigSet::SIGTTOU);
 
 #[derive(Clone, Copy, Debug)]
 pub enum SigActionState {
@@ -200,6 +213,11 @@ pub struct SignalState {
     action: Arc<SpinLock<SigActionSet>>,
     pending: SigSet,
     pub alt_stack: Option<AltSigStack>,
+    /// Counts the signals raised for the process or any of its threads, so
+    /// that waiters, such as signalfd readers, can tell when to look again.
+    raised: CondVar<u64>,
+    /// The signal which stopped the process, while it's stopped.
+    stopped: CondVar<Option<SigId>>,
 }
 
 impl Clone for SignalState {
@@ -208,6 +226,8 @@ impl Clone for SignalState {
             action: self.action.clone(),
             pending: SigSet::empty(),
             alt_stack: None,
+            raised: CondVar::new(0),
+            stopped: CondVar::new(None),
         }
     }
 }
@@ -218,6 +238,8 @@ impl SignalState {
             action: Arc::new(SpinLock::new(SigActionSet([SigActionState::Ignore; 64]))),
             pending: SigSet::empty(),
             alt_stack: None,
+            raised: CondVar::new(0),
+            stopped: CondVar::new(None),
         }
     }
 
@@ -226,6 +248,8 @@ impl SignalState {
             action: Arc::new(SpinLock::new(SigActionSet([SigActionState::Default; 64]))),
             pending: SigSet::empty(),
             alt_stack: None,
+            raised: CondVar::new(0),
+            stopped: CondVar::new(None),
         }
     }
 
@@ -234,6 +258,8 @@ impl SignalState {
             action: self.action.clone(),
             pending: SigSet::empty(),
             alt_stack: None,
+            raised: CondVar::new(0),
+            stopped: CondVar::new(None),
         }
     }
 
@@ -242,11 +268,120 @@ impl SignalState {
             action: Arc::new(SpinLock::new(self.action.lock_save_irq().clone())),
             pending: SigSet::empty(),
             alt_stack: None,
+            raised: CondVar::new(0),
+            stopped: CondVar::new(None),
         }
     }
 
     pub fn set_pending(&mut self, signal: SigId) {
+        match signal {
+            // A stopped process is continued as soon as SIGCONT or SIGKILL is
+            // raised, even if the signal is blocked or ignored. SIGCONT also
+            // discards any pending stop signals, and vice versa.
+            SigId::SIGCONT => {
+                self.pending.remove(STOP_SIGNALS);
+                self.resume();
+            }
+            SigId::SIGKILL => self.resume(),
+            SigId::SIGSTOP | SigId::SIGTSTP | SigId::SIGTTIN | SigId::SIGTTOU => {
+                self.pending.remove(SigSet::SIGCONT);
+            }
+            _ => {}
+        }
+
         self.pending.insert(signal.into());
+        self.notify_raised();
+    }
+
+    /// Marks the process as stopped by `signal`. Returns `false` if it was
+    /// already stopped.
+    pub fn stop(&self, signal: SigId) -> bool {
+        let mut stopped = false;
+
+        self.stopped.update(|state| {
+            if state.is_none() {
+                *state = Some(signal);
+                stopped = true;
+            }
+
+            WakeupType::None
+        });
+
+        stopped
+    }
+
+    /// Continues the process if it's stopped, waking its threads.
+    fn resume(&self) {
+        self.stopped.update(|state| {
+            if state.take().is_some() {
+                WakeupType::All
+            } else {
+                WakeupType::None
+            }
+        });
+    }
+
+    /// Returns whether the process is stopped.
+    pub fn is_stopped(&self) -> bool {
+        let mut stopped = false;
+
+        self.stopped.update(|state| {
+            stopped = state.is_some();
+            WakeupType::None
+        });
+
+        stopped
+    }
+
+    /// Returns the signal which stopped the process, which is cleared when
+    /// the process is continued.
+    pub fn stopped(&self) -> CondVar<Option<SigId>> {
+        self.stopped.clone()
+    }
+
+    /// Wakes anything waiting for a signal to be raised.
+    pub fn notify_raised(&self) {
+        self.raised.update(|count| {
+            *count = count.wrapping_add(1);
+            WakeupType::All
+        });
+    }
+
+    /// Returns the count of raised signals, which changes whenever a signal is
+    /// raised.
+    pub fn raised(&self) -> CondVar<u64> {
+        self.raised.clone()
+    }
+
+    /// Returns whether any signal in `mask` is pending for the process.
+    pub fn is_pending(&self, mask: SigSet) -> bool {
+        self.pending.intersects(mask)
+    }
+
+    /// Removes and returns a signal in `mask` which is pending for the
+    /// process, without actioning it.
+    pub fn take_pending(&mut self, mask: SigSet) -> Option<SigId> {
+        let signal = self.pending.intersection(mask).iter().next()?;
+
+        self.pending.remove(signal);
+
+        Some(signal.into())
+    }
+
+    /// Removes and returns the next signal, pending for either the process or
+    /// the task, which isn't blocked by `mask`, whatever its disposition.
+    pub fn dequeue_signal(&mut self, mask: SigSet, task_pending: &mut SigSet) -> Option<SigId> {
+        let signal = self
+            .pending
+            .union(*task_pending)
+            .difference(mask)
+            .iter()
+            .next()?;
+
+        self.pending.remove(signal);
+        task_pending.remove(signal);
+
+        Some(signal.into())
     }
 
     pub fn action_signal(
@@ -255,32 +390,36 @@ impl SignalState {
         task_pending: &mut SigSet,
     ) -> Option<(SigId, KSignalAction)> {
         loop {
-            let signal = self
-                .pending
-                .union(*task_pending)
-                .difference(mask)
-                .iter()
-                .next()?;
-
             // Consume the signal we are about to action.
-            self.pending.remove(signal);
-            task_pending.remove(signal);
-
-            let id: SigId = signal.into();
-
-            match self.action.lock_save_irq()[id] {
-                SigActionState::Ignore => continue, // look for another signal,
-                SigActionState::Default => {
-                    let action = KSignalAction::default_action(id);
-
-                    if let Some(action) = action {
-                        return Some((id, action));
-                    }
-                    // Signal is ignored by default. Look for another signal.
-                }
-                SigActionState::Action(userspace_sig_action) => {
-                    return Some((id, KSignalAction::Userspace(id, userspace_sig_action)));
-                }
+            let id = self.dequeue_signal(mask, task_pending)?;
+
+            if let Some(action) = self.action_for(id) {
+                return Some((id, action));
+            }
+
+            // The signal is ignored. Look for another signal.
+        }
+    }
+
+    /// Resets the disposition of `id` to its default action if the signal is
+    /// ignored.
+    pub fn unignore(&mut self, id: SigId) {
+        let mut action = self.action.lock_save_irq();
+
+        if matches!(action[id], SigActionState::Ignore) {
+            action[id] = SigActionState::Default;
+        }
+    }
+
+    /// Returns how `id` should be actioned under the current dispositions,
+    /// without consulting or consuming any pending signals. `None` is returned
+    /// if the signal is ignored.
+    pub fn action_for(&self, id: SigId) -> Option<KSignalAction> {
+        match self.action.lock_save_irq()[id] {
+            SigActionState::Ignore => None,
+            SigActionState::Default => KSignalAction::default_action(id),
+            SigActionState::Action(userspace_sig_action) => {
+                Some(KSignalAction::Userspace(id, userspace_sig_action))
             }
         }
     }
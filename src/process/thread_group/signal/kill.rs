@@ -1,7 +1,10 @@
 use crate::{
     process::{
-        Tid,
-        thread_group::{Pgid, Tgid, ThreadGroup, pid::PidT},
+        creds::Credentials,
+        thread_group::{
+            Pgid, ThreadGroup,
+            pid::{PidT, find_vpid, pid_vnr, tgid_from_vpid, tid_from_vtid},
+        },
     },
     sched::current_task,
 };
@@ -10,12 +13,72 @@ use libkernel::error::{KernelError, Result};
 
 use super::{SigId, uaccess::UserSigId};
 
+/// Raises `signal` on `target` if the sender's credentials permit it.
+///
+/// A process may always be sent `SIGCONT` by another process in the same
+/// session.
+pub fn signal_tg_checked(
+    sender: &Credentials,
+    sender_tg: &ThreadGroup,
+    target: &ThreadGroup,
+    signal: SigId,
+) -> Result<()> {
+    let permitted = match target.creds() {
+        Some(target_creds) => {
+            sender.may_signal(&target_creds)
+                || (signal == SigId::SIGCONT
+                    && *sender_tg.sid.lock_save_irq() == *target.sid.lock_save_irq())
+        }
+        // The process has no live threads left; there is nobody to check
+        // against, and nobody to deliver to.
+        None => true,
+    };
+
+    if !permitted {
+        return Err(KernelError::NotPermitted);
+    }
+
+    target.signals.lock_save_irq().set_pending(signal);
+
+    Ok(())
+}
+
+/// Signals every process in the group matching `pgid` which is visible in the
+/// sender's PID namespace. Succeeds if at least one process was signalled.
+fn signal_pgrp_checked(
+    sender: &Credentials,
+    sender_tg: &ThreadGroup,
+    pgid: Pgid,
+    signal: SigId,
+) -> Result<()> {
+    let mut result = Err(KernelError::NoProcess);
+
+    for tg_weak in crate::process::thread_group::TG_LIST
+        .lock_save_irq()
+        .values()
+    {
+        if let Some(tg) = tg_weak.upgrade()
+            && *tg.pgid.lock_save_irq() == pgid
+            && sender_tg.pid_ns.pid_of(tg.tgid).is_some()
+        {
+            let res = signal_tg_checked(sender, sender_tg, &tg, signal);
+
+            if result.is_err() {
+                result = res;
+            }
+        }
+    }
+
+    result
+}
+
 pub fn sys_kill(pid: PidT, signal: UserSigId) -> Result<usize> {
     let signal: SigId = signal.try_into()?;
 
     let current_task = current_task();
+    let creds = current_task.creds.lock_save_irq().clone();
     // Kill ourselves
-    if pid == current_task.process.tgid.value() as PidT {
+    if pid == pid_vnr(current_task.process.tgid) {
         current_task
             .process
             .signals
@@ -26,38 +89,19 @@ pub fn sys_kill(pid: PidT, signal: UserSigId) -> Result<usize> {
 
     match pid {
         p if p > 0 => {
-            let target_tg = ThreadGroup::get(Tgid(p as _)).ok_or(KernelError::NoProcess)?;
-            target_tg.signals.lock_save_irq().set_pending(signal);
+            let target_tg = find_vpid(p).ok_or(KernelError::NoProcess)?;
+            signal_tg_checked(&creds, &current_task.process, &target_tg, signal)?;
         }
 
         0 => {
             let our_pgid = *current_task.process.pgid.lock_save_irq();
-            // Iterate over all thread groups and signal the ones that are in
-            // the same PGID.
-            for tg_weak in crate::process::thread_group::TG_LIST
-                .lock_save_irq()
-                .values()
-            {
-                if let Some(tg) = tg_weak.upgrade()
-                    && *tg.pgid.lock_save_irq() == our_pgid
-                {
-                    tg.signals.lock_save_irq().set_pending(signal);
-                }
-            }
+            signal_pgrp_checked(&creds, &current_task.process, our_pgid, signal)?;
         }
 
         p if p < 0 && p != -1 => {
-            let target_pgid = Pgid((-p) as _);
-            for tg_weak in crate::process::thread_group::TG_LIST
-                .lock_save_irq()
-                .values()
-            {
-                if let Some(tg) = tg_weak.upgrade()
-                    && *tg.pgid.lock_save_irq() == target_pgid
-                {
-                    tg.signals.lock_save_irq().set_pending(signal);
-                }
-            }
+            let pgid = tgid_from_vpid(-p).ok_or(KernelError::NoProcess)?;
+
+            signal_pgrp_checked(&creds, &current_task.process, Pgid(pgid.value()), signal)?;
         }
 
         _ => return Err(KernelError::NotSupported),
@@ -67,9 +111,10 @@ pub fn sys_kill(pid: PidT, signal: UserSigId) -> Result<usize> {
 }
 
 pub fn sys_tkill(tid: PidT, signal: UserSigId) -> Result<usize> {
-    let target_tid = Tid(tid as _);
     let current_task = current_task();
 
+    let target_tid = tid_from_vtid(tid).ok_or(KernelError::NoProcess)?;
+
     let signal: SigId = signal.try_into()?;
 
     // The fast-path case.
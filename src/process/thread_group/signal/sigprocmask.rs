@@ -9,6 +9,18 @@ pub const SIG_BLOCK: u32 = 0;
 pub const SIG_UNBLOCK: u32 = 1;
 pub const SIG_SETMASK: u32 = 2;
 
+/// Replaces the current task's signal mask with `set`, returning the old mask.
+///
+/// This is for syscalls such as `pselect6` which wait with a temporary mask,
+/// and restore the old one afterwards.
+pub fn replace_sigmask(set: SigSet) -> SigSet {
+    let task = current_task();
+    let mut sigmask = task.sig_mask.lock_save_irq();
+
+    // SIGSTOP and SIGKILL can never be masked.
+    core::mem::replace(&mut *sigmask, set.union(UNMASKABLE_SIGNALS))
+}
+
 pub async fn sys_rt_sigprocmask(
     how: u32,
     set: TUA<SigSet>,
@@ -0,0 +1,71 @@
+//! Group stop: the stopping of every thread in a process by a stop signal,
+//! until it's continued by `SIGCONT` or killed.
+
+use alloc::sync::{Arc, Weak};
+
+use super::{SigId, SigSet, ksigaction::KSignalAction, sigaction::SigActionFlags};
+use crate::process::{
+    Task,
+    thread_group::{ThreadGroup, wait::ChildState},
+};
+
+/// Tells the parent of `process` that it has stopped or continued. `SIGCHLD`
+/// isn't raised if the parent asked not to hear about this with
+/// `SA_NOCLDSTOP`.
+fn notify_parent(process: &ThreadGroup, state: ChildState) {
+    let Some(parent) = process
+        .parent
+        .lock_save_irq()
+        .as_ref()
+        .and_then(Weak::upgrade)
+    else {
+        return;
+    };
+
+    parent.child_notifiers.child_update(process, state);
+
+    let mut signals = parent.signals.lock_save_irq();
+
+    let nocldstop = matches!(
+        signals.action_for(SigId::SIGCHLD),
+        Some(KSignalAction::Userspace(_, action))
+            if action.flags.contains(SigActionFlags::SA_NOCLDSTOP)
+    );
+
+    if !nocldstop {
+        signals.set_pending(SigId::SIGCHLD);
+    }
+}
+
+/// Stops the process of `task` for `signal`, reporting the stop to its
+/// parent, and parks `task` until the process is continued. The other threads
+/// of the process park themselves on their way back to userspace.
+pub async fn group_stop(task: Arc<Task>, signal: SigId) {
+    let stopped = task.process.signals.lock_save_irq().stop(signal);
+
+    if stopped {
+        notify_parent(&task.process, ChildState::Stop { signal });
+    }
+
+    wait_while_stopped(task.clone()).await;
+
+    // A process which is being killed isn't reported as continued.
+    if stopped
+        && !task
+            .process
+            .signals
+            .lock_save_irq()
+            .is_pending(SigSet::SIGKILL)
+    {
+        notify_parent(&task.process, ChildState::Continue);
+    }
+}
+
+/// Parks `task` until its process is no longer stopped.
+pub async fn wait_while_stopped(task: Arc<Task>) {
+    let stopped = task.process.signals.lock_save_irq().stopped();
+
+    stopped
+        .wait_until(|signal| if signal.is_none() { Some(()) } else { None })
+        .await;
+}
@@ -0,0 +1,268 @@
+use super::{
+    ThreadGroup,
+    pid::tid_from_vtid,
+    signal::{SigId, uaccess::UserSigId},
+};
+use crate::{
+    clock::{gettime::ClockId, realtime::date, timespec::TimeSpec},
+    drivers::timer::{ktimer::KTimer, uptime},
+    memory::uaccess::{UserCopyable, copy_from_user, copy_to_user},
+    process::Tid,
+    sched::current_task,
+};
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+use core::time::Duration;
+use libkernel::{
+    error::{KernelError, Result},
+    memory::address::TUA,
+};
+
+pub type TimerId = i32;
+
+pub const CLOCK_REALTIME: ClockId = 0;
+pub const CLOCK_MONOTONIC: ClockId = 1;
+pub const CLOCK_BOOTTIME: ClockId = 7;
+
+const SIGEV_SIGNAL: i32 = 0;
+const SIGEV_NONE: i32 = 1;
+const SIGEV_THREAD_ID: i32 = 4;
+
+const TIMER_ABSTIME: i32 = 1;
+
+/// The maximum number of POSIX timers a process may have.
+const MAX_TIMERS: usize = 4096;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SigEvent {
+    sigev_value: u64,
+    sigev_signo: i32,
+    sigev_notify: i32,
+    sigev_notify_thread_id: i32,
+    _pad: [i32; 11],
+}
+
+unsafe impl UserCopyable for SigEvent {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ITimerSpec {
+    it_interval: TimeSpec,
+    it_value: TimeSpec,
+}
+
+unsafe impl UserCopyable for ITimerSpec {}
+
+impl ITimerSpec {
+    pub fn new(value: Duration, interval: Duration) -> Self {
+        Self {
+            it_interval: interval.into(),
+            it_value: value.into(),
+        }
+    }
+
+    /// Validates the specification, returning its value and interval.
+    pub fn durations(&self) -> Result<(Duration, Duration)> {
+        self.it_value.validate()?;
+        self.it_interval.validate()?;
+
+        Ok((self.it_value.into(), self.it_interval.into()))
+    }
+}
+
+/// How the owner of a timer is told that it has expired.
+///
+/// There is no queue of signal information, so the `sigev_value` given to
+/// `timer_create` is not delivered with the signal.
+enum Notify {
+    None,
+    Process(SigId),
+    Thread(SigId, Tid),
+}
+
+struct PosixTimer {
+    clock: ClockId,
+    timer: KTimer,
+}
+
+/// The POSIX timers of a process, created by `timer_create`.
+#[derive(Default)]
+pub struct PosixTimers {
+    timers: BTreeMap<TimerId, PosixTimer>,
+    next_id: TimerId,
+}
+
+impl PosixTimers {
+    /// Deletes all of the timers.
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    fn insert(&mut self, timer: PosixTimer) -> Result<TimerId> {
+        if self.timers.len() >= MAX_TIMERS {
+            return Err(KernelError::TryAgain);
+        }
+
+        while self.timers.contains_key(&self.next_id) {
+            self.next_id = self.next_id.wrapping_add(1).max(0);
+        }
+
+        let id = self.next_id;
+
+        self.timers.insert(id, timer);
+        self.next_id = id.wrapping_add(1).max(0);
+
+        Ok(id)
+    }
+
+    fn get(&self, id: TimerId) -> Result<&PosixTimer> {
+        self.timers.get(&id).ok_or(KernelError::InvalidValue)
+    }
+}
+
+/// Returns the current time on `clock`, one of the clocks timers may use.
+pub fn now(clock: ClockId) -> Duration {
+    match clock {
+        CLOCK_REALTIME => date(),
+        _ => uptime(),
+    }
+}
+
+fn notify(process: &ThreadGroup, notify: &Notify) {
+    match *notify {
+        Notify::None => {}
+        Notify::Process(signal) => process.signals.lock_save_irq().set_pending(signal),
+        Notify::Thread(signal, tid) => {
+            if let Some(task) = process
+                .threads
+                .lock_save_irq()
+                .get(&tid)
+                .and_then(|t| t.upgrade())
+            {
+                task.raise_task_signal(signal);
+            }
+        }
+    }
+}
+
+pub async fn sys_timer_create(
+    clock: ClockId,
+    sevp: TUA<SigEvent>,
+    timer_id: TUA<TimerId>,
+) -> Result<usize> {
+    match clock {
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_BOOTTIME => {}
+        _ => return Err(KernelError::InvalidValue),
+    }
+
+    let process = current_task().process.clone();
+
+    let how = if sevp.is_null() {
+        Notify::Process(SigId::SIGALRM)
+    } else {
+        let event = copy_from_user(sevp).await?;
+        let signal = || SigId::try_from(UserSigId::from(event.sigev_signo as u64));
+
+        match event.sigev_notify {
+            SIGEV_NONE => Notify::None,
+            SIGEV_SIGNAL => Notify::Process(signal()?),
+            SIGEV_THREAD_ID => {
+                let tid = tid_from_vtid(event.sigev_notify_thread_id)
+                    .filter(|tid| process.threads.lock_save_irq().contains_key(tid))
+                    .ok_or(KernelError::InvalidValue)?;
+
+                Notify::Thread(signal()?, tid)
+            }
+            _ => return Err(KernelError::InvalidValue),
+        }
+    };
+
+    let owner = Arc::downgrade(&process);
+    let timer = KTimer::new(move |_| {
+        if let Some(process) = owner.upgrade() {
+            notify(&process, &how);
+        }
+    });
+
+    let id = process
+        .posix_timers
+        .lock_save_irq()
+        .insert(PosixTimer { clock, timer })?;
+
+    if let Err(e) = copy_to_user(timer_id, id).await {
+        process.posix_timers.lock_save_irq().timers.remove(&id);
+        return Err(e);
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_timer_settime(
+    timer_id: TimerId,
+    flags: i32,
+    new_value: TUA<ITimerSpec>,
+    old_value: TUA<ITimerSpec>,
+) -> Result<usize> {
+    let new: ITimerSpec = copy_from_user(new_value).await?;
+    let (mut value, interval) = new.durations()?;
+    let process = current_task().process.clone();
+
+    let (prev_value, prev_interval) = {
+        let timers = process.posix_timers.lock_save_irq();
+        let timer = timers.get(timer_id)?;
+
+        // An absolute expiry time which has already passed expires
+        // immediately.
+        if flags & TIMER_ABSTIME != 0 && !value.is_zero() {
+            value = value
+                .saturating_sub(now(timer.clock))
+                .max(Duration::from_nanos(1));
+        }
+
+        timer.timer.set(value, interval)
+    };
+
+    if !old_value.is_null() {
+        copy_to_user(old_value, ITimerSpec::new(prev_value, prev_interval)).await?;
+    }
+
+    Ok(0)
+}
+
+pub async fn sys_timer_gettime(timer_id: TimerId, curr_value: TUA<ITimerSpec>) -> Result<usize> {
+    let (value, interval) = current_task()
+        .process
+        .posix_timers
+        .lock_save_irq()
+        .get(timer_id)?
+        .timer
+        .get();
+
+    copy_to_user(curr_value, ITimerSpec::new(value, interval)).await?;
+
+    Ok(0)
+}
+
+pub fn sys_timer_getoverrun(timer_id: TimerId) -> Result<usize> {
+    let overrun = current_task()
+        .process
+        .posix_timers
+        .lock_save_irq()
+        .get(timer_id)?
+        .timer
+        .overrun();
+
+    Ok(overrun as _)
+}
+
+pub fn sys_timer_delete(timer_id: TimerId) -> Result<usize> {
+    current_task()
+        .process
+        .posix_timers
+        .lock_save_irq()
+        .timers
+        .remove(&timer_id)
+        .ok_or(KernelError::InvalidValue)?;
+
+    Ok(0)
+}
@@ -1,41 +1,130 @@
-use crate::clock::timespec::TimeSpec;
-use crate::memory::uaccess::copy_to_user;
+use crate::clock::timeofday::TimeVal;
+use crate::memory::uaccess::{UserCopyable, copy_to_user};
+use crate::process::fd_table::Fd;
+use crate::process::pidfd::pidfd_process;
+use crate::process::{Task, TaskDescriptor, Tid};
 use crate::sched::current_task;
 use crate::sync::CondVar;
 use alloc::collections::btree_map::BTreeMap;
 use bitflags::Flags;
+use core::time::Duration;
+use libkernel::proc::ids::Uid;
 use libkernel::sync::condvar::WakeupType;
 use libkernel::{
     error::{KernelError, Result},
     memory::address::TUA,
 };
 
-use super::Tgid;
+use super::pid::{task_vnr, tgid_from_vpid};
 use super::signal::SigId;
+use super::{Pgid, Tgid, ThreadGroup};
 
 pub type PidT = i32;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct RUsage {
-    pub ru_utime: TimeSpec, // user time used
-    pub ru_stime: TimeSpec, // system time used
-    pub ru_maxrss: i64,     // maximum resident set size
-    pub ru_ixrss: i64,      // integral shared memory size
-    pub ru_idrss: i64,      // integral unshared data size
-    pub ru_isrss: i64,      // integral unshared stack size
-    pub ru_minflt: i64,     // page reclaims
-    pub ru_majflt: i64,     // page faults
-    pub ru_nswap: i64,      // swaps
-    pub ru_inblock: i64,    // block input operations
-    pub ru_oublock: i64,    // block output operations
-    pub ru_msgsnd: i64,     // messages sent
-    pub ru_msgrcv: i64,     // messages received
-    pub ru_nsignals: i64,   // signals received
-    pub ru_nvcsw: i64,      // voluntary context switches
-    pub ru_nivcsw: i64,     // involuntary context switches
+    pub ru_utime: TimeVal, // user time used
+    pub ru_stime: TimeVal, // system time used
+    pub ru_maxrss: i64,    // maximum resident set size
+    pub ru_ixrss: i64,     // integral shared memory size
+    pub ru_idrss: i64,     // integral unshared data size
+    pub ru_isrss: i64,     // integral unshared stack size
+    pub ru_minflt: i64,    // page reclaims
+    pub ru_majflt: i64,    // page faults
+    pub ru_nswap: i64,     // swaps
+    pub ru_inblock: i64,   // block input operations
+    pub ru_oublock: i64,   // block output operations
+    pub ru_msgsnd: i64,    // messages sent
+    pub ru_msgrcv: i64,    // messages received
+    pub ru_nsignals: i64,  // signals received
+    pub ru_nvcsw: i64,     // voluntary context switches
+    pub ru_nivcsw: i64,    // involuntary context switches
 }
 
+unsafe impl UserCopyable for RUsage {}
+
+impl RUsage {
+    /// Returns the usage of a process which consumed `cpu_time` nanoseconds.
+    ///
+    /// There is no separate accounting of user and system time, so it's all
+    /// reported as user time.
+    fn new(cpu_time: u64) -> Self {
+        Self {
+            ru_utime: Duration::from_nanos(cpu_time).into(),
+            ru_stime: Duration::ZERO.into(),
+            ru_maxrss: 0,
+            ru_ixrss: 0,
+            ru_idrss: 0,
+            ru_isrss: 0,
+            ru_minflt: 0,
+            ru_majflt: 0,
+            ru_nswap: 0,
+            ru_inblock: 0,
+            ru_oublock: 0,
+            ru_msgsnd: 0,
+            ru_msgrcv: 0,
+            ru_nsignals: 0,
+            ru_nvcsw: 0,
+            ru_nivcsw: 0,
+        }
+    }
+}
+
+/// The `siginfo_t` filled in by `waitid`, laid out for `SIGCHLD`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ChildSigInfo {
+    si_signo: i32,
+    si_errno: i32,
+    si_code: i32,
+    _pad0: i32,
+    si_pid: i32,
+    si_uid: u32,
+    si_status: i32,
+    _pad1: i32,
+    si_utime: i64,
+    si_stime: i64,
+    _pad: [u8; 80],
+}
+
+unsafe impl UserCopyable for ChildSigInfo {}
+
+impl ChildSigInfo {
+    fn empty() -> Self {
+        Self {
+            si_signo: 0,
+            si_errno: 0,
+            si_code: 0,
+            _pad0: 0,
+            si_pid: 0,
+            si_uid: 0,
+            si_status: 0,
+            _pad1: 0,
+            si_utime: 0,
+            si_stime: 0,
+            _pad: [0; 80],
+        }
+    }
+}
+
+// `si_code` values for SIGCHLD.
+const CLD_EXITED: i32 = 1;
+const CLD_KILLED: i32 = 2;
+const CLD_DUMPED: i32 = 3;
+const CLD_TRAPPED: i32 = 4;
+const CLD_STOPPED: i32 = 5;
+const CLD_CONTINUED: i32 = 6;
+
+// `idtype` values for waitid.
+const P_ALL: i32 = 0;
+const P_PID: i32 = 1;
+const P_PGID: i32 = 2;
+const P_PIDFD: i32 = 3;
+
+/// The rate of the clock ticks in which `waitid` reports CPU time.
+const CLOCKS_PER_SEC: u64 = 100;
+
 bitflags::bitflags! {
     #[derive(Clone, Copy, Debug)]
     pub struct WaitFlags: u32 {
@@ -50,11 +139,25 @@ bitflags::bitflags! {
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum ChildState {
-    NormalExit { code: u32 },
-    SignalExit { signal: SigId, core: bool },
-    Stop { signal: SigId },
+    NormalExit {
+        code: u32,
+    },
+    SignalExit {
+        signal: SigId,
+        core: bool,
+    },
+    Stop {
+        signal: SigId,
+    },
     Continue,
+    /// A tracee has entered a ptrace-stop. `stop_sig` and `event` occupy bits
+    /// 8-15 and 16-23 of the reported status respectively.
+    TraceStop {
+        stop_sig: u32,
+        event: u32,
+    },
 }
 
 impl ChildState {
@@ -65,12 +168,56 @@ impl ChildState {
             }
             ChildState::Stop { .. } => flags.contains(WaitFlags::WSTOPPED),
             ChildState::Continue => flags.contains(WaitFlags::WCONTINUED),
+            // Tracers are always told about ptrace-stops.
+            ChildState::TraceStop { .. } => true,
         }
     }
+
+    /// Returns the status reported by `wait4`.
+    fn wait_status(&self) -> i32 {
+        match *self {
+            ChildState::NormalExit { code } => (code as i32 & 0xff) << 8,
+            ChildState::SignalExit { signal, core } => {
+                (signal.user_id() as i32) | if core { 0x80 } else { 0x0 }
+            }
+            ChildState::Stop { signal } => ((signal.user_id() as i32) << 8) | 0x7f,
+            ChildState::Continue => 0xffff,
+            ChildState::TraceStop { stop_sig, event } => {
+                ((event << 16) | (stop_sig << 8) | 0x7f) as i32
+            }
+        }
+    }
+
+    /// Returns the `si_code` and `si_status` reported by `waitid`.
+    fn siginfo(&self) -> (i32, i32) {
+        match *self {
+            ChildState::NormalExit { code } => (CLD_EXITED, code as i32 & 0xff),
+            ChildState::SignalExit { signal, core } => (
+                if core { CLD_DUMPED } else { CLD_KILLED },
+                signal.user_id() as _,
+            ),
+            ChildState::Stop { signal } => (CLD_STOPPED, signal.user_id() as _),
+            ChildState::Continue => (CLD_CONTINUED, SigId::SIGCONT.user_id() as _),
+            ChildState::TraceStop { stop_sig, .. } => (CLD_TRAPPED, stop_sig as _),
+        }
+    }
+}
+
+/// A state change of a child, as recorded for its waiter.
+#[derive(Clone, Copy)]
+struct ChildEvent {
+    state: ChildState,
+    /// The child's process group when the change happened.
+    pgid: Pgid,
+    uid: Uid,
+    /// The CPU time consumed by the child, in nanoseconds.
+    cpu_time: u64,
 }
 
 pub struct ChildNotifiers {
-    inner: CondVar<BTreeMap<Tgid, ChildState>>,
+    /// The changes of each child process, keyed by its main thread, and of
+    /// each traced thread.
+    inner: CondVar<BTreeMap<TaskDescriptor, ChildEvent>>,
 }
 
 impl Default for ChildNotifiers {
@@ -86,9 +233,43 @@ impl ChildNotifiers {
         }
     }
 
-    pub fn child_update(&self, tgid: Tgid, new_state: ChildState) {
+    pub fn child_update(&self, child: &ThreadGroup, new_state: ChildState) {
+        self.update(main_thread(child.tgid), child, new_state);
+    }
+
+    /// Records a change of the traced thread `tracee`, which its tracer waits
+    /// for by its TID.
+    pub fn tracee_update(&self, tracee: &Task, new_state: ChildState) {
+        self.update(tracee.descriptor(), &tracee.process, new_state);
+    }
+
+    /// Discards any ptrace-stop of `tracee` which hasn't been waited for, as
+    /// it's no longer traced.
+    pub fn forget_tracee(&self, tracee: &Task) {
+        self.inner.update(|state| {
+            let desc = tracee.descriptor();
+
+            if state
+                .get(&desc)
+                .is_some_and(|e| matches!(e.state, ChildState::TraceStop { .. }))
+            {
+                state.remove(&desc);
+            }
+
+            WakeupType::None
+        });
+    }
+
+    fn update(&self, key: TaskDescriptor, child: &ThreadGroup, new_state: ChildState) {
+        let event = ChildEvent {
+            state: new_state,
+            pgid: *child.pgid.lock_save_irq(),
+            uid: child.creds().map_or(Uid::new_root(), |c| c.uid()),
+            cpu_time: child.cpu_time(),
+        };
+
         self.inner.update(|state| {
-            state.insert(tgid, new_state);
+            state.insert(key, event);
 
             // Since some wakers may be conditional upon state update changes,
             // notify everyone whenever a child updates it's state.
@@ -97,17 +278,120 @@ impl ChildNotifiers {
     }
 }
 
+/// Returns the main thread of the process `tgid`, by which its changes are
+/// recorded.
+fn main_thread(tgid: Tgid) -> TaskDescriptor {
+    TaskDescriptor::from_tgid_tid(tgid, Tid::from_tgid(tgid))
+}
+
+/// The children a wait applies to.
+#[derive(Clone, Copy)]
+enum WaitId {
+    Any,
+    /// A child process, by its main thread, or a traced thread.
+    Pid(TaskDescriptor),
+    Pgid(Pgid),
+}
+
+impl WaitId {
+    /// Returns the child process, or failing that the traced thread, with
+    /// `pid` in the current task's PID namespace.
+    fn from_vpid(pid: PidT) -> Result<Self> {
+        current_task()
+            .process
+            .pid_ns
+            .task_of(pid)
+            .map(WaitId::Pid)
+            .ok_or(KernelError::NoChild)
+    }
+
+    fn matches(self, task: TaskDescriptor, pgid: Pgid) -> bool {
+        match self {
+            WaitId::Any => true,
+            WaitId::Pid(id) => id == task,
+            WaitId::Pgid(id) => id == pgid,
+        }
+    }
+}
+
+/// Returns whether `process` has any child, or tracee, which `id` matches.
+fn has_waitable(process: &ThreadGroup, id: WaitId) -> bool {
+    let children = process
+        .children
+        .lock_save_irq()
+        .values()
+        .any(|c| id.matches(main_thread(c.tgid), *c.pgid.lock_save_irq()));
+
+    let tracees = process
+        .tracees
+        .lock_save_irq()
+        .iter()
+        .filter_map(|t| t.upgrade())
+        .any(|t| id.matches(t.descriptor(), *t.process.pgid.lock_save_irq()));
+
+    // Children which have exited are only found in the notifier.
+    let mut notified = false;
+
+    process.child_notifiers.inner.update(|state| {
+        notified = state.iter().any(|(task, e)| id.matches(*task, e.pgid));
+        WakeupType::None
+    });
+
+    children || tracees || notified
+}
+
+/// Waits for a child matching `id` to change state in a way selected by
+/// `flags`, returning its ID and the change. With `WNOHANG`, `None` is returned
+/// rather than waiting. With `WNOWAIT`, the change is left to be waited for
+/// again.
+async fn do_wait(id: WaitId, flags: WaitFlags) -> Result<Option<(TaskDescriptor, ChildEvent)>> {
+    let process = current_task().process.clone();
+
+    if !has_waitable(&process, id) {
+        return Err(KernelError::NoChild);
+    }
+
+    let take = move |state: &mut BTreeMap<TaskDescriptor, ChildEvent>| {
+        let (task, event) = state
+            .iter()
+            .find(|(task, e)| id.matches(**task, e.pgid) && e.state.matches_wait_flags(flags))
+            .map(|(task, e)| (*task, *e))?;
+
+        if !flags.contains(WaitFlags::WNOWAIT) {
+            state.remove(&task);
+        }
+
+        Some((task, event))
+    };
+
+    if flags.contains(WaitFlags::WNOHANG) {
+        let mut found = None;
+
+        process.child_notifiers.inner.update(|state| {
+            found = take(state);
+            WakeupType::None
+        });
+
+        Ok(found)
+    } else {
+        Ok(Some(process.child_notifiers.inner.wait_until(take).await))
+    }
+}
+
+/// Returns the process group with the ID `pgid` in the current task's PID
+/// namespace.
+fn vpgid(pgid: PidT) -> Result<Pgid> {
+    tgid_from_vpid(pgid)
+        .map(|tgid| Pgid(tgid.value()))
+        .ok_or(KernelError::NoChild)
+}
+
 pub async fn sys_wait4(
     pid: PidT,
     stat_addr: TUA<i32>,
     flags: u32,
     rusage: TUA<RUsage>,
 ) -> Result<usize> {
-    if pid < -1 {
-        // TODO: Funky waiting.
-        return Err(KernelError::NotSupported);
-    }
-
     let mut flags = WaitFlags::from_bits_retain(flags);
 
     if flags.contains_unknown_bits() {
@@ -132,62 +416,80 @@ pub async fn sys_wait4(
     // wait4 implies WEXITED.
     flags.insert(WaitFlags::WEXITED);
 
-    if !rusage.is_null() {
-        // TODO: Funky waiting.
-        return Err(KernelError::NotSupported);
-    }
-
-    let task = current_task();
-
-
-    let (tgid, child_state): (Tgid, ChildState) = task
-        .process
-        .child_notifiers
-        .inner
-        .wait_until(|state: &mut BTreeMap<Tgid, ChildState>| {
-            let key = if pid == -1 {
-                state.iter().find_map(|(k, v)| {
-                    if v.matches_wait_flags(flags) {
-                        Some(*k)
-                    } else {
-                        None
-                    }
-                })
-            } else {
-                state
-                    .get_key_value(&Tgid::from_pid_t(pid))
-                    .and_then(|(k, v)| {
-                        if v.matches_wait_flags(flags) {
-                            Some(*k)
-                        } else {
-                            None
-                        }
-                    })
-            }?;
-
-            Some(state.remove_entry(&key).unwrap())
-        })
-        .await;
+    let id = match pid {
+        -1 => WaitId::Any,
+        0 => WaitId::Pgid(*current_task().process.pgid.lock_save_irq()),
+        p if p < 0 => WaitId::Pgid(vpgid(-p)?),
+        p => WaitId::from_vpid(p)?,
+    };
+
+    let Some((task, event)) = do_wait(id, flags).await? else {
+        return Ok(0);
+    };
+
     if !stat_addr.is_null() {
-        match child_state {
-            ChildState::NormalExit { code } => {
-                copy_to_user(stat_addr, (code as i32 & 0xff) << 8).await?;
-            }
-            ChildState::SignalExit { signal, core } => {
-                copy_to_user(
-                    stat_addr,
-                    (signal.user_id() as i32) | if core { 0x80 } else { 0x0 },
-                )
-                .await?;
-            }
-            ChildState::Stop { signal } => {
-                copy_to_user(stat_addr, ((signal as i32) << 8) | 0x7f).await?;
-            }
-            ChildState::Continue => {
-                copy_to_user(stat_addr, 0xffff).await?;
-            }
+        copy_to_user(stat_addr, event.state.wait_status()).await?;
+    }
+
+    if !rusage.is_null() {
+        copy_to_user(rusage, RUsage::new(event.cpu_time)).await?;
+    }
+
+    Ok(task_vnr(task) as _)
+}
+
+pub async fn sys_waitid(
+    idtype: i32,
+    id: PidT,
+    infop: TUA<ChildSigInfo>,
+    options: u32,
+    rusage: TUA<RUsage>,
+) -> Result<usize> {
+    let flags = WaitFlags::from_bits_retain(options);
+
+    if flags.contains_unknown_bits()
+        || !flags.intersects(WaitFlags::WEXITED | WaitFlags::WSTOPPED | WaitFlags::WCONTINUED)
+    {
+        return Err(KernelError::InvalidValue);
+    }
+
+    let id = match idtype {
+        P_ALL => WaitId::Any,
+        P_PID if id > 0 => WaitId::from_vpid(id)?,
+        P_PGID if id == 0 => WaitId::Pgid(*current_task().process.pgid.lock_save_irq()),
+        P_PGID if id > 0 => WaitId::Pgid(vpgid(id)?),
+        P_PIDFD => WaitId::Pid(main_thread(pidfd_process(Fd(id)).await?.tgid)),
+        _ => return Err(KernelError::InvalidValue),
+    };
+
+    let (info, usage) = match do_wait(id, flags).await? {
+        Some((task, event)) => {
+            let (code, status) = event.state.siginfo();
+
+            let info = ChildSigInfo {
+                si_signo: SigId::SIGCHLD.user_id() as _,
+                si_code: code,
+                si_pid: task_vnr(task),
+                si_uid: event.uid.into(),
+                si_status: status,
+                si_utime: (event.cpu_time / (1_000_000_000 / CLOCKS_PER_SEC)) as _,
+                ..ChildSigInfo::empty()
+            };
+
+            (info, RUsage::new(event.cpu_time))
         }
+        // Nothing was ready to be waited for, which is reported with a zero
+        // PID.
+        None => (ChildSigInfo::empty(), RUsage::new(0)),
+    };
+
+    if !infop.is_null() {
+        copy_to_user(infop, info).await?;
+    }
+
+    if !rusage.is_null() {
+        copy_to_user(rusage, usage).await?;
     }
 
-    Ok(tgid.value() as _)
+    Ok(0)
 }
@@ -1,17 +1,19 @@
 use core::ffi::c_long;
 
-use crate::sched::current_task;
+use crate::{process::thread_group::pid::tid_vnr, sched::current_task};
 use libkernel::{
     error::{KernelError, Result},
-    memory::address::{TUA, VA},
+    memory::address::{TUA, UA, VA},
 };
 
-pub async fn sys_set_tid_address(_tidptr: VA) -> Result<usize> {
-    let tid = current_task().tid;
+pub async fn sys_set_tid_address(tidptr: VA) -> Result<usize> {
+    let task = current_task();
+
+    *task.tid_address.lock_save_irq() = (!tidptr.is_null()).then(|| UA::from_value(tidptr.value()));
 
     // TODO: implement threading and this system call properly. For now, we just
     // return the PID as the thread id.
-    Ok(tid.value() as _)
+    Ok(tid_vnr(&task) as _)
 }
 
 #[repr(C)]
@@ -5,9 +5,12 @@ use crate::{
     process::{TASK_LIST, Task, TaskDescriptor, TaskState},
     sync::OnceLock,
 };
-use alloc::{boxed::Box, collections::btree_map::BTreeMap, sync::Arc};
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
 use core::cmp::Ordering;
-use libkernel::{UserAddressSpace, error::Result};
+use libkernel::{
+    CpuOps, UserAddressSpace,
+    error::{KernelError, Result},
+};
 
 pub mod uspc_ret;
 pub mod waker;
@@ -46,11 +49,26 @@ fn schedule() {
     let previous_task = current_task();
     *previous_task.last_run.lock_save_irq() = now();
     let mut sched_state = SCHED_STATE.borrow_mut();
-    let next_task = sched_state.find_next_runnable_task();
 
-    sched_state
-        .switch_to_task(Some(previous_task), next_task.clone())
-        .expect("Could not schedule next task");
+    // Reap any tasks that were killed by another CPU while they weren't
+    // running. They're dropped once the scheduler state is released.
+    let reaped = sched_state.reap_finished_tasks(&previous_task);
+
+    // The task picked may be killed by another CPU before it's switched to, in
+    // which case another is picked.
+    loop {
+        let next_task = sched_state.find_next_runnable_task();
+
+        if sched_state
+            .switch_to_task(Some(previous_task.clone()), next_task)
+            .is_ok()
+        {
+            break;
+        }
+    }
+
+    drop(sched_state);
+    drop(reaped);
 }
 
 pub fn spawn_kernel_work(fut: impl Future<Output = ()> + 'static + Send) {
@@ -90,18 +108,43 @@ impl SchedState {
     ) -> Result<()> {
         let now_inst = now().expect("System timer not initialised");
 
+        // Charge the previous task's vruntime, and its process's CPU time, for
+        // the time it has run. This is done even if it is selected again, as
+        // there's no timer tick to account for long-running tasks.
+        if let Some(ref prev_task) = previous_task {
+            let mut exec_start = prev_task.exec_start.lock_save_irq();
+
+            if let Some(start) = *exec_start {
+                let delta = (now_inst - start).as_nanos() as u64;
+
+                *prev_task.vruntime.lock_save_irq() += delta;
+                prev_task.process.charge_cpu_time(delta);
+                *exec_start = Some(now_inst);
+            }
+        }
+
         if let Some(ref prev_task) = previous_task
             && Arc::ptr_eq(&next_task, prev_task)
         {
             return Ok(());
         }
 
-        // Update vruntime and clear exec_start for the previous task.
-        if let Some(ref prev_task) = previous_task {
-            if let Some(start) = *prev_task.exec_start.lock_save_irq() {
-                let delta = now_inst - start;
-                *prev_task.vruntime.lock_save_irq() += delta.as_nanos() as u64;
+        // Claim the next task for this CPU. Its state is checked under the
+        // same lock that `kill_other_threads` holds while it reads `on_cpu`,
+        // so a task that has been killed is never switched to.
+        {
+            let mut state = next_task.state.lock_save_irq();
+
+            if state.is_finished() {
+                return Err(KernelError::NoProcess);
             }
+
+            *state = TaskState::Running;
+            *next_task.on_cpu.lock_save_irq() = Some(ArchImpl::id());
+        }
+
+        // Clear exec_start for the previous task.
+        if let Some(ref prev_task) = previous_task {
             *prev_task.exec_start.lock_save_irq() = None;
         }
 
@@ -109,7 +152,7 @@ impl SchedState {
         *next_task.exec_start.lock_save_irq() = Some(now_inst);
 
         // Context switch.
-        if let Some(previous_task) = previous_task {
+        if let Some(ref previous_task) = previous_task {
             let mut state = previous_task.state.lock_save_irq();
 
             if *state == TaskState::Running {
@@ -117,17 +160,38 @@ impl SchedState {
             }
         }
 
-        *next_task.state.lock_save_irq() = TaskState::Running;
-
         // Update the scheduler's state to reflect the new running task.
         self.running_task = Some(next_task.clone());
 
         // Perform the architecture-specific context switch.
         ArchImpl::context_switch(next_task);
 
+        // The previous task's address space is no longer active on this CPU.
+        if let Some(previous_task) = previous_task {
+            *previous_task.on_cpu.lock_save_irq() = None;
+        }
+
         Ok(())
     }
 
+    /// Removes every finished task, other than `current`, from the run queue,
+    /// returning them.
+    fn reap_finished_tasks(&mut self, current: &Arc<Task>) -> Vec<Arc<Task>> {
+        let finished: Vec<_> = self
+            .run_queue
+            .iter()
+            .filter(|(_, task)| {
+                !Arc::ptr_eq(task, current) && task.state.lock_save_irq().is_finished()
+            })
+            .map(|(desc, _)| *desc)
+            .collect();
+
+        finished
+            .iter()
+            .filter_map(|desc| self.run_queue.remove(desc))
+            .collect()
+    }
+
     fn find_next_runnable_task(&self) -> Arc<Task> {
         let idle_task = self
             .run_queue
@@ -182,7 +246,7 @@ pub fn sched_init() {
     let init_task = Arc::new(Task::create_init_task());
 
     init_task
-        .vm
+        .vm()
         .lock_save_irq()
         .mm_mut()
         .address_space_mut()
@@ -190,6 +254,12 @@ pub fn sched_init() {
 
     *init_task.state.lock_save_irq() = TaskState::Running;
 
+    init_task
+        .process
+        .threads
+        .lock_save_irq()
+        .insert(init_task.tid, Arc::downgrade(&init_task));
+
     {
         let mut task_list = TASK_LIST.lock_save_irq();
 
@@ -2,10 +2,11 @@ use super::{SCHED_STATE, current_task, schedule, waker::create_waker};
 use crate::{
     arch::{Arch, ArchImpl},
     process::{
-        TaskState,
+        Task, TaskState, coredump,
         ctx::UserCtx,
         exit::kernel_exit_with_signal,
-        thread_group::signal::{SigId, ksigaction::KSignalAction},
+        ptrace,
+        thread_group::signal::{SigId, ksigaction::KSignalAction, stop},
     },
 };
 use alloc::boxed::Box;
@@ -81,6 +82,13 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
             State::ProcessKernelWork => {
                 let task = current_task();
 
+                // A task killed while it was running (see
+                // `kill_other_threads`) must not do any more work.
+                if reap_if_finished(&task) {
+                    state = State::PickNewTask;
+                    continue;
+                }
+
                 // First, let's handle signals. If there is any scheduled signal
                 // work (this has to be async to handle faults, etc).
                 let signal_work = task.ctx.lock_save_irq().take_signal_work();
@@ -94,6 +102,10 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
                         .poll(&mut core::task::Context::from_waker(&create_waker(
                             task.descriptor(),
                         ))) {
+                        Poll::Ready(Ok(_)) if reap_if_finished(&task) => {
+                            state = State::PickNewTask;
+                            continue;
+                        }
                         Poll::Ready(Ok(state)) => {
                             // Signal actioning is complete. Return to userspace.
                             unsafe { ptr::copy_nonoverlapping(&state as _, ctx, 1) };
@@ -102,16 +114,21 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
                         Poll::Ready(Err(_)) => {
                             // If we errored, then we *cannot* progress the task.
                             // Delivery of the signal failed. Force the process to
-                            // terminate.
-                            kernel_exit_with_signal(SigId::SIGSEGV, true);
+                            // terminate, dumping core as for any other SIGSEGV.
+                            let dying = task.clone();
+                            task.ctx
+                                .lock_save_irq()
+                                .put_kernel_work(Box::pin(async move {
+                                    let core = coredump::do_coredump(&dying, SigId::SIGSEGV).await;
+                                    kernel_exit_with_signal(SigId::SIGSEGV, core);
+                                }));
 
-                            // Look for another task, this one is now dead.
-                            state = State::PickNewTask;
+                            state = State::ProcessKernelWork;
                             continue;
                         }
                         Poll::Pending => {
                             task.ctx.lock_save_irq().put_signal_work(signal_work);
-                            *task.state.lock_save_irq() = TaskState::Sleeping;
+                            sleep(&task);
 
                             state = State::PickNewTask;
                             continue;
@@ -136,12 +153,7 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
                             // don't return to it's userspace, instead, find another
                             // task to execute, removing this task from the
                             // runqueue, reaping it's resouces.
-                            if task.state.lock_save_irq().is_finished() {
-                                SCHED_STATE
-                                    .borrow_mut()
-                                    .run_queue
-                                    .remove(&task.descriptor());
-
+                            if reap_if_finished(&task) {
                                 state = State::PickNewTask;
                                 continue;
                             }
@@ -160,7 +172,7 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
                             // state to sleeping so it's not scheduled again and
                             // search for another task to execute.
                             task.ctx.lock_save_irq().put_kernel_work(kern_work);
-                            *task.state.lock_save_irq() = TaskState::Sleeping;
+                            sleep(&task);
                             state = State::PickNewTask;
                             continue;
                         }
@@ -175,26 +187,96 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
                     continue;
                 }
 
-                // See if there are any signals we need to action.
                 let task = current_task();
+
+                // A process stopped by one of its threads stops them all.
+                if task.process.signals.lock_save_irq().is_stopped() {
+                    task.ctx
+                        .lock_save_irq()
+                        .put_kernel_work(Box::pin(stop::wait_while_stopped(task.clone())));
+
+                    state = State::ProcessKernelWork;
+                    continue;
+                }
+
+                // See if there are any signals we need to action. A signal
+                // injected by a tracer bypasses the pending set, as it has
+                // already been through a signal-delivery-stop.
+                let mut injected = task.ptrace.take_injected_signal();
                 let mut pending_task_sigs = task.pending_signals.lock_save_irq();
                 let mask = task.sig_mask.lock_save_irq();
-                if let Some((id, action)) = task
-                    .process
-                    .signals
-                    .lock_save_irq()
-                    .action_signal(*mask, &mut pending_task_sigs)
+                let mut signals = task.process.signals.lock_save_irq();
+
+                // An injected signal which the task blocks is left pending, to
+                // be reported to the tracer again once it's unblocked.
+                if let Some(id) = injected
+                    && mask.contains(id.into())
                 {
+                    pending_task_sigs.insert(id.into());
+                    injected = None;
+                }
+
+                let next = match injected {
+                    Some(id) => signals.action_for(id).map(|action| (id, Some(action))),
+                    // A traced task reports each signal to its tracer, even one
+                    // which it ignores, before the signal's disposition is
+                    // consulted. SIGKILL cannot be intercepted.
+                    None if task.ptrace.is_traced() => signals
+                        .dequeue_signal(*mask, &mut pending_task_sigs)
+                        .map(|id| match id {
+                            SigId::SIGKILL => (id, signals.action_for(id)),
+                            _ => (id, None),
+                        }),
+                    None => signals
+                        .action_signal(*mask, &mut pending_task_sigs)
+                        .map(|(id, action)| (id, Some(action))),
+                };
+                drop(signals);
+
+                if let Some((id, action)) = next {
+                    // The tracer decides what (if anything) is delivered in the
+                    // reported signal's place.
+                    let Some(action) = action else {
+                        task.ctx.lock_save_irq().put_kernel_work(Box::pin(
+                            ptrace::signal_delivery_stop(task.clone(), id),
+                        ));
+
+                        state = State::ProcessKernelWork;
+                        continue;
+                    };
+
                     match action {
-                        KSignalAction::Term | KSignalAction::Core => {
+                        KSignalAction::Term => {
                             // Terminate the process, and find a new task.
                             kernel_exit_with_signal(id, false);
 
                             state = State::PickNewTask;
                             continue;
                         }
-                        KSignalAction::Stop => todo!(),
-                        KSignalAction::Continue => todo!(),
+                        KSignalAction::Core => {
+                            // Writing the core file requires I/O, so terminate
+                            // the process from kernel work once it's done.
+                            let dying = task.clone();
+                            task.ctx
+                                .lock_save_irq()
+                                .put_kernel_work(Box::pin(async move {
+                                    let core = coredump::do_coredump(&dying, id).await;
+                                    kernel_exit_with_signal(id, core);
+                                }));
+
+                            state = State::ProcessKernelWork;
+                            continue;
+                        }
+                        KSignalAction::Stop => {
+                            task.ctx
+                                .lock_save_irq()
+                                .put_kernel_work(Box::pin(stop::group_stop(task.clone(), id)));
+
+                            state = State::ProcessKernelWork;
+                            continue;
+                        }
+                        // The process was continued when SIGCONT was raised.
+                        KSignalAction::Continue => {}
                         KSignalAction::Userspace(id, action) => {
                             let fut = ArchImpl::do_signal(id, action);
 
@@ -217,3 +299,28 @@ pub fn dispatch_userspace_task(ctx: *mut UserCtx) {
         }
     }
 }
+
+/// Removes `task` from this CPU's run queue if it has finished, returning
+/// whether it had.
+fn reap_if_finished(task: &Task) -> bool {
+    if !task.state.lock_save_irq().is_finished() {
+        return false;
+    }
+
+    SCHED_STATE
+        .borrow_mut()
+        .run_queue
+        .remove(&task.descriptor());
+
+    true
+}
+
+/// Puts `task` to sleep, unless it was killed in the meantime, in which case
+/// it must stay finished.
+fn sleep(task: &Task) {
+    let mut state = task.state.lock_save_irq();
+
+    if !state.is_finished() {
+        *state = TaskState::Sleeping;
+    }
+}